@@ -0,0 +1,284 @@
+use crate::error::InvalidArgument;
+use crate::payload::copy_bytes;
+use crate::rng::RadioRng;
+use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES};
+use core::ops::Deref;
+
+/// Random numbers `Address::random()` draws for one byte before it gives
+/// up, more than a working source ever needs
+const RANDOM_TRIES: usize = 16;
+
+/// A pipe address of `MIN_ADDR_BYTES` to `MAX_ADDR_BYTES` bytes
+///
+/// Bytes are in the order they are written to the chip, that is least
+/// significant byte first.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Address {
+    bytes: [u8; MAX_ADDR_BYTES],
+    len: u8,
+}
+
+impl Address {
+    /// Copy a slice, validating its length
+    pub fn new(bytes: &[u8]) -> Result<Self, InvalidArgument> {
+        let len = bytes.len();
+        if !(MIN_ADDR_BYTES..=MAX_ADDR_BYTES).contains(&len) {
+            return Err(InvalidArgument::AddressLength);
+        }
+        let mut address = Address {
+            bytes: [0; MAX_ADDR_BYTES],
+            len: len as u8,
+        };
+        copy_bytes(&mut address.bytes, bytes);
+        Ok(address)
+    }
+
+    /// A full-length address
+    pub const fn from_array(bytes: [u8; MAX_ADDR_BYTES]) -> Self {
+        Address {
+            bytes,
+            len: MAX_ADDR_BYTES as u8,
+        }
+    }
+
+    /// Derive a full-length address from `seed`, e.g. a unique chip
+    /// ID shared by a device pair
+    ///
+    /// Every byte has two to five level shifts, which rules out
+    /// `0x00`/`0xFF` runs and the `0x55`/`0xAA` patterns that the
+    /// datasheet warns are mistaken for the preamble or noise.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Self::random(&mut SeedRng::new(seed))
+    }
+
+    /// Random full-length address, with the same level shifts as
+    /// [`from_seed()`](#method.from_seed)
+    ///
+    /// Draws up to `RANDOM_TRIES` numbers per byte, so that a stuck
+    /// `rng` can not hang the caller, and falls back to `0xC3` after
+    /// that.
+    pub fn random<R: RadioRng>(rng: &mut R) -> Self {
+        let mut bytes = [0; MAX_ADDR_BYTES];
+        for byte in bytes.iter_mut() {
+            *byte = (0..RANDOM_TRIES)
+                .map(|_| rng.next_u32() as u8)
+                .find(|candidate| {
+                    let shifts = ((candidate ^ (candidate >> 1)) & 0x7F).count_ones();
+                    (2..=5).contains(&shifts)
+                })
+                .unwrap_or(0xC3);
+        }
+        Address::from_array(bytes)
+    }
+
+    /// Address bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.get(..usize::from(self.len)).unwrap_or_default()
+    }
+
+    /// Address width in bytes
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Always `false`, addresses have at least `MIN_ADDR_BYTES`
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Up to five addresses that differ only in the least significant byte,
+/// received on pipes 1 to 5
+///
+/// Apply with [`listen_family()`](trait.Configuration.html#method.listen_family),
+/// then map the pipe numbers that packets arrive on back to suffixes
+/// with [`suffix_of()`](#method.suffix_of). Useful for group addressing
+/// where a node listens to its own address and a few group addresses.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct AddressFamily {
+    /// Shared bytes 1 and up
+    prefix: [u8; MAX_ADDR_BYTES - 1],
+    prefix_len: u8,
+    /// Least significant bytes of pipes 1 to 5
+    suffixes: [Option<u8>; 5],
+}
+
+impl AddressFamily {
+    /// Family of addresses that end in `prefix`, that is with `prefix`
+    /// following the least significant byte
+    ///
+    /// `prefix` is one byte shorter than the address width.
+    pub fn new(prefix: &[u8]) -> Result<Self, InvalidArgument> {
+        let len = prefix.len();
+        if !(MIN_ADDR_BYTES - 1..MAX_ADDR_BYTES).contains(&len) {
+            return Err(InvalidArgument::AddressLength);
+        }
+        let mut family = AddressFamily {
+            prefix: [0; MAX_ADDR_BYTES - 1],
+            prefix_len: len as u8,
+            suffixes: [None; 5],
+        };
+        copy_bytes(&mut family.prefix, prefix);
+        Ok(family)
+    }
+
+    /// Add the address with least significant byte `suffix`, returning
+    /// the pipe number it will be received on
+    pub fn add(&mut self, suffix: u8) -> Result<u8, InvalidArgument> {
+        if let Some(pipe_no) = self.pipe_of(suffix) {
+            return Ok(pipe_no);
+        }
+        let (pipe_no, free) = (1..)
+            .zip(self.suffixes.iter_mut())
+            .find(|(_, slot)| slot.is_none())
+            .ok_or(InvalidArgument::Pipe)?;
+        *free = Some(suffix);
+        Ok(pipe_no)
+    }
+
+    /// Pipe number the address with `suffix` is received on
+    pub fn pipe_of(&self, suffix: u8) -> Option<u8> {
+        (1..)
+            .zip(self.suffixes.iter())
+            .find(|(_, s)| **s == Some(suffix))
+            .map(|(pipe_no, _)| pipe_no)
+    }
+
+    /// Least significant byte of the address received on `pipe_no`
+    pub fn suffix_of(&self, pipe_no: u8) -> Option<u8> {
+        let index = usize::from(pipe_no).checked_sub(1)?;
+        self.suffixes.get(index).copied().flatten()
+    }
+
+    /// Full address with least significant byte `suffix`
+    pub fn address(&self, suffix: u8) -> Address {
+        let mut bytes = [0; MAX_ADDR_BYTES];
+        let [first, rest @ ..] = &mut bytes;
+        *first = suffix;
+        copy_bytes(rest, self.prefix.get(..usize::from(self.prefix_len)).unwrap_or_default());
+        Address {
+            bytes,
+            len: self.width(),
+        }
+    }
+
+    /// Address width in bytes
+    pub fn width(&self) -> u8 {
+        // At most `MAX_ADDR_BYTES - 1`
+        self.prefix_len.saturating_add(1)
+    }
+
+    /// Suffixes of pipes 1 to 5
+    pub(crate) fn suffixes(&self) -> &[Option<u8>; 5] {
+        &self.suffixes
+    }
+}
+
+/// Deterministic generator for `from_seed()` helpers: FNV-1a over the
+/// seed, expanded with SplitMix64
+pub(crate) struct SeedRng(u64);
+
+impl SeedRng {
+    pub(crate) fn new(seed: &[u8]) -> Self {
+        let hash = seed.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        SeedRng(hash)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl RadioRng for SeedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next() as u32
+    }
+}
+
+impl AsRef<[u8]> for Address {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Deref for Address {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// Serialized as a byte string, validated on deserialization
+#[cfg(feature = "serde")]
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AddressVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for AddressVisitor {
+            type Value = Address;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(f, "{} to {} address bytes", MIN_ADDR_BYTES, MAX_ADDR_BYTES)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Address, E> {
+                Address::new(bytes).map_err(|_| E::invalid_length(bytes.len(), &self))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Address, A::Error> {
+                let mut bytes = [0; MAX_ADDR_BYTES];
+                let mut len = 0_usize;
+                while let Some(byte) = seq.next_element()? {
+                    let Some(slot) = bytes.get_mut(len) else {
+                        return Err(serde::de::Error::invalid_length(len.saturating_add(1), &self));
+                    };
+                    *slot = byte;
+                    len = len.saturating_add(1);
+                }
+                self.visit_bytes(bytes.get(..len).unwrap_or_default())
+            }
+        }
+
+        deserializer.deserialize_bytes(AddressVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Constant(u32);
+
+    impl RadioRng for Constant {
+        fn next_u32(&mut self) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn random_returns_with_a_stuck_rng() {
+        for value in [0x00, 0xFF, 0x55, 0xAA] {
+            let address = Address::random(&mut Constant(value));
+            assert_eq!(address.as_bytes(), &[0xC3; MAX_ADDR_BYTES]);
+        }
+    }
+
+    #[test]
+    fn random_keeps_good_bytes() {
+        let address = Address::random(&mut Constant(0x1234_56E3));
+        assert_eq!(address.as_bytes(), &[0xE3; MAX_ADDR_BYTES]);
+    }
+}
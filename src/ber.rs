@@ -0,0 +1,259 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::config::{Configuration, CrcMode, PowerLevel, RadioConfig};
+use crate::device::Device;
+use crate::link_test::LinkTestError;
+use crate::rx::RxMode;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use crate::PIPES_COUNT;
+use embedded_hal_async::delay::DelayNs;
+
+/// Length of BER test packets, static so that frames are received
+/// without CRC
+pub const BER_PACKET_LEN: usize = 32;
+
+/// Address used by the [`BerTester`](struct.BerTester.html)
+const BER_ADDR: [u8; 5] = [0x5A, 0xC3, 0x3C, 0xA5, 0x69];
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Known test pattern: a little-endian sequence number followed by
+/// PRBS-15 (x¹⁵ + x¹⁴ + 1) seeded from it
+pub fn ber_packet(seq: u16) -> [u8; BER_PACKET_LEN] {
+    let mut packet = [0; BER_PACKET_LEN];
+    packet[..2].copy_from_slice(&seq.to_le_bytes());
+    // Any non-zero 15 bit seed
+    let mut lfsr = (seq & 0x7FFF) | 1;
+    for byte in packet[2..].iter_mut() {
+        for _ in 0..8 {
+            let bit = ((lfsr >> 14) ^ (lfsr >> 13)) & 1;
+            lfsr = ((lfsr << 1) | bit) & 0x7FFF;
+            *byte = (*byte << 1) | bit as u8;
+        }
+    }
+    packet
+}
+
+/// Bit and packet error counts
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct BerStats {
+    /// Packets sent
+    pub packets_sent: u32,
+    /// Packets received, with or without errors
+    pub packets_received: u32,
+    /// Received packets with at least one bit error
+    pub packets_errored: u32,
+    /// Bits compared against the pattern
+    pub bits_compared: u32,
+    /// Bits that differed from the pattern
+    pub bit_errors: u32,
+}
+
+impl BerStats {
+    /// Bit errors per bit received
+    pub fn bit_error_rate(&self) -> f32 {
+        if self.bits_compared == 0 {
+            return 0.0;
+        }
+        self.bit_errors as f32 / self.bits_compared as f32
+    }
+
+    /// Lost or errored packets per packet sent
+    pub fn packet_error_rate(&self) -> f32 {
+        if self.packets_sent == 0 {
+            return 0.0;
+        }
+        let good = self.packets_received.saturating_sub(self.packets_errored);
+        1.0 - good as f32 / self.packets_sent as f32
+    }
+}
+
+/// Compares received packets against [`ber_packet()`](fn.ber_packet.html)
+///
+/// Use on the receiving end when sender and receiver are separate
+/// devices. The sender is expected to count up from sequence number 0.
+#[derive(Debug, Default, Clone)]
+pub struct BerReceiver {
+    expected: u16,
+    stats: BerStats,
+}
+
+impl BerReceiver {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Statistics so far, with `packets_sent` derived from the highest
+    /// sequence number seen
+    pub fn stats(&self) -> BerStats {
+        self.stats
+    }
+
+    /// Start over
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Account for a received packet
+    pub fn check(&mut self, payload: &[u8]) {
+        let received = payload
+            .get(..2)
+            .and_then(|seq| seq.try_into().ok())
+            .map(u16::from_le_bytes)
+            .unwrap_or(self.expected);
+        // The sequence number itself may be corrupted, use whichever
+        // candidate matches better
+        let errors_received = bit_errors(&ber_packet(received), payload);
+        let errors_expected = bit_errors(&ber_packet(self.expected), payload);
+        let (seq, errors) = if errors_expected <= errors_received {
+            (self.expected, errors_expected)
+        } else {
+            (received, errors_received)
+        };
+
+        let stats = &mut self.stats;
+        stats.packets_received = stats.packets_received.saturating_add(1);
+        let bits = (BER_PACKET_LEN as u32).saturating_mul(8);
+        stats.bits_compared = stats.bits_compared.saturating_add(bits);
+        stats.bit_errors = stats.bit_errors.saturating_add(errors);
+        if errors > 0 {
+            stats.packets_errored = stats.packets_errored.saturating_add(1);
+        }
+        self.expected = seq.wrapping_add(1);
+        self.stats.packets_sent = self.stats.packets_sent.max(u32::from(self.expected));
+    }
+}
+
+/// Differing bits, counting missing bytes as all wrong
+fn bit_errors(expected: &[u8; BER_PACKET_LEN], payload: &[u8]) -> u32 {
+    expected
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| match payload.get(i) {
+            Some(received) => (byte ^ received).count_ones(),
+            None => 8,
+        })
+        .sum()
+}
+
+/// Settings of a [`BerTester`](struct.BerTester.html) run
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BerConfig {
+    /// Packets per channel and power level
+    pub packets: u16,
+    /// `CrcMode::Disabled` lets corrupted frames through to be counted
+    pub crc: CrcMode,
+    /// Retransmit until acknowledged, which hides errors. The chip
+    /// forces CRC on with auto-ack.
+    pub auto_ack: bool,
+}
+
+impl Default for BerConfig {
+    /// Raw link quality: 100 packets, no CRC, no ACK
+    fn default() -> Self {
+        BerConfig {
+            packets: 100,
+            crc: CrcMode::Disabled,
+            auto_ack: false,
+        }
+    }
+}
+
+/// Measures bit and packet error rates between a transmitter and a
+/// receiver, e.g. two modules on one MCU, across channels and power
+/// levels
+pub struct BerTester<T: Device, R: Device> {
+    tx: TxMode<T>,
+    rx: RxMode<R>,
+    config: BerConfig,
+}
+
+impl<T: Device, R: Device> BerTester<T, R> {
+    /// Constructor
+    pub fn new(tx: TxMode<T>, rx: RxMode<R>, config: BerConfig) -> Self {
+        BerTester { tx, rx, config }
+    }
+
+    /// Return the radios
+    pub fn into_inner(self) -> (TxMode<T>, RxMode<R>) {
+        (self.tx, self.rx)
+    }
+
+    /// Measure every combination of `channels` and `powers`, calling
+    /// `report` with the results of each
+    pub async fn sweep<DL, F>(
+        &mut self,
+        channels: &[Channel],
+        powers: &[PowerLevel],
+        delay: &mut DL,
+        mut report: F,
+    ) -> Result<(), LinkTestError<T::Error, R::Error>>
+    where
+        DL: DelayNs,
+        F: FnMut(Channel, PowerLevel, &BerStats),
+    {
+        for &channel in channels {
+            for &power in powers {
+                let stats = self.measure(channel, power, delay).await?;
+                report(channel, power, &stats);
+            }
+        }
+        Ok(())
+    }
+
+    /// Measure on one `channel` at one `power` level
+    pub async fn measure<DL: DelayNs>(
+        &mut self,
+        channel: Channel,
+        power: PowerLevel,
+        delay: &mut DL,
+    ) -> Result<BerStats, LinkTestError<T::Error, R::Error>> {
+        let address = Address::from_array(BER_ADDR);
+        let mut rx_addrs = [None; PIPES_COUNT];
+        rx_addrs[0] = Some(address);
+        let config = RadioConfig {
+            channel,
+            power,
+            crc: self.config.crc,
+            auto_retransmit_delay: 5,
+            auto_retransmit_count: if self.config.auto_ack { 15 } else { 0 },
+            auto_ack: [self.config.auto_ack; PIPES_COUNT],
+            pipes_rx_lengths: [Some(BER_PACKET_LEN as u8); PIPES_COUNT],
+            tx_addr: address,
+            rx_addrs,
+            ..RadioConfig::default()
+        };
+        self.tx.configure(&config).await.map_err(LinkTestError::Tx)?;
+        self.tx.flush_tx().await.map_err(LinkTestError::Tx)?;
+        self.tx.clear_interrupts().await.map_err(LinkTestError::Tx)?;
+        // Settings must not change while listening
+        self.rx.device().ce_disable();
+        self.rx.configure(&config).await.map_err(LinkTestError::Rx)?;
+        self.rx.flush_rx().await.map_err(LinkTestError::Rx)?;
+        self.rx.clear_interrupts().await.map_err(LinkTestError::Rx)?;
+        self.rx.device().ce_enable();
+        // Rx settling
+        delay.delay_us(130).await;
+
+        let mut receiver = BerReceiver::new();
+        for seq in 0..self.config.packets {
+            self.tx.send(&ber_packet(seq)).await.map_err(LinkTestError::Tx)?;
+            self.tx
+                .wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay)
+                .await
+                .map_err(LinkTestError::Tx)?;
+            // Air time of 32 bytes at 250 kbps is about 1.3 ms
+            delay.delay_us(1_500).await;
+            while self.rx.can_read().await.map_err(LinkTestError::Rx)?.is_some() {
+                let payload = self.rx.read().await.map_err(LinkTestError::Rx)?;
+                receiver.check(&payload);
+            }
+        }
+
+        let mut stats = receiver.stats();
+        stats.packets_sent = u32::from(self.config.packets);
+        Ok(stats)
+    }
+}
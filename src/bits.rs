@@ -0,0 +1,45 @@
+/// Writes bit fields into a zeroed buffer, most significant bit first
+pub(crate) struct BitWriter<'a> {
+    pub buf: &'a mut [u8],
+    pub bits: usize,
+}
+
+impl BitWriter<'_> {
+    /// Append the lowest `count` bits of `value`, or `None` if full
+    pub fn push(&mut self, value: u32, count: u8) -> Option<()> {
+        for shift in (0..count).rev() {
+            let byte = self.buf.get_mut(self.bits / 8)?;
+            if value >> shift & 1 != 0 {
+                *byte |= 0x80 >> (self.bits % 8);
+            }
+            self.bits = self.bits.saturating_add(1);
+        }
+        Some(())
+    }
+}
+
+/// Reads bit fields written by `BitWriter`
+#[cfg(any(feature = "compress", feature = "pack"))]
+pub(crate) struct BitReader<'a> {
+    pub buf: &'a [u8],
+    pub bits: usize,
+}
+
+#[cfg(any(feature = "compress", feature = "pack"))]
+impl BitReader<'_> {
+    #[cfg_attr(not(feature = "compress"), allow(dead_code))]
+    pub fn remaining(&self) -> usize {
+        self.buf.len().saturating_mul(8).saturating_sub(self.bits)
+    }
+
+    /// Take the next `count` bits, or `None` at the end
+    pub fn pull(&mut self, count: u8) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            let byte = self.buf.get(self.bits / 8)?;
+            value = value << 1 | u32::from(byte & (0x80 >> (self.bits % 8)) != 0);
+            self.bits = self.bits.saturating_add(1);
+        }
+        Some(value)
+    }
+}
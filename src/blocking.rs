@@ -0,0 +1,240 @@
+//! Support for blocking HALs, such as `linux-embedded-hal` on SBC gateways
+
+use crate::device::Device;
+use crate::payload::Payload;
+use crate::rx::RxMode;
+use crate::standby::StandbyMode;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use core::future::Future;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Turns a blocking `embedded_hal::spi::SpiDevice` into the async
+/// `embedded_hal_async::spi::SpiDevice` expected by the driver
+///
+/// Useful with `linux_embedded_hal::SpidevDevice` on a Raspberry Pi
+/// gateway, or with MCU HALs that only provide blocking SPI. Every
+/// transaction completes before the future is polled the first time.
+#[derive(Debug)]
+pub struct BlockingSpi<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> BlockingSpi<SPI> {
+    /// Wrap a blocking SPI device
+    pub fn new(spi: SPI) -> Self {
+        BlockingSpi { spi }
+    }
+
+    /// Return the wrapped SPI device
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI: SpiDevice> ErrorType for BlockingSpi<SPI> {
+    type Error = SPI::Error;
+}
+
+impl<SPI: SpiDevice> embedded_hal_async::spi::SpiDevice for BlockingSpi<SPI> {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        self.spi.transaction(operations)
+    }
+}
+
+/// Turns a blocking `embedded_hal::delay::DelayNs` into the async
+/// `embedded_hal_async::delay::DelayNs` expected by the driver
+///
+/// Every delay completes before the future is polled the first time.
+#[derive(Debug)]
+pub struct BlockingDelay<DL> {
+    delay: DL,
+}
+
+impl<DL: DelayNs> BlockingDelay<DL> {
+    /// Wrap a blocking delay
+    pub fn new(delay: DL) -> Self {
+        BlockingDelay { delay }
+    }
+
+    /// Return the wrapped delay
+    pub fn into_inner(self) -> DL {
+        self.delay
+    }
+}
+
+impl<DL: DelayNs> embedded_hal_async::delay::DelayNs for BlockingDelay<DL> {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_ns(ns)
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us)
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms)
+    }
+}
+
+/// Executor hook for [`BlockingAdapter`](struct.BlockingAdapter.html)
+///
+/// Implement it with the executor of your platform, e.g.
+/// `embassy_futures::block_on()`, or use
+/// [`ThreadBlockOn`](struct.ThreadBlockOn.html) with `std`.
+pub trait BlockOn {
+    /// Run `future` to completion
+    fn block_on<F: Future>(&mut self, future: F) -> F::Output;
+}
+
+/// [`BlockOn`](trait.BlockOn.html) with [`block_on()`](fn.block_on.html)
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ThreadBlockOn;
+
+#[cfg(feature = "std")]
+impl BlockOn for ThreadBlockOn {
+    fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+        block_on(future)
+    }
+}
+
+/// Blocking calls on a driver mode, for codebases that are mostly
+/// blocking
+///
+/// Wraps a [`StandbyMode`](struct.StandbyMode.html),
+/// [`RxMode`](struct.RxMode.html) or [`TxMode`](struct.TxMode.html)
+/// and runs each operation to completion on `B`. The common operations
+/// have blocking versions here; run any other one through
+/// [`split()`](#method.split):
+///
+/// ```ignore
+/// let mut radio = BlockingAdapter::new(nrf24, ThreadBlockOn).tx().map_err(|(_, e)| e)?;
+/// let (tx, executor) = radio.split();
+/// executor.block_on(tx.set_frequency(Channel::new(8)?))?;
+/// let acked = radio.send(b"hello")?;
+/// ```
+#[derive(Debug)]
+pub struct BlockingAdapter<T, B: BlockOn> {
+    radio: T,
+    executor: B,
+}
+
+impl<T, B: BlockOn> BlockingAdapter<T, B> {
+    /// Wrap `radio`, running its operations on `executor`
+    pub fn new(radio: T, executor: B) -> Self {
+        BlockingAdapter { radio, executor }
+    }
+
+    /// Borrow the mode and the executor, to block on any operation
+    pub fn split(&mut self) -> (&mut T, &mut B) {
+        (&mut self.radio, &mut self.executor)
+    }
+
+    /// Return the mode and the executor
+    pub fn into_inner(self) -> (T, B) {
+        (self.radio, self.executor)
+    }
+}
+
+impl<D: Device, B: BlockOn> BlockingAdapter<StandbyMode<D>, B> {
+    /// Go into RX mode, see [`StandbyMode::rx()`](struct.StandbyMode.html#method.rx)
+    pub fn rx(mut self) -> Result<BlockingAdapter<RxMode<D>, B>, (D, D::Error)> {
+        let radio = self.executor.block_on(self.radio.rx())?;
+        Ok(BlockingAdapter::new(radio, self.executor))
+    }
+
+    /// Go into TX mode, see [`StandbyMode::tx()`](struct.StandbyMode.html#method.tx)
+    pub fn tx(mut self) -> Result<BlockingAdapter<TxMode<D>, B>, (D, D::Error)> {
+        let radio = self.executor.block_on(self.radio.tx())?;
+        Ok(BlockingAdapter::new(radio, self.executor))
+    }
+}
+
+impl<D: Device, B: BlockOn> BlockingAdapter<RxMode<D>, B> {
+    /// Is there any incoming data to read? Return the pipe number.
+    pub fn can_read(&mut self) -> Result<Option<u8>, D::Error> {
+        self.executor.block_on(self.radio.can_read())
+    }
+
+    /// Read the next received packet and its pipe, or `None` if there
+    /// is none
+    pub fn read(&mut self) -> Result<Option<(u8, Payload)>, D::Error> {
+        self.executor.block_on(self.radio.read_with_pipe())
+    }
+
+    /// Go back to Standby-I
+    pub fn standby(self) -> BlockingAdapter<StandbyMode<D>, B> {
+        BlockingAdapter::new(self.radio.standby(), self.executor)
+    }
+}
+
+impl<D: Device, B: BlockOn> BlockingAdapter<TxMode<D>, B> {
+    /// Send `packet` and wait until the transmission has completed,
+    /// polling with the blocking `delay`. Returns whether it was
+    /// acknowledged.
+    pub fn send<DL: DelayNs>(&mut self, packet: &[u8], delay: &mut DL) -> Result<bool, D::Error> {
+        let tx = &mut self.radio;
+        let mut delay = BlockingDelay::new(delay);
+        self.executor.block_on(async move {
+            tx.send(packet).await?;
+            tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), &mut delay).await
+        })
+    }
+
+    /// Wait for all queued packets to be sent, see
+    /// [`TxMode::wait_empty()`](struct.TxMode.html#method.wait_empty)
+    pub fn wait_empty(&mut self) -> Result<(), D::Error> {
+        self.executor.block_on(self.radio.wait_empty())
+    }
+
+    /// Go back to Standby-I once all queued packets are sent
+    pub fn standby(mut self) -> Result<BlockingAdapter<StandbyMode<D>, B>, D::Error> {
+        let radio = self.executor.block_on(self.radio.standby())?;
+        Ok(BlockingAdapter::new(radio, self.executor))
+    }
+}
+
+/// Run a driver future to completion on the current thread
+///
+/// A minimal executor for gateways that do not run an async runtime:
+///
+/// ```ignore
+/// let mut nrf24 = block_on(NRF24L01::new(ce, BlockingSpi::new(spi)))?;
+/// block_on(nrf24.set_frequency(Channel::new(8)?))?;
+/// ```
+///
+/// The future is polled again whenever it wakes the calling thread, so
+/// the driver can be owned by (or moved between) ordinary threads.
+#[cfg(any(test, feature = "std"))]
+pub fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    use core::task::{Context, Poll};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = core::pin::pin!(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
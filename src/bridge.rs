@@ -0,0 +1,315 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::payload::{copy_bytes, Payload};
+use crate::MAX_ADDR_BYTES;
+
+/// Uplink frame type of a received packet
+pub(crate) const UP_PACKET: u8 = 0x01;
+/// Uplink frame type of a send result
+pub(crate) const UP_SENT: u8 = 0x02;
+/// Uplink frame type of a capture record
+pub(crate) const UP_CAPTURE: u8 = 0x03;
+/// Downlink frame type of a send command
+pub(crate) const DOWN_SEND: u8 = 0x81;
+/// Downlink frame type of a channel command
+pub(crate) const DOWN_CHANNEL: u8 = 0x82;
+
+/// First bytes of a capture record, `"N4"`
+pub(crate) const CAPTURE_MAGIC: [u8; 2] = *b"N4";
+/// Capture record layout version
+pub(crate) const CAPTURE_VERSION: u8 = 1;
+
+/// Size of the longest [`CaptureRecord`](struct.CaptureRecord.html)
+/// encoding
+pub const CAPTURE_MAX_LEN: usize = 8 + MAX_ADDR_BYTES + 32;
+
+/// Longest unencoded frame: a capture record with a full address and
+/// payload
+const RAW_MAX: usize = 1 + CAPTURE_MAX_LEN;
+
+/// Longest encoded frame, with the COBS overhead byte and the `0x00`
+/// delimiter
+pub const BRIDGE_FRAME_MAX: usize = RAW_MAX + 2;
+
+/// Command from the host, decoded by
+/// [`SerialBridge::push()`](struct.SerialBridge.html#method.push)
+pub enum BridgeCommand {
+    /// Send `payload` to `address`, to be answered with
+    /// [`SerialBridge::encode_sent()`](struct.SerialBridge.html#method.encode_sent)
+    Send {
+        /// Destination
+        address: Address,
+        /// Packet to send
+        payload: Payload,
+    },
+    /// Switch to another channel
+    SetChannel(Channel),
+}
+
+/// Packet as seen on air, in a stable format for Wireshark
+///
+/// [`encode()`](#method.encode) produces the layout that the Lua
+/// dissector in `tools/wireshark/nrf24.lua` decodes, so that an extcap
+/// reading a dongle's
+/// [`SerialBridge::encode_capture()`](struct.SerialBridge.html#method.encode_capture)
+/// frames can show live traffic:
+///
+/// | Offset | Content                                    |
+/// |--------|--------------------------------------------|
+/// | 0..2   | `"N4"`                                     |
+/// | 2      | Version, currently `1`                     |
+/// | 3      | Channel                                    |
+/// | 4      | `STATUS` register as read with the packet  |
+/// | 5      | Pipe, `0x80` set if `RPD` was high         |
+/// | 6      | Address width `w`                          |
+/// | 7..    | Address, least significant byte first      |
+/// | 7 + w  | Payload length `n`                         |
+/// | 8 + w..| Payload                                    |
+pub struct CaptureRecord<'a> {
+    /// Channel the packet was received on
+    pub channel: Channel,
+    /// Address of the pipe it was received on
+    pub address: &'a Address,
+    /// Pipe number
+    pub pipe_no: u8,
+    /// `RPD` as read right after the packet
+    pub rpd: bool,
+    /// `STATUS` register
+    pub status: u8,
+    /// Packet content
+    pub payload: &'a [u8],
+}
+
+impl CaptureRecord<'_> {
+    /// Encode into `out`, returning the length
+    pub fn encode(&self, out: &mut [u8; CAPTURE_MAX_LEN]) -> usize {
+        let address = self.address.as_bytes();
+        let payload = self.payload.get(..32).unwrap_or(self.payload);
+        out[0..2].copy_from_slice(&CAPTURE_MAGIC);
+        out[2] = CAPTURE_VERSION;
+        out[3] = self.channel.into();
+        out[4] = self.status;
+        out[5] = (self.pipe_no & 0x07) | if self.rpd { 0x80 } else { 0 };
+        out[6] = address.len() as u8;
+        let len = put(out, 7, address);
+        let len = put(out, len, &[payload.len() as u8]);
+        put(out, len, payload)
+    }
+}
+
+/// Frames radio traffic over a serial link, for USB or UART dongles
+/// that let a PC tool talk to the radio network
+///
+/// Frames are COBS encoded and end with `0x00`, so that the host can
+/// resynchronize after lost bytes. Unencoded, they start with a type
+/// byte:
+///
+/// | Type   | Direction | Content                                       |
+/// |--------|-----------|-----------------------------------------------|
+/// | `0x01` | Uplink    | Pipe, `RPD` (0 or 1), payload                 |
+/// | `0x02` | Uplink    | Acknowledged (0 or 1), retransmits            |
+/// | `0x03` | Uplink    | [Capture record](struct.CaptureRecord.html)   |
+/// | `0x81` | Downlink  | Address width, address (LSB first), payload   |
+/// | `0x82` | Downlink  | Channel                                       |
+///
+/// The bridge only does the framing. The dongle's firmware writes the
+/// encoded frames to its serial port, feeds received bytes to
+/// [`push()`](#method.push), and carries out the commands.
+pub struct SerialBridge {
+    buf: [u8; BRIDGE_FRAME_MAX],
+    len: usize,
+    /// The current frame is too long, skip to the next delimiter
+    overflow: bool,
+}
+
+impl Default for SerialBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialBridge {
+    /// Constructor
+    pub const fn new() -> Self {
+        SerialBridge {
+            buf: [0; BRIDGE_FRAME_MAX],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Encode a packet received on `pipe_no` into `out`, with `rpd` as
+    /// read right after it, and return the encoded length
+    pub fn encode_packet(
+        pipe_no: u8,
+        rpd: bool,
+        payload: &[u8],
+        out: &mut [u8; BRIDGE_FRAME_MAX],
+    ) -> usize {
+        let mut raw = [0; RAW_MAX];
+        raw[0] = UP_PACKET;
+        raw[1] = pipe_no;
+        raw[2] = u8::from(rpd);
+        let len = put(&mut raw, 3, payload.get(..32).unwrap_or(payload));
+        encode(raw.get(..len).unwrap_or_default(), out)
+    }
+
+    /// Encode the result of a
+    /// [`BridgeCommand::Send`](enum.BridgeCommand.html#variant.Send)
+    /// into `out`, and return the encoded length
+    pub fn encode_sent(acked: bool, retransmits: u8, out: &mut [u8; BRIDGE_FRAME_MAX]) -> usize {
+        encode(&[UP_SENT, u8::from(acked), retransmits], out)
+    }
+
+    /// Encode a capture record into `out`, and return the encoded
+    /// length
+    ///
+    /// For sniffer dongles, in place of
+    /// [`encode_packet()`](#method.encode_packet).
+    pub fn encode_capture(record: &CaptureRecord, out: &mut [u8; BRIDGE_FRAME_MAX]) -> usize {
+        let mut raw = [0; RAW_MAX];
+        raw[0] = UP_CAPTURE;
+        let mut encoded = [0; CAPTURE_MAX_LEN];
+        let len = record.encode(&mut encoded);
+        let len = put(&mut raw, 1, encoded.get(..len).unwrap_or_default());
+        encode(raw.get(..len).unwrap_or_default(), out)
+    }
+
+    /// Feed a byte received from the host, returning a command once a
+    /// complete and valid frame has arrived
+    ///
+    /// Invalid and unknown frames are dropped, and so are sends of an
+    /// empty payload or one longer than 32 bytes.
+    pub fn push(&mut self, byte: u8) -> Option<BridgeCommand> {
+        if byte != 0 {
+            if let Some(slot) = self.buf.get_mut(self.len) {
+                *slot = byte;
+                self.len = self.len.saturating_add(1);
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+        let len = self.len;
+        let overflow = self.overflow;
+        self.len = 0;
+        self.overflow = false;
+        if overflow {
+            return None;
+        }
+        let mut raw = [0; RAW_MAX];
+        let raw_len = decode(self.buf.get(..len)?, &mut raw)?;
+        parse(raw.get(..raw_len)?)
+    }
+}
+
+/// Copy `bytes` into `out` at `at`, returning the position after them
+fn put(out: &mut [u8], at: usize, bytes: &[u8]) -> usize {
+    at.saturating_add(copy_bytes(out.get_mut(at..).unwrap_or_default(), bytes))
+}
+
+/// Set `out[at]`, if there is such a byte
+fn set(out: &mut [u8], at: usize, byte: u8) {
+    if let Some(slot) = out.get_mut(at) {
+        *slot = byte;
+    }
+}
+
+/// COBS encode `raw` followed by the delimiter, returning the length
+fn encode(raw: &[u8], out: &mut [u8; BRIDGE_FRAME_MAX]) -> usize {
+    // Frames are shorter than 254 bytes, so one code byte per zero
+    let mut code_at = 0;
+    let mut len = 1_usize;
+    for &byte in raw {
+        if byte == 0 {
+            set(out, code_at, len.saturating_sub(code_at) as u8);
+            code_at = len;
+        } else {
+            set(out, len, byte);
+        }
+        len = len.saturating_add(1);
+    }
+    set(out, code_at, len.saturating_sub(code_at) as u8);
+    set(out, len, 0);
+    len.saturating_add(1).min(out.len())
+}
+
+/// COBS decode `encoded`, without the delimiter, into `raw`
+fn decode(encoded: &[u8], raw: &mut [u8; RAW_MAX]) -> Option<usize> {
+    let mut len = 0_usize;
+    let mut pos = 0_usize;
+    while let Some(&code) = encoded.get(pos) {
+        let code = usize::from(code);
+        let block = encoded.get(pos.saturating_add(1)..pos.saturating_add(code))?;
+        let end = len.saturating_add(block.len());
+        raw.get_mut(len..end)?.copy_from_slice(block);
+        len = end;
+        pos = pos.saturating_add(code);
+        if pos < encoded.len() {
+            *raw.get_mut(len)? = 0;
+            len = len.saturating_add(1);
+        }
+    }
+    Some(len)
+}
+
+/// Parse an unencoded downlink frame
+fn parse(raw: &[u8]) -> Option<BridgeCommand> {
+    match *raw {
+        [DOWN_SEND, width, ref rest @ ..] => {
+            let width = usize::from(width);
+            let address = Address::new(rest.get(..width)?).ok()?;
+            let payload = rest.get(width..)?;
+            // Payload::new() would cut a longer one short
+            if !(1..=32).contains(&payload.len()) {
+                return None;
+            }
+            Some(BridgeCommand::Send {
+                address,
+                payload: Payload::new(payload),
+            })
+        }
+        [DOWN_CHANNEL, channel] => Channel::new(channel).ok().map(BridgeCommand::SetChannel),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed the frame `raw` to `bridge`, COBS encoded
+    fn feed(bridge: &mut SerialBridge, raw: &[u8]) -> Option<BridgeCommand> {
+        let mut out = [0; BRIDGE_FRAME_MAX];
+        let len = encode(raw, &mut out);
+        out[..len].iter().find_map(|&byte| bridge.push(byte))
+    }
+
+    /// A send of `len` bytes to `[0xE7; 5]`
+    fn send(len: usize) -> Vec<u8> {
+        let mut raw = vec![DOWN_SEND, 5, 0xE7, 0xE7, 0xE7, 0xE7, 0xE7];
+        raw.resize(raw.len() + len, 0x55);
+        raw
+    }
+
+    #[test]
+    fn send_is_decoded() {
+        let mut bridge = SerialBridge::new();
+        match feed(&mut bridge, &send(32)) {
+            Some(BridgeCommand::Send { address, payload }) => {
+                assert_eq!(*address, [0xE7; 5]);
+                assert_eq!(*payload, [0x55; 32]);
+            }
+            _ => panic!("send not decoded"),
+        }
+    }
+
+    #[test]
+    fn send_must_fit_a_packet() {
+        let mut bridge = SerialBridge::new();
+        assert!(feed(&mut bridge, &send(33)).is_none());
+        assert!(feed(&mut bridge, &send(0)).is_none());
+        // Later frames get through
+        assert!(feed(&mut bridge, &send(1)).is_some());
+    }
+}
@@ -0,0 +1,331 @@
+use crate::address::Address;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::payload::Payload;
+use crate::rx::RxMode;
+use crate::strategy::Strategy;
+use crate::timer::DelayTimer;
+use crate::tx::TxMode;
+use crate::PIPES_COUNT;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Outcome of polling one slave with
+/// [`BusMaster::poll_next()`](struct.BusMaster.html#method.poll_next)
+pub enum BusPoll {
+    /// The slave at this index acknowledged, with its answer if it had
+    /// one loaded
+    Answered(usize, Option<Payload>),
+    /// The slave at this index did not acknowledge within the timeout
+    Missed(usize),
+    /// The slave at this index is considered dead and was not polled
+    Skipped(usize),
+}
+
+/// Master of a polled bus, in the manner of RS485 multi-drop links
+///
+/// Polls its slaves round-robin, one per call of
+/// [`poll_next()`](#method.poll_next), so that each read takes a
+/// bounded time and no two slaves ever send at once. Slaves answer in
+/// ACK payloads, see [`BusSlave`](struct.BusSlave.html).
+///
+/// A slave that misses `dead_after` polls in a row is considered dead
+/// and skipped, so that it does not cost the timeout every round. Every
+/// `retry_after` rounds it is polled once more, and back in the round
+/// once it answers.
+///
+/// The master needs auto-ack and auto-retransmit, and dynamic payload
+/// lengths with `EN_ACK_PAY`. Leaves its TX and pipe 0 addresses at the
+/// last slave polled.
+pub struct BusMaster<const N: usize> {
+    slaves: [Address; N],
+    timeout_us: u32,
+    dead_after: u8,
+    retry_after: u16,
+    misses: [u8; N],
+    skipped: [u16; N],
+    next: usize,
+}
+
+impl<const N: usize> BusMaster<N> {
+    /// Constructor, waiting `timeout_us` for each slave's ACK
+    pub fn new(slaves: [Address; N], timeout_us: u32, dead_after: u8, retry_after: u16) -> Self {
+        BusMaster {
+            slaves,
+            timeout_us,
+            dead_after: dead_after.max(1),
+            retry_after,
+            misses: [0; N],
+            skipped: [0; N],
+            next: 0,
+        }
+    }
+
+    /// Addresses of the slaves, in polling order
+    pub fn slaves(&self) -> &[Address; N] {
+        &self.slaves
+    }
+
+    /// Whether the slave at `index` is in the round, i.e. has not
+    /// missed `dead_after` polls in a row
+    pub fn is_alive(&self, index: usize) -> bool {
+        self.misses.get(index).is_some_and(|&misses| misses < self.dead_after)
+    }
+
+    /// Put the slave at `index` back in the round, e.g. after it was
+    /// replaced
+    pub fn revive(&mut self, index: usize) {
+        if let (Some(misses), Some(skipped)) =
+            (self.misses.get_mut(index), self.skipped.get_mut(index))
+        {
+            *misses = 0;
+            *skipped = 0;
+        }
+    }
+
+    /// Poll the next slave with `request`. Skips right away if there
+    /// are no slaves.
+    pub async fn poll_next<D: Device, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        request: &[u8],
+        delay: &mut DL,
+    ) -> Result<BusPoll, D::Error> {
+        let index = self.next;
+        let Some(slave) = self.slaves.get(index) else {
+            return Ok(BusPoll::Skipped(index));
+        };
+        let next = index.saturating_add(1);
+        self.next = if next < N { next } else { 0 };
+
+        if !self.is_alive(index) {
+            if let Some(skipped) = self.skipped.get_mut(index) {
+                if *skipped < self.retry_after {
+                    *skipped = skipped.saturating_add(1);
+                    return Ok(BusPoll::Skipped(index));
+                }
+                *skipped = 0;
+            }
+        }
+
+        // Settings must not change while sending
+        tx.wait_empty().await?;
+        tx.set_tx_addr(slave).await?;
+        tx.set_rx_addr(0, slave).await?;
+        tx.send(request).await?;
+        let acked = self.wait_sent(tx, delay).await?;
+        if let Some(misses) = self.misses.get_mut(index) {
+            *misses = if acked { 0 } else { misses.saturating_add(1) };
+        }
+        if !acked {
+            return Ok(BusPoll::Missed(index));
+        }
+        let answer = tx.read_ack_payload().await?;
+        Ok(BusPoll::Answered(index, answer))
+    }
+
+    /// Poll every slave once with `request`, starting with the next.
+    /// Returns which slaves answered, in the order of
+    /// [`slaves()`](#method.slaves).
+    ///
+    /// `on_answer` gets the index and answer of each slave that had one
+    /// loaded.
+    pub async fn round<D, DL, F>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        request: &[u8],
+        delay: &mut DL,
+        mut on_answer: F,
+    ) -> Result<[bool; N], D::Error>
+    where
+        D: Device,
+        DL: DelayNs,
+        F: FnMut(usize, &[u8]),
+    {
+        let mut answered = [false; N];
+        for _ in 0..N {
+            if let BusPoll::Answered(index, answer) = self.poll_next(tx, request, delay).await? {
+                if let Some(answered) = answered.get_mut(index) {
+                    *answered = true;
+                }
+                if let Some(answer) = answer {
+                    on_answer(index, &answer);
+                }
+            }
+        }
+        Ok(answered)
+    }
+
+    /// Wait up to the timeout for the transmission to complete, and
+    /// return whether it was acknowledged. Flushes it on timeout.
+    async fn wait_sent<D: Device, DL: DelayNs>(
+        &self,
+        tx: &mut TxMode<D>,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let mut timer = DelayTimer::new(delay);
+        tx.wait_send_for(&mut Strategy::polling(POLL_INTERVAL_US), &mut timer, self.timeout_us)
+            .await
+    }
+}
+
+/// Slave of a polled bus, see [`BusMaster`](struct.BusMaster.html)
+///
+/// Keeps the latest answer loaded as ACK payload for the master's
+/// polls on its pipe, so that each poll gets it without waiting for the
+/// slave to react. Set it with [`update()`](#method.update) whenever
+/// the data changes, and call [`serve()`](#method.serve) for every
+/// packet received. Needs dynamic payload lengths and `EN_ACK_PAY`, and
+/// auto-ack on the pipe.
+pub struct BusSlave {
+    pipe_no: u8,
+    answer: Option<Payload>,
+}
+
+impl BusSlave {
+    /// Constructor, for polls received on `pipe_no`
+    pub fn new(pipe_no: u8) -> Result<Self, InvalidArgument> {
+        if usize::from(pipe_no) >= PIPES_COUNT {
+            return Err(InvalidArgument::Pipe);
+        }
+        Ok(BusSlave {
+            pipe_no,
+            answer: None,
+        })
+    }
+
+    /// Replace the answer to the next polls
+    pub async fn update<D: Device>(
+        &mut self,
+        rx: &mut RxMode<D>,
+        answer: &[u8],
+    ) -> Result<(), D::Error> {
+        if answer.len() > 32 {
+            return Err(InvalidArgument::PayloadLength.into());
+        }
+        rx.flush_ack_payloads(Some(self.pipe_no)).await?;
+        rx.queue_ack_payload(self.pipe_no, answer).await?;
+        self.answer = Some(Payload::new(answer));
+        Ok(())
+    }
+
+    /// Read the next received packet, or `None` if there is none, and
+    /// load the answer again after a poll on the bus pipe took it
+    pub async fn serve<D: Device>(
+        &mut self,
+        rx: &mut RxMode<D>,
+    ) -> Result<Option<(u8, Payload)>, D::Error> {
+        let packet = rx.read_with_pipe().await?;
+        if let (Some((pipe_no, _)), Some(answer)) = (&packet, &self.answer) {
+            if *pipe_no == self.pipe_no {
+                rx.queue_ack_payload(self.pipe_no, answer).await?;
+            }
+        }
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimRadio, SimTimer};
+    use crate::{RadioConfig, StandbyMode};
+
+    fn address(byte: u8) -> Address {
+        Address::from_array([byte; 5])
+    }
+
+    /// A slave chip in Standby-I, listening on pipe 1 at `address(byte)`
+    async fn slave_standby(air: &Air, byte: u8) -> StandbyMode<SimRadio> {
+        let mut config = RadioConfig::default();
+        config.rx_addrs[1] = Some(address(byte));
+        let mut standby = sim::standby(air, &config).await;
+        standby.set_ack_payloads(true).await.unwrap();
+        standby
+    }
+
+    async fn master(air: &Air) -> TxMode<SimRadio> {
+        let mut standby = sim::standby(air, &RadioConfig::default()).await;
+        standby.set_ack_payloads(true).await.unwrap();
+        standby.tx().await.map_err(|(_, e)| e).unwrap()
+    }
+
+    #[test]
+    fn round_collects_answers() {
+        block_on(async {
+            let air = Air::new();
+            let mut tx = master(&air).await;
+            let mut rxs = [
+                slave_standby(&air, 0xA1).await.rx().await.map_err(|(_, e)| e).unwrap(),
+                slave_standby(&air, 0xA2).await.rx().await.map_err(|(_, e)| e).unwrap(),
+            ];
+            let mut slaves = [BusSlave::new(1).unwrap(), BusSlave::new(1).unwrap()];
+            for ((slave, rx), answer) in slaves.iter_mut().zip(&mut rxs).zip([b"s1", b"s2"]) {
+                slave.update(rx, answer).await.unwrap();
+            }
+            let mut bus = BusMaster::new([address(0xA1), address(0xA2)], 1_000, 2, 4);
+            let mut timer = SimTimer::new();
+
+            for _ in 0..2 {
+                let mut answers = Vec::new();
+                let answered = bus
+                    .round(&mut tx, b"poll", &mut timer, |index, answer| {
+                        answers.push((index, answer.to_vec()))
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(answered, [true, true]);
+                assert_eq!(answers, [(0, b"s1".to_vec()), (1, b"s2".to_vec())]);
+                // Each slave loads its answer again for the next round
+                for (slave, rx) in slaves.iter_mut().zip(&mut rxs) {
+                    let (pipe_no, packet) = slave.serve(rx).await.unwrap().unwrap();
+                    assert_eq!((pipe_no, &packet[..]), (1, &b"poll"[..]));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn dead_slave_is_skipped_and_retried() {
+        block_on(async {
+            let air = Air::new();
+            let mut tx = master(&air).await;
+            // Not listening yet
+            let standby = slave_standby(&air, 0xA1).await;
+            let mut bus = BusMaster::new([address(0xA1)], 1_000, 2, 2);
+            let mut timer = SimTimer::new();
+            for _ in 0..2 {
+                let polled = bus.poll_next(&mut tx, b"poll", &mut timer).await.unwrap();
+                assert!(matches!(polled, BusPoll::Missed(0)));
+            }
+            assert!(!bus.is_alive(0));
+
+            let mut rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+            let mut slave = BusSlave::new(1).unwrap();
+            slave.update(&mut rx, b"up").await.unwrap();
+            for _ in 0..2 {
+                let polled = bus.poll_next(&mut tx, b"poll", &mut timer).await.unwrap();
+                assert!(matches!(polled, BusPoll::Skipped(0)));
+            }
+            let polled = bus.poll_next(&mut tx, b"poll", &mut timer).await.unwrap();
+            assert!(matches!(polled, BusPoll::Answered(0, Some(answer)) if &answer[..] == b"up"));
+            assert!(bus.is_alive(0));
+        });
+    }
+
+    #[test]
+    fn slave_checks_its_arguments() {
+        assert!(BusSlave::new(PIPES_COUNT as u8).is_err());
+        let mut bus = BusMaster::<0>::new([], 1_000, 2, 2);
+        block_on(async {
+            let air = Air::new();
+            let mut tx = master(&air).await;
+            let polled = bus.poll_next(&mut tx, b"poll", &mut SimTimer::new()).await.unwrap();
+            assert!(matches!(polled, BusPoll::Skipped(0)));
+        });
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::InvalidArgument;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+/// Lowest channel that the common WiFi channels 1 to 11 leave free
+const ABOVE_WIFI: u8 = 76;
+
+/// RF channel, that is a frequency of `2400 + n` MHz for `n` from 0 to
+/// 125
+///
+/// Many regions only permit 2400 to 2483.5 MHz, that is channels 0 to
+/// 83. Channels read back from the chip can also be 126 and 127, which
+/// `RF_CH` holds although the datasheet specifies no frequency for them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Channel(u8);
+
+impl Channel {
+    /// Lowest channel
+    pub const MIN: Channel = Channel(0);
+    /// Highest channel the chip supports
+    pub const MAX: Channel = Channel(125);
+
+    /// Channel `n`, below 126
+    pub const fn new(n: u8) -> Result<Self, InvalidArgument> {
+        if n > Self::MAX.0 {
+            return Err(InvalidArgument::Channel);
+        }
+        Ok(Channel(n))
+    }
+
+    /// Channel as held by `RF_CH`, up to 127
+    pub(crate) const fn from_register(rf_ch: u8) -> Self {
+        Channel(rf_ch & 0x7F)
+    }
+
+    /// Channel `n`, or `MAX` above, e.g. for constants
+    pub(crate) const fn clamped(n: u8) -> Self {
+        if n > Self::MAX.0 {
+            Self::MAX
+        } else {
+            Channel(n)
+        }
+    }
+
+    /// Channels of `range` in ascending order, e.g. to scan
+    /// `Channel::MIN..=Channel::MAX`
+    pub fn range(range: RangeInclusive<Channel>) -> impl Iterator<Item = Channel> + Clone {
+        (range.start().0..=range.end().0).map(Channel)
+    }
+
+    /// Channel on `mhz`, 2400 to 2525
+    pub fn from_mhz(mhz: u16) -> Result<Self, InvalidArgument> {
+        let n = mhz.checked_sub(2400).ok_or(InvalidArgument::Channel)?;
+        Self::new(u8::try_from(n).map_err(|_| InvalidArgument::Channel)?)
+    }
+
+    /// Channels 76 and up, above WiFi channels 1 to 11
+    ///
+    /// WiFi channels 12 and 13, where permitted, reach up to channel 83.
+    pub fn above_wifi() -> impl Iterator<Item = Channel> {
+        Self::range(Channel(ABOVE_WIFI)..=Self::MAX)
+    }
+
+    /// Channel number
+    pub const fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Frequency in MHz
+    pub fn mhz(self) -> u16 {
+        2400 + u16::from(self.0)
+    }
+
+    /// Is this above WiFi channels 1 to 11, see
+    /// [`above_wifi()`](#method.above_wifi)?
+    pub fn is_above_wifi(self) -> bool {
+        self.0 >= ABOVE_WIFI
+    }
+
+    /// Does this overlap with the 22 MHz of 2.4 GHz WiFi channel
+    /// `wifi`, 1 to 14?
+    pub fn overlaps_wifi_channel(self, wifi: u8) -> bool {
+        let center = match wifi {
+            1..=13 => u16::from(wifi).saturating_mul(5).saturating_add(2407),
+            14 => 2484,
+            _ => return false,
+        };
+        // Half the WiFi bandwidth plus half of 2 MHz at 2 Mbps
+        self.mhz().abs_diff(center) < 11 + 1
+    }
+}
+
+/// Channel 2, as after reset
+impl Default for Channel {
+    fn default() -> Self {
+        Channel(2)
+    }
+}
+
+impl TryFrom<u8> for Channel {
+    type Error = InvalidArgument;
+
+    fn try_from(n: u8) -> Result<Self, InvalidArgument> {
+        Self::new(n)
+    }
+}
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> u8 {
+        channel.0
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} MHz", self.mhz())
+    }
+}
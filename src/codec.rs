@@ -0,0 +1,571 @@
+//! Self-checks of the register and command codecs
+//!
+//! Entry points for property tests and fuzzers on the host, e.g. a
+//! `proptest` strategy or a `cargo fuzz` target feeding arbitrary bytes
+//! into [`register_roundtrip()`](fn.register_roundtrip.html) and
+//! [`command_roundtrip()`](fn.command_roundtrip.html). They return an
+//! error instead of panicking, so assert on the result.
+//!
+//! [`check_layout()`](fn.check_layout.html) compares every bitfield
+//! against the bit positions of the datasheet, section 9, to catch
+//! offset regressions that a round trip cannot.
+//!
+//! [`esb_frame()`](fn.esb_frame.html) builds the exact Enhanced
+//! ShockBurst frames the chip puts on air, to verify interoperability
+//! with other stacks on the host, e.g. an SDR modulator or a Nordic SDK
+//! peer. [`REGRESSION_FRAMES`](constant.REGRESSION_FRAMES.html) pin
+//! down its output, and [`check_golden()`](fn.check_golden.html) checks
+//! them along with the published check values of both CRCs.
+//!
+//! [`ble_frame()`](fn.ble_frame.html) builds the payload that makes the
+//! chip send a Bluetooth LE advertising packet, as RF24's `BTLE`
+//! library does. RF24 itself sends plain Enhanced ShockBurst frames,
+//! which `esb_frame()` covers with its default settings.
+
+use crate::command::{Command, ReadRegister, WriteRegister};
+use crate::config::CrcMode;
+use crate::payload::copy_bytes;
+use crate::registers::*;
+use crate::sniff::{decode_esb, encode_esb, esb_crc};
+
+/// A codec check failed
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CodecError {
+    /// Decoding and encoding the register at this address changed it
+    Register(u8),
+    /// The command frame for the register at this address is wrong
+    Command(u8),
+    /// This bitfield is not where the datasheet puts it
+    Layout(&'static str),
+    /// The address is not 1 to 5 bytes long, or the payload is longer
+    /// than 32 bytes
+    Frame,
+    /// The regression frame at this index does not match
+    Golden(usize),
+    /// A CRC does not yield its published check value
+    Crc,
+    /// The BLE PDU is longer than 29 bytes, or the channel is not an
+    /// advertising channel
+    Ble,
+}
+
+/// Decode `data` as the value of the register at `addr` and check
+/// that encoding it again yields the same bytes
+///
+/// Unknown addresses and too short `data` pass.
+pub fn register_roundtrip(addr: u8, data: &[u8]) -> Result<(), CodecError> {
+    macro_rules! dispatch {
+        ($($name: ident),+) => {
+            $(
+                if addr == $name::addr() {
+                    return roundtrip::<$name>(data);
+                }
+            )+
+        };
+    }
+    dispatch!(
+        Config, EnAa, EnRxaddr, SetupAw, SetupRetr, RfCh, RfSetup, Status, ObserveTx, CD,
+        RxAddrP0, RxAddrP1, RxAddrP2, RxAddrP3, RxAddrP4, RxAddrP5, TxAddr, RxPwP0, RxPwP1,
+        RxPwP2, RxPwP3, RxPwP4, RxPwP5, FifoStatus, Dynpd, Feature
+    );
+    Ok(())
+}
+
+fn roundtrip<R: Register>(data: &[u8]) -> Result<(), CodecError> {
+    let Some(data) = data.get(..R::read_len()) else {
+        return Ok(());
+    };
+    let register = R::decode(data);
+    let mut buf = [0; 5];
+    let len = register.write_len();
+    let encoded = buf.get_mut(..len).ok_or(CodecError::Register(R::addr()))?;
+    register.encode(encoded);
+    if data.get(..len) != Some(&*encoded) {
+        return Err(CodecError::Register(R::addr()));
+    }
+    Ok(())
+}
+
+/// Build `W_REGISTER` and `R_REGISTER` frames for the register at
+/// `data[0]` with the value in the remaining bytes, and check opcodes,
+/// lengths and contents
+///
+/// Unknown addresses and too short `data` pass.
+pub fn command_roundtrip(data: &[u8]) -> Result<(), CodecError> {
+    let Some((&addr, value)) = data.split_first() else {
+        return Ok(());
+    };
+    macro_rules! dispatch {
+        ($($name: ident),+) => {
+            $(
+                if addr == $name::addr() {
+                    return frames::<$name>(value);
+                }
+            )+
+        };
+    }
+    dispatch!(
+        Config, EnAa, EnRxaddr, SetupAw, SetupRetr, RfCh, RfSetup, Status, ObserveTx, CD,
+        RxAddrP0, RxAddrP1, RxAddrP2, RxAddrP3, RxAddrP4, RxAddrP5, TxAddr, RxPwP0, RxPwP1,
+        RxPwP2, RxPwP3, RxPwP4, RxPwP5, FifoStatus, Dynpd, Feature
+    );
+    Ok(())
+}
+
+fn frames<R: Register>(value: &[u8]) -> Result<(), CodecError> {
+    let Some(value) = value.get(..R::read_len()) else {
+        return Ok(());
+    };
+    let error = CodecError::Command(R::addr());
+    let mut buf = [0; 6];
+
+    let write = WriteRegister::new(R::decode(value));
+    let frame = buf.get_mut(..write.len()).ok_or(error)?;
+    write.encode(frame);
+    let Some((&opcode, written)) = frame.split_first() else {
+        return Err(error);
+    };
+    if opcode != 0b10_0000 | R::addr() || value.get(..written.len()) != Some(written) {
+        return Err(error);
+    }
+
+    let read = ReadRegister::<R>::new();
+    let frame = buf.get_mut(..read.len()).ok_or(error)?;
+    read.encode(frame);
+    if frame.first() != Some(&R::addr()) {
+        return Err(error);
+    }
+    // Response as clocked in on MISO
+    copy_bytes(frame.get_mut(1..).unwrap_or_default(), value);
+    let response = ReadRegister::<R>::decode_response(frame);
+    let mut encoded = [0; 5];
+    let encoded = encoded.get_mut(..response.write_len()).ok_or(error)?;
+    response.encode(encoded);
+    if value.get(..encoded.len()) != Some(&*encoded) {
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn raw<R: Register>(register: &R) -> u8 {
+    let mut buf = [0];
+    register.encode(&mut buf);
+    buf[0]
+}
+
+/// Check every bitfield against the datasheet
+pub fn check_layout() -> Result<(), CodecError> {
+    macro_rules! fail {
+        ($reg: ident, $get: ident) => {
+            return Err(CodecError::Layout(concat!(stringify!($reg), "::", stringify!($get))))
+        };
+    }
+    // Writable single bit
+    macro_rules! flag {
+        ($reg: ident, $get: ident, $set: ident, $bit: expr) => {{
+            let mut register = $reg::decode(&[0]);
+            register.$set(true);
+            if raw(&register) != 1 << $bit || !$reg::decode(&[1 << $bit]).$get() {
+                fail!($reg, $get);
+            }
+        }};
+    }
+    // Read-only single bit
+    macro_rules! ro_flag {
+        ($reg: ident, $get: ident, $bit: expr) => {{
+            if !$reg::decode(&[1 << $bit]).$get() || $reg::decode(&[!(1 << $bit)]).$get() {
+                fail!($reg, $get);
+            }
+        }};
+    }
+    // Writable bits `hi` down to `lo`
+    macro_rules! field {
+        ($reg: ident, $get: ident, $set: ident, $hi: expr, $lo: expr) => {{
+            let ones = ((1u16 << ($hi - $lo + 1)) - 1) as u8;
+            let mut register = $reg::decode(&[0]);
+            register.$set(ones);
+            if raw(&register) != ones << $lo || $reg::decode(&[ones << $lo]).$get() != ones {
+                fail!($reg, $get);
+            }
+        }};
+    }
+    // Read-only bits `hi` down to `lo`
+    macro_rules! ro_field {
+        ($reg: ident, $get: ident, $hi: expr, $lo: expr) => {{
+            let ones = ((1u16 << ($hi - $lo + 1)) - 1) as u8;
+            if $reg::decode(&[ones << $lo]).$get() != ones
+                || $reg::decode(&[!(ones << $lo)]).$get() != 0
+            {
+                fail!($reg, $get);
+            }
+        }};
+    }
+
+    flag!(Config, mask_rx_dr, set_mask_rx_dr, 6);
+    flag!(Config, mask_tx_ds, set_mask_tx_ds, 5);
+    flag!(Config, mask_max_rt, set_mask_max_rt, 4);
+    flag!(Config, en_crc, set_en_crc, 3);
+    flag!(Config, crco, set_crco, 2);
+    flag!(Config, pwr_up, set_pwr_up, 1);
+    flag!(Config, prim_rx, set_prim_rx, 0);
+    field!(SetupAw, aw, set_aw, 1, 0);
+    field!(SetupRetr, ard, set_ard, 7, 4);
+    field!(SetupRetr, arc, set_arc, 3, 0);
+    field!(RfCh, rf_ch, set_rf_ch, 6, 0);
+    flag!(RfSetup, rf_dr_low, set_rf_dr_low, 5);
+    flag!(RfSetup, rf_dr_high, set_rf_dr_high, 3);
+    field!(RfSetup, rf_pwr, set_rf_pwr, 2, 1);
+    flag!(RfSetup, lna_hcurr, set_lna_hcurr, 0);
+    flag!(Status, rx_dr, set_rx_dr, 6);
+    flag!(Status, tx_ds, set_tx_ds, 5);
+    flag!(Status, max_rt, set_max_rt, 4);
+    ro_field!(Status, rx_p_no, 3, 1);
+    ro_flag!(Status, tx_full, 0);
+    ro_field!(ObserveTx, plos_cnt, 7, 4);
+    ro_field!(ObserveTx, arc_cnt, 3, 0);
+    field!(RxPwP0, get, set, 5, 0);
+    field!(RxPwP1, get, set, 5, 0);
+    field!(RxPwP2, get, set, 5, 0);
+    field!(RxPwP3, get, set, 5, 0);
+    field!(RxPwP4, get, set, 5, 0);
+    field!(RxPwP5, get, set, 5, 0);
+    ro_flag!(FifoStatus, tx_reuse, 6);
+    ro_flag!(FifoStatus, tx_full, 5);
+    ro_flag!(FifoStatus, tx_empty, 4);
+    ro_flag!(FifoStatus, rx_full, 1);
+    ro_flag!(FifoStatus, rx_empty, 0);
+    flag!(Feature, en_dpl, set_en_dpl, 2);
+    flag!(Feature, en_ack_pay, set_en_ack_pay, 1);
+    flag!(Feature, en_dyn_ack, set_en_dyn_ack, 0);
+    Ok(())
+}
+
+/// Longest frame from [`esb_frame()`](fn.esb_frame.html), in bytes
+pub const ESB_FRAME_MAX: usize = crate::sniff::ESB_FRAME_MAX;
+
+/// Build the on-air Enhanced ShockBurst frame for `payload`, from the
+/// preamble to the CRC, and return its length in bits
+///
+/// `address` is in register order, least significant byte first, like
+/// [`Address`](../struct.Address.html). `pid` is the 2 bit packet ID
+/// that the chip increments for each new packet, and `no_ack` is set
+/// for `W_TX_PAYLOAD_NOACK`. The 9 bit packet control field leaves the
+/// frame unaligned, so the last byte is padded with zeros.
+pub fn esb_frame(
+    address: &[u8],
+    pid: u8,
+    no_ack: bool,
+    payload: &[u8],
+    crc: CrcMode,
+    out: &mut [u8; ESB_FRAME_MAX],
+) -> Result<usize, CodecError> {
+    let crc_len = match crc {
+        CrcMode::Disabled => 0,
+        CrcMode::OneByte => 1,
+        CrcMode::TwoBytes => 2,
+    };
+    encode_esb(address, pid, no_ack, payload, crc_len, out).ok_or(CodecError::Frame)
+}
+
+/// Address to configure for [`ble_frame()`](fn.ble_frame.html), in
+/// register order: the BLE advertising access address `0x8E89BED6`
+/// with its bits reversed, as the chip sends most significant bits first
+pub const BLE_ADDRESS: [u8; 4] = [0x71, 0x91, 0x7D, 0x6B];
+
+/// RF channels of the BLE advertising channels 37, 38 and 39
+pub const BLE_ADV_CHANNELS: [(u8, u8); 3] = [(37, 2), (38, 26), (39, 80)];
+
+/// Longest BLE PDU for [`ble_frame()`](fn.ble_frame.html): 32 payload
+/// bytes less the 3 byte CRC
+pub const BLE_PDU_MAX: usize = 29;
+
+/// CRC-24/BLE over `data`, least significant bits first, as a
+/// reflected register
+fn ble_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0xAA_AAAA, |crc, &byte| {
+        (0..8).fold(crc, |crc, i| {
+            let feedback = (crc ^ u32::from(byte >> i)) & 1 != 0;
+            if feedback {
+                (crc >> 1) ^ 0xDA_6000
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Build the payload that makes the chip send the BLE advertising
+/// packet `pdu` on advertising channel `channel` (37 to 39), and return
+/// its length
+///
+/// `pdu` starts with the 2 byte header, e.g. `[0x42, len]` for
+/// `ADV_NONCONN_IND` from a random address, followed by the advertiser
+/// address least significant byte first and the AD structures. The CRC
+/// is appended, everything whitened for `channel` and every byte
+/// reversed. Send it at 1 Mbps with a 4 byte
+/// [`BLE_ADDRESS`](constant.BLE_ADDRESS.html) on the RF channel of
+/// [`BLE_ADV_CHANNELS`](constant.BLE_ADV_CHANNELS.html), with CRC,
+/// auto-ack, auto-retransmit and dynamic payload lengths disabled, so
+/// that the chip sends no packet control field.
+pub fn ble_frame(channel: u8, pdu: &[u8], out: &mut [u8; 32]) -> Result<usize, CodecError> {
+    if pdu.len() > BLE_PDU_MAX || !(37..=39).contains(&channel) {
+        return Err(CodecError::Ble);
+    }
+    let len = pdu.len().saturating_add(3);
+    *out = [0; 32];
+    copy_bytes(out, pdu);
+    let crc = ble_crc(pdu);
+    copy_bytes(out.get_mut(pdu.len()..).unwrap_or_default(), &crc.to_le_bytes()[..3]);
+    // x^7 + x^4 + 1, seeded with 1 and the channel index
+    let mut lfsr = channel.reverse_bits() | 2;
+    for byte in out.get_mut(..len).unwrap_or_default() {
+        for bit in 0..8 {
+            if lfsr & 0x80 != 0 {
+                lfsr ^= 0x11;
+                *byte ^= 1 << bit;
+            }
+            lfsr <<= 1;
+        }
+        *byte = byte.reverse_bits();
+    }
+    Ok(len)
+}
+
+/// Regression frame for [`esb_frame()`](fn.esb_frame.html)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct RegressionFrame {
+    /// Address, in register order
+    pub address: &'static [u8],
+    /// Packet ID
+    pub pid: u8,
+    /// Sent without requesting an ACK
+    pub no_ack: bool,
+    /// Payload
+    pub payload: &'static [u8],
+    /// CRC length
+    pub crc: CrcMode,
+    /// Length of `frame` in bits
+    pub bits: usize,
+    /// On-air bytes from the preamble, the last one padded with zeros
+    pub frame: &'static [u8],
+}
+
+/// Regression vectors of Enhanced ShockBurst frames, following the
+/// packet format of the datasheet, section 7.3: preamble, address most
+/// significant byte first, 6 bit length, 2 bit PID, NO_ACK flag,
+/// payload, and a CRC over everything after the preamble
+///
+/// They were produced by `esb_frame()` and checked by hand against the
+/// datasheet, not captured from hardware, so they catch changes of the
+/// encoder rather than prove it right. The CRCs are checked
+/// independently by [`check_golden()`](fn.check_golden.html).
+///
+/// They cover the reset addresses of pipes 0 and 1, both CRC lengths,
+/// an empty ACK, a full payload with a 3 byte address and a `0x55`
+/// preamble, a frame without CRC, and RF24 defaults: a `"1Node"`
+/// address with a dynamic payload and a 2 byte CRC.
+pub const REGRESSION_FRAMES: &[RegressionFrame] = &[
+    RegressionFrame {
+        address: &[0xE7; 5],
+        pid: 0,
+        no_ack: false,
+        payload: b"hello",
+        crc: CrcMode::TwoBytes,
+        bits: 113,
+        frame: &[
+            0xAA, 0xE7, 0xE7, 0xE7, 0xE7, 0xE7, 0x14, 0x34, 0x32, 0xB6, 0x36, 0x37, 0x94, 0x40,
+            0x00,
+        ],
+    },
+    RegressionFrame {
+        address: &[0xE7; 5],
+        pid: 1,
+        no_ack: false,
+        payload: b"hello",
+        crc: CrcMode::OneByte,
+        bits: 105,
+        frame: &[
+            0xAA, 0xE7, 0xE7, 0xE7, 0xE7, 0xE7, 0x15, 0x34, 0x32, 0xB6, 0x36, 0x37, 0xE2, 0x80,
+        ],
+    },
+    RegressionFrame {
+        address: &[0xC2; 5],
+        pid: 2,
+        no_ack: false,
+        payload: &[],
+        crc: CrcMode::TwoBytes,
+        bits: 73,
+        frame: &[0xAA, 0xC2, 0xC2, 0xC2, 0xC2, 0xC2, 0x02, 0x0A, 0x74, 0x00],
+    },
+    RegressionFrame {
+        address: &[0x35, 0x04, 0x03],
+        pid: 3,
+        no_ack: true,
+        payload: &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B,
+            0x1C, 0x1D, 0x1E, 0x1F,
+        ],
+        crc: CrcMode::TwoBytes,
+        bits: 313,
+        frame: &[
+            0x55, 0x03, 0x04, 0x35, 0x83, 0x80, 0x00, 0x81, 0x01, 0x82, 0x02, 0x83, 0x03, 0x84,
+            0x04, 0x85, 0x05, 0x86, 0x06, 0x87, 0x07, 0x88, 0x08, 0x89, 0x09, 0x8A, 0x0A, 0x8B,
+            0x0B, 0x8C, 0x0C, 0x8D, 0x0D, 0x8E, 0x0E, 0x8F, 0x0F, 0xA0, 0xAD, 0x80,
+        ],
+    },
+    RegressionFrame {
+        address: &[0x01, 0x02, 0x03, 0x04, 0x35],
+        pid: 0,
+        no_ack: true,
+        payload: &[0x00, 0xFF],
+        crc: CrcMode::Disabled,
+        bits: 73,
+        frame: &[0x55, 0x35, 0x04, 0x03, 0x02, 0x01, 0x08, 0x80, 0x7F, 0x80],
+    },
+    RegressionFrame {
+        address: b"1Node",
+        pid: 0,
+        no_ack: false,
+        payload: b"RF24",
+        crc: CrcMode::TwoBytes,
+        bits: 105,
+        frame: &[
+            0x55, 0x65, 0x64, 0x6F, 0x4E, 0x31, 0x10, 0x29, 0x23, 0x19, 0x1A, 0x40, 0xD3, 0x80,
+        ],
+    },
+];
+
+/// Check value of CRC-16/CCITT-FALSE, the 2 byte CRC of the chip
+const CRC16_CHECK: u16 = 0x29B1;
+/// Check value of CRC-24/BLE
+const CRC24_BLE_CHECK: u32 = 0xC25A56;
+
+/// Check that the 2 byte and the BLE CRCs yield their published check values over
+/// `"123456789"`, that [`esb_frame()`](fn.esb_frame.html) reproduces
+/// every regression frame, and that the promiscuous mode decoder
+/// recovers those with a 2 byte CRC
+pub fn check_golden() -> Result<(), CodecError> {
+    if esb_crc(b"123456789", 72, 2) != CRC16_CHECK
+        || ble_crc(b"123456789") != CRC24_BLE_CHECK
+    {
+        return Err(CodecError::Crc);
+    }
+    for (index, golden) in REGRESSION_FRAMES.iter().enumerate() {
+        let mut out = [0; ESB_FRAME_MAX];
+        let bits = esb_frame(
+            golden.address,
+            golden.pid,
+            golden.no_ack,
+            golden.payload,
+            golden.crc,
+            &mut out,
+        )?;
+        if bits != golden.bits || out.get(..golden.frame.len()) != Some(golden.frame) {
+            return Err(CodecError::Golden(index));
+        }
+        // Promiscuous mode never sees empty payloads or 1 byte CRCs
+        if golden.crc == CrcMode::TwoBytes && !golden.payload.is_empty() {
+            let width = golden.address.len();
+            let decoded = out.get(1..).and_then(|raw| decode_esb(raw, width));
+            let matches = decoded.is_some_and(|esb| {
+                esb.address.get(..width) == Some(golden.address)
+                    && esb.pid == golden.pid
+                    && esb.no_ack == golden.no_ack
+                    && *esb.payload == *golden.payload
+            });
+            if !matches {
+                return Err(CodecError::Golden(index));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values of a register longer than a byte: every byte value in
+    /// each position, over a background of zeros and of ones
+    fn values(byte: u8, position: usize) -> [[u8; 5]; 2] {
+        let mut zeros = [0; 5];
+        zeros[position] = byte;
+        let mut ones = [0xFF; 5];
+        ones[position] = byte;
+        [zeros, ones]
+    }
+
+    #[test]
+    fn registers_roundtrip() {
+        for addr in 0..=0x1F {
+            for byte in 0..=u8::MAX {
+                for position in 0..5 {
+                    for value in values(byte, position) {
+                        assert_eq!(register_roundtrip(addr, &value), Ok(()), "{:#x}", addr);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn commands_roundtrip() {
+        for addr in 0..=0x1F {
+            for byte in 0..=u8::MAX {
+                for position in 0..5 {
+                    for value in values(byte, position) {
+                        let mut data = [addr; 6];
+                        data[1..].copy_from_slice(&value);
+                        assert_eq!(command_roundtrip(&data), Ok(()), "{:#x}", addr);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn short_data_passes() {
+        assert_eq!(register_roundtrip(RxAddrP0::addr(), &[1, 2]), Ok(()));
+        assert_eq!(command_roundtrip(&[TxAddr::addr(), 1]), Ok(()));
+        assert_eq!(command_roundtrip(&[]), Ok(()));
+    }
+
+    #[test]
+    fn layout_matches_datasheet() {
+        assert_eq!(check_layout(), Ok(()));
+    }
+
+    #[test]
+    fn regression_frames_and_check_values_match() {
+        assert_eq!(check_golden(), Ok(()));
+        assert_eq!(esb_crc(b"123456789", 72, 2), 0x29B1);
+    }
+
+    #[test]
+    fn ble_frame_carries_crc() {
+        let pdu = [0x42, 0x09, 1, 2, 3, 4, 5, 0xC6, 2, 1, 6];
+        let mut out = [0; 32];
+        assert_eq!(ble_frame(37, &pdu, &mut out), Ok(14));
+        assert_eq!(
+            out[..14],
+            [0xF3, 0xDB, 0x6A, 0xC5, 0x7C, 0xC5, 0xC6, 0x6E, 0xEE, 0x0C, 0xE8, 0xEB, 0x82, 0xAD]
+        );
+        // A reflected CRC over data followed by its CRC leaves zero
+        let mut plain = [0; 32];
+        plain[..pdu.len()].copy_from_slice(&pdu);
+        let crc = ble_crc(&pdu).to_le_bytes();
+        plain[pdu.len()..14].copy_from_slice(&crc[..3]);
+        assert_eq!(ble_crc(&plain[..14]), 0);
+        let mut other = [0; 32];
+        ble_frame(38, &pdu, &mut other).unwrap();
+        assert_ne!(out, other);
+    }
+
+    #[test]
+    fn ble_frame_rejects_bad_input() {
+        let mut out = [0; 32];
+        assert_eq!(ble_frame(36, &[0x42, 0], &mut out), Err(CodecError::Ble));
+        assert_eq!(ble_frame(37, &[0; 30], &mut out), Err(CodecError::Ble));
+    }
+}
@@ -0,0 +1,18 @@
+/// Arbitration of the 2.4 GHz medium with co-located radios, e.g. a BLE
+/// SoC or a WiFi module sharing the antenna or board
+///
+/// Set with
+/// [`TxMode::set_coexistence()`](struct.TxMode.html#method.set_coexistence).
+/// The driver calls `request` whenever it is about to raise `CE` with
+/// packets in the TX FIFO, and only raises it if that returns `true`.
+/// The grant is held until `CE` drops again, when `release` is called.
+/// Both run synchronously in the driver call, so keep them short, e.g.
+/// reading a `GRANT` pin after driving a `REQUEST` pin, or checking
+/// the other stack's schedule.
+#[derive(Debug, Copy, Clone)]
+pub struct Coexistence {
+    /// Ask for the medium, returning whether it was granted
+    pub request: fn() -> bool,
+    /// Give the medium back
+    pub release: fn(),
+}
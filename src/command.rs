@@ -1,7 +1,30 @@
-pub use crate::payload::Payload;
 use crate::registers::Register;
+use crate::payload::copy_bytes;
 use core::marker::PhantomData;
 
+/// Longest command: opcode plus a 32 byte payload
+pub const MAX_COMMAND_LEN: usize = 1 + 32;
+
+/// `R_RX_PAYLOAD` opcode, see `Device::read_payload()`
+pub const R_RX_PAYLOAD: u8 = 0b0110_0001;
+/// `W_TX_PAYLOAD` opcode, see `Device::write_payload()`
+pub const W_TX_PAYLOAD: u8 = 0b1010_0000;
+/// `W_TX_PAYLOAD_NOACK` opcode, requires `EN_DYN_ACK`
+pub const W_TX_PAYLOAD_NOACK: u8 = 0b1011_0000;
+/// `W_ACK_PAYLOAD` opcode, to be or'ed with the pipe number
+pub const W_ACK_PAYLOAD: u8 = 0b1010_1000;
+
+/// Write `opcode` to the start of `buf`, and return the rest
+fn opcode(buf: &mut [u8], opcode: u8) -> &mut [u8] {
+    match buf.split_first_mut() {
+        Some((first, rest)) => {
+            *first = opcode;
+            rest
+        }
+        None => &mut [],
+    }
+}
+
 pub trait Command {
     fn len(&self) -> usize;
     fn encode(&self, data: &mut [u8]);
@@ -24,16 +47,16 @@ impl<R: Register> ReadRegister<R> {
 
 impl<R: Register> Command for ReadRegister<R> {
     fn len(&self) -> usize {
-        1 + R::read_len()
+        R::read_len().saturating_add(1)
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = R::addr();
+        opcode(buf, R::addr());
     }
 
     type Response = R;
     fn decode_response(data: &[u8]) -> Self::Response {
-        R::decode(&data[1..])
+        R::decode(data.get(1..).unwrap_or_default())
     }
 }
 
@@ -49,123 +72,110 @@ impl<R: Register> WriteRegister<R> {
 
 impl<R: Register> Command for WriteRegister<R> {
     fn len(&self) -> usize {
-        1 + self.register.write_len()
+        self.register.write_len().saturating_add(1)
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b10_0000 | R::addr();
-        self.register.encode(&mut buf[1..]);
+        self.register.encode(opcode(buf, 0b10_0000 | R::addr()));
     }
 
     type Response = ();
     fn decode_response(_: &[u8]) -> Self::Response {}
 }
 
-pub struct ReadRxPayload {
-    payload_width: usize,
-}
-
-impl ReadRxPayload {
-    pub fn new(payload_width: usize) -> Self {
-        ReadRxPayload { payload_width }
-    }
-}
+pub struct ReadRxPayloadWidth;
 
-impl Command for ReadRxPayload {
+impl Command for ReadRxPayloadWidth {
     fn len(&self) -> usize {
-        1 + self.payload_width
+        2
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b0110_0001;
+        opcode(buf, 0b0110_0000);
     }
 
-    type Response = Payload;
+    type Response = u8;
     fn decode_response(data: &[u8]) -> Self::Response {
-        Payload::new(&data[1..])
+        data.get(1).copied().unwrap_or(0)
     }
 }
 
-pub struct WriteTxPayload<'a> {
-    data: &'a [u8],
-}
-
-impl<'a> WriteTxPayload<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
-        WriteTxPayload { data }
-    }
-}
+pub struct FlushRx;
 
-impl<'a> Command for WriteTxPayload<'a> {
+impl Command for FlushRx {
     fn len(&self) -> usize {
-        1 + self.data.len()
+        1
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b1010_0000;
-        buf[1..].copy_from_slice(self.data);
+        opcode(buf, 0b1110_0010);
     }
 
     type Response = ();
     fn decode_response(_: &[u8]) -> Self::Response {}
 }
 
-pub struct ReadRxPayloadWidth;
+pub struct FlushTx;
 
-impl Command for ReadRxPayloadWidth {
+impl Command for FlushTx {
     fn len(&self) -> usize {
-        2
+        1
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b0110_0000;
+        opcode(buf, 0b1110_0001);
     }
 
-    type Response = u8;
-    fn decode_response(data: &[u8]) -> Self::Response {
-        data[1]
-    }
+    type Response = ();
+    fn decode_response(_: &[u8]) -> Self::Response {}
 }
 
-pub struct FlushRx;
+pub struct Nop;
 
-impl Command for FlushRx {
+impl Command for Nop {
     fn len(&self) -> usize {
         1
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b1110_0010;
+        opcode(buf, 0b1111_1111);
     }
 
     type Response = ();
     fn decode_response(_: &[u8]) -> Self::Response {}
 }
 
-pub struct FlushTx;
+/// `W_ACK_PAYLOAD`: `payload` for the ACK of the next packet on `pipe`
+pub struct WriteAckPayload<'a> {
+    pub pipe: u8,
+    pub payload: &'a [u8],
+}
 
-impl Command for FlushTx {
+impl Command for WriteAckPayload<'_> {
     fn len(&self) -> usize {
-        1
+        self.payload.len().saturating_add(1)
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b1110_0001;
+        copy_bytes(opcode(buf, W_ACK_PAYLOAD | self.pipe), self.payload);
     }
 
     type Response = ();
     fn decode_response(_: &[u8]) -> Self::Response {}
 }
 
-pub struct Nop;
+/// `ACTIVATE`, see `ChipVariant`
+pub struct Activate(pub u8);
 
-impl Command for Nop {
+impl Command for Activate {
     fn len(&self) -> usize {
-        1
+        2
     }
 
     fn encode(&self, buf: &mut [u8]) {
-        buf[0] = 0b1111_1111;
+        if let Some(data) = opcode(buf, 0b0101_0000).first_mut() {
+            *data = self.0;
+        }
     }
 
     type Response = ();
@@ -0,0 +1,378 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::config::{DataRate, RadioConfig};
+use crate::device::Device;
+use crate::error::StateError;
+use crate::rx::RxMode;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use crate::PIPES_COUNT;
+use embedded_hal_async::delay::DelayNs;
+
+/// Channel on which unconfigured nodes wait for their configuration,
+/// 2.480 GHz at the top of the ISM band, clear of WiFi channels 1, 6 and
+/// 11
+pub const COMMISSION_CHANNEL: Channel = Channel::clamped(80);
+/// Address on which unconfigured nodes wait for their configuration
+pub const COMMISSION_ADDRESS: Address = Address::from_array([0xC0, 0x3E, 0x55, 0x10, 0x9A]);
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+/// Length of the tag a [`Sealer`](trait.Sealer.html) computes
+pub const SEAL_TAG_LEN: usize = 8;
+
+/// Authenticated along with the nonce, but not sent, so that packets
+/// of other layouts fail to open
+const TAG: u8 = b'C';
+/// Layout version, see `TAG`
+const VERSION: u8 = 2;
+/// Size of the nonce
+const NONCE_LEN: usize = 4;
+/// Size of the sealed part
+const BODY_LEN: usize = 20;
+
+/// Authenticated encryption of provisioning packets, with a secret
+/// that the node and the provisioner share, e.g. an install code
+/// printed on each node's label
+///
+/// The commissioning channel is open to anyone in range, so the network
+/// key must not travel in clear. Implement `Sealer` on the AES or
+/// ChaCha peripheral of your MCU, or with a crate such as `aes` and
+/// `ccm`, keyed with the node's secret, and pad `nonce` to the nonce
+/// size of the cipher.
+///
+/// A nonce must never repeat under the same key. A
+/// [`Commissioner`](struct.Commissioner.html) counts it up from a value
+/// that you persist, and a
+/// [`Commissionee`](struct.Commissionee.html) rejects nonces that are
+/// not above the last one it accepted.
+pub trait Sealer {
+    /// Encrypt `body` in place and return a tag authenticating it along
+    /// with `header`
+    fn seal(&mut self, nonce: u32, header: &[u8], body: &mut [u8]) -> [u8; SEAL_TAG_LEN];
+
+    /// Check `tag` and decrypt `body` in place, or return `false` if the
+    /// packet is not authentic, e.g. sealed for another node
+    fn open(&mut self, nonce: u32, header: &[u8], body: &mut [u8], tag: &[u8; SEAL_TAG_LEN])
+        -> bool;
+}
+
+/// Network parameters pushed to an unconfigured node
+#[derive(Debug, PartialEq, Clone)]
+pub struct Provisioning {
+    /// Node ID in the network, e.g. to derive its address from
+    pub node_id: u16,
+    /// Operational channel
+    pub channel: Channel,
+    /// Operational air data rate
+    pub data_rate: DataRate,
+    /// Key for application-level encryption of the network, which the
+    /// driver does not use itself
+    pub network_key: [u8; 16],
+}
+
+impl Provisioning {
+    /// Size of a provisioning packet
+    ///
+    /// | Offset | Content                                            |
+    /// |--------|----------------------------------------------------|
+    /// | 0..4   | Nonce, little endian                               |
+    /// | 4..6   | Node ID, little endian                             |
+    /// | 6      | Channel                                            |
+    /// | 7      | Data rate: `0`: 250 Kbps, `1`: 1 Mbps, `2`: 2 Mbps |
+    /// | 8..24  | Network key                                        |
+    /// | 24..32 | Tag over `'C'`, the version `2` and bytes 0..24    |
+    ///
+    /// Bytes 4..24 are sealed. The header that the sealer authenticates
+    /// is `'C'`, `2` and the nonce, but only the nonce is sent: nothing
+    /// else fits in a packet with a 32-bit nonce.
+    pub const LEN: usize = NONCE_LEN + BODY_LEN + SEAL_TAG_LEN;
+
+    /// Encode and seal with `nonce`
+    pub fn seal<S: Sealer>(&self, nonce: u32, sealer: &mut S) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        buf[0..4].copy_from_slice(&nonce.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.node_id.to_le_bytes());
+        buf[6] = self.channel.into();
+        buf[7] = match self.data_rate {
+            DataRate::R250Kbps => 0,
+            DataRate::R1Mbps => 1,
+            DataRate::R2Mbps => 2,
+        };
+        buf[8..24].copy_from_slice(&self.network_key);
+        let header = header(nonce);
+        let tag = sealer.seal(nonce, &header, &mut buf[NONCE_LEN..NONCE_LEN + BODY_LEN]);
+        buf[NONCE_LEN + BODY_LEN..].copy_from_slice(&tag);
+        buf
+    }
+
+    /// Check and decode a received packet along with its nonce, or
+    /// `None` if it is not an authentic provisioning packet
+    ///
+    /// Does not check that the nonce is fresh, see
+    /// [`Commissionee`](struct.Commissionee.html).
+    pub fn open<S: Sealer>(packet: &[u8], sealer: &mut S) -> Option<(u32, Self)> {
+        let packet: &[u8; Self::LEN] = packet.try_into().ok()?;
+        let nonce = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        let mut body = [0; BODY_LEN];
+        body.copy_from_slice(&packet[NONCE_LEN..NONCE_LEN + BODY_LEN]);
+        let mut tag = [0; SEAL_TAG_LEN];
+        tag.copy_from_slice(&packet[NONCE_LEN + BODY_LEN..]);
+        if !sealer.open(nonce, &header(nonce), &mut body, &tag) {
+            return None;
+        }
+        let data_rate = match body[3] {
+            0 => DataRate::R250Kbps,
+            1 => DataRate::R1Mbps,
+            2 => DataRate::R2Mbps,
+            _ => return None,
+        };
+        let mut network_key = [0; 16];
+        network_key.copy_from_slice(&body[4..]);
+        let provisioning = Provisioning {
+            node_id: u16::from_le_bytes([body[0], body[1]]),
+            channel: Channel::new(body[2]).ok()?,
+            data_rate,
+            network_key,
+        };
+        Some((nonce, provisioning))
+    }
+}
+
+/// Header that the sealer authenticates for `nonce`
+fn header(nonce: u32) -> [u8; 2 + NONCE_LEN] {
+    let [a, b, c, d] = nonce.to_le_bytes();
+    [TAG, VERSION, a, b, c, d]
+}
+
+/// Well-known settings of the commissioning channel, for both the
+/// unconfigured node and the provisioner
+///
+/// 250 Kbps for range, pipe 0 on
+/// [`COMMISSION_ADDRESS`](constant.COMMISSION_ADDRESS.html) with
+/// auto-ack, and patient retransmits.
+pub fn commission_config() -> RadioConfig {
+    let mut rx_addrs = [None; PIPES_COUNT];
+    rx_addrs[0] = Some(COMMISSION_ADDRESS);
+    RadioConfig {
+        channel: COMMISSION_CHANNEL,
+        data_rate: DataRate::R250Kbps,
+        // 1500 µs, enough for an ACK at 250 Kbps
+        auto_retransmit_delay: 5,
+        auto_retransmit_count: 15,
+        tx_addr: COMMISSION_ADDRESS,
+        rx_addrs,
+        ..RadioConfig::default()
+    }
+}
+
+/// Unconfigured node waiting on the commissioning channel
+///
+/// Configure the radio with
+/// [`commission_config()`](fn.commission_config.html), enter RX mode,
+/// and read until a [`Provisioning`](struct.Provisioning.html) arrives.
+/// Store it, then configure the radio for the network.
+///
+/// Store [`last_nonce()`](#method.last_nonce) too, where a factory reset
+/// does not erase it, and pass it to [`new()`](#method.new): otherwise a
+/// provisioning packet recorded earlier provisions the node again.
+pub struct Commissionee<S: Sealer> {
+    sealer: S,
+    last_nonce: Option<u32>,
+}
+
+impl<S: Sealer> Commissionee<S> {
+    /// Constructor, with the sealer keyed by this node's secret and the
+    /// last nonce that the node accepted, if any
+    pub fn new(sealer: S, last_nonce: Option<u32>) -> Self {
+        Commissionee { sealer, last_nonce }
+    }
+
+    /// Nonce of the last provisioning packet accepted
+    pub fn last_nonce(&self) -> Option<u32> {
+        self.last_nonce
+    }
+
+    /// Return the sealer
+    pub fn into_inner(self) -> S {
+        self.sealer
+    }
+
+    /// Read packets until one is a provisioning packet for this node,
+    /// or `None` once the RX FIFO is empty
+    ///
+    /// Other packets are dropped, and so are replays: packets with a
+    /// nonce that is not above the last one accepted. Packets sealed for
+    /// other nodes are acknowledged all the same, so keep one node at a
+    /// time in range of the provisioner for a clear answer there.
+    pub async fn read<D: Device>(
+        &mut self,
+        rx: &mut RxMode<D>,
+    ) -> Result<Option<Provisioning>, D::Error> {
+        while let Some((_, packet)) = rx.read_with_pipe().await? {
+            let Some((nonce, provisioning)) = Provisioning::open(&packet, &mut self.sealer) else {
+                continue;
+            };
+            if self.last_nonce.is_some_and(|last| nonce <= last) {
+                continue;
+            }
+            self.last_nonce = Some(nonce);
+            return Ok(Some(provisioning));
+        }
+        Ok(None)
+    }
+}
+
+/// Provisioner pushing network parameters to unconfigured nodes
+///
+/// Configure the radio with
+/// [`commission_config()`](fn.commission_config.html), then
+/// [`provision()`](#method.provision) each node with a sealer for its
+/// secret.
+///
+/// The nonces count up from a value that must survive restarts, so that
+/// no nonce is used twice. Persist a value ahead of
+/// [`next_nonce()`](#method.next_nonce), e.g. store `next_nonce() + 100`
+/// and store the next one once the nonces reach it, and restart from the
+/// stored value.
+pub struct Commissioner {
+    next_nonce: Option<u32>,
+}
+
+impl Commissioner {
+    /// Constructor, starting the nonces at the persisted `next_nonce`,
+    /// 0 for a new provisioner
+    pub fn new(next_nonce: u32) -> Self {
+        Commissioner {
+            next_nonce: Some(next_nonce),
+        }
+    }
+
+    /// Nonce of the next provisioning packet, or `None` once all are
+    /// used
+    pub fn next_nonce(&self) -> Option<u32> {
+        self.next_nonce
+    }
+
+    /// Send `provisioning` sealed by `sealer`, and return whether a node
+    /// acknowledged it, polling with `delay`
+    ///
+    /// Waits for the TX FIFO to drain first. Every call uses a fresh
+    /// nonce, and fails with `NoncesExhausted` once none is left.
+    pub async fn provision<D: Device, S: Sealer, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        provisioning: &Provisioning,
+        sealer: &mut S,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let nonce = self.next_nonce.ok_or(StateError::NoncesExhausted)?;
+        let packet = provisioning.seal(nonce, sealer);
+        self.next_nonce = nonce.checked_add(1);
+
+        tx.wait_empty().await?;
+        tx.send(&packet).await?;
+        tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+
+    /// Stand-in for a cipher: XOR with the key, and a sum as the tag
+    struct XorSealer(u8);
+
+    impl XorSealer {
+        fn tag(&self, nonce: u32, header: &[u8], body: &[u8]) -> [u8; SEAL_TAG_LEN] {
+            let sum = header.iter().chain(body).fold(self.0, |sum, b| sum.wrapping_add(*b));
+            let mut tag = [sum; SEAL_TAG_LEN];
+            tag[..4].copy_from_slice(&nonce.to_le_bytes());
+            tag
+        }
+    }
+
+    impl Sealer for XorSealer {
+        fn seal(&mut self, nonce: u32, header: &[u8], body: &mut [u8]) -> [u8; SEAL_TAG_LEN] {
+            body.iter_mut().for_each(|b| *b ^= self.0);
+            self.tag(nonce, header, body)
+        }
+
+        fn open(
+            &mut self,
+            nonce: u32,
+            header: &[u8],
+            body: &mut [u8],
+            tag: &[u8; SEAL_TAG_LEN],
+        ) -> bool {
+            if self.tag(nonce, header, body) != *tag {
+                return false;
+            }
+            body.iter_mut().for_each(|b| *b ^= self.0);
+            true
+        }
+    }
+
+    fn provisioning(node_id: u16) -> Provisioning {
+        Provisioning {
+            node_id,
+            channel: Channel::clamped(40),
+            data_rate: DataRate::R1Mbps,
+            network_key: [0x5A; 16],
+        }
+    }
+
+    #[test]
+    fn commission_channel_is_in_the_ism_band() {
+        assert!(u8::from(COMMISSION_CHANNEL) <= 83);
+    }
+
+    #[test]
+    fn packets_open_with_their_nonce() {
+        let packet = provisioning(7).seal(0x0102_0304, &mut XorSealer(9));
+        assert_eq!(packet[..4], [4, 3, 2, 1]);
+        let opened = Provisioning::open(&packet, &mut XorSealer(9));
+        assert_eq!(opened, Some((0x0102_0304, provisioning(7))));
+        assert_eq!(Provisioning::open(&packet, &mut XorSealer(8)), None);
+    }
+
+    #[test]
+    fn stale_nonces_are_rejected() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut commissionee = Commissionee::new(XorSealer(9), Some(4));
+
+            // A recording of nonce 4 is replayed, then nonce 5 comes
+            for (nonce, node_id) in [(4, 1), (5, 2)] {
+                let mut commissioner = Commissioner::new(nonce);
+                let provisioning = provisioning(node_id);
+                let mut sealer = XorSealer(9);
+                let acked = commissioner.provision(&mut tx, &provisioning, &mut sealer, &mut timer);
+                assert_eq!(acked.await.ok(), Some(true));
+                assert_eq!(commissioner.next_nonce(), Some(nonce + 1));
+            }
+            let read = commissionee.read(&mut rx).await.unwrap();
+            assert_eq!(read, Some(provisioning(2)));
+            assert_eq!(commissionee.last_nonce(), Some(5));
+        });
+    }
+
+    #[test]
+    fn nonces_do_not_wrap() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut commissioner = Commissioner::new(u32::MAX);
+            let mut sealer = XorSealer(9);
+            let provisioning = provisioning(1);
+            let acked = commissioner.provision(&mut tx, &provisioning, &mut sealer, &mut timer);
+            assert_eq!(acked.await.ok(), Some(true));
+            let acked = commissioner.provision(&mut tx, &provisioning, &mut sealer, &mut timer);
+            assert!(matches!(acked.await, Err(crate::Error::NoncesExhausted)));
+        });
+    }
+}
@@ -0,0 +1,265 @@
+use crate::bits::{BitReader, BitWriter};
+use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::payload::copy_bytes;
+use crate::registers::Status;
+use crate::rx::RxMode;
+use crate::tx::TxMode;
+
+/// First byte of a payload sent as it is
+const RAW: u8 = 0x00;
+/// First byte of a compressed payload
+const LZ: u8 = 0x01;
+/// Back-references reach up to this many bytes back
+const WINDOW: usize = 256;
+/// Bits of a back-reference length
+const LENGTH_BITS: u8 = 4;
+/// Shortest back-reference, as one costs less than two literals
+const MIN_MATCH: usize = 2;
+/// Longest back-reference
+const MAX_MATCH: usize = MIN_MATCH + (1 << LENGTH_BITS) - 1;
+/// Bits of a literal token
+const LITERAL_BITS: usize = 9;
+
+/// Tiny LZSS compression of payloads, in the style of heatshrink, to
+/// fit more telemetry into a 32 byte frame than fragmenting would
+///
+/// Data of at least the threshold length is compressed if that helps,
+/// and shorter data is sent as it is. Each payload starts with a byte
+/// telling which, so [`decode()`](#method.decode) and
+/// [`read()`](#method.read) handle both transparently. Compressed
+/// payloads are a bit stream, most significant bit first, of:
+///
+/// * `1` and 8 bits: a literal byte
+/// * `0`, 8 bits of distance - 1 and 4 bits of length - 2: a copy of 2
+///   to 17 bytes from up to 256 bytes back
+///
+/// padded with zeros. Repetitive data like sensor records with mostly
+/// constant fields compresses well, random data does not at all. Needs
+/// no memory beyond the buffers passed in.
+#[derive(Debug, Copy, Clone)]
+pub struct Compressor {
+    threshold: usize,
+}
+
+impl Compressor {
+    /// Longest payload, including the header byte
+    pub const MAX_LEN: usize = 32;
+
+    /// Constructor, compressing data of at least `threshold` bytes
+    pub fn new(threshold: usize) -> Self {
+        Compressor { threshold }
+    }
+
+    /// Encode `data` into `out`, returning the payload length, or
+    /// `None` if it does not fit into a payload even compressed
+    pub fn encode(&self, data: &[u8], out: &mut [u8; Self::MAX_LEN]) -> Option<usize> {
+        let fits_raw = data.len() < Self::MAX_LEN;
+        if fits_raw && data.len() < self.threshold {
+            return Some(Self::encode_raw(data, out));
+        }
+        match Self::compress(data, out) {
+            Some(len) if !fits_raw || len <= data.len() => Some(len),
+            _ if fits_raw => Some(Self::encode_raw(data, out)),
+            _ => None,
+        }
+    }
+
+    fn encode_raw(data: &[u8], out: &mut [u8; Self::MAX_LEN]) -> usize {
+        out[0] = RAW;
+        copy_bytes(&mut out[1..], data).saturating_add(1)
+    }
+
+    fn compress(data: &[u8], out: &mut [u8; Self::MAX_LEN]) -> Option<usize> {
+        *out = [0; Self::MAX_LEN];
+        out[0] = LZ;
+        let mut writer = BitWriter {
+            buf: &mut out[1..],
+            bits: 0,
+        };
+        let mut pos = 0_usize;
+        while let Some(&byte) = data.get(pos) {
+            let (distance, len) = longest_match(data, pos);
+            if len >= MIN_MATCH {
+                writer.push(0, 1)?;
+                writer.push(distance.saturating_sub(1) as u32, 8)?;
+                writer.push(len.saturating_sub(MIN_MATCH) as u32, LENGTH_BITS)?;
+                pos = pos.saturating_add(len);
+            } else {
+                writer.push(1, 1)?;
+                writer.push(u32::from(byte), 8)?;
+                pos = pos.saturating_add(1);
+            }
+        }
+        Some(writer.bits.div_ceil(8).saturating_add(1))
+    }
+
+    /// Decode a received `payload` into `out`, returning the data
+    /// length, or `None` if it is malformed or `out` is too short
+    pub fn decode(payload: &[u8], out: &mut [u8]) -> Option<usize> {
+        let (&header, body) = payload.split_first()?;
+        match header {
+            RAW => {
+                out.get_mut(..body.len())?.copy_from_slice(body);
+                Some(body.len())
+            }
+            LZ => Self::decompress(body, out),
+            _ => None,
+        }
+    }
+
+    fn decompress(body: &[u8], out: &mut [u8]) -> Option<usize> {
+        let mut reader = BitReader { buf: body, bits: 0 };
+        let mut len = 0_usize;
+        // Fewer bits than a literal are padding
+        while reader.remaining() >= LITERAL_BITS {
+            if reader.pull(1)? == 1 {
+                *out.get_mut(len)? = reader.pull(8)? as u8;
+                len = len.saturating_add(1);
+            } else {
+                let distance = (reader.pull(8)? as usize).saturating_add(1);
+                let count = (reader.pull(LENGTH_BITS)? as usize).saturating_add(MIN_MATCH);
+                let start = len.checked_sub(distance)?;
+                // Byte by byte, as the copy may overlap its source
+                for i in 0..count {
+                    *out.get_mut(len.checked_add(i)?)? = *out.get(start.checked_add(i)?)?;
+                }
+                len = len.saturating_add(count);
+            }
+        }
+        Some(len)
+    }
+
+    /// Encode `data` and send it like
+    /// [`TxMode::send()`](struct.TxMode.html#method.send)
+    ///
+    /// Fails with `InvalidArgument::PayloadLength` if it does not fit.
+    pub async fn send<D: Device>(
+        &self,
+        tx: &mut TxMode<D>,
+        data: &[u8],
+    ) -> Result<Status, D::Error> {
+        let mut payload = [0; Self::MAX_LEN];
+        let len = self
+            .encode(data, &mut payload)
+            .ok_or(InvalidArgument::PayloadLength)?;
+        tx.send(payload.get(..len).unwrap_or_default()).await
+    }
+
+    /// Read and decode the next packet into `out` like
+    /// [`RxMode::read_with_pipe()`](struct.RxMode.html#method.read_with_pipe),
+    /// returning its pipe number and data length
+    ///
+    /// Packets that do not decode, or do not fit `out`, are dropped.
+    pub async fn read<D: Device>(
+        rx: &mut RxMode<D>,
+        out: &mut [u8],
+    ) -> Result<Option<(u8, usize)>, D::Error> {
+        while let Some((pipe_no, payload)) = rx.read_with_pipe().await? {
+            if let Some(len) = Self::decode(&payload, out) {
+                return Ok(Some((pipe_no, len)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Distance and length of the longest earlier match for `data[pos..]`
+fn longest_match(data: &[u8], pos: usize) -> (usize, usize) {
+    let mut best = (0, 0);
+    for start in pos.saturating_sub(WINDOW)..pos {
+        let len = data
+            .get(pos..)
+            .unwrap_or_default()
+            .iter()
+            .take(MAX_MATCH)
+            .zip(data.get(start..).unwrap_or_default())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if len > best.1 {
+            best = (pos.saturating_sub(start), len);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 20 times `a`: a literal, then copies of 17 and 2 bytes
+    const RUN: [u8; 20] = [b'a'; 20];
+    const RUN_ENCODED: [u8; 6] = [LZ, 0xB0, 0x80, 0x3C, 0x22, 0x00];
+
+    fn encode(compressor: &Compressor, data: &[u8]) -> Option<Vec<u8>> {
+        let mut out = [0; Compressor::MAX_LEN];
+        let len = compressor.encode(data, &mut out)?;
+        Some(out[..len].to_vec())
+    }
+
+    fn decode(payload: &[u8]) -> Option<Vec<u8>> {
+        let mut out = [0; 64];
+        let len = Compressor::decode(payload, &mut out)?;
+        Some(out[..len].to_vec())
+    }
+
+    #[test]
+    fn short_data_is_raw() {
+        let compressor = Compressor::new(16);
+        assert_eq!(encode(&compressor, b"abc").unwrap(), [RAW, b'a', b'b', b'c']);
+        assert_eq!(decode(&[RAW, b'a', b'b', b'c']).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn overlapping_copies() {
+        let compressor = Compressor::new(16);
+        assert_eq!(encode(&compressor, &RUN).unwrap(), RUN_ENCODED);
+        assert_eq!(decode(&RUN_ENCODED).unwrap(), RUN);
+    }
+
+    #[test]
+    fn raw_when_compression_does_not_help() {
+        let compressor = Compressor::new(16);
+        let data: Vec<u8> = (0..20).collect();
+        let payload = encode(&compressor, &data).unwrap();
+        assert_eq!(payload[0], RAW);
+        assert_eq!(payload.len(), 21);
+        assert_eq!(decode(&payload).unwrap(), data);
+
+        // Too long to send raw, and does not compress
+        let data: Vec<u8> = (0..40).collect();
+        assert_eq!(encode(&compressor, &data), None);
+    }
+
+    #[test]
+    fn long_data_round_trips() {
+        let compressor = Compressor::new(16);
+        let mut record = [0; 48];
+        for (i, chunk) in record.chunks_mut(8).enumerate() {
+            chunk.copy_from_slice(&[0x17, 0x00, 0x42, i as u8, 0, 0, 0, 0xFF]);
+        }
+        let payload = encode(&compressor, &record).unwrap();
+        assert_eq!(payload[0], LZ);
+        assert!(payload.len() <= Compressor::MAX_LEN);
+        assert_eq!(decode(&payload).unwrap(), record);
+    }
+
+    #[test]
+    fn short_output_buffer() {
+        let mut out = [0; 5];
+        assert_eq!(Compressor::decode(&RUN_ENCODED, &mut out), None);
+        assert_eq!(Compressor::decode(&[RAW; 7], &mut out), None);
+    }
+
+    #[test]
+    fn truncated_or_unknown_input() {
+        // Stops within the copy of 2 bytes
+        assert_eq!(decode(&RUN_ENCODED[..5]), None);
+        // Fewer bits than a token left are padding
+        assert_eq!(decode(&RUN_ENCODED[..3]).unwrap(), b"a");
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&[0x02, 0x00]), None);
+        // A copy from before the start
+        assert_eq!(decode(&[LZ, 0x00, 0x00]), None);
+    }
+}
@@ -1,29 +1,35 @@
-use crate::command::{FlushRx, FlushTx, Nop};
+use crate::address::{Address, AddressFamily, SeedRng};
+use crate::channel::Channel;
+use crate::command::{Activate, FlushRx, FlushTx, Nop};
 use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::payload::copy_bytes;
+use crate::standby::SETTLE_MS;
+use crate::stats::Stats;
+use crate::variant::ChipVariant;
 use crate::registers::{
-    Config, Dynpd, EnAa, EnRxaddr, Feature, RfCh, RfSetup, SetupAw, SetupRetr, Status, TxAddr,
+    Config, Dynpd, EnAa, EnRxaddr, Feature, RfCh, RfSetup, RxAddrP0, RxAddrP1, SetupAw, SetupRetr,
+    Status, TxAddr,
 };
-use crate::PIPES_COUNT;
+use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES, PIPES_COUNT};
+use embedded_hal_async::delay::DelayNs;
 
 /// Supported air data rates.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataRate {
     /// 250 Kbps
     R250Kbps,
     /// 1 Mbps
+    #[default]
     R1Mbps,
     /// 2 Mbps
     R2Mbps,
 }
 
-impl Default for DataRate {
-    fn default() -> DataRate {
-        DataRate::R1Mbps
-    }
-}
-
 /// Supported CRC modes
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CrcMode {
     /// Disable all CRC generation/checking
     Disabled,
@@ -43,6 +49,626 @@ impl CrcMode {
         config.set_en_crc(en_crc);
         config.set_crco(crco);
     }
+
+    fn from_config(config: &Config) -> Self {
+        match (config.en_crc(), config.crco()) {
+            (false, _) => CrcMode::Disabled,
+            (true, false) => CrcMode::OneByte,
+            (true, true) => CrcMode::TwoBytes,
+        }
+    }
+}
+
+/// Typed view of the `CONFIG` register, see
+/// [`get_chip_config()`](trait.Configuration.html#method.get_chip_config)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ChipConfig {
+    /// `PWR_UP`: powered up, in standby or an active mode
+    pub power_up: bool,
+    /// `PRIM_RX`: RX mode when `CE` is high, TX mode otherwise
+    pub prim_rx: bool,
+    /// `EN_CRC` and `CRCO`
+    pub crc: CrcMode,
+    /// `MASK_RX_DR`: `RX_DR` does not drive the `IRQ` pin
+    pub mask_rx_dr: bool,
+    /// `MASK_TX_DS`: `TX_DS` does not drive the `IRQ` pin
+    pub mask_tx_ds: bool,
+    /// `MASK_MAX_RT`: `MAX_RT` does not drive the `IRQ` pin
+    pub mask_max_rt: bool,
+}
+
+impl ChipConfig {
+    fn from_register(config: &Config) -> Self {
+        ChipConfig {
+            power_up: config.pwr_up(),
+            prim_rx: config.prim_rx(),
+            crc: CrcMode::from_config(config),
+            mask_rx_dr: config.mask_rx_dr(),
+            mask_tx_ds: config.mask_tx_ds(),
+            mask_max_rt: config.mask_max_rt(),
+        }
+    }
+
+    fn apply(&self, config: &mut Config) {
+        config.set_pwr_up(self.power_up);
+        config.set_prim_rx(self.prim_rx);
+        self.crc.set_config(config);
+        config.set_mask_rx_dr(self.mask_rx_dr);
+        config.set_mask_tx_ds(self.mask_tx_ds);
+        config.set_mask_max_rt(self.mask_max_rt);
+    }
+}
+
+/// RF output power in TX mode
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PowerLevel {
+    /// -18 dBm
+    Min = 0,
+    /// -12 dBm
+    Low = 1,
+    /// -6 dBm
+    High = 2,
+    /// 0 dBm
+    #[default]
+    Max = 3,
+}
+
+impl PowerLevel {
+    /// Value for the `power` argument of
+    /// [`set_rf()`](trait.Configuration.html#method.set_rf)
+    pub fn value(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Complete radio configuration, to be applied at once with
+/// [`configure()`](trait.Configuration.html#method.configure) or
+/// [`NRF24L01::new_with_config()`](struct.NRF24L01.html#method.new_with_config)
+///
+/// The default mirrors the chip's reset values, except that all pipes
+/// use dynamic payload lengths.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RadioConfig {
+    /// Frequency offset (channel)
+    pub channel: Channel,
+    /// Air data rate
+    pub data_rate: DataRate,
+    /// TX output power
+    pub power: PowerLevel,
+    /// CRC mode
+    pub crc: CrcMode,
+    /// Auto retransmit delay, that is `250 + (250 * ard) µS`
+    pub auto_retransmit_delay: u8,
+    /// Auto retransmit count, `0` to disable
+    pub auto_retransmit_count: u8,
+    /// Auto-acknowledgment per pipe
+    pub auto_ack: [bool; PIPES_COUNT],
+    /// Payload lengths per pipe, see
+    /// [`set_pipes_rx_lengths()`](trait.Configuration.html#method.set_pipes_rx_lengths)
+    pub pipes_rx_lengths: [Option<u8>; PIPES_COUNT],
+    /// TX address, which also determines the address width
+    pub tx_addr: Address,
+    /// Addresses of the enabled RX pipes
+    ///
+    /// Pipes 2 to 5 share all but the first byte with pipe 1, so only
+    /// their first byte is used.
+    pub rx_addrs: [Option<Address>; PIPES_COUNT],
+}
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        RadioConfig {
+            channel: Channel::default(),
+            data_rate: DataRate::R2Mbps,
+            power: PowerLevel::Max,
+            crc: CrcMode::OneByte,
+            auto_retransmit_delay: 0,
+            auto_retransmit_count: 3,
+            auto_ack: [true; PIPES_COUNT],
+            pipes_rx_lengths: [None; PIPES_COUNT],
+            tx_addr: Address::from_array([0xE7; 5]),
+            rx_addrs: [
+                Some(Address::from_array([0xE7; 5])),
+                Some(Address::from_array([0xC2; 5])),
+                None,
+                None,
+                None,
+                None,
+            ],
+        }
+    }
+}
+
+/// Presets. Those for a use case combine data rate, retransmission,
+/// and CRC as the datasheet permits (section 7.4.2), with the default
+/// addresses and pipe setup.
+impl RadioConfig {
+    /// Default configuration on a channel and with an address derived
+    /// from `seed`, for ad-hoc device pairs that share the seed
+    ///
+    /// The address is [`Address::from_seed()`](struct.Address.html#method.from_seed),
+    /// used for TX and RX pipe 0. The channel is within 2.400 to
+    /// 2.483 GHz (channels 0 to 83) where the band is legal worldwide.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut rng = SeedRng::new(seed);
+        let address = Address::random(&mut rng);
+        let mut rx_addrs = [None; PIPES_COUNT];
+        rx_addrs[0] = Some(address);
+        RadioConfig {
+            channel: Channel::clamped((rng.next() % 84) as u8),
+            tx_addr: address,
+            rx_addrs,
+            ..RadioConfig::default()
+        }
+    }
+
+    /// Interoperate with peers that have dynamic payload lengths
+    /// disabled, e.g. RF24 without `enableDynamicPayloads()`
+    ///
+    /// All pipes receive `len` bytes and `EN_DPL` is cleared. Combine
+    /// with [`TxMode::set_static_payload_len()`](struct.TxMode.html#method.set_static_payload_len)
+    /// so that sent packets are padded or truncated to the same length.
+    /// Auto-ack works as with dynamic lengths, turn it off in `auto_ack`
+    /// if the peer does.
+    pub fn static_payloads(len: u8) -> Self {
+        RadioConfig {
+            pipes_rx_lengths: [Some(len); PIPES_COUNT],
+            ..RadioConfig::default()
+        }
+    }
+
+    /// Maximize range: 250 kbps, maximum power, two byte CRC and
+    /// persistent retransmission
+    ///
+    /// The 1500 µS retransmit delay leaves time for a 32 byte ACK
+    /// payload, the datasheet minimum for this rate.
+    pub fn long_range() -> Self {
+        RadioConfig {
+            data_rate: DataRate::R250Kbps,
+            power: PowerLevel::Max,
+            crc: CrcMode::TwoBytes,
+            auto_retransmit_delay: 5,
+            auto_retransmit_count: 15,
+            ..RadioConfig::default()
+        }
+    }
+
+    /// Minimize latency: 2 Mbps, the shortest retransmit delay and few
+    /// retries, so that a lost packet is reported quickly
+    ///
+    /// ACK payloads must not exceed 15 bytes with the 250 µS delay.
+    pub fn low_latency() -> Self {
+        RadioConfig {
+            data_rate: DataRate::R2Mbps,
+            power: PowerLevel::Max,
+            crc: CrcMode::OneByte,
+            auto_retransmit_delay: 0,
+            auto_retransmit_count: 1,
+            ..RadioConfig::default()
+        }
+    }
+
+    /// Maximize throughput: 2 Mbps with one byte CRC to reduce
+    /// overhead
+    ///
+    /// The 500 µS retransmit delay allows ACK payloads of any length.
+    pub fn high_throughput() -> Self {
+        RadioConfig {
+            data_rate: DataRate::R2Mbps,
+            power: PowerLevel::Max,
+            crc: CrcMode::OneByte,
+            auto_retransmit_delay: 1,
+            auto_retransmit_count: 15,
+            ..RadioConfig::default()
+        }
+    }
+}
+
+/// Settings for raw frames from third-party protocols, see
+/// [`configure_raw()`](trait.Configuration.html#method.configure_raw)
+///
+/// Frames have a static length and only pipe 0 is used. With CRC and
+/// auto-ack disabled, the payload is everything on air after the
+/// address, so any packet control field, CRC or whitening of the
+/// protocol is up to the application.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RawConfig {
+    /// Channel
+    pub channel: Channel,
+    /// Air data rate
+    pub data_rate: DataRate,
+    /// TX output power
+    pub power: PowerLevel,
+    /// CRC mode. The chip forces CRC on while auto-ack is enabled.
+    pub crc: CrcMode,
+    /// Auto-acknowledgment with 3 retransmits on pipe 0
+    pub auto_ack: bool,
+    /// Address for TX and pipe 0, of which `address_width` bytes are
+    /// used, least significant byte first
+    pub address: [u8; MAX_ADDR_BYTES],
+    /// 2 to 5 bytes. 2 bytes is outside of the datasheet but works on
+    /// most chips, e.g. to sniff with a preamble-like address.
+    pub address_width: u8,
+    /// Frame length, 1 to 32 bytes
+    pub payload_len: u8,
+}
+
+impl RawConfig {
+    /// 1 Mbps frames of `payload_len` bytes at `address` on `channel`,
+    /// without CRC and auto-ack
+    pub fn new(channel: Channel, address: &[u8], payload_len: u8) -> Result<Self, InvalidArgument> {
+        if !(2..=MAX_ADDR_BYTES).contains(&address.len()) {
+            return Err(InvalidArgument::AddressWidth);
+        }
+        if !(1..=32).contains(&payload_len) {
+            return Err(InvalidArgument::PayloadLength);
+        }
+        let mut bytes = [0; MAX_ADDR_BYTES];
+        copy_bytes(&mut bytes, address);
+        Ok(RawConfig {
+            channel,
+            data_rate: DataRate::R1Mbps,
+            power: PowerLevel::Max,
+            crc: CrcMode::Disabled,
+            auto_ack: false,
+            address: bytes,
+            address_width: address.len() as u8,
+            payload_len,
+        })
+    }
+}
+
+/// Addresses that the chip can not tell apart, see
+/// [`RadioConfig::address_conflict()`](struct.RadioConfig.html#method.address_conflict)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AddressConflict {
+    /// Two RX pipes have the same full address
+    Pipes(u8, u8),
+    /// This RX pipe, other than 0, has the TX address which pipe 0 takes
+    /// for auto-ack, so that ACKs and packets for the pipe mix up
+    TxAddress(u8),
+}
+
+impl RadioConfig {
+    /// Find RX pipes that would receive the same packets
+    ///
+    /// Compares full addresses at the width of `tx_addr`, with pipes 2
+    /// to 5 sharing all but their first byte with pipe 1.
+    /// [`configure()`](trait.Configuration.html#method.configure) rejects
+    /// a configuration with a conflict.
+    pub fn address_conflict(&self) -> Option<AddressConflict> {
+        let width = self.tx_addr.len();
+        let full = |pipe_no: usize| -> Option<[u8; MAX_ADDR_BYTES]> {
+            let own = (*self.rx_addrs.get(pipe_no)?)?;
+            let base = if pipe_no < 2 { own } else { self.rx_addrs[1].unwrap_or(own) };
+            let mut bytes = [0; MAX_ADDR_BYTES];
+            copy_bytes(&mut bytes, base.as_bytes().get(..width).unwrap_or(base.as_bytes()));
+            copy_bytes(&mut bytes, own.as_bytes().get(..1).unwrap_or_default());
+            Some(bytes)
+        };
+
+        for pipe_no in 0..PIPES_COUNT {
+            let Some(addr) = full(pipe_no) else {
+                continue;
+            };
+            for other in (pipe_no..PIPES_COUNT).skip(1) {
+                if full(other) == Some(addr) {
+                    return Some(AddressConflict::Pipes(pipe_no as u8, other as u8));
+                }
+            }
+        }
+
+        let mut tx_addr = [0; MAX_ADDR_BYTES];
+        copy_bytes(&mut tx_addr, &self.tx_addr);
+        if self.auto_ack[0] && self.auto_retransmit_count > 0 {
+            for pipe_no in 1..PIPES_COUNT {
+                if full(pipe_no) == Some(tx_addr) {
+                    return Some(AddressConflict::TxAddress(pipe_no as u8));
+                }
+            }
+        }
+        None
+    }
+
+    /// Does `chip`, as read back with
+    /// [`get_radio_config()`](trait.Configuration.html#method.get_radio_config),
+    /// hold this configuration? Pipes 2 to 5 only compare their first
+    /// address byte.
+    fn is_applied(&self, chip: &RadioConfig) -> bool {
+        let rx_addrs_match = self.rx_addrs.iter().zip(&chip.rx_addrs).enumerate().all(
+            |(pipe_no, (own, read))| match (own, read) {
+                (Some(own), Some(read)) if pipe_no >= 2 => own.first() == read.first(),
+                _ => own == read,
+            },
+        );
+        self.channel == chip.channel
+            && self.data_rate == chip.data_rate
+            && self.power == chip.power
+            && self.crc == chip.crc
+            && self.auto_retransmit_delay == chip.auto_retransmit_delay
+            && self.auto_retransmit_count == chip.auto_retransmit_count
+            && self.auto_ack == chip.auto_ack
+            && self.pipes_rx_lengths == chip.pipes_rx_lengths
+            && self.tx_addr == chip.tx_addr
+            && rx_addrs_match
+    }
+}
+
+/// Why [`RadioConfig::from_bytes()`](struct.RadioConfig.html#method.from_bytes) failed
+#[cfg(feature = "config-bytes")]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DecodeError {
+    /// Not `RadioConfig::ENCODED_LEN` bytes
+    Length,
+    /// Written by an unknown version of the encoding
+    UnknownVersion(u8),
+    /// Checksum mismatch, the storage is corrupted or was never written
+    Checksum,
+    /// A field holds a value that is not allowed
+    InvalidField,
+}
+
+#[cfg(feature = "config-bytes")]
+impl RadioConfig {
+    /// Version byte of the current encoding
+    pub const ENCODING_VERSION: u8 = 1;
+    /// Size of the encoding
+    pub const ENCODED_LEN: usize = 36;
+
+    /// Encode into a stable fixed-size layout for EEPROM storage
+    ///
+    /// | Offset | Content                                           |
+    /// |--------|---------------------------------------------------|
+    /// | 0      | Version, currently `1`                            |
+    /// | 1      | Channel                                           |
+    /// | 2      | Data rate: `0`: 250 Kbps, `1`: 1 Mbps, `2`: 2 Mbps |
+    /// | 3      | Power level, `0` (-18 dBm) to `3` (0 dBm)         |
+    /// | 4      | CRC: `0`: disabled, `1`: 1 byte, `2`: 2 bytes     |
+    /// | 5      | Auto retransmit delay `<< 4` \| count             |
+    /// | 6      | Auto-ack bitmask, bit `n` for pipe `n`            |
+    /// | 7      | Enabled RX pipes bitmask                          |
+    /// | 8..14  | Payload length per pipe, `0xFF` for dynamic       |
+    /// | 14     | Address width                                     |
+    /// | 15..20 | TX address                                        |
+    /// | 20..25 | Pipe 0 address                                    |
+    /// | 25..30 | Pipe 1 address                                    |
+    /// | 30..34 | First byte of the pipe 2 to 5 addresses           |
+    /// | 34..36 | CRC-16/CCITT-FALSE over bytes 0..34, big endian   |
+    ///
+    /// Like the chip, the encoding has one address width for all
+    /// addresses, and pipes 2 to 5 only store their first byte. The
+    /// pipe 1 address slot holds the shared bytes of pipes 2 to 5 if
+    /// pipe 1 is disabled.
+    ///
+    /// Fails for what would not decode to the same configuration:
+    /// * `InvalidArgument::AutoRetransmit` for a delay or count above 15
+    /// * `InvalidArgument::AddressWidth` for a pipe 0 or 1 address not
+    ///   as long as the TX address
+    /// * `InvalidArgument::AddressPrefix` for pipes 1 to 5 with
+    ///   different bytes but the first
+    pub fn to_bytes(&self) -> Result<[u8; Self::ENCODED_LEN], InvalidArgument> {
+        if self.auto_retransmit_delay > 15 || self.auto_retransmit_count > 15 {
+            return Err(InvalidArgument::AutoRetransmit);
+        }
+        let width = self.tx_addr.len();
+        if self.rx_addrs[..2].iter().flatten().any(|addr| addr.len() != width) {
+            return Err(InvalidArgument::AddressWidth);
+        }
+        let mut shared = self.rx_addrs[1..].iter().flatten();
+        let prefix = shared.next();
+        if let Some(prefix) = prefix {
+            if shared.any(|addr| addr.len() != width || addr.get(1..) != prefix.get(1..)) {
+                return Err(InvalidArgument::AddressPrefix);
+            }
+        }
+
+        let mut buf = [0; Self::ENCODED_LEN];
+        buf[0] = Self::ENCODING_VERSION;
+        buf[1] = self.channel.value();
+        buf[2] = match self.data_rate {
+            DataRate::R250Kbps => 0,
+            DataRate::R1Mbps => 1,
+            DataRate::R2Mbps => 2,
+        };
+        buf[3] = self.power.value();
+        buf[4] = match self.crc {
+            CrcMode::Disabled => 0,
+            CrcMode::OneByte => 1,
+            CrcMode::TwoBytes => 2,
+        };
+        buf[5] = (self.auto_retransmit_delay << 4) | self.auto_retransmit_count;
+        buf[6] = EnAa::from_bools(&self.auto_ack).0;
+        for (pipe_no, addr) in self.rx_addrs.iter().enumerate() {
+            if addr.is_some() {
+                buf[7] |= 1 << pipe_no;
+            }
+        }
+        for (byte, length) in buf[8..14].iter_mut().zip(&self.pipes_rx_lengths) {
+            *byte = length.unwrap_or(0xFF);
+        }
+
+        buf[14] = width as u8;
+        copy_bytes(&mut buf[15..20], &self.tx_addr);
+        if let Some(addr) = &self.rx_addrs[0] {
+            copy_bytes(&mut buf[20..25], addr);
+        }
+        if let Some(addr) = self.rx_addrs[1].as_ref().or(prefix) {
+            copy_bytes(&mut buf[25..30], addr);
+        }
+        for (byte, addr) in buf[30..34].iter_mut().zip(&self.rx_addrs[2..]) {
+            if let Some(addr) = addr {
+                copy_bytes(core::slice::from_mut(byte), addr);
+            }
+        }
+
+        let crc = crc16(&buf[0..34]);
+        buf[34..36].copy_from_slice(&crc.to_be_bytes());
+        Ok(buf)
+    }
+
+    /// Short fingerprint of the configuration, the CRC of
+    /// [`to_bytes()`](#method.to_bytes), e.g. for
+    /// [`HealthFrame::config_hash`](struct.HealthFrame.html#structfield.config_hash)
+    pub fn config_hash(&self) -> Result<u16, InvalidArgument> {
+        let buf = self.to_bytes()?;
+        Ok(u16::from_be_bytes([buf[34], buf[35]]))
+    }
+
+    /// Decode from the layout written by [`to_bytes()`](#method.to_bytes)
+    ///
+    /// The addresses of pipes 2 to 5 are restored as the pipe 1 address
+    /// with their own first byte.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, DecodeError> {
+        let Ok(buf) = <&[u8; Self::ENCODED_LEN]>::try_from(buf) else {
+            return Err(DecodeError::Length);
+        };
+        if buf[0] != Self::ENCODING_VERSION {
+            return Err(DecodeError::UnknownVersion(buf[0]));
+        }
+        if buf[34..36] != crc16(&buf[0..34]).to_be_bytes() {
+            return Err(DecodeError::Checksum);
+        }
+
+        let channel = Channel::new(buf[1]).map_err(|_| DecodeError::InvalidField)?;
+        let data_rate = match buf[2] {
+            0 => DataRate::R250Kbps,
+            1 => DataRate::R1Mbps,
+            2 => DataRate::R2Mbps,
+            _ => return Err(DecodeError::InvalidField),
+        };
+        let power = match buf[3] {
+            0 => PowerLevel::Min,
+            1 => PowerLevel::Low,
+            2 => PowerLevel::High,
+            3 => PowerLevel::Max,
+            _ => return Err(DecodeError::InvalidField),
+        };
+        let crc = match buf[4] {
+            0 => CrcMode::Disabled,
+            1 => CrcMode::OneByte,
+            2 => CrcMode::TwoBytes,
+            _ => return Err(DecodeError::InvalidField),
+        };
+        let mut pipes_rx_lengths = [None; PIPES_COUNT];
+        for (length, &byte) in pipes_rx_lengths.iter_mut().zip(&buf[8..14]) {
+            *length = match byte {
+                0xFF => None,
+                len @ 0..=32 => Some(len),
+                _ => return Err(DecodeError::InvalidField),
+            };
+        }
+
+        let width = usize::from(buf[14]);
+        let address = |offset: usize| {
+            buf.get(offset..offset.saturating_add(width))
+                .and_then(|bytes| Address::new(bytes).ok())
+                .ok_or(DecodeError::InvalidField)
+        };
+        let tx_addr = address(15)?;
+        let pipe0 = address(20)?;
+        let pipe1 = address(25)?;
+        let mut rx_addrs = [None; PIPES_COUNT];
+        // Pipes 2 to 5 store their first byte at 30 to 33
+        let firsts = &buf[28..34];
+        for ((pipe_no, rx_addr), &first) in rx_addrs.iter_mut().enumerate().zip(firsts) {
+            if buf[7] & (1 << pipe_no) == 0 {
+                continue;
+            }
+            *rx_addr = Some(match pipe_no {
+                0 => pipe0,
+                1 => pipe1,
+                _ => {
+                    let mut bytes = [0; MAX_ADDR_BYTES];
+                    copy_bytes(&mut bytes, &pipe1);
+                    bytes[0] = first;
+                    let bytes = bytes.get(..width).unwrap_or_default();
+                    Address::new(bytes).map_err(|_| DecodeError::InvalidField)?
+                }
+            });
+        }
+
+        Ok(RadioConfig {
+            channel,
+            data_rate,
+            power,
+            crc,
+            auto_retransmit_delay: buf[5] >> 4,
+            auto_retransmit_count: buf[5] & 0xF,
+            auto_ack: EnAa(buf[6]).to_bools(),
+            pipes_rx_lengths,
+            tx_addr,
+            rx_addrs,
+        })
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+#[cfg(feature = "config-bytes")]
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Build `RF_SETUP`, kept out of the generic trait methods
+fn rf_setup(rate: &DataRate, power: u8, variant: ChipVariant) -> Result<RfSetup, InvalidArgument> {
+    if power >= 0b100 {
+        return Err(InvalidArgument::Power);
+    }
+    let mut register = RfSetup(0);
+    register.set_rf_pwr(power);
+    if variant == ChipVariant::NonPlus {
+        if *rate == DataRate::R250Kbps {
+            return Err(InvalidArgument::DataRate);
+        }
+        register.set_lna_hcurr(true);
+    }
+
+    let (dr_low, dr_high) = match *rate {
+        DataRate::R250Kbps => (true, false),
+        DataRate::R1Mbps => (false, false),
+        DataRate::R2Mbps => (false, true),
+    };
+    register.set_rf_dr_low(dr_low);
+    register.set_rf_dr_high(dr_high);
+    Ok(register)
+}
+
+/// Shortest `ARD` value that leaves time to receive an ACK carrying
+/// `ack_payload_len` bytes, after section 7.4.2 of the datasheet
+fn min_ard(rate: &DataRate, ack_payload_len: u8) -> u8 {
+    match (*rate, ack_payload_len) {
+        // 250 µS
+        (DataRate::R2Mbps, 0..=15) | (DataRate::R1Mbps, 0..=5) => 0,
+        // 500 µS is enough for any ACK payload
+        (DataRate::R2Mbps, _) | (DataRate::R1Mbps, _) => 1,
+        // 500 µS, then another 250 µS for every 8 bytes
+        (DataRate::R250Kbps, len) => len.div_ceil(8).min(4).saturating_add(1),
+    }
+}
+
+/// Pipes 0 and 1 take a full address, pipes 2 to 5 a single byte
+fn check_rx_addr_len(pipe_no: usize, len: usize) -> Result<(), InvalidArgument> {
+    let valid_len = match pipe_no {
+        0 | 1 => (MIN_ADDR_BYTES..=MAX_ADDR_BYTES).contains(&len),
+        _ => len == 1,
+    };
+    if valid_len {
+        Ok(())
+    } else {
+        Err(InvalidArgument::AddressLength)
+    }
 }
 
 /// Configuration methods
@@ -54,6 +680,17 @@ pub trait Configuration {
     /// Get a mutable reference to the underlying device
     fn device(&mut self) -> &mut Self::Inner;
 
+    /// Traffic counters since construction or the last
+    /// [`reset_stats()`](#method.reset_stats)
+    fn stats(&mut self) -> Stats {
+        *self.device().stats_mut()
+    }
+
+    /// Reset all traffic counters to zero
+    fn reset_stats(&mut self) {
+        *self.device().stats_mut() = Stats::default();
+    }
+
     /// Flush RX queue
     ///
     /// Discards all received packets that have not yet been [read](struct.RxMode.html#method.read) from the RX FIFO
@@ -69,21 +706,18 @@ pub trait Configuration {
     }
 
     /// Get frequency offset (channel)
-    async fn get_frequency(&mut self) -> Result<u8, <<Self as Configuration>::Inner as Device>::Error> {
+    async fn get_frequency(&mut self) -> Result<Channel, <<Self as Configuration>::Inner as Device>::Error> {
         let (_, register) = self.device().read_register::<RfCh>().await?;
-        let freq_offset = register.rf_ch();
-        Ok(freq_offset)
+        Ok(Channel::from_register(register.rf_ch()))
     }
 
     /// Set frequency offset (channel)
     async fn set_frequency(
         &mut self,
-        freq_offset: u8,
+        channel: Channel,
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
-        assert!(freq_offset < 126);
-
         let mut register = RfCh(0);
-        register.set_rf_ch(freq_offset);
+        register.set_rf_ch(channel.value());
         self.device().write_register(register).await?;
 
         Ok(())
@@ -95,22 +729,72 @@ pub trait Configuration {
         rate: &DataRate,
         power: u8,
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
-        assert!(power < 0b100);
-        let mut register = RfSetup(0);
-        register.set_rf_pwr(power);
-
-        let (dr_low, dr_high) = match *rate {
-            DataRate::R250Kbps => (true, false),
-            DataRate::R1Mbps => (false, false),
-            DataRate::R2Mbps => (false, true),
-        };
-        register.set_rf_dr_low(dr_low);
-        register.set_rf_dr_high(dr_high);
-
+        let variant = self.device().variant();
+        let register = rf_setup(rate, power, variant)?;
         self.device().write_register(register).await?;
         Ok(())
     }
 
+    /// Find out which [`ChipVariant`](enum.ChipVariant.html) this is, and
+    /// record it so that the other methods apply its quirks
+    ///
+    /// A Beken chip shows its register bank in `STATUS` after
+    /// `ACTIVATE 0x53`. Only the nRF24L01+ can select 250 kbps in
+    /// `RF_SETUP`. The original nRF24L01 ignores `FEATURE` until it
+    /// gets `ACTIVATE 0x73`, so this activates and rewrites `FEATURE`
+    /// as set up by the constructor. `RF_SETUP` is restored.
+    async fn detect_variant(
+        &mut self,
+    ) -> Result<ChipVariant, <<Self as Configuration>::Inner as Device>::Error> {
+        const RBANK: u8 = 0x80;
+        // Probing writes that the chip ignores are no SPI faults
+        let verify = self.device().set_verify_writes(false);
+        let variant = async {
+            let (status, ()) = self.device().send_command(&Nop).await?;
+            self.device().send_command(&Activate(0x53)).await?;
+            // The STATUS of ACTIVATE itself is from before switching
+            let (switched, ()) = self.device().send_command(&Nop).await?;
+            let variant = if switched.0 & RBANK != status.0 & RBANK {
+                // Back to bank 0
+                self.device().send_command(&Activate(0x53)).await?;
+                ChipVariant::Beken
+            } else {
+                let (_, original) = self.device().read_register::<RfSetup>().await?;
+                let mut probe = original.clone();
+                probe.set_rf_dr_low(true);
+                probe.set_rf_dr_high(false);
+                self.device().write_register(probe).await?;
+                let (_, probed) = self.device().read_register::<RfSetup>().await?;
+                self.device().write_register(original).await?;
+                if probed.rf_dr_low() {
+                    ChipVariant::Plus
+                } else {
+                    ChipVariant::NonPlus
+                }
+            };
+            Ok::<_, <<Self as Configuration>::Inner as Device>::Error>(variant)
+        }
+        .await;
+        self.device().set_verify_writes(verify);
+        let variant = variant?;
+
+        if variant == ChipVariant::NonPlus {
+            let mut features = Feature(0);
+            features.set_en_dyn_ack(true);
+            features.set_en_dpl(true);
+            let (_, current) = self.device().read_register::<Feature>().await?;
+            // ACTIVATE toggles, only send it if still inactive
+            if current != features {
+                self.device().send_command(&Activate(0x73)).await?;
+                self.device().write_register(features).await?;
+                // EN_DPL only takes effect now
+                self.device().set_rx_lengths(None);
+            }
+        }
+        self.device().set_variant(variant);
+        Ok(variant)
+    }
+
     /// Set CRC mode
     async fn set_crc(
         &mut self,
@@ -136,6 +820,28 @@ pub trait Configuration {
         }).await
     }
 
+    /// Read the `CONFIG` register from the chip, e.g. to snapshot the
+    /// exact chip mode around a custom sequence
+    async fn get_chip_config(
+        &mut self,
+    ) -> Result<ChipConfig, <<Self as Configuration>::Inner as Device>::Error> {
+        let (_, config) = self.device().read_register::<Config>().await?;
+        Ok(ChipConfig::from_register(&config))
+    }
+
+    /// Write the `CONFIG` register, e.g. to restore a snapshot from
+    /// [`get_chip_config()`](#method.get_chip_config)
+    ///
+    /// Keeps the driver's copy of `CONFIG` in sync, but `power_up` and
+    /// `prim_rx` bypass the operation modes: leave them as the current
+    /// mode has them once the custom sequence is done.
+    async fn set_chip_config(
+        &mut self,
+        chip_config: &ChipConfig,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        self.device().update_config(|config| chip_config.apply(config)).await
+    }
+
     /// Configure which RX pipes to enable
     async fn set_pipes_rx_enable(
         &mut self,
@@ -146,11 +852,16 @@ pub trait Configuration {
     }
 
     /// Set address `addr` of pipe number `pipe_no`
+    ///
+    /// Pipes 0 and 1 take a full address, pipes 2 to 5 only the least
+    /// significant byte.
     async fn set_rx_addr(
         &mut self,
         pipe_no: usize,
         addr: &[u8],
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        check_rx_addr_len(pipe_no, addr.len())?;
+
         macro_rules! w {
             ( $($no: expr, $name: ident);+ ) => (
                 match pipe_no {
@@ -161,7 +872,7 @@ pub trait Configuration {
                             self.device().write_register(register).await?;
                         }
                     )+
-                        _ => panic!("No such pipe {}", pipe_no)
+                        _ => return Err(InvalidArgument::Pipe.into())
                 }
             )
         }
@@ -174,24 +885,204 @@ pub trait Configuration {
         Ok(())
     }
 
+    /// Receive an [`AddressFamily`](struct.AddressFamily.html) on pipes
+    /// 1 to 5, disabling the pipes it does not use
+    ///
+    /// Sets the address width to the width of the family, which applies
+    /// to pipe 0 and the TX address too. Pipe 0 is left as it is.
+    async fn listen_family(
+        &mut self,
+        family: &AddressFamily,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        self.set_address_width(family.width()).await?;
+        let (_, enabled) = self.device().read_register::<EnRxaddr>().await?;
+        let mut enable = [false; PIPES_COUNT];
+        enable[0] = enabled.0 & 1 != 0;
+        for ((pipe_no, suffix), enable) in (1..).zip(family.suffixes()).zip(&mut enable[1..]) {
+            if let Some(suffix) = *suffix {
+                if pipe_no == 1 {
+                    self.set_rx_addr(1, &family.address(suffix)).await?;
+                } else {
+                    self.set_rx_addr(pipe_no, &[suffix]).await?;
+                }
+                *enable = true;
+            }
+        }
+        // Pipes 2 to 5 share the upper bytes of pipe 1
+        if family.suffixes()[0].is_none() {
+            self.set_rx_addr(1, &family.address(0)).await?;
+        }
+        self.set_pipes_rx_enable(&enable).await
+    }
+
+    /// Set up for raw frames of a third-party protocol
+    ///
+    /// Unlike [`configure()`](#method.configure), this accepts 2 byte
+    /// addresses and does not check addresses against each other. Pad
+    /// frames to the length with
+    /// [`TxMode::set_static_payload_len()`](struct.TxMode.html#method.set_static_payload_len).
+    async fn configure_raw(
+        &mut self,
+        raw: &RawConfig,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        let width = usize::from(raw.address_width);
+        if !(2..=MAX_ADDR_BYTES).contains(&width) {
+            return Err(InvalidArgument::AddressWidth.into());
+        }
+        self.set_pipes_rx_lengths(&[Some(raw.payload_len); PIPES_COUNT]).await?;
+        self.set_frequency(raw.channel).await?;
+        self.set_rf(&raw.data_rate, raw.power.value()).await?;
+        self.set_crc(raw.crc).await?;
+        let retransmits = if raw.auto_ack { 3 } else { 0 };
+        self.set_auto_retransmit(1, retransmits).await?;
+        let mut auto_ack = [false; PIPES_COUNT];
+        auto_ack[0] = raw.auto_ack;
+        self.set_auto_ack(&auto_ack).await?;
+
+        // SETUP_AW = 0b00 for 2 bytes is undocumented
+        self.device().write_register(SetupAw(raw.address_width.saturating_sub(2))).await?;
+        let address = raw.address.get(..width).unwrap_or_default();
+        self.device().write_register(RxAddrP0::new(address)).await?;
+        self.device().write_register(TxAddr::new(address)).await?;
+        let mut enable = [false; PIPES_COUNT];
+        enable[0] = true;
+        self.set_pipes_rx_enable(&enable).await
+    }
+
+    /// Rewrite every register after the chip has lost its state, e.g.
+    /// in a brown-out, from the cached `CONFIG` and `FEATURE` and from
+    /// `config`
+    ///
+    /// `FEATURE` keeps bits such as `EN_ACK_PAY`, and is unlocked again
+    /// with `ACTIVATE` on the original nRF24L01 and clones. Waits `Tpd2stby` on
+    /// `delay` for the oscillator in case the chip powered up again.
+    /// Flushes both FIFOs and clears interrupts. `CE` is left alone, so
+    /// disable it first while listening.
+    async fn reinit<DL: DelayNs>(
+        &mut self,
+        config: &RadioConfig,
+        delay: &mut DL,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        let features = self.device().features().unwrap_or_else(|| {
+            // As set up by NRF24L01::new()
+            let mut features = Feature(0);
+            features.set_en_dyn_ack(true);
+            features.set_en_dpl(true);
+            features
+        });
+        self.device().restore_config().await?;
+        delay.delay_ms(SETTLE_MS).await;
+        // A locked FEATURE is no SPI fault
+        let verify = self.device().set_verify_writes(false);
+        let restored = async {
+            self.device().write_register(features.clone()).await?;
+            if self.device().variant() != ChipVariant::Plus {
+                let (_, current) = self.device().read_register::<Feature>().await?;
+                // ACTIVATE toggles, only send it if locked
+                if current != features {
+                    self.device().send_command(&Activate(0x73)).await?;
+                    self.device().write_register(features).await?;
+                }
+            }
+            Ok::<_, <<Self as Configuration>::Inner as Device>::Error>(())
+        }
+        .await;
+        self.device().set_verify_writes(verify);
+        restored?;
+        self.device().set_rx_lengths(None);
+        self.configure(config).await?;
+        self.flush_rx().await?;
+        self.flush_tx().await?;
+        self.clear_interrupts().await
+    }
+
     /// Set address of the TX pipe
     async fn set_tx_addr(
         &mut self,
         addr: &[u8],
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        if !(MIN_ADDR_BYTES..=MAX_ADDR_BYTES).contains(&addr.len()) {
+            return Err(InvalidArgument::AddressLength.into());
+        }
         let register = TxAddr::new(addr);
         self.device().write_register(register).await?;
         Ok(())
     }
 
+    /// Set the TX address and the addresses of all RX pipes, enabling
+    /// exactly the pipes given
+    ///
+    /// All addresses must be as long as `tx`, which sets the address
+    /// width. Pipes 2 to 5 share all but their first byte with pipe 1,
+    /// so those bytes must be equal across pipes 1 to 5. Nothing is
+    /// written if a check fails:
+    /// * `InvalidArgument::AddressLength` for an address of another width
+    /// * `InvalidArgument::AddressPrefix` for pipes 1 to 5 with different
+    ///   shared bytes
+    /// * `InvalidArgument::AddressConflict` for addresses the chip can
+    ///   not tell apart, including an RX pipe other than 0 with the TX
+    ///   address while pipe 0 auto-acks, see
+    ///   [`RadioConfig::address_conflict()`](struct.RadioConfig.html#method.address_conflict)
+    ///
+    /// If a write fails midway, the previous addresses are written back.
+    async fn set_addresses(
+        &mut self,
+        tx: Address,
+        rx: [Option<Address>; PIPES_COUNT],
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        let width = tx.len();
+        if rx.iter().flatten().any(|addr| addr.len() != width) {
+            return Err(InvalidArgument::AddressLength.into());
+        }
+        let mut shared =
+            rx.iter().skip(1).flatten().map(|addr| addr.as_bytes().get(1..).unwrap_or_default());
+        if let Some(prefix) = shared.next() {
+            if shared.any(|other| other != prefix) {
+                return Err(InvalidArgument::AddressPrefix.into());
+            }
+        }
+        // Only the addresses and the auto-ack of pipe 0 matter for
+        // conflicts
+        let (_, setup_retr) = self.device().read_register::<SetupRetr>().await?;
+        let check = RadioConfig {
+            auto_ack: self.get_auto_ack().await?,
+            auto_retransmit_count: setup_retr.arc(),
+            tx_addr: tx,
+            rx_addrs: rx,
+            ..RadioConfig::default()
+        };
+        if let Some(conflict) = check.address_conflict() {
+            return Err(InvalidArgument::AddressConflict(conflict).into());
+        }
+
+        let snapshot = RegisterSnapshot::read(self.device()).await?;
+        let written = match self.set_address_width(width as u8).await {
+            Ok(()) => match self.set_tx_addr(&tx).await {
+                Ok(()) => set_rx_addrs(self, &rx).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            // The bus may be gone, report the first error
+            let _ = snapshot.restore(self.device()).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
     /// Configure auto-retransmit
     ///
-    /// To disable, call as `set_auto_retransmit(0, 0)`.
+    /// `delay` and `count` go up to 15. To disable, call as
+    /// `set_auto_retransmit(0, 0)`.
     async fn set_auto_retransmit(
         &mut self,
         delay: u8,
         count: u8,
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        if delay > 15 || count > 15 {
+            return Err(InvalidArgument::AutoRetransmit.into());
+        }
         let mut register = SetupRetr(0);
         register.set_ard(delay);
         register.set_arc(count);
@@ -199,6 +1090,29 @@ pub trait Configuration {
         Ok(())
     }
 
+    /// Program the shortest legal auto retransmit delay for `rate` and
+    /// ACK payloads of up to `ack_payload_len` bytes, keeping the
+    /// retransmit count. Returns the `ARD` value.
+    ///
+    /// The delay runs from the end of a transmission, so it does not
+    /// depend on `payload_len`, which is only validated. A delay that is
+    /// too short makes the PTX miss ACKs that carry a payload.
+    async fn auto_retransmit_for(
+        &mut self,
+        payload_len: u8,
+        rate: &DataRate,
+        ack_payload_len: u8,
+    ) -> Result<u8, <<Self as Configuration>::Inner as Device>::Error> {
+        if payload_len > 32 || ack_payload_len > 32 {
+            return Err(InvalidArgument::PayloadLength.into());
+        }
+        let ard = min_ard(rate, ack_payload_len);
+        self.device()
+            .update_register::<SetupRetr, _, _>(|register| register.set_ard(ard))
+            .await?;
+        Ok(ard)
+    }
+
     /// Obtain auto-acknowledgment configuration for all pipes
     async fn get_auto_ack(
         &mut self,
@@ -224,19 +1138,48 @@ pub trait Configuration {
         Ok(())
     }
 
+    /// Whether ACK payloads (`EN_ACK_PAY`) are enabled
+    async fn get_ack_payloads(
+        &mut self,
+    ) -> Result<bool, <<Self as Configuration>::Inner as Device>::Error> {
+        let (_, feature) = self.device().read_register::<Feature>().await?;
+        Ok(feature.en_ack_pay())
+    }
+
+    /// Enable or disable ACK payloads (`EN_ACK_PAY`), for replies
+    /// queued with
+    /// [`RxMode::write_ack_payload()`](struct.RxMode.html#method.write_ack_payload)
+    /// and read with
+    /// [`TxMode::read_ack_payload()`](struct.TxMode.html#method.read_ack_payload)
+    ///
+    /// Both ends need it, along with dynamic payload lengths and auto
+    /// ack. Longer ACKs need a longer retransmit delay, see
+    /// [`auto_retransmit_for()`](#method.auto_retransmit_for).
+    async fn set_ack_payloads(
+        &mut self,
+        enable: bool,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        self.device()
+            .update_register::<Feature, _, _>(|feature| feature.set_en_ack_pay(enable))
+            .await
+    }
+
     /// Get address width configuration
     async fn get_address_width(
         &mut self,
     ) -> Result<u8, <<Self as Configuration>::Inner as Device>::Error> {
         let (_, register) = self.device().read_register::<SetupAw>().await?;
-        Ok(2 + register.aw())
+        Ok(register.aw().saturating_add(2))
     }
 
     /// Set address width configuration
     async fn set_address_width(&mut self, width: u8)
         -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        if !(MIN_ADDR_BYTES..=MAX_ADDR_BYTES).contains(&usize::from(width)) {
+            return Err(InvalidArgument::AddressWidth.into());
+        }
 
-        let register = SetupAw(width - 2);
+        let register = SetupAw(width.saturating_sub(2));
         self.device().write_register(register).await?;
         Ok(())
     }
@@ -264,24 +1207,181 @@ pub trait Configuration {
         Ok(())
     }
 
+    /// Apply a complete [`RadioConfig`](struct.RadioConfig.html)
+    ///
+    /// Fails before writing any register with
+    /// * `InvalidArgument::AddressLength` for RX addresses of another
+    ///   width than `tx_addr`, as the chip has one `SETUP_AW` for all
+    /// * `InvalidArgument::AddressConflict` for addresses the chip can
+    ///   not tell apart, see
+    ///   [`RadioConfig::address_conflict()`](struct.RadioConfig.html#method.address_conflict)
+    async fn configure(
+        &mut self,
+        config: &RadioConfig,
+    ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
+        let width = config.tx_addr.len();
+        if config.rx_addrs.iter().flatten().any(|addr| addr.len() != width) {
+            return Err(InvalidArgument::AddressLength.into());
+        }
+        if let Some(conflict) = config.address_conflict() {
+            return Err(InvalidArgument::AddressConflict(conflict).into());
+        }
+        self.set_frequency(config.channel).await?;
+        self.set_rf(&config.data_rate, config.power.value()).await?;
+        self.set_crc(config.crc).await?;
+        self.set_auto_retransmit(config.auto_retransmit_delay, config.auto_retransmit_count)
+            .await?;
+        self.set_auto_ack(&config.auto_ack).await?;
+        self.set_pipes_rx_lengths(&config.pipes_rx_lengths).await?;
+
+        self.set_address_width(config.tx_addr.len() as u8).await?;
+        self.set_tx_addr(&config.tx_addr).await?;
+        set_rx_addrs(self, &config.rx_addrs).await
+    }
+
+    /// Read the complete configuration back from the chip
+    ///
+    /// Addresses of pipes 2 to 5 are completed with the shared bytes of
+    /// pipe 1.
+    async fn get_radio_config(
+        &mut self,
+    ) -> Result<RadioConfig, <<Self as Configuration>::Inner as Device>::Error> {
+        let channel = self.get_frequency().await?;
+        let (_, rf_setup) = self.device().read_register::<RfSetup>().await?;
+        let data_rate = if rf_setup.rf_dr_low() {
+            DataRate::R250Kbps
+        } else if rf_setup.rf_dr_high() {
+            DataRate::R2Mbps
+        } else {
+            DataRate::R1Mbps
+        };
+        let power = match rf_setup.rf_pwr() {
+            0 => PowerLevel::Min,
+            1 => PowerLevel::Low,
+            2 => PowerLevel::High,
+            _ => PowerLevel::Max,
+        };
+        let (_, config) = self.device().read_register::<Config>().await?;
+        let crc = CrcMode::from_config(&config);
+        let (_, setup_retr) = self.device().read_register::<SetupRetr>().await?;
+        let auto_ack = self.get_auto_ack().await?;
+        let pipes_rx_lengths = self.get_pipes_rx_lengths().await?;
+
+        let width = usize::from(self.get_address_width().await?);
+        let (_, tx_addr) = self.device().read_register::<TxAddr>().await?;
+        let (_, p0) = self.device().read_register::<RxAddrP0>().await?;
+        let (_, p1) = self.device().read_register::<RxAddrP1>().await?;
+        let mut own = [0; PIPES_COUNT];
+        macro_rules! get_rx_addr {
+            ($name: ident, $index: expr) => {{
+                use crate::registers::$name;
+                let (_, register) = self.device().read_register::<$name>().await?;
+                own[$index] = register.0;
+            }};
+        }
+        get_rx_addr!(RxAddrP2, 2);
+        get_rx_addr!(RxAddrP3, 3);
+        get_rx_addr!(RxAddrP4, 4);
+        get_rx_addr!(RxAddrP5, 5);
+        let (_, enabled) = self.device().read_register::<EnRxaddr>().await?;
+
+        let mut rx_addrs = [None; PIPES_COUNT];
+        let pipes = rx_addrs.iter_mut().zip(enabled.to_bools()).zip(own).enumerate();
+        for (pipe_no, ((addr, enabled), own)) in pipes {
+            if !enabled {
+                continue;
+            }
+            let mut bytes = [0; MAX_ADDR_BYTES];
+            copy_bytes(&mut bytes, if pipe_no == 0 { p0.as_bytes() } else { p1.as_bytes() });
+            if pipe_no >= 2 {
+                copy_bytes(&mut bytes, &[own]);
+            }
+            *addr = Some(Address::new(bytes.get(..width).unwrap_or_default())?);
+        }
+
+        Ok(RadioConfig {
+            channel,
+            data_rate,
+            power,
+            crc,
+            auto_retransmit_delay: setup_retr.ard(),
+            auto_retransmit_count: setup_retr.arc(),
+            auto_ack,
+            pipes_rx_lengths,
+            tx_addr: Address::new(tx_addr.as_bytes().get(..width).unwrap_or_default())?,
+            rx_addrs,
+        })
+    }
+
+    /// Change several settings at once, all or nothing
+    ///
+    /// Reads the current configuration from the chip, lets `f` change a
+    /// copy of it, and writes the settings that changed. If reading the
+    /// configuration back does not show the change, or a write fails
+    /// midway, the registers are restored as they were, including
+    /// states such as an `RF_CH` of 126 that `f` could not set.
+    ///
+    /// Returns `false` if the change was rolled back after a failed
+    /// read-back, and the error of a failed write after trying to roll
+    /// back.
+    async fn transaction<F>(
+        &mut self,
+        f: F,
+    ) -> Result<bool, <<Self as Configuration>::Inner as Device>::Error>
+    where
+        F: FnOnce(&mut RadioConfig),
+    {
+        let snapshot = RegisterSnapshot::read(self.device()).await?;
+        let previous = self.get_radio_config().await?;
+        let mut staged = previous.clone();
+        f(&mut staged);
+        if staged == previous {
+            return Ok(true);
+        }
+
+        let applied = match apply_changes(self, &previous, &staged).await {
+            Ok(()) => self.get_radio_config().await,
+            Err(e) => Err(e),
+        };
+        match applied {
+            Ok(chip) if staged.is_applied(&chip) => Ok(true),
+            Ok(_) => {
+                snapshot.restore(self.device()).await?;
+                Ok(false)
+            }
+            Err(e) => {
+                // The bus may be gone, report the first error
+                let _ = snapshot.restore(self.device()).await;
+                Err(e)
+            }
+        }
+    }
+
     /// ## `bools`
     /// * `None`: Dynamic payload length
-    /// * `Some(len)`: Static payload length `len`
+    /// * `Some(len)`: Static payload length `len`, 1 to 32 bytes, or 0
+    ///   for a pipe on which the chip receives nothing, as after reset
+    ///
+    /// If all pipes are static, `EN_DPL` is cleared for peers that do
+    /// not use dynamic payload lengths at all, see
+    /// [`RadioConfig::static_payloads()`](struct.RadioConfig.html#method.static_payloads).
     async fn set_pipes_rx_lengths(
         &mut self,
         lengths: &[Option<u8>; PIPES_COUNT],
     ) -> Result<(), <<Self as Configuration>::Inner as Device>::Error> {
-        // Enable dynamic payload lengths
-        let mut bools = [true; PIPES_COUNT];
-        for (i, length) in lengths.iter().enumerate() {
-            bools[i] = length.is_none();
+        if lengths.iter().flatten().any(|&len| len > 32) {
+            return Err(InvalidArgument::PayloadLength.into());
         }
+        // Unknown if a write fails midway
+        self.device().set_rx_lengths(None);
+
+        // Enable dynamic payload lengths, or disable them completely for
+        // peers that use static lengths only
+        let bools = lengths.map(|length| length.is_none());
         let dynpd = Dynpd::from_bools(&bools);
-        if dynpd.0 != 0 {
-            self.device().update_register::<Feature, _, _>(|feature| {
-                feature.set_en_dpl(true);
-            }).await?;
-        }
+        self.device().update_register::<Feature, _, _>(|feature| {
+            feature.set_en_dpl(dynpd.0 != 0);
+        }).await?;
         self.device().write_register(dynpd).await?;
 
         // Set static payload lengths
@@ -301,6 +1401,368 @@ pub trait Configuration {
         set_rx_pw!(RxPwP4, 4);
         set_rx_pw!(RxPwP5, 5);
 
+        self.device().set_rx_lengths(Some(*lengths));
         Ok(())
     }
+
+    /// Read back the payload lengths in the representation of
+    /// [`set_pipes_rx_lengths()`](#method.set_pipes_rx_lengths)
+    ///
+    /// A pipe is dynamic if both its `DYNPD` bit and `EN_DPL` are set.
+    /// `Some(0)` is a static pipe without a length, as after reset, on
+    /// which the chip receives nothing.
+    async fn get_pipes_rx_lengths(
+        &mut self,
+    ) -> Result<[Option<u8>; PIPES_COUNT], <<Self as Configuration>::Inner as Device>::Error> {
+        let (_, feature) = self.device().read_register::<Feature>().await?;
+        let (_, dynpd) = self.device().read_register::<Dynpd>().await?;
+
+        let mut lengths = [None; PIPES_COUNT];
+        macro_rules! get_rx_pw {
+            ($name: ident, $index: expr) => {{
+                use crate::registers::$name;
+                if !(feature.en_dpl() && dynpd.dpl_p($index)) {
+                    let (_, register) = self.device().read_register::<$name>().await?;
+                    lengths[$index] = Some(register.get());
+                }
+            }};
+        }
+        get_rx_pw!(RxPwP0, 0);
+        get_rx_pw!(RxPwP1, 1);
+        get_rx_pw!(RxPwP2, 2);
+        get_rx_pw!(RxPwP3, 3);
+        get_rx_pw!(RxPwP4, 4);
+        get_rx_pw!(RxPwP5, 5);
+
+        self.device().set_rx_lengths(Some(lengths));
+        Ok(lengths)
+    }
+}
+
+/// Write `rx_addrs` and enable the pipes that have one
+async fn set_rx_addrs<C: Configuration + ?Sized>(
+    c: &mut C,
+    rx_addrs: &[Option<Address>; PIPES_COUNT],
+) -> Result<(), <C::Inner as Device>::Error> {
+    let mut enable = [false; PIPES_COUNT];
+    for ((pipe_no, addr), enable) in rx_addrs.iter().enumerate().zip(&mut enable) {
+        if let Some(addr) = addr {
+            let addr = if pipe_no < 2 { &addr[..] } else { addr.get(..1).unwrap_or_default() };
+            c.set_rx_addr(pipe_no, addr).await?;
+            *enable = true;
+        }
+    }
+    // Pipes 2 to 5 take the shared bytes from pipe 1 even while it is
+    // disabled
+    if rx_addrs[1].is_none() {
+        if let Some(addr) = rx_addrs[2..].iter().flatten().next() {
+            c.set_rx_addr(1, addr).await?;
+        }
+    }
+    c.set_pipes_rx_enable(&enable).await
+}
+
+/// Write the settings of `to` that differ from `from`, which the chip
+/// holds, so that the others stay exactly as they are
+pub(crate) async fn apply_changes<C: Configuration + ?Sized>(
+    c: &mut C,
+    from: &RadioConfig,
+    to: &RadioConfig,
+) -> Result<(), <C::Inner as Device>::Error> {
+    if let Some(conflict) = to.address_conflict() {
+        return Err(InvalidArgument::AddressConflict(conflict).into());
+    }
+    if to.channel != from.channel {
+        c.set_frequency(to.channel).await?;
+    }
+    if to.data_rate != from.data_rate || to.power != from.power {
+        c.set_rf(&to.data_rate, to.power.value()).await?;
+    }
+    if to.crc != from.crc {
+        c.set_crc(to.crc).await?;
+    }
+    if to.auto_retransmit_delay != from.auto_retransmit_delay
+        || to.auto_retransmit_count != from.auto_retransmit_count
+    {
+        c.set_auto_retransmit(to.auto_retransmit_delay, to.auto_retransmit_count).await?;
+    }
+    if to.auto_ack != from.auto_ack {
+        c.set_auto_ack(&to.auto_ack).await?;
+    }
+    if to.pipes_rx_lengths != from.pipes_rx_lengths {
+        c.set_pipes_rx_lengths(&to.pipes_rx_lengths).await?;
+    }
+    let width_changed = to.tx_addr.len() != from.tx_addr.len();
+    if width_changed {
+        c.set_address_width(to.tx_addr.len() as u8).await?;
+    }
+    if width_changed || to.tx_addr != from.tx_addr {
+        c.set_tx_addr(&to.tx_addr).await?;
+    }
+    if width_changed || to.rx_addrs != from.rx_addrs {
+        set_rx_addrs(c, &to.rx_addrs).await?;
+    }
+    Ok(())
+}
+
+/// Raw values of the registers that a [`RadioConfig`](struct.RadioConfig.html)
+/// covers
+///
+/// Restores the chip exactly, also in states that `RadioConfig` cannot
+/// express or `configure()` rejects, such as `RF_CH` above 125.
+pub(crate) struct RegisterSnapshot {
+    crc: CrcMode,
+    registers: [u8; 18],
+    addrs: [[u8; MAX_ADDR_BYTES]; 3],
+}
+
+macro_rules! snapshot_registers {
+    ($m: ident) => {
+        // FEATURE before DYNPD, which depends on it
+        $m!(0, Feature);
+        $m!(1, Dynpd);
+        $m!(2, EnAa);
+        $m!(3, EnRxaddr);
+        $m!(4, SetupAw);
+        $m!(5, SetupRetr);
+        $m!(6, RfCh);
+        $m!(7, RfSetup);
+        $m!(8, RxAddrP2);
+        $m!(9, RxAddrP3);
+        $m!(10, RxAddrP4);
+        $m!(11, RxAddrP5);
+        $m!(12, RxPwP0);
+        $m!(13, RxPwP1);
+        $m!(14, RxPwP2);
+        $m!(15, RxPwP3);
+        $m!(16, RxPwP4);
+        $m!(17, RxPwP5);
+    };
+}
+
+macro_rules! snapshot_addrs {
+    ($m: ident) => {
+        $m!(0, TxAddr);
+        $m!(1, RxAddrP0);
+        $m!(2, RxAddrP1);
+    };
+}
+
+impl RegisterSnapshot {
+    /// Read all registers
+    pub(crate) async fn read<D: Device>(device: &mut D) -> Result<Self, D::Error> {
+        use crate::registers::*;
+
+        let (_, config) = device.read_register::<Config>().await?;
+        let mut snapshot = RegisterSnapshot {
+            crc: CrcMode::from_config(&config),
+            registers: [0; 18],
+            addrs: [[0; MAX_ADDR_BYTES]; 3],
+        };
+        macro_rules! read {
+            ($index: expr, $name: ident) => {{
+                let (_, register) = device.read_register::<$name>().await?;
+                register.encode(&mut snapshot.registers[$index..=$index]);
+            }};
+        }
+        snapshot_registers!(read);
+        macro_rules! read_addr {
+            ($index: expr, $name: ident) => {{
+                let (_, register) = device.read_register::<$name>().await?;
+                register.encode(&mut snapshot.addrs[$index]);
+            }};
+        }
+        snapshot_addrs!(read_addr);
+        Ok(snapshot)
+    }
+
+    /// Write all registers back
+    pub(crate) async fn restore<D: Device>(&self, device: &mut D) -> Result<(), D::Error> {
+        use crate::registers::*;
+
+        // Unknown if a write fails midway
+        device.set_rx_lengths(None);
+        macro_rules! write {
+            ($index: expr, $name: ident) => {{
+                let register = $name::decode(&self.registers[$index..=$index]);
+                device.write_register(register).await?;
+            }};
+        }
+        snapshot_registers!(write);
+        macro_rules! write_addr {
+            ($index: expr, $name: ident) => {{
+                device.write_register($name::decode(&self.addrs[$index])).await?;
+            }};
+        }
+        snapshot_addrs!(write_addr);
+        let crc = self.crc;
+        device.update_config(|config| crc.set_config(config)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::registers::RfCh;
+    use crate::sim::{Air, SimChip, SimRadio};
+    use crate::{Error, StandbyMode};
+
+    /// A driver as after `new()`, without `configure()`, and its chip
+    async fn fresh(air: &Air) -> (StandbyMode<SimRadio>, SimChip) {
+        let (ce, spi) = air.radio();
+        let chip = spi.chip();
+        (SimRadio::new(ce, spi).await.unwrap(), chip)
+    }
+
+    #[test]
+    fn transaction_on_fresh_driver() {
+        block_on(async {
+            let air = Air::new();
+            let (mut standby, chip) = fresh(&air).await;
+            assert_eq!(standby.get_pipes_rx_lengths().await.ok(), Some([Some(0); PIPES_COUNT]));
+
+            let applied = standby.transaction(|config| config.power = PowerLevel::Low).await;
+            assert_eq!(applied.ok(), Some(true));
+            assert_eq!(standby.get_radio_config().await.unwrap().power, PowerLevel::Low);
+            // Pipes without a length stay so
+            assert_eq!(chip.register(0x11), 0);
+        });
+    }
+
+    #[test]
+    fn transaction_keeps_rf_ch_above_125() {
+        block_on(async {
+            let air = Air::new();
+            let (mut standby, chip) = fresh(&air).await;
+            let mut rf_ch = RfCh(0);
+            rf_ch.set_rf_ch(127);
+            standby.device().write_register(rf_ch).await.unwrap();
+            assert_eq!(standby.get_frequency().await.ok().map(Channel::value), Some(127));
+
+            let applied = standby.transaction(|config| config.data_rate = DataRate::R2Mbps).await;
+            assert_eq!(applied.ok(), Some(true));
+            assert_eq!(chip.register(0x05), 127);
+        });
+    }
+
+    #[test]
+    fn transaction_rolls_back_to_raw_registers() {
+        block_on(async {
+            let air = Air::new();
+            let (mut standby, chip) = fresh(&air).await;
+            let mut rf_ch = RfCh(0);
+            rf_ch.set_rf_ch(126);
+            standby.device().write_register(rf_ch).await.unwrap();
+            // FEATURE ignores writes until ACTIVATE, so dynamic lengths
+            // never read back
+            chip.set_variant(ChipVariant::NonPlus);
+
+            let applied = standby
+                .transaction(|config| {
+                    config.power = PowerLevel::Min;
+                    config.pipes_rx_lengths = [None; PIPES_COUNT];
+                })
+                .await;
+            assert_eq!(applied.ok(), Some(false));
+            assert_eq!(chip.register(0x05), 126);
+            assert_eq!(chip.register(0x06) & 0b110, 0b110);
+            assert_eq!(chip.register(0x1C), 0);
+        });
+    }
+
+    #[test]
+    fn configure_checks_address_widths() {
+        block_on(async {
+            let air = Air::new();
+            let (mut standby, chip) = fresh(&air).await;
+            let mut config = RadioConfig {
+                channel: Channel::new(90).unwrap(),
+                ..RadioConfig::default()
+            };
+            config.rx_addrs[1] = Some(Address::new(&[9, 8, 7, 6]).unwrap());
+            assert!(matches!(
+                standby.configure(&config).await,
+                Err(Error::InvalidArgument(InvalidArgument::AddressLength))
+            ));
+            assert_eq!(chip.register(0x05), 2);
+        });
+    }
+
+    #[test]
+    fn set_addresses_checks_tx_address() {
+        block_on(async {
+            let air = Air::new();
+            let (mut standby, _) = fresh(&air).await;
+            let tx = Address::from_array([1, 2, 3, 4, 5]);
+            let rx = [None, Some(tx), None, None, None, None];
+            let conflict = AddressConflict::TxAddress(1);
+            assert!(matches!(
+                standby.set_addresses(tx, rx).await,
+                Err(Error::InvalidArgument(InvalidArgument::AddressConflict(c))) if c == conflict
+            ));
+        });
+    }
+
+    #[test]
+    fn set_addresses_is_all_or_nothing() {
+        block_on(async {
+            let air = Air::new();
+            let tx = Address::from_array([1, 2, 3, 4, 5]);
+            let rx = [
+                Some(tx),
+                Some(Address::from_array([9, 8, 7, 6, 5])),
+                Some(Address::from_array([10, 8, 7, 6, 5])),
+                None,
+                None,
+                None,
+            ];
+            let (mut standby, chip) = fresh(&air).await;
+            chip.clear_opcodes();
+            standby.set_addresses(tx, rx).await.unwrap();
+            let transactions = chip.opcodes().len() as u32;
+
+            for after in 0..transactions {
+                let (mut standby, chip) = fresh(&air).await;
+                let before = standby.get_radio_config().await.unwrap();
+                chip.fail_transfers_after(after, 1);
+                if standby.set_addresses(tx, rx).await.is_err() {
+                    assert_eq!(standby.get_radio_config().await.ok(), Some(before));
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "config-bytes")]
+    #[test]
+    fn bytes_round_trip() {
+        let mut config = RadioConfig {
+            auto_retransmit_delay: 15,
+            auto_retransmit_count: 15,
+            pipes_rx_lengths: [Some(0), Some(32), None, None, None, None],
+            ..RadioConfig::default()
+        };
+        config.rx_addrs[1] = None;
+        config.rx_addrs[2] = Some(Address::from_array([0x33, 0xB2, 0xB3, 0xB4, 0xB5]));
+        config.rx_addrs[3] = Some(Address::from_array([0x34, 0xB2, 0xB3, 0xB4, 0xB5]));
+        let bytes = config.to_bytes().unwrap();
+        assert_eq!(RadioConfig::from_bytes(&bytes), Ok(config));
+    }
+
+    #[cfg(feature = "config-bytes")]
+    #[test]
+    fn bytes_reject_what_does_not_round_trip() {
+        let config = RadioConfig { auto_retransmit_delay: 16, ..RadioConfig::default() };
+        assert_eq!(config.to_bytes(), Err(InvalidArgument::AutoRetransmit));
+        assert_eq!(config.config_hash(), Err(InvalidArgument::AutoRetransmit));
+
+        let mut config = RadioConfig::default();
+        config.rx_addrs[0] = Some(Address::new(&[1, 2, 3]).unwrap());
+        assert_eq!(config.to_bytes(), Err(InvalidArgument::AddressWidth));
+
+        let mut config = RadioConfig::default();
+        config.rx_addrs[1] = Some(Address::from_array([1, 2, 3, 4, 5]));
+        config.rx_addrs[2] = Some(Address::from_array([6, 2, 3, 4, 9]));
+        assert_eq!(config.to_bytes(), Err(InvalidArgument::AddressPrefix));
+    }
 }
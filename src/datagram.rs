@@ -0,0 +1,107 @@
+use crate::address::Address;
+use crate::device::Device;
+use crate::payload::copy_bytes;
+use crate::socket::RadioSocket;
+use embedded_hal_async::delay::DelayNs;
+
+/// Error of a [`Datagram`](trait.Datagram.html) transfer
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DatagramError<E> {
+    /// Radio failure
+    Device(E),
+    /// The datagram is longer than
+    /// [`Datagram::MAX_LEN`](trait.Datagram.html#associatedconstant.MAX_LEN)
+    TooLong,
+    /// The peer did not acknowledge the datagram
+    NotAcknowledged,
+}
+
+impl<E> From<E> for DatagramError<E> {
+    fn from(e: E) -> Self {
+        DatagramError::Device(e)
+    }
+}
+
+/// Minimal UDP-like datagram interface, shaped after `embedded-nal`'s
+/// `UnconnectedUdp` with nRF24 addresses in place of socket addresses
+///
+/// Lets protocol crates written against a datagram abstraction run over
+/// nRF24 links. Unlike UDP, the sender of a datagram is not known, as
+/// it is not part of the packet. Receiving never waits, so that the
+/// caller can wait for the IRQ pin or poll as suits the application.
+pub trait Datagram {
+    /// Error of the underlying link
+    type Error;
+    /// Largest datagram that can be sent
+    const MAX_LEN: usize;
+
+    /// Send `data` to `remote`
+    async fn send_to(&mut self, remote: &Address, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receive the next datagram into `buffer`, truncating it if
+    /// `buffer` is shorter. Returns the length copied and the local
+    /// address the datagram was sent to, or `None` if there is none.
+    async fn receive_into(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<Option<(usize, Address)>, Self::Error>;
+}
+
+/// [`RadioSocket`](struct.RadioSocket.html) with the delay that its
+/// sends poll with, as [`Datagram`](trait.Datagram.html) has no room
+/// for one
+pub struct DatagramSocket<D: Device, DL: DelayNs> {
+    socket: RadioSocket<D>,
+    delay: DL,
+}
+
+impl<D: Device, DL: DelayNs> DatagramSocket<D, DL> {
+    /// Constructor
+    pub fn new(socket: RadioSocket<D>, delay: DL) -> Self {
+        DatagramSocket { socket, delay }
+    }
+
+    /// Get a mutable reference to the socket, e.g. to bind it
+    pub fn socket(&mut self) -> &mut RadioSocket<D> {
+        &mut self.socket
+    }
+
+    /// Return the socket and the delay
+    pub fn into_inner(self) -> (RadioSocket<D>, DL) {
+        (self.socket, self.delay)
+    }
+}
+
+/// Datagrams of up to one frame of the socket, received on the bound
+/// address
+impl<D: Device, DL: DelayNs> Datagram for DatagramSocket<D, DL> {
+    type Error = DatagramError<D::Error>;
+    const MAX_LEN: usize = RadioSocket::<D>::MAX_LEN;
+
+    async fn send_to(&mut self, remote: &Address, data: &[u8]) -> Result<(), Self::Error> {
+        if data.len() > Self::MAX_LEN {
+            return Err(DatagramError::TooLong);
+        }
+        if self.socket.send_to(remote, data, &mut self.delay).await? {
+            Ok(())
+        } else {
+            Err(DatagramError::NotAcknowledged)
+        }
+    }
+
+    async fn receive_into(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<Option<(usize, Address)>, Self::Error> {
+        let payload = match self.socket.recv().await? {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+        let local = match self.socket.local_addr() {
+            Some(local) => *local,
+            None => return Ok(None),
+        };
+        let len = copy_bytes(buffer, &payload);
+        Ok(Some((len, local)))
+    }
+}
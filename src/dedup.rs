@@ -0,0 +1,146 @@
+use crate::PIPES_COUNT;
+
+/// How a [`DuplicateFilter`](struct.DuplicateFilter.html) recognizes
+/// a retransmitted packet
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DedupKey {
+    /// The payload byte at this offset is a sequence number that the
+    /// sender increments for every new packet
+    SequenceByte(usize),
+    /// Compare a hash of the whole payload
+    ///
+    /// The PID of the enhanced shockburst packet is not visible to
+    /// the PRX, so a retransmission is assumed whenever the same
+    /// payload arrives twice in a row on the same pipe. Senders that
+    /// legitimately repeat identical payloads should use
+    /// `SequenceByte` instead.
+    PayloadHash,
+}
+
+/// Drops packets that were delivered twice because an ACK got lost
+/// and the PTX retransmitted.
+///
+/// Keeps state for each pipe separately.
+#[derive(Debug, Clone)]
+pub struct DuplicateFilter {
+    key: DedupKey,
+    enabled: [bool; PIPES_COUNT],
+    last: [Option<u32>; PIPES_COUNT],
+}
+
+impl DuplicateFilter {
+    /// Constructor, with filtering enabled on all pipes
+    pub fn new(key: DedupKey) -> Self {
+        DuplicateFilter {
+            key,
+            enabled: [true; PIPES_COUNT],
+            last: [None; PIPES_COUNT],
+        }
+    }
+
+    /// Enable or disable filtering for pipe number `pipe_no`
+    pub fn set_pipe_enabled(&mut self, pipe_no: usize, enable: bool) {
+        if let (Some(enabled), Some(last)) =
+            (self.enabled.get_mut(pipe_no), self.last.get_mut(pipe_no))
+        {
+            *enabled = enable;
+            *last = None;
+        }
+    }
+
+    /// Forget all previously seen packets
+    pub fn reset(&mut self) {
+        self.last = [None; PIPES_COUNT];
+    }
+
+    /// Record `payload` as received on pipe `pipe_no`, returning
+    /// `true` if it is a duplicate of the previous packet on that pipe.
+    pub fn is_duplicate(&mut self, pipe_no: u8, payload: &[u8]) -> bool {
+        let pipe_no = usize::from(pipe_no);
+        if !self.enabled.get(pipe_no).copied().unwrap_or(false) {
+            return false;
+        }
+
+        let id = match self.key {
+            DedupKey::SequenceByte(offset) => match payload.get(offset) {
+                Some(seq) => u32::from(*seq),
+                // Too short to carry a sequence number
+                None => return false,
+            },
+            DedupKey::PayloadHash => fnv1a(payload),
+        };
+        let Some(last) = self.last.get_mut(pipe_no) else {
+            return false;
+        };
+        let duplicate = *last == Some(id);
+        *last = Some(id);
+        duplicate
+    }
+}
+
+/// 32-bit FNV-1a
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, b| {
+        (hash ^ u32::from(*b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_repeated_sequence_numbers() {
+        let mut filter = DuplicateFilter::new(DedupKey::SequenceByte(0));
+        assert!(!filter.is_duplicate(1, &[7, 1]));
+        assert!(filter.is_duplicate(1, &[7, 2]));
+        assert!(!filter.is_duplicate(1, &[8, 2]));
+        // Too short for a sequence number
+        assert!(!filter.is_duplicate(1, &[]));
+        assert!(!filter.is_duplicate(1, &[]));
+    }
+
+    #[test]
+    fn drops_repeated_payloads() {
+        let mut filter = DuplicateFilter::new(DedupKey::PayloadHash);
+        assert!(!filter.is_duplicate(0, b"one"));
+        assert!(filter.is_duplicate(0, b"one"));
+        assert!(!filter.is_duplicate(0, b"two"));
+        assert!(!filter.is_duplicate(0, b"one"));
+    }
+
+    #[test]
+    fn pipes_are_separate() {
+        let mut filter = DuplicateFilter::new(DedupKey::SequenceByte(0));
+        assert!(!filter.is_duplicate(1, &[5]));
+        assert!(!filter.is_duplicate(2, &[5]));
+        assert!(filter.is_duplicate(1, &[5]));
+        assert!(filter.is_duplicate(2, &[5]));
+
+        filter.set_pipe_enabled(2, false);
+        assert!(!filter.is_duplicate(2, &[5]));
+        assert!(!filter.is_duplicate(2, &[5]));
+        assert!(filter.is_duplicate(1, &[5]));
+        // No such pipe
+        filter.set_pipe_enabled(PIPES_COUNT, false);
+        assert!(!filter.is_duplicate(PIPES_COUNT as u8, &[5]));
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let mut filter = DuplicateFilter::new(DedupKey::SequenceByte(1));
+        assert!(!filter.is_duplicate(0, &[0, 254]));
+        assert!(!filter.is_duplicate(0, &[0, 255]));
+        assert!(!filter.is_duplicate(0, &[0, 0]));
+        assert!(filter.is_duplicate(0, &[0, 0]));
+        assert!(!filter.is_duplicate(0, &[0, 1]));
+    }
+
+    #[test]
+    fn reset_forgets() {
+        let mut filter = DuplicateFilter::new(DedupKey::SequenceByte(0));
+        assert!(!filter.is_duplicate(3, &[9]));
+        filter.reset();
+        assert!(!filter.is_duplicate(3, &[9]));
+    }
+}
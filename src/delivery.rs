@@ -0,0 +1,275 @@
+use crate::device::Device;
+use crate::payload::Payload;
+use crate::strategy::Strategy;
+use crate::timer::RadioTimer;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Storage for messages that have not been acknowledged yet
+///
+/// Implement this on top of flash, EEPROM or battery-backed RAM to
+/// keep messages across resets. Messages are handled in FIFO order.
+///
+/// Each message can have a deadline from
+/// [`push_until()`](#method.push_until), which the store keeps with it
+/// and returns from [`front_deadline()`](#method.front_deadline).
+pub trait MessageStore {
+    /// Error from the storage backend
+    type Error;
+
+    /// Append a message to the end of the queue
+    async fn push(&mut self, message: &[u8]) -> Result<(), Self::Error>;
+    /// Get a copy of the oldest message without removing it
+    async fn front(&mut self) -> Result<Option<Payload>, Self::Error>;
+    /// Remove the oldest message once it has been acknowledged
+    async fn pop(&mut self) -> Result<(), Self::Error>;
+
+    /// Append a message that expires at `deadline`, in µS of the
+    /// [`RadioTimer`](trait.RadioTimer.html) passed to
+    /// [`ReliableSender::pump_timed()`](struct.ReliableSender.html#method.pump_timed)
+    async fn push_until(&mut self, message: &[u8], deadline: u64) -> Result<(), Self::Error>;
+    /// Get the deadline of the oldest message, `None` if it was pushed
+    /// without one
+    async fn front_deadline(&mut self) -> Result<Option<u64>, Self::Error>;
+}
+
+/// Outcome of [`ReliableSender::pump()`](struct.ReliableSender.html#method.pump)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Delivery {
+    /// The store is empty
+    Idle,
+    /// The oldest message was acknowledged and removed from the store
+    Delivered,
+    /// Maximum retransmissions reached, the message stays in the store
+    Failed,
+    /// The deadline of the oldest message had passed, it was removed
+    /// from the store without sending it
+    Expired,
+}
+
+/// Error of the [`ReliableSender`](struct.ReliableSender.html)
+#[derive(Debug)]
+pub enum DeliveryError<DE, SE> {
+    /// Radio failure
+    Device(DE),
+    /// Storage failure
+    Store(SE),
+}
+
+/// Store-and-forward layer with at-least-once semantics
+///
+/// Messages are written to the [`MessageStore`](trait.MessageStore.html)
+/// first and only removed after the peer has acknowledged them. A
+/// message that has been received but whose ACK got lost will be sent
+/// again, so receivers should drop duplicates, e.g. with a
+/// [`DuplicateFilter`](struct.DuplicateFilter.html).
+///
+/// Requires auto-ack and auto-retransmit to be enabled, and the TX FIFO
+/// not to be used by anything else while pumping.
+pub struct ReliableSender<S: MessageStore> {
+    store: S,
+}
+
+impl<S: MessageStore> ReliableSender<S> {
+    /// Constructor
+    ///
+    /// Messages already present in `store`, e.g. from before a reset,
+    /// are sent first.
+    pub fn new(store: S) -> Self {
+        ReliableSender { store }
+    }
+
+    /// Get a mutable reference to the underlying store
+    pub fn store(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Return the underlying store
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Queue a message for delivery
+    pub async fn enqueue(&mut self, message: &[u8]) -> Result<(), S::Error> {
+        self.store.push(message).await
+    }
+
+    /// Queue a message that is dropped if it has not been delivered by
+    /// `deadline`, e.g. a control command that is useless when late
+    ///
+    /// Only [`pump_timed()`](#method.pump_timed) checks deadlines.
+    pub async fn enqueue_until(&mut self, message: &[u8], deadline: u64) -> Result<(), S::Error> {
+        self.store.push_until(message, deadline).await
+    }
+
+    /// Like [`pump()`](#method.pump), but first drop the oldest message
+    /// if its deadline has passed according to `timer`
+    ///
+    /// Call again after `Delivery::Expired` to send the next message.
+    pub async fn pump_timed<D: Device, T: RadioTimer>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        timer: &mut T,
+    ) -> Result<Delivery, DeliveryError<D::Error, S::Error>> {
+        let deadline = self.store.front_deadline().await.map_err(DeliveryError::Store)?;
+        if deadline.is_some_and(|deadline| timer.now_micros() >= deadline) {
+            self.store.pop().await.map_err(DeliveryError::Store)?;
+            return Ok(Delivery::Expired);
+        }
+        self.pump(tx, timer).await
+    }
+
+    /// Try to deliver the oldest stored message, waiting until the
+    /// transmission has completed, polling with `delay`.
+    pub async fn pump<D: Device, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        delay: &mut DL,
+    ) -> Result<Delivery, DeliveryError<D::Error, S::Error>> {
+        let message = match self.store.front().await.map_err(DeliveryError::Store)? {
+            Some(message) => message,
+            None => return Ok(Delivery::Idle),
+        };
+
+        tx.send(&message).await.map_err(DeliveryError::Device)?;
+        let acked = tx
+            .wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay)
+            .await
+            .map_err(DeliveryError::Device)?;
+
+        if acked {
+            self.store.pop().await.map_err(DeliveryError::Store)?;
+            Ok(Delivery::Delivered)
+        } else {
+            Ok(Delivery::Failed)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+
+    /// Messages in RAM, with their deadlines
+    #[derive(Default)]
+    pub(crate) struct RamStore(pub(crate) VecDeque<(Payload, Option<u64>)>);
+
+    impl MessageStore for RamStore {
+        type Error = Infallible;
+
+        async fn push(&mut self, message: &[u8]) -> Result<(), Infallible> {
+            self.0.push_back((Payload::new(message), None));
+            Ok(())
+        }
+        async fn front(&mut self) -> Result<Option<Payload>, Infallible> {
+            Ok(self.0.front().map(|(message, _)| Payload::new(message)))
+        }
+        async fn pop(&mut self) -> Result<(), Infallible> {
+            self.0.pop_front();
+            Ok(())
+        }
+        async fn push_until(&mut self, message: &[u8], deadline: u64) -> Result<(), Infallible> {
+            self.0.push_back((Payload::new(message), Some(deadline)));
+            Ok(())
+        }
+        async fn front_deadline(&mut self) -> Result<Option<u64>, Infallible> {
+            Ok(self.0.front().and_then(|(_, deadline)| *deadline))
+        }
+    }
+
+    fn sent(air: &Air, message: &[u8]) -> usize {
+        air.frames().iter().filter(|frame| frame.payload == message).count()
+    }
+
+    #[test]
+    fn delivers_in_order() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut sender = ReliableSender::new(RamStore::default());
+            sender.enqueue(b"one").await.unwrap();
+            sender.enqueue(b"two").await.unwrap();
+
+            for message in [b"one", b"two"] {
+                assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Delivered));
+                assert_eq!(rx.read().await.unwrap().as_ref(), message);
+            }
+            assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Idle));
+        });
+    }
+
+    #[test]
+    fn failed_message_is_sent_again() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut sender = ReliableSender::new(RamStore::default());
+            sender.enqueue(b"one").await.unwrap();
+
+            // The packet and its 3 retransmits
+            air.lose_next(4);
+            assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Failed));
+            assert_eq!(sender.store().0.len(), 1);
+            assert!(rx.can_read().await.unwrap().is_none());
+
+            assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Delivered));
+            assert!(sender.store().0.is_empty());
+            assert_eq!(rx.read().await.unwrap().as_ref(), b"one");
+            assert!(rx.can_read().await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn unreachable_peer_keeps_the_message() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &crate::RadioConfig::default()).await;
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut timer = SimTimer::new();
+            let mut sender = ReliableSender::new(RamStore::default());
+            sender.enqueue(b"one").await.unwrap();
+
+            for _ in 0..3 {
+                assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Failed));
+                assert!(tx.is_empty().await.unwrap());
+            }
+            assert_eq!(sender.into_inner().0.len(), 1);
+        });
+    }
+
+    #[test]
+    fn deadlines() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut sender = ReliableSender::new(RamStore::default());
+            sender.enqueue_until(b"late", 1_000).await.unwrap();
+            sender.enqueue_until(b"soon", 5_000).await.unwrap();
+            sender.enqueue(b"any").await.unwrap();
+
+            timer.advance(1_000);
+            let delivery = sender.pump_timed(&mut tx, &mut timer).await.ok();
+            assert_eq!(delivery, Some(Delivery::Expired));
+            assert_eq!(sent(&air, b"late"), 0);
+            for message in [&b"soon"[..], b"any"] {
+                let delivery = sender.pump_timed(&mut tx, &mut timer).await.ok();
+                assert_eq!(delivery, Some(Delivery::Delivered));
+                assert_eq!(rx.read().await.unwrap().as_ref(), message);
+            }
+
+            // `pump()` does not check deadlines
+            sender.enqueue_until(b"late", 0).await.unwrap();
+            assert_eq!(sender.pump(&mut tx, &mut timer).await.ok(), Some(Delivery::Delivered));
+        });
+    }
+}
@@ -1,15 +1,26 @@
 use crate::command::Command;
-use crate::registers::{Config, Register, Status};
+use crate::error::{InvalidArgument, StateError};
+use crate::events::RadioEvents;
+use crate::registers::{Config, Feature, Register, Status};
+use crate::stats::Stats;
+use crate::variant::ChipVariant;
+use crate::PIPES_COUNT;
 
 /// Trait that hides all the GPIO/SPI type parameters for use by the
 /// operation modes
 pub trait Device {
     /// Error from the SPI implementation
-    type Error;
+    type Error: From<InvalidArgument> + From<StateError>;
+    /// Receiver of lifecycle callbacks
+    type Events: RadioEvents;
 
     /// Set CE pin high
+    ///
+    /// A failure is reported by the next command.
     fn ce_enable(&mut self);
     /// Set CE pin low
+    ///
+    /// A failure is reported by the next command.
     fn ce_disable(&mut self);
     /// Helper; the receiving during RX and sending during TX require `CE`
     /// to be low.
@@ -23,22 +34,91 @@ pub trait Device {
         r
     }
 
+    /// `CE` is tied high, see [`NoCe`](struct.NoCe.html), so that
+    /// `ce_enable()` and `ce_disable()` have no effect
+    fn ce_tied(&self) -> bool {
+        false
+    }
+
+    /// Turn read-back checks of register writes on or off, and return
+    /// whether they were on
+    ///
+    /// Each write is then read back, and a mismatch is reported to
+    /// [`RadioEvents::on_write_mismatch()`](trait.RadioEvents.html#method.on_write_mismatch).
+    /// Devices without the checks ignore this.
+    fn set_verify_writes(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    /// Chip family the configuration methods adapt to
+    fn variant(&self) -> ChipVariant;
+    /// Record the chip family
+    fn set_variant(&mut self, variant: ChipVariant);
+
+    /// Payload length per pipe as last set with
+    /// [`set_pipes_rx_lengths()`](trait.Configuration.html#method.set_pipes_rx_lengths)
+    /// or read back, or `None` if unknown
+    ///
+    /// Lets `RxMode` tell static pipes apart without reading `DYNPD` and
+    /// `RX_PW_Px` for every packet. Devices without the cache read them
+    /// back every time.
+    fn rx_lengths(&self) -> Option<[Option<u8>; PIPES_COUNT]> {
+        None
+    }
+    /// Record the payload lengths, or forget them with `None`
+    fn set_rx_lengths(&mut self, _lengths: Option<[Option<u8>; PIPES_COUNT]>) {}
+
+    /// `FEATURE` as last written, or `None` if unknown
+    ///
+    /// Lets [`reinit()`](trait.Configuration.html#method.reinit) restore
+    /// it after the chip lost its state, with bits such as `EN_ACK_PAY`
+    /// that `RadioConfig` does not hold. Devices without the cache
+    /// restore the setup of `NRF24L01::new()`.
+    fn features(&self) -> Option<Feature> {
+        None
+    }
+
+    /// `STATUS` as clocked out by the last command, or `None` if
+    /// unknown
+    ///
+    /// Lets `TxMode::try_send()` see a full TX FIFO without clocking in
+    /// a payload. Devices without the cache return `None`.
+    fn last_status(&self) -> Option<Status> {
+        None
+    }
+
+    /// Traffic counters, updated by the device and the operation modes
+    fn stats_mut(&mut self) -> &mut Stats;
+
+    /// Lifecycle callbacks, invoked by the operation modes
+    fn events(&mut self) -> &mut Self::Events;
+
     /// Send a command via SPI
     async fn send_command<C: Command>(&mut self, command: &C) -> Result<(Status, C::Response), Self::Error>;
+    /// Send the command `opcode` followed by `data`
+    ///
+    /// `data` is written straight from the slice, without copying it
+    /// into a command buffer, while keeping `CSN` asserted throughout.
+    async fn write_payload(&mut self, opcode: u8, data: &[u8]) -> Result<Status, Self::Error>;
+    /// Send the command `opcode` and read its response straight into `buf`
+    async fn read_payload(&mut self, opcode: u8, buf: &mut [u8]) -> Result<Status, Self::Error>;
+    /// Write an already encoded command straight from `buf`, without
+    /// copying it, and return the `STATUS` clocked out with its first
+    /// byte
+    async fn write_raw(&mut self, buf: &[u8]) -> Result<Status, Self::Error>;
     /// Send `W_REGISTER` command
     async fn write_register<R: Register>(&mut self, register: R) -> Result<Status, Self::Error>;
     /// Send `R_REGISTER` command
     async fn read_register<R: Register>(&mut self) -> Result<(Status, R), Self::Error>;
 
     /// Read, and modify a register, and write it back if it has been changed.
+    ///
+    /// Use `update_config()` for `registers::Config` which is cached.
     async fn update_register<Reg, F, R>(&mut self, f: F) -> Result<R, Self::Error>
     where
         Reg: Register + PartialEq + Clone,
         F: FnOnce(&mut Reg) -> R,
     {
-        // Use `update_config()` for `registers::Config`
-        assert_ne!(Reg::addr(), 0x00);
-
         let (_, old_register) = self.read_register::<Reg>().await?;
         let mut register = old_register.clone();
         let result = f(&mut register);
@@ -53,4 +133,7 @@ pub trait Device {
     async fn update_config<F, R>(&mut self, f: F) -> Result<R, Self::Error>
     where
         F: FnOnce(&mut Config) -> R;
+    /// Write the cached `CONFIG` register unconditionally, e.g. after
+    /// the chip has lost its state
+    async fn restore_config(&mut self) -> Result<(), Self::Error>;
 }
@@ -1,18 +1,248 @@
+use crate::config::AddressConflict;
+use core::fmt;
 use core::fmt::Debug;
 
 /// Wraps an SPI error
 ///
 /// TODO: eliminate this?
+///
+/// Subsystems behind a feature add their variants only with that
+/// feature, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error<SPIE: Debug> {
-    /// Wrap an SPI error
-    SpiError(SPIE),
+    /// Wrap an SPI error, with the command that failed
+    SpiError(SpiOp, SPIE),
     /// Module not connected
     NotConnected,
+    /// Driving the `CE` pin failed
+    PinError,
+    /// An argument is outside of what the chip supports
+    InvalidArgument(InvalidArgument),
+    /// `RadioSocket::send()` needs a peer from `connect()`
+    #[cfg(feature = "socket")]
+    NoPeer,
+    /// `RadioSocket::recv()` needs an address from `bind()`
+    #[cfg(feature = "socket")]
+    NotBound,
+    /// A cancelled `RadioSocket` call dropped the radio while switching
+    /// modes
+    #[cfg(feature = "socket")]
+    RadioLost,
+    /// The chip still reads as powered up or with packets to send after
+    /// `StandbyMode::power_down_verified()`
+    NotSilent,
+    /// The operation needs control of `CE`, which is tied high, see
+    /// `NoCe`
+    CeTied,
+    /// The chip did not keep the registers of a `SuspendState`, e.g.
+    /// because it lost power
+    StateLost,
+    /// The `Coexistence` hook denied the medium, so the packets wait in
+    /// the TX FIFO
+    MediumDenied,
+    /// `Commissioner` used its last nonce
+    #[cfg(feature = "commission")]
+    NoncesExhausted,
+}
+
+impl<SPIE: Debug> Error<SPIE> {
+    /// Wrap an SPI error of the operation `op`
+    ///
+    /// Replaces the `From<SPIE>` conversion of earlier versions, which
+    /// overlaps with `From<InvalidArgument>` for a generic `SPIE`.
+    pub fn spi(op: SpiOp, e: SPIE) -> Self {
+        Error::SpiError(op, e)
+    }
+}
+
+impl<SPIE: Debug> fmt::Display for Error<SPIE> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::SpiError(op, e) => write!(f, "{} failed: {:?}", op, e),
+            Error::NotConnected => write!(f, "Module not connected"),
+            Error::PinError => write!(f, "Cannot drive CE pin"),
+            Error::InvalidArgument(e) => write!(f, "Invalid argument: {:?}", e),
+            #[cfg(feature = "socket")]
+            Error::NoPeer => write!(f, "Socket has no peer"),
+            #[cfg(feature = "socket")]
+            Error::NotBound => write!(f, "Socket is not bound"),
+            #[cfg(feature = "socket")]
+            Error::RadioLost => write!(f, "Radio dropped by a cancelled call"),
+            Error::NotSilent => write!(f, "Chip still active after power down"),
+            Error::CeTied => write!(f, "CE is tied high"),
+            Error::StateLost => write!(f, "Chip lost its registers"),
+            Error::MediumDenied => write!(f, "Medium denied by coexistence"),
+            #[cfg(feature = "commission")]
+            Error::NoncesExhausted => write!(f, "Commissioning nonces used up"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<SPIE: Debug> std::error::Error for Error<SPIE> {}
+
+impl<SPIE: Debug> From<InvalidArgument> for Error<SPIE> {
+    fn from(e: InvalidArgument) -> Self {
+        Error::InvalidArgument(e)
+    }
 }
 
-impl<SPIE: Debug> From<SPIE> for Error<SPIE> {
-    fn from(e: SPIE) -> Self {
-        Error::SpiError(e)
+impl<SPIE: Debug> From<StateError> for Error<SPIE> {
+    fn from(e: StateError) -> Self {
+        match e {
+            #[cfg(feature = "socket")]
+            StateError::NoPeer => Error::NoPeer,
+            #[cfg(feature = "socket")]
+            StateError::NotBound => Error::NotBound,
+            #[cfg(feature = "socket")]
+            StateError::RadioLost => Error::RadioLost,
+            StateError::NotSilent => Error::NotSilent,
+            StateError::CeTied => Error::CeTied,
+            StateError::StateLost => Error::StateLost,
+            StateError::MediumDenied => Error::MediumDenied,
+            #[cfg(feature = "commission")]
+            StateError::NoncesExhausted => Error::NoncesExhausted,
+        }
     }
 }
+
+/// SPI command that failed, see `Error::SpiError`
+///
+/// Displays with the datasheet names, e.g. `WriteRegister(RF_CH)`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SpiOp {
+    /// `R_REGISTER` at this address
+    ReadRegister(u8),
+    /// `W_REGISTER` at this address
+    WriteRegister(u8),
+    /// Any other command, by opcode
+    Command(u8),
+}
+
+impl SpiOp {
+    /// Classify a command by its `opcode`
+    pub fn from_opcode(opcode: u8) -> Self {
+        match opcode {
+            0x00..=0x1F => SpiOp::ReadRegister(opcode),
+            0x20..=0x3F => SpiOp::WriteRegister(opcode & 0x1F),
+            _ => SpiOp::Command(opcode),
+        }
+    }
+}
+
+/// Datasheet name of the register at `addr`
+fn register_name(addr: u8) -> Option<&'static str> {
+    const NAMES: [&str; 0x18] = [
+        "CONFIG", "EN_AA", "EN_RXADDR", "SETUP_AW", "SETUP_RETR", "RF_CH", "RF_SETUP", "STATUS",
+        "OBSERVE_TX", "RPD", "RX_ADDR_P0", "RX_ADDR_P1", "RX_ADDR_P2", "RX_ADDR_P3", "RX_ADDR_P4",
+        "RX_ADDR_P5", "TX_ADDR", "RX_PW_P0", "RX_PW_P1", "RX_PW_P2", "RX_PW_P3", "RX_PW_P4",
+        "RX_PW_P5", "FIFO_STATUS",
+    ];
+    match addr {
+        0x1C => Some("DYNPD"),
+        0x1D => Some("FEATURE"),
+        _ => NAMES.get(usize::from(addr)).copied(),
+    }
+}
+
+/// Datasheet name of the command with `opcode`, other than registers
+fn command_name(opcode: u8) -> Option<&'static str> {
+    match opcode {
+        0x50 => Some("ACTIVATE"),
+        0x60 => Some("R_RX_PL_WID"),
+        0x61 => Some("R_RX_PAYLOAD"),
+        0xA0 => Some("W_TX_PAYLOAD"),
+        0xA8..=0xAD => Some("W_ACK_PAYLOAD"),
+        0xB0 => Some("W_TX_PAYLOAD_NOACK"),
+        0xE1 => Some("FLUSH_TX"),
+        0xE2 => Some("FLUSH_RX"),
+        0xE3 => Some("REUSE_TX_PL"),
+        0xFF => Some("NOP"),
+        _ => None,
+    }
+}
+
+impl fmt::Display for SpiOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, addr) = match *self {
+            SpiOp::ReadRegister(addr) => ("ReadRegister", addr),
+            SpiOp::WriteRegister(addr) => ("WriteRegister", addr),
+            SpiOp::Command(opcode) => match command_name(opcode) {
+                Some(name) => return f.write_str(name),
+                None => return write!(f, "Command(0x{:02X})", opcode),
+            },
+        };
+        match register_name(addr) {
+            Some(name) => write!(f, "{}({})", kind, name),
+            None => write!(f, "{}(0x{:02X})", kind, addr),
+        }
+    }
+}
+
+/// Which argument has been rejected
+///
+/// Returned instead of panicking so that the driver can be used in
+/// builds that must not panic.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InvalidArgument {
+    /// Channel (frequency offset) must be below 126
+    Channel,
+    /// Power level must be below 4
+    Power,
+    /// No such pipe number
+    Pipe,
+    /// Address width must be between `MIN_ADDR_BYTES` and `MAX_ADDR_BYTES`
+    AddressWidth,
+    /// Address length does not fit the address register of the pipe
+    AddressLength,
+    /// Payloads can be at most 32 bytes long
+    PayloadLength,
+    /// Data rate not supported by the `ChipVariant`
+    DataRate,
+    /// Pipes 1 to 5 must share all but the first address byte
+    AddressPrefix,
+    /// The chip can not tell these addresses apart, see
+    /// `RadioConfig::address_conflict()`
+    AddressConflict(AddressConflict),
+    /// TX FIFO watermark must be between 1 and 3
+    Watermark,
+    /// Auto retransmit delay and count must be below 16
+    AutoRetransmit,
+}
+
+impl fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid argument: {:?}", self)
+    }
+}
+
+/// Operation not possible in the current state of the driver or chip,
+/// for generic code over [`Device`](trait.Device.html)
+///
+/// Converts into the `Error` variant of the same name, `NoPeer` and so
+/// on.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
+pub enum StateError {
+    /// See `Error::NoPeer`
+    #[cfg(feature = "socket")]
+    NoPeer,
+    /// See `Error::NotBound`
+    #[cfg(feature = "socket")]
+    NotBound,
+    /// See `Error::RadioLost`
+    #[cfg(feature = "socket")]
+    RadioLost,
+    /// See `Error::NotSilent`
+    NotSilent,
+    /// See `Error::CeTied`
+    CeTied,
+    /// See `Error::StateLost`
+    StateLost,
+    /// See `Error::MediumDenied`
+    MediumDenied,
+    /// See `Error::NoncesExhausted`
+    #[cfg(feature = "commission")]
+    NoncesExhausted,
+}
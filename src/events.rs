@@ -0,0 +1,75 @@
+use crate::address::Address;
+
+/// Operation mode, as reported to
+/// [`RadioEvents::on_mode_change()`](trait.RadioEvents.html#method.on_mode_change)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ModeKind {
+    /// Powered down
+    PowerDown,
+    /// Standby-I
+    Standby,
+    /// RX mode
+    Rx,
+    /// TX mode
+    Tx,
+}
+
+/// Callbacks for radio lifecycle events
+///
+/// Pass an implementation to
+/// [`NRF24L01::new_with_events()`](struct.NRF24L01.html#method.new_with_events)
+/// to attach instrumentation, LED indicators, or watchdog kicks. The
+/// callbacks run synchronously in the driver call that observed the
+/// event, so keep them short. All methods default to doing nothing.
+pub trait RadioEvents {
+    /// A packet has been read from the RX FIFO
+    fn on_packet_received(&mut self, _pipe_no: u8, _payload: &[u8]) {}
+    /// A packet is about to be reported as received, and arrived at
+    /// `at` according to the source set with
+    /// [`RxMode::set_timestamp_source()`](struct.RxMode.html#method.set_timestamp_source),
+    /// e.g. for inter-arrival and jitter analysis
+    fn on_packet_arrival(&mut self, _pipe_no: u8, _at: u64) {}
+    /// The RX FIFO is full, packets arriving before it is read are
+    /// dropped. The application is reading too slowly.
+    fn on_rx_overflow(&mut self) {}
+    /// The TX FIFO has been sent completely
+    fn on_send_complete(&mut self) {}
+    /// A packet reached the maximum number of retransmits and the TX
+    /// FIFO was flushed
+    fn on_max_rt(&mut self) {}
+    /// Stale packets were flushed from the TX FIFO by
+    /// [`TxMode::flush_stale()`](struct.TxMode.html#method.flush_stale)
+    fn on_stale_flush(&mut self) {}
+    /// The TX FIFO has drained below the watermark set with
+    /// [`TxMode::set_watermark()`](struct.TxMode.html#method.set_watermark)
+    /// and is at `level` packets
+    fn on_tx_watermark(&mut self, _level: u8) {}
+    /// A [`Watchdog`](struct.Watchdog.html) found the chip locked up
+    /// and reinitialized it
+    fn on_recovery(&mut self) {}
+    /// A [`Failsafe`](struct.Failsafe.html) has not seen a valid packet
+    /// within its timeout
+    fn on_link_lost(&mut self) {}
+    /// A [`Failsafe`](struct.Failsafe.html) has seen a valid packet
+    /// again after the link was lost
+    fn on_link_restored(&mut self) {}
+    /// The chip has entered another mode
+    fn on_mode_change(&mut self, _mode: ModeKind) {}
+    /// Register `addr` read back as `read` right after `written` was
+    /// written to it, with
+    /// [`Device::set_verify_writes()`](trait.Device.html#method.set_verify_writes)
+    /// on. Points to marginal SPI signals, e.g. long wires or missing
+    /// decoupling.
+    fn on_write_mismatch(&mut self, _addr: u8, _written: &[u8], _read: &[u8]) {}
+    /// A [`ReachTracker`](struct.ReachTracker.html) marked `peer`
+    /// unreachable after repeated `MAX_RT`, and holds back sends to it
+    fn on_peer_unreachable(&mut self, _peer: &Address) {}
+    /// A peer marked unreachable acknowledged a probe
+    fn on_peer_reachable(&mut self, _peer: &Address) {}
+}
+
+/// Default [`RadioEvents`](trait.RadioEvents.html) that ignores everything
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoEvents;
+
+impl RadioEvents for NoEvents {}
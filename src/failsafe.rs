@@ -0,0 +1,251 @@
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::events::RadioEvents;
+use crate::payload::Payload;
+use crate::rx::RxMode;
+use crate::timer::RadioTimer;
+
+/// Receiver-side failsafe for RC and actuator links
+///
+/// Read packets through [`read()`](#method.read) instead of
+/// [`RxMode::read_with_pipe()`](struct.RxMode.html#method.read_with_pipe).
+/// If no valid packet arrives within the timeout, the failsafe trips:
+/// [`is_tripped()`](#method.is_tripped) returns `true` and
+/// [`RadioEvents::on_link_lost()`](trait.RadioEvents.html#method.on_link_lost)
+/// is called, e.g. to center servos or stop motors. The first valid
+/// packet afterwards calls
+/// [`RadioEvents::on_link_restored()`](trait.RadioEvents.html#method.on_link_restored).
+///
+/// Corrupt packets, which the driver returns as empty payloads, do not
+/// count as valid.
+pub struct Failsafe {
+    timeout_us: u64,
+    /// Time of the last valid packet, or of the first check
+    last_valid: Option<u64>,
+    tripped: bool,
+    trips: u32,
+    notice: Option<Payload>,
+}
+
+impl Failsafe {
+    /// Constructor, tripping after `timeout_us` without a valid packet
+    ///
+    /// The timer starts with the first call to [`read()`](#method.read).
+    pub fn new(timeout_us: u64) -> Self {
+        Failsafe {
+            timeout_us,
+            last_valid: None,
+            tripped: false,
+            trips: 0,
+            notice: None,
+        }
+    }
+
+    /// Has the link been lost?
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Number of times the link has been lost
+    pub fn trips(&self) -> u32 {
+        self.trips
+    }
+
+    /// Notify the transmitter when the link returns by loading `notice`
+    /// as ACK payload on the pipe the first valid packet came in on
+    ///
+    /// The notice goes out with the ACK of the next packet on that pipe.
+    /// Requires auto-ack and dynamic payload lengths on both ends.
+    /// `None` disables the notice.
+    pub fn set_notice(&mut self, notice: Option<&[u8]>) {
+        self.notice = notice.map(Payload::new);
+    }
+
+    /// Read the next received packet like
+    /// [`RxMode::read_with_pipe()`](struct.RxMode.html#method.read_with_pipe),
+    /// and trip if the timeout has passed on `timer` without a valid
+    /// packet
+    pub async fn read<D: Device, T: RadioTimer>(
+        &mut self,
+        rx: &mut RxMode<D>,
+        timer: &T,
+    ) -> Result<Option<(u8, Payload)>, D::Error> {
+        let now = timer.now_micros();
+        let packet = rx.read_with_pipe().await?;
+        match &packet {
+            Some((pipe_no, payload)) if !payload.is_empty() => {
+                self.last_valid = Some(now);
+                if self.tripped {
+                    self.tripped = false;
+                    if let Some(notice) = &self.notice {
+                        rx.set_ack_payloads(true).await?;
+                        rx.queue_ack_payload(*pipe_no, notice).await?;
+                    }
+                    rx.device().events().on_link_restored();
+                }
+            }
+            _ => {
+                let since = *self.last_valid.get_or_insert(now);
+                if !self.tripped && now.saturating_sub(since) >= self.timeout_us {
+                    self.tripped = true;
+                    self.trips = self.trips.wrapping_add(1);
+                    rx.device().events().on_link_lost();
+                }
+            }
+        }
+        Ok(packet)
+    }
+
+    /// Restart the timer, e.g. after switching channels, without
+    /// reporting a restored link
+    pub fn reset<T: RadioTimer>(&mut self, timer: &T) {
+        self.last_valid = Some(timer.now_micros());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimCe, SimSpi, SimTimer};
+    use crate::{Configuration, RadioConfig, TxMode, NRF24L01};
+    use core::convert::Infallible;
+
+    /// Counts the link events
+    #[derive(Debug, Default)]
+    struct Links {
+        lost: u32,
+        restored: u32,
+    }
+
+    impl RadioEvents for Links {
+        fn on_link_lost(&mut self) {
+            self.lost += 1;
+        }
+
+        fn on_link_restored(&mut self) {
+            self.restored += 1;
+        }
+    }
+
+    type Radio = NRF24L01<Infallible, SimCe, SimSpi, Links>;
+
+    async fn receiver(air: &Air) -> RxMode<Radio> {
+        let (ce, spi) = air.radio();
+        let mut standby = Radio::new_with_events(ce, spi, Links::default()).await.unwrap();
+        standby.configure(&RadioConfig::default()).await.unwrap();
+        standby.set_ack_payloads(true).await.unwrap();
+        standby.rx().await.map_err(|(_, e)| e).unwrap()
+    }
+
+    async fn transmitter(air: &Air) -> TxMode<sim::SimRadio> {
+        let mut standby = sim::standby(air, &RadioConfig::default()).await;
+        standby.set_ack_payloads(true).await.unwrap();
+        standby.tx().await.map_err(|(_, e)| e).unwrap()
+    }
+
+    fn links(rx: &mut RxMode<Radio>) -> (u32, u32) {
+        let events = rx.device().events();
+        (events.lost, events.restored)
+    }
+
+    #[test]
+    fn trips_after_the_timeout_and_restores() {
+        block_on(async {
+            let air = Air::new();
+            let mut rx = receiver(&air).await;
+            let mut tx = transmitter(&air).await;
+            let timer = SimTimer::new();
+            let mut failsafe = Failsafe::new(1_000);
+
+            assert!(failsafe.read(&mut rx, &timer).await.unwrap().is_none());
+            timer.advance(999);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert!(!failsafe.is_tripped());
+            timer.advance(1);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert!(failsafe.is_tripped());
+            timer.advance(5_000);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert_eq!((failsafe.trips(), links(&mut rx)), (1, (1, 0)));
+
+            tx.send(b"stick").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            let (pipe_no, packet) = failsafe.read(&mut rx, &timer).await.unwrap().unwrap();
+            assert_eq!((pipe_no, &packet[..]), (0, &b"stick"[..]));
+            assert!(!failsafe.is_tripped());
+            assert_eq!(links(&mut rx), (1, 1));
+
+            // The timeout runs from the last valid packet
+            timer.advance(999);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert!(!failsafe.is_tripped());
+            timer.advance(1);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert_eq!((failsafe.trips(), links(&mut rx)), (2, (2, 1)));
+        });
+    }
+
+    #[test]
+    fn corrupt_packets_do_not_count() {
+        block_on(async {
+            let air = Air::new();
+            let mut rx = receiver(&air).await;
+            let chip = rx.device().spi.chip();
+            let timer = SimTimer::new();
+            let mut failsafe = Failsafe::new(1_000);
+
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            timer.advance(1_000);
+            // A width above 32 bytes is corrupt
+            assert!(chip.receive(0, &[0; 33]));
+            let (_, packet) = failsafe.read(&mut rx, &timer).await.unwrap().unwrap();
+            assert!(packet.is_empty());
+            assert!(failsafe.is_tripped());
+            assert_eq!(links(&mut rx), (1, 0));
+        });
+    }
+
+    #[test]
+    fn notice_goes_out_with_the_next_ack() {
+        block_on(async {
+            let air = Air::new();
+            let mut rx = receiver(&air).await;
+            let mut tx = transmitter(&air).await;
+            let timer = SimTimer::new();
+            let mut failsafe = Failsafe::new(1_000);
+            failsafe.set_notice(Some(b"back"));
+
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            timer.advance(1_000);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert!(failsafe.is_tripped());
+
+            tx.send(b"one").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert!(tx.read_ack_payload().await.unwrap().is_none());
+            failsafe.read(&mut rx, &timer).await.unwrap().unwrap();
+            tx.send(b"two").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert_eq!(&tx.read_ack_payload().await.unwrap().unwrap()[..], b"back");
+        });
+    }
+
+    #[test]
+    fn reset_restarts_the_timer() {
+        block_on(async {
+            let air = Air::new();
+            let mut rx = receiver(&air).await;
+            let timer = SimTimer::new();
+            let mut failsafe = Failsafe::new(1_000);
+
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            timer.advance(900);
+            failsafe.reset(&timer);
+            timer.advance(900);
+            failsafe.read(&mut rx, &timer).await.unwrap();
+            assert!(!failsafe.is_tripped());
+            assert_eq!(links(&mut rx), (0, 0));
+        });
+    }
+}
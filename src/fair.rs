@@ -0,0 +1,245 @@
+use crate::delivery::{Delivery, DeliveryError, MessageStore, ReliableSender};
+use crate::device::Device;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Counters of one stream of a [`FairSender`](struct.FairSender.html)
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct StreamStats {
+    /// Transmissions of the stream's messages, delivered or not
+    pub attempts: u32,
+    /// Messages delivered
+    pub delivered: u32,
+    /// Transmissions of other streams while this one had messages
+    /// waiting
+    pub starved: u32,
+    /// Longest run of such transmissions, the worst wait for a turn
+    pub max_wait: u32,
+}
+
+/// State of a stream between turns
+struct Stream<S: MessageStore> {
+    sender: ReliableSender<S>,
+    quota: u8,
+    /// Not known to be empty
+    pending: bool,
+    /// Current run of other streams' transmissions while `pending`
+    wait: u32,
+    stats: StreamStats,
+}
+
+/// Round-robin between the queues of several producers, so that one
+/// chatty producer cannot starve the others
+///
+/// Each stream is a [`ReliableSender`](struct.ReliableSender.html) on
+/// its own store, and gets up to its quota of transmissions in a row
+/// before the turn passes on to the next stream with messages. Quotas
+/// act as weights, e.g. `[4, 1]` gives the first stream four times the
+/// share of airtime while the second still gets through. A failed
+/// transmission uses up quota as well, so that a stream stuck on an
+/// unreachable peer does not hold up the rest.
+///
+/// Deadlines of [`MessageStore::push_until()`](trait.MessageStore.html#method.push_until)
+/// are not checked.
+pub struct FairSender<S: MessageStore, const N: usize> {
+    streams: [Stream<S>; N],
+    current: usize,
+    /// Transmissions in the current turn
+    used: u8,
+}
+
+impl<S: MessageStore, const N: usize> FairSender<S, N> {
+    /// Constructor, with transmissions per turn for each stream
+    ///
+    /// Streams are served in the order given. Messages already present
+    /// in the stores, e.g. from before a reset, are sent as well. A
+    /// quota of 0 counts as 1.
+    pub fn new(stores: [S; N], quotas: [u8; N]) -> Self {
+        let mut quotas = quotas.into_iter();
+        FairSender {
+            streams: stores.map(|store| Stream {
+                sender: ReliableSender::new(store),
+                quota: quotas.next().unwrap_or(1).max(1),
+                pending: false,
+                wait: 0,
+                stats: StreamStats::default(),
+            }),
+            current: 0,
+            used: 0,
+        }
+    }
+
+    /// Get a mutable reference to the store of `stream`
+    pub fn store(&mut self, stream: usize) -> Option<&mut S> {
+        self.streams.get_mut(stream).map(|stream| stream.sender.store())
+    }
+
+    /// Counters of `stream`
+    pub fn stats(&self, stream: usize) -> Option<&StreamStats> {
+        self.streams.get(stream).map(|stream| &stream.stats)
+    }
+
+    /// Queue a message for delivery on `stream`. Does nothing if there
+    /// is no such stream.
+    pub async fn enqueue(&mut self, stream: usize, message: &[u8]) -> Result<(), S::Error> {
+        let Some(stream) = self.streams.get_mut(stream) else {
+            return Ok(());
+        };
+        stream.sender.enqueue(message).await?;
+        stream.pending = true;
+        Ok(())
+    }
+
+    /// Try to deliver the oldest message of the stream whose turn it
+    /// is, waiting until the transmission has completed, polling with
+    /// `delay`
+    ///
+    /// Returns the stream along with the outcome, which is
+    /// `Delivery::Idle` only if all streams are empty.
+    pub async fn pump<D: Device, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        delay: &mut DL,
+    ) -> Result<(usize, Delivery), DeliveryError<D::Error, S::Error>> {
+        // Each stream once, and the current one again for a fresh turn
+        for _ in 0..=N {
+            let index = self.current;
+            let Some(stream) = self.streams.get_mut(index) else {
+                break;
+            };
+            if self.used < stream.quota {
+                let delivery = stream.sender.pump(tx, delay).await?;
+                if delivery != Delivery::Idle {
+                    self.used = self.used.saturating_add(1);
+                    self.account(index, delivery);
+                    return Ok((index, delivery));
+                }
+                stream.pending = false;
+            }
+            let next = index.saturating_add(1);
+            self.current = if next < N { next } else { 0 };
+            self.used = 0;
+        }
+        Ok((self.current, Delivery::Idle))
+    }
+
+    /// Update counters after a transmission of `index`
+    fn account(&mut self, index: usize, delivery: Delivery) {
+        for (i, stream) in self.streams.iter_mut().enumerate() {
+            if i == index {
+                // Counted as waiting until a turn finds it empty
+                stream.pending = true;
+                stream.wait = 0;
+                stream.stats.attempts = stream.stats.attempts.saturating_add(1);
+                if delivery == Delivery::Delivered {
+                    stream.stats.delivered = stream.stats.delivered.saturating_add(1);
+                }
+            } else if stream.pending {
+                stream.wait = stream.wait.saturating_add(1);
+                stream.stats.starved = stream.stats.starved.saturating_add(1);
+                stream.stats.max_wait = stream.stats.max_wait.max(stream.wait);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::delivery::tests::RamStore;
+    use crate::sim::{self, Air, SimTimer};
+    use crate::Configuration;
+
+    #[test]
+    fn round_robin_by_quota() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut fair = FairSender::new([RamStore::default(), RamStore::default()], [2, 1]);
+            for message in [b"a0", b"a1", b"a2", b"a3"] {
+                fair.enqueue(0, message).await.unwrap();
+            }
+            for message in [b"b0", b"b1"] {
+                fair.enqueue(1, message).await.unwrap();
+            }
+
+            let mut order = Vec::new();
+            let mut received = Vec::new();
+            loop {
+                match fair.pump(&mut tx, &mut timer).await.ok().unwrap() {
+                    (_, Delivery::Idle) => break,
+                    (stream, delivery) => {
+                        assert_eq!(delivery, Delivery::Delivered);
+                        order.push(stream);
+                        received.push(rx.read().await.unwrap().to_vec());
+                    }
+                }
+            }
+            assert_eq!(order, [0, 0, 1, 0, 0, 1]);
+            assert_eq!(received, [b"a0", b"a1", b"b0", b"a2", b"a3", b"b1"]);
+        });
+    }
+
+    #[test]
+    fn starvation_is_bounded() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut fair = FairSender::new([RamStore::default(), RamStore::default()], [2, 1]);
+            for message in [b"a0", b"a1", b"a2", b"a3"] {
+                fair.enqueue(0, message).await.unwrap();
+            }
+            for message in [b"b0", b"b1"] {
+                fair.enqueue(1, message).await.unwrap();
+            }
+            while fair.pump(&mut tx, &mut timer).await.ok().unwrap().1 != Delivery::Idle {
+                // Keep the RX FIFO from filling up
+                rx.flush_rx().await.unwrap();
+            }
+
+            let stats = [*fair.stats(0).unwrap(), *fair.stats(1).unwrap()];
+            assert_eq!(
+                stats,
+                [
+                    StreamStats {
+                        attempts: 4,
+                        delivered: 4,
+                        starved: 2,
+                        max_wait: 1,
+                    },
+                    StreamStats {
+                        attempts: 2,
+                        delivered: 2,
+                        starved: 4,
+                        max_wait: 2,
+                    },
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn failures_use_up_quota() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &crate::RadioConfig::default()).await;
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut timer = SimTimer::new();
+            let mut fair = FairSender::new([RamStore::default(), RamStore::default()], [1, 1]);
+            fair.enqueue(0, b"a").await.unwrap();
+            fair.enqueue(1, b"b").await.unwrap();
+
+            // Nobody answers, and the first stream does not hold up the
+            // second
+            for stream in [0, 1, 0, 1] {
+                let pumped = fair.pump(&mut tx, &mut timer).await.ok();
+                assert_eq!(pumped, Some((stream, Delivery::Failed)));
+            }
+            assert_eq!(fair.stats(0).unwrap().delivered, 0);
+            assert_eq!(fair.stats(1).unwrap().attempts, 2);
+        });
+    }
+}
@@ -0,0 +1,161 @@
+use crate::address::Address;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::strategy::Strategy;
+use crate::timer::DelayTimer;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Broadcast-then-poll rounds of a hub, e.g. for synchronized actuators
+///
+/// [`run()`](#method.run) broadcasts a command without acknowledgment,
+/// so that all nodes receive it at the same time without their ACKs
+/// colliding. It then sends a poll packet to each node in turn and
+/// waits up to the window for its ACK, passing along any ACK payload
+/// the node has loaded as its response.
+///
+/// Nodes listen on the broadcast address on one pipe and on their own
+/// address with auto-ack on another. The hub needs auto-ack and
+/// auto-retransmit for the polls, and dynamic payload lengths with
+/// `EN_ACK_PAY` to receive responses. Leaves the hub's TX and pipe 0
+/// addresses at the last node.
+pub struct GroupRound<const N: usize> {
+    broadcast: Address,
+    nodes: [Address; N],
+    window_us: u32,
+}
+
+impl<const N: usize> GroupRound<N> {
+    /// Constructor, waiting `window_us` for each node's ACK
+    pub fn new(broadcast: Address, nodes: [Address; N], window_us: u32) -> Self {
+        GroupRound {
+            broadcast,
+            nodes,
+            window_us,
+        }
+    }
+
+    /// Addresses of the nodes, in polling order
+    pub fn nodes(&self) -> &[Address; N] {
+        &self.nodes
+    }
+
+    /// Broadcast `command`, then poll every node with `poll`. Returns
+    /// which nodes answered, in the order of
+    /// [`nodes()`](#method.nodes).
+    ///
+    /// `on_response` gets the index and ACK payload of each node that
+    /// answered with one.
+    pub async fn run<D, DL, F>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        command: &[u8],
+        poll: &[u8],
+        delay: &mut DL,
+        mut on_response: F,
+    ) -> Result<[bool; N], D::Error>
+    where
+        D: Device,
+        DL: DelayNs,
+        F: FnMut(usize, &[u8]),
+    {
+        // Settings must not change while sending
+        tx.wait_empty().await?;
+        tx.set_tx_addr(&self.broadcast).await?;
+        tx.send_no_ack(command).await?;
+        // Without an ACK to wait for, this completes after one transmission
+        self.wait_sent(tx, delay).await?;
+
+        let mut answered = [false; N];
+        for ((index, node), answered) in self.nodes.iter().enumerate().zip(&mut answered) {
+            tx.set_tx_addr(node).await?;
+            tx.set_rx_addr(0, node).await?;
+            tx.send(poll).await?;
+            *answered = self.wait_sent(tx, delay).await?;
+            if *answered {
+                if let Some(response) = tx.read_ack_payload().await? {
+                    on_response(index, &response);
+                }
+            }
+        }
+        Ok(answered)
+    }
+
+    /// Wait up to the window for the transmission to complete, and
+    /// return whether it was acknowledged. Flushes it on timeout.
+    async fn wait_sent<D: Device, DL: DelayNs>(
+        &self,
+        tx: &mut TxMode<D>,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let mut timer = DelayTimer::new(delay);
+        tx.wait_send_for(&mut Strategy::polling(POLL_INTERVAL_US), &mut timer, self.window_us)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::rx::RxMode;
+    use crate::sim::{self, Air, SimRadio, SimTimer};
+    use crate::RadioConfig;
+
+    const BROADCAST: Address = Address::from_array([0xB0; 5]);
+
+    fn address(byte: u8) -> Address {
+        Address::from_array([byte; 5])
+    }
+
+    /// A node listening on `BROADCAST` on pipe 0 and on `address(byte)`
+    /// on pipe 1
+    async fn node(air: &Air, byte: u8) -> RxMode<SimRadio> {
+        let mut config = RadioConfig::default();
+        config.rx_addrs[0] = Some(BROADCAST);
+        config.rx_addrs[1] = Some(address(byte));
+        let mut standby = sim::standby(air, &config).await;
+        standby.set_ack_payloads(true).await.unwrap();
+        standby.rx().await.map_err(|(_, e)| e).unwrap()
+    }
+
+    #[test]
+    fn broadcast_then_poll() {
+        block_on(async {
+            let air = Air::new();
+            let mut standby = sim::standby(&air, &RadioConfig::default()).await;
+            standby.set_ack_payloads(true).await.unwrap();
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut nodes = [node(&air, 0xA1).await, node(&air, 0xA2).await];
+            nodes[1].queue_ack_payload(1, b"done").await.unwrap();
+            // The third node is gone
+            let addresses = [address(0xA1), address(0xA2), address(0xA3)];
+            let mut round = GroupRound::new(BROADCAST, addresses, 1_000);
+
+            let mut responses = Vec::new();
+            let answered = round
+                .run(&mut tx, b"go", b"poll", &mut SimTimer::new(), |index, response| {
+                    responses.push((index, response.to_vec()))
+                })
+                .await
+                .unwrap();
+            assert_eq!(answered, [true, true, false]);
+            assert_eq!(responses, [(1, b"done".to_vec())]);
+
+            for rx in &mut nodes {
+                let (pipe_no, command) = rx.read_with_pipe().await.unwrap().unwrap();
+                assert_eq!((pipe_no, &command[..]), (0, &b"go"[..]));
+                let (pipe_no, poll) = rx.read_with_pipe().await.unwrap().unwrap();
+                assert_eq!((pipe_no, &poll[..]), (1, &b"poll"[..]));
+            }
+            // Sent once, without waiting for ACKs
+            let frames = air.frames();
+            let commands: Vec<_> = frames.iter().filter(|f| f.payload == b"go").collect();
+            assert_eq!(commands.len(), 1);
+            assert!(commands[0].no_ack);
+        });
+    }
+}
@@ -0,0 +1,109 @@
+use crate::stats::Stats;
+
+/// First byte of a health frame
+pub(crate) const TAG: u8 = b'H';
+/// Second byte, the layout version
+pub(crate) const VERSION: u8 = 1;
+
+/// Compact radio health report for fleet monitoring
+///
+/// Nodes send it periodically, e.g. every minute, and gateways decode
+/// it with [`decode()`](#method.decode), so that projects share one
+/// format. Encoded in [`HealthFrame::LEN`](#associatedconstant.LEN)
+/// bytes, little endian:
+///
+/// | Offset | Content                                   |
+/// |--------|-------------------------------------------|
+/// | 0      | `'H'`                                     |
+/// | 1      | Version, currently `1`                    |
+/// | 2..6   | Uptime in seconds                         |
+/// | 6..10  | Packets sent                              |
+/// | 10..14 | Packets acknowledged                      |
+/// | 14..18 | Packets lost after all retransmits        |
+/// | 18     | Carrier busy percentage from `RPD`        |
+/// | 19..21 | Configuration hash                        |
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct HealthFrame {
+    /// Seconds since the node started
+    pub uptime_s: u32,
+    /// Packets written to the TX FIFO
+    pub sent: u32,
+    /// Packets acknowledged by the receiver
+    pub acked: u32,
+    /// Packets that reached the maximum number of retransmits
+    pub lost: u32,
+    /// How often `RPD` saw a carrier recently, as from
+    /// [`LinkMonitor::carrier_busy_percent()`](struct.LinkMonitor.html#method.carrier_busy_percent)
+    pub busy_percent: u8,
+    /// Identifies the configuration, so that a gateway can spot nodes
+    /// on an outdated one, e.g.
+    /// [`RadioConfig::config_hash()`](struct.RadioConfig.html#method.config_hash)
+    pub config_hash: u16,
+}
+
+impl HealthFrame {
+    /// Size of the encoding
+    pub const LEN: usize = 21;
+
+    /// Report from the driver's [`Stats`](struct.Stats.html)
+    ///
+    /// Packets sent without asking for an ACK are left out. Every other
+    /// packet that did not reach `MAX_RT` is counted as acknowledged,
+    /// which holds with auto-ack and auto-retransmit.
+    pub fn from_stats(uptime_s: u32, stats: &Stats, busy_percent: u8, config_hash: u16) -> Self {
+        let sent = stats.packets_sent.wrapping_sub(stats.packets_sent_no_ack);
+        HealthFrame {
+            uptime_s,
+            sent,
+            acked: sent.saturating_sub(stats.max_rt),
+            lost: stats.max_rt,
+            busy_percent,
+            config_hash,
+        }
+    }
+
+    /// Percentage of sent packets that were lost
+    pub fn loss_percent(&self) -> u8 {
+        (u64::from(self.lost) * 100)
+            .checked_div(u64::from(self.sent))
+            .unwrap_or(0)
+            .min(100) as u8
+    }
+
+    /// Encode, e.g. for [`TxMode::send()`](struct.TxMode.html#method.send)
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        buf[0] = TAG;
+        buf[1] = VERSION;
+        buf[2..6].copy_from_slice(&self.uptime_s.to_le_bytes());
+        buf[6..10].copy_from_slice(&self.sent.to_le_bytes());
+        buf[10..14].copy_from_slice(&self.acked.to_le_bytes());
+        buf[14..18].copy_from_slice(&self.lost.to_le_bytes());
+        buf[18] = self.busy_percent;
+        buf[19..21].copy_from_slice(&self.config_hash.to_le_bytes());
+        buf
+    }
+
+    /// Decode a received payload, or `None` if it is not a health frame
+    ///
+    /// Longer payloads are accepted so that later versions can append
+    /// fields.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let frame: &[u8; Self::LEN] = payload.get(..Self::LEN)?.try_into().ok()?;
+        if frame[0] != TAG || frame[1] < VERSION {
+            return None;
+        }
+        let u32_at = |offset: usize| {
+            let bytes = frame.get(offset..offset.saturating_add(4))?;
+            bytes.try_into().ok().map(u32::from_le_bytes)
+        };
+        Some(HealthFrame {
+            uptime_s: u32_at(2)?,
+            sent: u32_at(6)?,
+            acked: u32_at(10)?,
+            lost: u32_at(14)?,
+            busy_percent: frame[18],
+            config_hash: u16::from_le_bytes([frame[19], frame[20]]),
+        })
+    }
+}
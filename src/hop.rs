@@ -0,0 +1,219 @@
+use crate::channel::Channel;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::rx::RxMode;
+use crate::timer::RadioTimer;
+
+/// Length of a beacon built by [`HopSync::beacon()`](struct.HopSync.html#method.beacon)
+pub const BEACON_LEN: usize = 8;
+
+/// Parts per billion
+const PPB: i128 = 1_000_000_000;
+
+/// Drift beyond which a beacon is taken as a restart of either clock,
+/// far above the ±50 ppm of common crystals
+const MAX_DRIFT_PPB: i128 = 1_000_000;
+
+/// Order of channels to hop through, dwelling on each for a fixed time
+#[derive(Debug, Clone, Copy)]
+pub struct HopSchedule<'a> {
+    channels: &'a [Channel],
+    dwell_us: u64,
+}
+
+impl<'a> HopSchedule<'a> {
+    /// Constructor, hopping through `channels` in order and staying
+    /// `dwell_us` on each
+    ///
+    /// Both ends must use the same schedule. Fails with
+    /// `InvalidArgument::Channel` if `channels` is empty.
+    pub fn new(channels: &'a [Channel], dwell_us: u64) -> Result<Self, InvalidArgument> {
+        if channels.is_empty() {
+            return Err(InvalidArgument::Channel);
+        }
+        Ok(HopSchedule {
+            channels,
+            dwell_us: dwell_us.max(1),
+        })
+    }
+
+    /// Number of the dwell slot at `network_time`
+    pub fn slot_at(&self, network_time: u64) -> u64 {
+        network_time.checked_div(self.dwell_us).unwrap_or(0)
+    }
+
+    /// Channel to be on at `network_time`
+    pub fn channel_at(&self, network_time: u64) -> Channel {
+        let index = self.slot_at(network_time).checked_rem(self.channels.len() as u64);
+        index.and_then(|index| self.channels.get(index as usize)).copied().unwrap_or_default()
+    }
+
+    /// Network time of the next hop after `network_time`
+    pub fn next_hop_at(&self, network_time: u64) -> u64 {
+        self.slot_at(network_time).saturating_add(1).saturating_mul(self.dwell_us)
+    }
+}
+
+/// Keeps a follower aligned with the hop schedule of a timing master
+/// that sends periodic beacons, without a shared clock
+///
+/// The master sends [`beacon()`](#method.beacon)s carrying its clock,
+/// which defines the network time. The follower passes each received
+/// beacon with the local receive time to
+/// [`on_beacon()`](#method.on_beacon), which corrects the offset and
+/// estimates how fast the two crystals drift apart. In between beacons,
+/// [`network_time()`](#method.network_time) extrapolates with the
+/// estimated drift, so beacons every few seconds keep the ends aligned
+/// within a dwell time over hours.
+#[derive(Debug, Default, Clone)]
+pub struct HopSync {
+    /// Local and network time of the last beacon
+    reference: Option<(u64, u64)>,
+    /// How much faster the network clock runs than the local one
+    drift_ppb: Option<i64>,
+    latency_us: u64,
+}
+
+impl HopSync {
+    /// Constructor
+    ///
+    /// `latency_us` is the time from the master reading its clock to
+    /// the follower reading its own on reception: air time at the data
+    /// rate plus SPI and interrupt latency on both ends.
+    pub fn new(latency_us: u64) -> Self {
+        HopSync {
+            latency_us,
+            ..Self::default()
+        }
+    }
+
+    /// Beacon payload for the master to send, carrying its clock
+    pub fn beacon(network_time: u64) -> [u8; BEACON_LEN] {
+        network_time.to_le_bytes()
+    }
+
+    /// Resynchronize with a beacon received at `local_time`
+    ///
+    /// Returns `false` and changes nothing if `payload` is not a beacon.
+    pub fn on_beacon(&mut self, local_time: u64, payload: &[u8]) -> bool {
+        let Some(Ok(beacon)) = payload.get(..BEACON_LEN).map(<[u8; BEACON_LEN]>::try_from) else {
+            return false;
+        };
+        let network_time = u64::from_le_bytes(beacon).wrapping_add(self.latency_us);
+
+        if let Some((last_local, last_network)) = self.reference {
+            let local_elapsed = i128::from(local_time).saturating_sub(i128::from(last_local));
+            let network_elapsed = i128::from(network_time).saturating_sub(i128::from(last_network));
+            if local_elapsed > 0 {
+                let offset = network_elapsed.saturating_sub(local_elapsed).saturating_mul(PPB);
+                let sample = offset.checked_div(local_elapsed).unwrap_or(0);
+                self.drift_ppb = match self.drift_ppb {
+                    _ if sample.abs() > MAX_DRIFT_PPB => None,
+                    // Smooth out jitter in the reception time
+                    Some(drift) => {
+                        Some(drift.saturating_add((sample as i64).saturating_sub(drift) / 4))
+                    }
+                    None => Some(sample as i64),
+                };
+            }
+        }
+        self.reference = Some((local_time, network_time));
+        true
+    }
+
+    /// Has a beacon been received yet?
+    pub fn is_synced(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    /// Estimated drift of the network clock against the local one, in
+    /// parts per million
+    pub fn drift_ppm(&self) -> i32 {
+        (self.drift_ppb.unwrap_or(0) / 1_000) as i32
+    }
+
+    /// Network time at `local_time`, once synced
+    pub fn network_time(&self, local_time: u64) -> Option<u64> {
+        let (last_local, last_network) = self.reference?;
+        let elapsed = i128::from(local_time).saturating_sub(i128::from(last_local));
+        let correction = elapsed.saturating_mul(i128::from(self.drift_ppb.unwrap_or(0))) / PPB;
+        let network_time =
+            i128::from(last_network).saturating_add(elapsed).saturating_add(correction);
+        Some(network_time.clamp(0, i128::from(u64::MAX)) as u64)
+    }
+
+    /// Local time at `network_time`, e.g. to schedule the next hop
+    pub fn local_time(&self, network_time: u64) -> Option<u64> {
+        let (last_local, last_network) = self.reference?;
+        let elapsed = i128::from(network_time).saturating_sub(i128::from(last_network));
+        let drift = PPB.saturating_add(i128::from(self.drift_ppb.unwrap_or(0)));
+        let scaled = elapsed.saturating_mul(PPB).checked_div(drift.max(1)).unwrap_or(0);
+        let local_time = i128::from(last_local).saturating_add(scaled);
+        Some(local_time.clamp(0, i128::from(u64::MAX)) as u64)
+    }
+
+    /// Channel to be on at `local_time`, once synced
+    pub fn channel_at(&self, schedule: &HopSchedule, local_time: u64) -> Option<Channel> {
+        self.network_time(local_time)
+            .map(|network_time| schedule.channel_at(network_time))
+    }
+
+    /// Retune `rx` to the channel to be on now by `timer`, and return the
+    /// local time of the next hop, or `None` before the first beacon
+    ///
+    /// Call it again at that time, e.g. after reading packets meanwhile.
+    /// Skips the write if `rx` is on that channel already.
+    pub async fn follow<D: Device, T: RadioTimer>(
+        &self,
+        rx: &mut RxMode<D>,
+        schedule: &HopSchedule<'_>,
+        timer: &T,
+    ) -> Result<Option<u64>, D::Error> {
+        let Some(network_time) = self.network_time(timer.now_micros()) else {
+            return Ok(None);
+        };
+        let channel = schedule.channel_at(network_time);
+        if rx.get_frequency().await? != channel {
+            rx.retune(channel).await?;
+        }
+        Ok(self.local_time(schedule.next_hop_at(network_time)))
+    }
+
+    /// Forget the synchronization, e.g. after the master restarted
+    pub fn reset(&mut self) {
+        self.reference = None;
+        self.drift_ppb = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+
+    #[test]
+    fn follow_hops_on_the_timer() {
+        block_on(async {
+            let air = Air::new();
+            let (_tx, mut rx) = sim::pair(&air).await;
+            let channels = [Channel::clamped(20), Channel::clamped(40)];
+            let schedule = HopSchedule::new(&channels, 1_000).unwrap();
+            let timer = SimTimer::new();
+            let mut sync = HopSync::new(0);
+            assert_eq!(sync.follow(&mut rx, &schedule, &timer).await.ok(), Some(None));
+
+            // Network time runs 500 µs ahead
+            assert!(sync.on_beacon(0, &HopSync::beacon(500)));
+            let next = sync.follow(&mut rx, &schedule, &timer).await.unwrap();
+            assert_eq!(next, Some(500));
+            assert_eq!(rx.get_frequency().await.ok(), Some(channels[0]));
+
+            timer.advance(500);
+            let next = sync.follow(&mut rx, &schedule, &timer).await.unwrap();
+            assert_eq!(next, Some(1_500));
+            assert_eq!(rx.get_frequency().await.ok(), Some(channels[1]));
+        });
+    }
+}
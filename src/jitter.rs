@@ -0,0 +1,71 @@
+use crate::device::Device;
+use crate::rng::RadioRng;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Random backoff before sending and between software retries
+///
+/// Identical sensors answering the same broadcast trigger transmit at
+/// the same time, and as their auto-retransmit delays are identical too,
+/// the retransmits collide again. [`send()`](#method.send) waits a
+/// random time first, and once more before each software retry after
+/// `MAX_RT`, so that the nodes drift apart.
+///
+/// Random numbers come from the caller's [`RadioRng`](trait.RadioRng.html),
+/// e.g. a hardware RNG or a PRNG seeded with a unique ID. Requires
+/// auto-ack and auto-retransmit.
+pub struct Jitter {
+    max_us: u32,
+    retries: u8,
+}
+
+impl Jitter {
+    /// Constructor, waiting up to `max_us` before each attempt and
+    /// retrying up to `retries` times after `MAX_RT`
+    ///
+    /// A few times the airtime of a packet with its retransmits is a
+    /// good `max_us`.
+    pub fn new(max_us: u32, retries: u8) -> Self {
+        Jitter { max_us, retries }
+    }
+
+    /// Wait a random time of up to the maximum, with a random number
+    /// from `rng`
+    pub async fn wait<DL: DelayNs, R: RadioRng>(&self, delay: &mut DL, rng: &mut R) {
+        let jitter = rng.next_u32().checked_rem(self.max_us.saturating_add(1)).unwrap_or(0);
+        if jitter > 0 {
+            delay.delay_us(jitter).await;
+        }
+    }
+
+    /// Send `packet` after a random wait, retrying after a fresh one on
+    /// each `MAX_RT`, and return whether it was acknowledged
+    ///
+    /// Waits for the TX FIFO to be empty afterwards, so do not queue
+    /// other packets meanwhile.
+    pub async fn send<D, DL, R>(
+        &self,
+        tx: &mut TxMode<D>,
+        packet: &[u8],
+        delay: &mut DL,
+        rng: &mut R,
+    ) -> Result<bool, D::Error>
+    where
+        D: Device,
+        DL: DelayNs,
+        R: RadioRng,
+    {
+        for _ in 0..=self.retries {
+            self.wait(delay, rng).await;
+            tx.send(packet).await?;
+            if tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
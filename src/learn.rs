@@ -0,0 +1,276 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::config::{Configuration, DataRate, RawConfig};
+use crate::device::Device;
+use crate::rx::RxMode;
+use crate::sniff::{decode_esb, PROMISCUOUS_ADDRESS};
+use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES};
+use core::ops::RangeInclusive;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval at which to poll the RX FIFO while dwelling on a channel
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Address, channel and data rate seen in use by an
+/// [`AddressLearner`](struct.AddressLearner.html)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate {
+    /// Address of the frames
+    pub address: Address,
+    /// Channel they were seen on
+    pub channel: Channel,
+    /// Their air data rate
+    pub data_rate: DataRate,
+    /// Frames seen, as a measure of confidence
+    pub frames: u16,
+    /// Whether any of them asked for an ACK, i.e. the link uses auto-ack
+    pub auto_ack: bool,
+    /// Longest payload seen
+    pub max_payload_len: u8,
+}
+
+/// Learn the addresses and channels of an existing deployment by
+/// listening in promiscuous mode, e.g. for a gateway whose
+/// configuration was lost
+///
+/// [`scan()`](#method.scan) sweeps channels and data rates with a
+/// preamble-like 2 byte address and no CRC, as sniffers do, and keeps
+/// the Enhanced ShockBurst frames whose 16 bit CRC matches for an
+/// address width of 5, 4 or 3 bytes. Deployments with a 1 byte CRC are
+/// not found. Each address seen is a candidate, so that the gateway can
+/// configure itself with the most frequent ones.
+pub struct AddressLearner<const N: usize> {
+    candidates: [Option<Candidate>; N],
+}
+
+impl<const N: usize> Default for AddressLearner<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> AddressLearner<N> {
+    /// Constructor, keeping up to `N` candidates
+    pub fn new() -> Self {
+        AddressLearner {
+            candidates: [const { None }; N],
+        }
+    }
+
+    /// Listen on each of `channels` at each of `data_rates` for
+    /// `dwell_us`, and return how many frames were recovered
+    ///
+    /// Leaves the radio in raw promiscuous mode, so configure it again
+    /// afterwards. If all `N` candidates are taken, new addresses replace
+    /// the one seen least.
+    pub async fn scan<D: Device, DL: DelayNs>(
+        &mut self,
+        rx: &mut RxMode<D>,
+        channels: RangeInclusive<Channel>,
+        data_rates: &[DataRate],
+        dwell_us: u32,
+        delay: &mut DL,
+    ) -> Result<u16, D::Error> {
+        let mut found = 0_u16;
+        for data_rate in data_rates {
+            for channel in Channel::range(channels.clone()) {
+                let mut raw = RawConfig::new(channel, &PROMISCUOUS_ADDRESS, 32)?;
+                raw.data_rate = *data_rate;
+                rx.device().ce_disable();
+                rx.configure_raw(&raw).await?;
+                rx.flush_rx().await?;
+                rx.device().ce_enable();
+
+                let mut waited_us = 0;
+                while waited_us < dwell_us {
+                    while let Some((_, payload)) = rx.read_with_pipe().await? {
+                        if self.learn(&payload, channel, *data_rate) {
+                            found = found.saturating_add(1);
+                        }
+                    }
+                    delay.delay_us(POLL_INTERVAL_US).await;
+                    waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Record the frame in `raw`, if there is one
+    fn learn(&mut self, raw: &[u8], channel: Channel, data_rate: DataRate) -> bool {
+        let Some(frame) = (MIN_ADDR_BYTES..=MAX_ADDR_BYTES)
+            .rev()
+            .find_map(|width| decode_esb(raw, width))
+        else {
+            return false;
+        };
+        let Ok(address) = Address::new(frame.address.get(..frame.width).unwrap_or_default()) else {
+            return false;
+        };
+        let known = self.candidates.iter_mut().flatten().find(|candidate| {
+            candidate.address == address
+                && candidate.channel == channel
+                && candidate.data_rate == data_rate
+        });
+        let auto_ack = !frame.no_ack;
+        let payload_len = frame.payload.len() as u8;
+        if let Some(candidate) = known {
+            candidate.frames = candidate.frames.saturating_add(1);
+            candidate.auto_ack |= auto_ack;
+            candidate.max_payload_len = candidate.max_payload_len.max(payload_len);
+            return true;
+        }
+        let slot = match self.candidates.iter().position(Option::is_none) {
+            Some(free) => self.candidates.get_mut(free),
+            None => self
+                .candidates
+                .iter_mut()
+                .min_by_key(|candidate| candidate.as_ref().map_or(0, |candidate| candidate.frames)),
+        };
+        if let Some(slot) = slot {
+            *slot = Some(Candidate {
+                address,
+                channel,
+                data_rate,
+                frames: 1,
+                auto_ack,
+                max_payload_len: payload_len,
+            });
+        }
+        true
+    }
+
+    /// Candidates seen so far, in no particular order
+    pub fn candidates(&self) -> impl Iterator<Item = &Candidate> {
+        self.candidates.iter().flatten()
+    }
+
+    /// The candidate seen most often
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates().max_by_key(|candidate| candidate.frames)
+    }
+
+    /// Forget all candidates
+    pub fn clear(&mut self) {
+        self.candidates = [const { None }; N];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimChip};
+    use crate::sniff::tests::raw_frame;
+    use crate::RadioConfig;
+    use std::vec::Vec;
+
+    const RF_CH: u8 = 0x05;
+    const RF_SETUP: u8 = 0x06;
+    const A: [u8; 5] = [0xE7; 5];
+    const B: [u8; 4] = [0xC2, 0xC2, 0xC2, 0x35];
+
+    /// Delay that puts the next of `frames` into the RX FIFO of `chip`
+    /// while it dwells on channel 11 at 2 Mbps
+    struct Deployment {
+        chip: SimChip,
+        frames: Vec<[u8; 32]>,
+    }
+
+    impl DelayNs for Deployment {
+        async fn delay_ns(&mut self, _ns: u32) {
+            let r2mbps = self.chip.register(RF_SETUP) & 0x28 == 0x08;
+            let on_air = self.chip.register(RF_CH) == 11 && r2mbps;
+            if on_air && !self.frames.is_empty() {
+                self.chip.receive(0, &self.frames.remove(0));
+            }
+        }
+    }
+
+    fn channel(n: u8) -> Channel {
+        Channel::new(n).unwrap()
+    }
+
+    #[test]
+    fn scan_finds_the_deployment() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let mut rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+            let chip = rx.device().spi.chip();
+            let frames = [
+                raw_frame(&A, 0, false, b"temp"),
+                [0x55; 32],
+                raw_frame(&A, 1, false, b"humidity"),
+                raw_frame(&B, 0, true, b"hi"),
+                raw_frame(&A, 2, true, b"temp"),
+            ];
+            let mut delay = Deployment {
+                chip,
+                frames: frames.to_vec(),
+            };
+            let mut learner = AddressLearner::<4>::new();
+            let data_rates = [DataRate::R1Mbps, DataRate::R2Mbps];
+            let found = learner
+                .scan(&mut rx, channel(10)..=channel(12), &data_rates, 1_000, &mut delay)
+                .await
+                .unwrap();
+            // The noise is not a frame
+            assert_eq!(found, 4);
+            assert!(delay.frames.is_empty());
+            assert_eq!(learner.candidates().count(), 2);
+            assert_eq!(
+                learner.best(),
+                Some(&Candidate {
+                    address: Address::from_array(A),
+                    channel: channel(11),
+                    data_rate: DataRate::R2Mbps,
+                    frames: 3,
+                    auto_ack: true,
+                    max_payload_len: 8,
+                })
+            );
+            let b = learner.candidates().find(|c| c.frames == 1).unwrap();
+            assert_eq!(&b.address[..], &B[..]);
+            assert!(!b.auto_ack);
+        });
+    }
+
+    #[test]
+    fn scan_without_a_deployment() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let mut rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+            let chip = rx.device().spi.chip();
+            let mut corrupt = raw_frame(&A, 0, false, b"temp");
+            corrupt[8] ^= 1;
+            let mut delay = Deployment {
+                chip,
+                frames: std::vec![[0x55; 32], [0; 32], corrupt],
+            };
+            let mut learner = AddressLearner::<4>::new();
+            let found = learner
+                .scan(&mut rx, channel(10)..=channel(12), &[DataRate::R2Mbps], 1_000, &mut delay)
+                .await
+                .unwrap();
+            assert_eq!(found, 0);
+            assert!(learner.best().is_none());
+        });
+    }
+
+    #[test]
+    fn least_seen_candidate_is_replaced() {
+        let mut learner = AddressLearner::<2>::new();
+        let rate = DataRate::R1Mbps;
+        for address in [&A[..], &A, &B, &[0x11; 3]] {
+            assert!(learner.learn(&raw_frame(address, 0, false, b"x"), channel(5), rate));
+        }
+        let mut frames: Vec<_> = learner.candidates().map(|c| (c.address, c.frames)).collect();
+        frames.sort_by_key(|(_, frames)| *frames);
+        let expected = [(Address::new(&[0x11; 3]).unwrap(), 1), (Address::from_array(A), 2)];
+        assert_eq!(frames, expected);
+        learner.clear();
+        assert_eq!(learner.candidates().count(), 0);
+    }
+}
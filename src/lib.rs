@@ -8,37 +8,236 @@
 
 #![warn(missing_docs, unused)]
 #![allow(async_fn_in_trait)]
+#![cfg_attr(
+    all(feature = "panic-free", not(test)),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing,
+        clippy::arithmetic_side_effects
+    )
+)]
 
-#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #[macro_use]
 extern crate bitfield;
 
+use core::convert::Infallible;
 use core::fmt;
 use core::fmt::Debug;
-use embedded_hal_async::spi::SpiDevice;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::{Operation, SpiDevice};
 use embedded_hal::digital::OutputPin;
 
 mod config;
-pub use crate::config::{Configuration, CrcMode, DataRate};
+pub use crate::config::{
+    AddressConflict, ChipConfig, Configuration, CrcMode, DataRate, PowerLevel, RadioConfig,
+    RawConfig,
+};
+#[cfg(feature = "config-bytes")]
+pub use crate::config::DecodeError;
+mod channel;
+pub use crate::channel::Channel;
+mod address;
+pub use crate::address::{Address, AddressFamily};
 pub mod setup;
 
-mod registers;
+pub mod registers;
 use crate::registers::{Config, Register, SetupAw, Status, Feature};
 mod command;
-use crate::command::{Command, ReadRegister, WriteRegister};
+use crate::command::{Command, ReadRegister, WriteRegister, MAX_COMMAND_LEN};
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "unifying")]
+pub mod unifying;
 mod payload;
-pub use crate::payload::Payload;
+pub use crate::payload::{ByteOrder, Payload, PayloadBuilder, TxBuffer};
 mod error;
-pub use crate::error::Error;
+pub use crate::error::{Error, InvalidArgument, SpiOp, StateError};
+#[cfg(feature = "dedup")]
+mod dedup;
+#[cfg(feature = "dedup")]
+pub use crate::dedup::{DedupKey, DuplicateFilter};
+#[cfg(feature = "stream")]
+mod reorder;
+#[cfg(feature = "stream")]
+pub use crate::reorder::{Arrival, Reorder};
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "stream")]
+pub use crate::stream::{StreamReceiver, StreamSender, FRAGMENT_LEN};
 
 mod device;
 pub use crate::device::Device;
+mod no_ce;
+pub use crate::no_ce::NoCe;
 mod standby;
 pub use crate::standby::StandbyMode;
 mod rx;
-pub use crate::rx::RxMode;
+pub use crate::rx::{LengthPolicy, PrefixFilter, RxMode, ZeroWidthPolicy};
 mod tx;
-pub use crate::tx::TxMode;
+pub use crate::tx::{Completion, TxMode, TxTelemetry};
+mod coex;
+pub use crate::coex::Coexistence;
+mod tracking;
+pub use crate::tracking::{SendOutcome, SendToken};
+mod stats;
+pub use crate::stats::Stats;
+mod probe;
+pub use crate::probe::{Diagnosis, ProbeReport};
+mod suspend;
+pub use crate::suspend::SuspendState;
+mod variant;
+pub use crate::variant::ChipVariant;
+mod time;
+pub use crate::time::Clock;
+mod timer;
+#[cfg(feature = "embassy-time")]
+pub use crate::timer::EmbassyTimer;
+pub use crate::timer::{ClockDelay, DelayTimer, RadioTimer};
+mod rng;
+#[cfg(feature = "rand_core")]
+pub use crate::rng::RandCore;
+pub use crate::rng::RadioRng;
+#[cfg(feature = "timing-debug")]
+mod timing;
+#[cfg(feature = "timing-debug")]
+pub use crate::timing::{TimingCheck, TimingViolation};
+mod strategy;
+pub use crate::strategy::{NoIrq, Strategy};
+mod events;
+pub use crate::events::{ModeKind, NoEvents, RadioEvents};
+mod blocking;
+pub use crate::blocking::{BlockOn, BlockingAdapter, BlockingDelay, BlockingSpi};
+#[cfg(feature = "std")]
+pub use crate::blocking::{block_on, ThreadBlockOn};
+mod send_check;
+#[cfg(test)]
+mod panic_check;
+#[cfg(feature = "embassy-sync")]
+mod shared;
+#[cfg(feature = "embassy-sync")]
+pub use crate::shared::SharedRadio;
+mod watchdog;
+pub use crate::watchdog::Watchdog;
+#[cfg(feature = "hop")]
+mod hop;
+#[cfg(feature = "hop")]
+pub use crate::hop::{HopSchedule, HopSync, BEACON_LEN};
+#[cfg(feature = "group")]
+mod group;
+#[cfg(feature = "group")]
+pub use crate::group::GroupRound;
+#[cfg(feature = "bus")]
+mod bus;
+#[cfg(feature = "bus")]
+pub use crate::bus::{BusMaster, BusPoll, BusSlave};
+mod profile;
+pub use crate::profile::LinkProfile;
+#[cfg(any(feature = "learn", feature = "codec", feature = "unifying"))]
+mod sniff;
+#[cfg(feature = "learn")]
+mod learn;
+#[cfg(feature = "learn")]
+pub use crate::learn::{AddressLearner, Candidate};
+#[cfg(feature = "peers")]
+mod peers;
+#[cfg(feature = "peers")]
+pub use crate::peers::{Peer, PeerRegistry};
+#[cfg(feature = "socket")]
+mod socket;
+#[cfg(feature = "socket")]
+pub use crate::socket::{Keepalive, PeerState, RadioSocket};
+#[cfg(feature = "socket")]
+mod datagram;
+#[cfg(feature = "socket")]
+pub use crate::datagram::{Datagram, DatagramError, DatagramSocket};
+#[cfg(feature = "roaming")]
+mod roaming;
+#[cfg(feature = "roaming")]
+pub use crate::roaming::{Gateway, Roaming};
+mod jitter;
+pub use crate::jitter::Jitter;
+mod failsafe;
+pub use crate::failsafe::Failsafe;
+#[cfg(feature = "ota")]
+mod ota;
+#[cfg(feature = "ota")]
+pub use crate::ota::{
+    mysensors_address, OtaAnswer, OtaError, OtaEvent, OtaServer, FIRMWARE_BLOCK_SIZE,
+};
+#[cfg(any(feature = "compress", feature = "pack", feature = "codec"))]
+mod bits;
+#[cfg(feature = "compress")]
+mod compress;
+#[cfg(feature = "compress")]
+pub use crate::compress::Compressor;
+#[cfg(feature = "pack")]
+mod pack;
+#[cfg(feature = "pack")]
+pub use crate::pack::{BatchPacker, BatchUnpacker, PackError, SampleField, MAX_FIELDS};
+#[cfg(feature = "diagnostics")]
+mod loopback;
+#[cfg(feature = "diagnostics")]
+pub use crate::loopback::LoopbackReport;
+#[cfg(feature = "diagnostics")]
+mod health;
+#[cfg(feature = "diagnostics")]
+pub use crate::health::HealthFrame;
+#[cfg(feature = "bridge")]
+mod bridge;
+#[cfg(feature = "bridge")]
+pub use crate::bridge::{
+    BridgeCommand, CaptureRecord, SerialBridge, BRIDGE_FRAME_MAX, CAPTURE_MAX_LEN,
+};
+#[cfg(any(feature = "bridge", feature = "diagnostics"))]
+pub mod schema;
+#[cfg(any(test, feature = "sim"))]
+// A host-side test double, where a bug should fail the test loudly
+#[cfg_attr(
+    feature = "panic-free",
+    allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)
+)]
+pub mod sim;
+#[cfg(feature = "diagnostics")]
+mod monitor;
+#[cfg(feature = "diagnostics")]
+pub use crate::monitor::{LinkMonitor, MONITOR_WINDOW};
+#[cfg(feature = "diagnostics")]
+mod link_test;
+#[cfg(feature = "diagnostics")]
+pub use crate::link_test::{LinkCase, LinkOutcome, LinkTestError, LinkTester};
+#[cfg(feature = "diagnostics")]
+mod range_test;
+#[cfg(feature = "diagnostics")]
+pub use crate::range_test::{RangeInitiator, RangeResponder, RangeStats};
+#[cfg(feature = "diagnostics")]
+mod ber;
+#[cfg(feature = "diagnostics")]
+pub use crate::ber::{ber_packet, BerConfig, BerReceiver, BerStats, BerTester, BER_PACKET_LEN};
+#[cfg(feature = "delivery")]
+mod delivery;
+#[cfg(feature = "delivery")]
+pub use crate::delivery::{Delivery, DeliveryError, MessageStore, ReliableSender};
+#[cfg(feature = "delivery")]
+mod fair;
+#[cfg(feature = "delivery")]
+pub use crate::fair::{FairSender, StreamStats};
+#[cfg(feature = "reach")]
+mod reach;
+#[cfg(feature = "reach")]
+pub use crate::reach::{ReachOutcome, ReachTracker};
+#[cfg(feature = "commission")]
+mod commission;
+#[cfg(feature = "commission")]
+pub use crate::commission::{
+    commission_config, Commissionee, Commissioner, Provisioning, Sealer, COMMISSION_ADDRESS,
+    COMMISSION_CHANNEL, SEAL_TAG_LEN,
+};
 
 /// Number of RX pipes with configurable addresses
 pub const PIPES_COUNT: usize = 6;
@@ -47,6 +246,46 @@ pub const MIN_ADDR_BYTES: usize = 2;
 /// Maximum address length
 pub const MAX_ADDR_BYTES: usize = 5;
 
+/// Time the chip may take to answer on SPI after power on
+const POWER_ON_RESET_MS: u32 = 100;
+/// Probes after the first one fails, see `NRF24L01::wait_connected()`
+const PROBE_RETRIES: u32 = 5;
+/// Delay after the first failed probe, doubling with each retry
+const PROBE_BACKOFF_MS: u32 = 5;
+
+/// Encode `register` into `buf`, and return the bytes written
+fn encoded<'a, R: Register>(register: &R, buf: &'a mut [u8; MAX_ADDR_BYTES]) -> &'a [u8] {
+    let len = register.write_len().min(MAX_ADDR_BYTES);
+    let bytes = buf.get_mut(..len).unwrap_or_default();
+    register.encode(bytes);
+    bytes
+}
+
+/// Mode to leave the chip in after
+/// [`NRF24L01::new_with_config()`](struct.NRF24L01.html#method.new_with_config)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InitialMode {
+    /// Powered down
+    PowerDown,
+    /// Standby-I
+    Standby,
+    /// Receiving
+    Rx,
+}
+
+/// Device in the mode requested with [`InitialMode`](enum.InitialMode.html)
+#[derive(Debug)]
+// Boxing is not an option without an allocator
+#[allow(clippy::large_enum_variant)]
+pub enum RadioMode<D: Device> {
+    /// Powered down, use `StandbyMode::power_up_settled()` to continue
+    PowerDown(D),
+    /// Standby-I
+    Standby(StandbyMode<D>),
+    /// Receiving
+    Rx(RxMode<D>),
+}
+
 /// Driver for the nRF24L01+
 ///
 /// Never deal with this directly. Instead, you store one of the following types:
@@ -56,15 +295,32 @@ pub const MAX_ADDR_BYTES: usize = 5;
 /// * [`TxMode<D>`](struct.TxMode.html)
 ///
 /// where `D: `[`Device`](trait.Device.html)
-pub struct NRF24L01<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8>> {
+///
+/// `EV` receives [lifecycle callbacks](trait.RadioEvents.html).
+pub struct NRF24L01<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8>, EV: RadioEvents = NoEvents> {
     ce: CE,
     /// 核心对象spi
     pub spi: SPI,
     config: Config,
+    /// Set when driving `CE` failed, reported on the next command
+    ce_error: bool,
+    /// `CE` is strapped high
+    ce_tied: bool,
+    /// Read back every register write
+    verify_writes: bool,
+    stats: Stats,
+    events: EV,
+    variant: ChipVariant,
+    /// Payload lengths, see `Device::rx_lengths()`
+    rx_lengths: Option<[Option<u8>; PIPES_COUNT]>,
+    /// `STATUS` of the last command, see `Device::last_status()`
+    last_status: Option<u8>,
+    /// `FEATURE` as last written, see `Device::features()`
+    features: Option<u8>,
 }
 
-impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug> fmt::Debug
-    for NRF24L01<E, CE, SPI>
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug, EV: RadioEvents> fmt::Debug
+    for NRF24L01<E, CE, SPI, EV>
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "NRF24L01")
@@ -75,21 +331,145 @@ impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE:
     NRF24L01<E, CE, SPI>
 {
     /// Construct a new driver instance.
-    pub async fn new(mut ce: CE, spi: SPI) -> Result<StandbyMode<Self>, Error<SPIE>> {
-        ce.set_low().unwrap();
+    pub async fn new(ce: CE, spi: SPI) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        Self::new_with_events(ce, spi, NoEvents).await
+    }
+
+    /// Construct a new driver instance right after power on
+    ///
+    /// Waits out the 100 ms power on reset before probing the chip, and
+    /// probes again with backoff for another 155 ms before failing with
+    /// `Error::NotConnected`. Then powers up as
+    /// [`StandbyMode::power_up_settled()`](struct.StandbyMode.html#method.power_up_settled)
+    /// does, so that the first packet after a cold boot is not lost.
+    pub async fn new_after_power_on<DL: DelayNs>(
+        ce: CE,
+        spi: SPI,
+        delay: &mut DL,
+    ) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        Self::new_with_events_after_power_on(ce, spi, NoEvents, delay).await
+    }
+
+    /// Construct a new driver instance, apply `config`, and enter `mode`.
+    ///
+    /// Probes the chip again with backoff if it does not answer yet, and
+    /// settles the chip after power up with
+    /// [`StandbyMode::settle()`](struct.StandbyMode.html#method.settle)
+    /// before entering `mode`. Use
+    /// [`new_after_power_on()`](#method.new_after_power_on) and
+    /// [`configure()`](trait.Configuration.html#method.configure) if
+    /// the chip has only just been powered.
+    pub async fn new_with_config<DL: DelayNs>(
+        ce: CE,
+        spi: SPI,
+        delay: &mut DL,
+        config: &RadioConfig,
+        mode: InitialMode,
+    ) -> Result<RadioMode<Self>, Error<SPIE>> {
+        let mut device = Self::assemble(ce, spi, NoEvents)?;
+        device.wait_connected(delay).await?;
+        let mut standby = device.init().await?;
+        standby.configure(config).await?;
+        standby.settle(delay).await?;
+
+        match mode {
+            InitialMode::PowerDown => standby
+                .power_down()
+                .await
+                .map(RadioMode::PowerDown)
+                .map_err(|(_, e)| e),
+            InitialMode::Standby => Ok(RadioMode::Standby(standby)),
+            InitialMode::Rx => standby.rx().await.map(RadioMode::Rx).map_err(|(_, e)| e),
+        }
+    }
+}
+
+impl<SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug> NRF24L01<Infallible, NoCe, SPI> {
+    /// Construct a new driver instance for a board with `CE` tied high,
+    /// see [`NoCe`](struct.NoCe.html)
+    pub async fn new_ce_tied(spi: SPI) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        let mut device = Self::assemble(NoCe, spi, NoEvents)?;
+        device.ce_tied = true;
+        device.init().await
+    }
+}
 
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug, EV: RadioEvents>
+    NRF24L01<E, CE, SPI, EV>
+{
+    /// Construct a new driver instance that reports to `events`.
+    pub async fn new_with_events(
+        ce: CE,
+        spi: SPI,
+        events: EV,
+    ) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        Self::assemble(ce, spi, events)?.init().await
+    }
+
+    /// Construct a new driver instance that reports to `events`, right
+    /// after power on, see
+    /// [`new_after_power_on()`](#method.new_after_power_on)
+    pub async fn new_with_events_after_power_on<DL: DelayNs>(
+        ce: CE,
+        spi: SPI,
+        events: EV,
+        delay: &mut DL,
+    ) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        let mut device = Self::assemble(ce, spi, events)?;
+        delay.delay_ms(POWER_ON_RESET_MS).await;
+        device.wait_connected(delay).await?;
+        let mut standby = device.init().await?;
+        standby.settle(delay).await?;
+        Ok(standby)
+    }
+
+    /// Take the pins, with `CE` low
+    fn assemble(ce: CE, spi: SPI, events: EV) -> Result<Self, Error<SPIE>> {
+        let mut device = Self::from_pins(ce, spi, events);
+        device.ce.set_low().map_err(|_| Error::PinError)?;
+        Ok(device)
+    }
+
+    /// Take the pins as they are
+    fn from_pins(ce: CE, spi: SPI, events: EV) -> Self {
         // Reset value
         let mut config = Config(0b0000_1000);
         config.set_mask_rx_dr(false);
         config.set_mask_tx_ds(false);
         config.set_mask_max_rt(false);
-        let mut device = NRF24L01 {
+        NRF24L01 {
             ce,
             spi,
             config,
-        };
+            ce_error: false,
+            ce_tied: false,
+            verify_writes: false,
+            stats: Stats::default(),
+            events,
+            variant: ChipVariant::Unknown,
+            rx_lengths: None,
+            last_status: None,
+            features: None,
+        }
+    }
 
-        match device.is_connected().await {
+    /// Probe `PROBE_RETRIES` times with doubling delays until the chip
+    /// answers. Leaves reporting `NotConnected` to `init()`.
+    async fn wait_connected<DL: DelayNs>(&mut self, delay: &mut DL) -> Result<(), Error<SPIE>> {
+        let mut backoff_ms = PROBE_BACKOFF_MS;
+        for _ in 0..PROBE_RETRIES {
+            if self.is_connected().await? {
+                break;
+            }
+            delay.delay_ms(backoff_ms).await;
+            backoff_ms = backoff_ms.saturating_mul(2);
+        }
+        Ok(())
+    }
+
+    /// Check that the chip is there, enable features and power up
+    async fn init(mut self) -> Result<StandbyMode<Self>, Error<SPIE>> {
+        match self.is_connected().await {
             Err(e) => return Err(e),
             Ok(false) => return Err(Error::NotConnected),
             _ => {}
@@ -99,9 +479,33 @@ impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE:
         let mut features = Feature(0);
         features.set_en_dyn_ack(true);
         features.set_en_dpl(true);
-        device.write_register(features).await?;
+        self.write_register(features).await?;
+
+        StandbyMode::power_up(self).await.map_err(|(_, e)| e)
+    }
+
+    /// Report a failure to drive `CE`, and count a command of `len`
+    /// bytes including the `opcode`
+    fn begin_command(&mut self, opcode: u8, len: usize) -> Result<(), Error<SPIE>> {
+        if self.ce_error {
+            self.ce_error = false;
+            return Err(Error::PinError);
+        }
+
+        self.stats.count_command(opcode, len);
+        Ok(())
+    }
 
-        StandbyMode::power_up(device).await.map_err(|(_, e)| e)
+    /// Non-generic part of `send_command()`, shared by all commands
+    async fn transfer(&mut self, buf: &mut [u8]) -> Result<(), Error<SPIE>> {
+        let opcode = buf.first().copied();
+        if let Some(opcode) = opcode {
+            self.begin_command(opcode, buf.len())?;
+        }
+        self.spi
+            .transfer_in_place(buf)
+            .await
+            .map_err(|e| Error::SpiError(SpiOp::from_opcode(opcode.unwrap_or(0xFF)), e))
     }
 
     /// Reads and validates content of the `SETUP_AW` register.
@@ -112,17 +516,22 @@ impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE:
     }
 }
 
-impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug> Device
-    for NRF24L01<E, CE, SPI>
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug, EV: RadioEvents> Device
+    for NRF24L01<E, CE, SPI, EV>
 {
     type Error = Error<SPIE>;
+    type Events = EV;
 
     fn ce_enable(&mut self) {
-        self.ce.set_high().unwrap();
+        if self.ce.set_high().is_err() {
+            self.ce_error = true;
+        }
     }
 
     fn ce_disable(&mut self) {
-        self.ce.set_low().unwrap();
+        if self.ce.set_low().is_err() {
+            self.ce_error = true;
+        }
     }
 
     async fn send_command<C: Command>(
@@ -130,24 +539,128 @@ impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE:
         command: &C,
     ) -> Result<(Status, C::Response), Self::Error> {
         // Allocate storage
-        let mut buf_storage = [0; 256];
+        let mut buf_storage = [0; MAX_COMMAND_LEN];
         let len = command.len();
-        let buf = &mut buf_storage[0..len];
+        let buf = buf_storage
+            .get_mut(0..len)
+            .ok_or(InvalidArgument::PayloadLength)?;
         // Serialize the command
         command.encode(buf);
 
         // SPI transaction
-        self.spi.transfer_in_place(buf).await.expect("TODO: panic message");
+        self.transfer(buf).await?;
 
         // Parse response
-        let status = Status(buf[0]);
+        let status = buf.first().copied().unwrap_or(0);
+        self.last_status = Some(status);
+        let status = Status(status);
         let response = C::decode_response(buf);
         // defmt::info!("send_command status: {:02X} _ {:08b}", status.0, status.0);
         Ok((status, response))
     }
 
+    async fn write_payload(&mut self, opcode: u8, data: &[u8]) -> Result<Status, Self::Error> {
+        self.begin_command(opcode, data.len().saturating_add(1))?;
+        let mut header = [opcode];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Write(data)])
+            .await
+            .map_err(|e| Error::SpiError(SpiOp::Command(opcode), e))?;
+        self.last_status = Some(header[0]);
+        Ok(Status(header[0]))
+    }
+
+    async fn read_payload(&mut self, opcode: u8, buf: &mut [u8]) -> Result<Status, Self::Error> {
+        self.begin_command(opcode, buf.len().saturating_add(1))?;
+        let mut header = [opcode];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Read(buf)])
+            .await
+            .map_err(|e| Error::SpiError(SpiOp::Command(opcode), e))?;
+        self.last_status = Some(header[0]);
+        Ok(Status(header[0]))
+    }
+
+    async fn write_raw(&mut self, buf: &[u8]) -> Result<Status, Self::Error> {
+        let Some((opcode, data)) = buf.split_first() else {
+            return Err(InvalidArgument::PayloadLength.into());
+        };
+        self.begin_command(*opcode, buf.len())?;
+        let mut header = [0];
+        self.spi
+            .transaction(&mut [
+                Operation::Transfer(&mut header, core::slice::from_ref(opcode)),
+                Operation::Write(data),
+            ])
+            .await
+            .map_err(|e| Error::SpiError(SpiOp::from_opcode(*opcode), e))?;
+        self.last_status = Some(header[0]);
+        Ok(Status(header[0]))
+    }
+
+    fn ce_tied(&self) -> bool {
+        self.ce_tied
+    }
+
+    fn set_verify_writes(&mut self, enabled: bool) -> bool {
+        core::mem::replace(&mut self.verify_writes, enabled)
+    }
+
+    fn variant(&self) -> ChipVariant {
+        self.variant
+    }
+
+    fn set_variant(&mut self, variant: ChipVariant) {
+        self.variant = variant;
+    }
+
+    fn rx_lengths(&self) -> Option<[Option<u8>; PIPES_COUNT]> {
+        self.rx_lengths
+    }
+
+    fn set_rx_lengths(&mut self, lengths: Option<[Option<u8>; PIPES_COUNT]>) {
+        self.rx_lengths = lengths;
+    }
+
+    fn features(&self) -> Option<Feature> {
+        self.features.map(Feature)
+    }
+
+    fn last_status(&self) -> Option<Status> {
+        self.last_status.map(Status)
+    }
+
+    fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    fn events(&mut self) -> &mut EV {
+        &mut self.events
+    }
+
     async fn write_register<R: Register>(&mut self, register: R) -> Result<Status, Self::Error> {
+        let mut written = [0; MAX_ADDR_BYTES];
+        let written = encoded(&register, &mut written);
+        let first = written.first().copied().unwrap_or(0);
         let (status, ()) = self.send_command(&WriteRegister::new(register)).await?;
+        if R::addr() == Feature::addr() {
+            self.features = Some(first);
+        }
+        // STATUS is clocked out before the write clears it
+        if R::addr() == Status::addr() && Status(first).tx_ds() && status.tx_ds() {
+            self.stats.count_tx_ds();
+        }
+
+        // Interrupt flags in STATUS are cleared by writing them
+        if self.verify_writes && R::addr() != Status::addr() {
+            let (_, read) = self.read_register::<R>().await?;
+            let mut readback = [0; MAX_ADDR_BYTES];
+            let readback = encoded(&read, &mut readback);
+            let readback = readback.get(..written.len()).unwrap_or(readback);
+            if written != readback {
+                self.events.on_write_mismatch(R::addr(), written, readback);
+            }
+        }
         Ok(status)
     }
 
@@ -169,4 +682,10 @@ impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE:
         }
         Ok(result)
     }
+
+    async fn restore_config(&mut self) -> Result<(), Self::Error> {
+        let config = self.config.clone();
+        self.write_register(config).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,262 @@
+use crate::address::Address;
+use crate::config::{Configuration, DataRate, RadioConfig};
+use crate::device::Device;
+use crate::rx::RxMode;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES, PIPES_COUNT};
+use embedded_hal_async::delay::DelayNs;
+
+/// Address used by the [`LinkTester`](struct.LinkTester.html),
+/// truncated to the width under test
+const TEST_ADDR: [u8; MAX_ADDR_BYTES] = [0x3A, 0x96, 0x4D, 0x2B, 0xC6];
+
+/// Upper bound for a packet to show up at the receiver, in µS
+const RX_TIMEOUT_US: u32 = 10_000;
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// One combination of settings to test a link with
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LinkCase {
+    /// Air data rate
+    pub data_rate: DataRate,
+    /// Payload length, 1 to 32 bytes
+    pub payload_len: u8,
+    /// Auto-acknowledgment with auto-retransmit, or a single shot
+    pub auto_ack: bool,
+    /// Address width, 3 to 5 bytes
+    pub address_width: u8,
+}
+
+impl LinkCase {
+    /// All combinations of 250 kbps, 1 Mbps and 2 Mbps, payloads of 1,
+    /// 16 and 32 bytes, with and without auto-ack, and address widths
+    /// of 3, 4 and 5 bytes
+    pub fn matrix() -> impl Iterator<Item = LinkCase> {
+        const RATES: [DataRate; 3] = [DataRate::R250Kbps, DataRate::R1Mbps, DataRate::R2Mbps];
+        RATES.into_iter().flat_map(|data_rate| {
+            [1, 16, 32].into_iter().flat_map(move |payload_len| {
+                [true, false].into_iter().flat_map(move |auto_ack| {
+                    [3, 4, 5].into_iter().map(move |address_width| LinkCase {
+                        data_rate,
+                        payload_len,
+                        auto_ack,
+                        address_width,
+                    })
+                })
+            })
+        })
+    }
+
+    /// Configuration for both ends of the link
+    fn config(&self) -> RadioConfig {
+        let width = usize::from(self.address_width).clamp(MIN_ADDR_BYTES, MAX_ADDR_BYTES);
+        let address = TEST_ADDR.get(..width).and_then(|bytes| Address::new(bytes).ok());
+        let address = address.unwrap_or(Address::from_array(TEST_ADDR));
+        let mut rx_addrs = [None; PIPES_COUNT];
+        rx_addrs[0] = Some(address);
+        RadioConfig {
+            data_rate: self.data_rate,
+            // 250 kbps needs 1500 µS for ACK payloads, see RadioConfig::long_range()
+            auto_retransmit_delay: if self.data_rate == DataRate::R250Kbps { 5 } else { 1 },
+            auto_retransmit_count: if self.auto_ack { 15 } else { 0 },
+            auto_ack: [self.auto_ack; PIPES_COUNT],
+            tx_addr: address,
+            rx_addrs,
+            ..RadioConfig::default()
+        }
+    }
+}
+
+/// Result of one [`LinkCase`](struct.LinkCase.html)
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct LinkOutcome {
+    /// Packets sent
+    pub sent: u16,
+    /// Packets the transmitter reported as acknowledged
+    pub acked: u16,
+    /// Packets received intact
+    pub received: u16,
+    /// Packets received with unexpected content or length
+    pub corrupted: u16,
+}
+
+impl LinkOutcome {
+    /// Did every packet arrive intact, and get acknowledged if
+    /// auto-ack was on?
+    pub fn passed(&self, case: &LinkCase) -> bool {
+        self.received == self.sent
+            && self.corrupted == 0
+            && (!case.auto_ack || self.acked == self.sent)
+    }
+}
+
+/// Error of the [`LinkTester`](struct.LinkTester.html) and the
+/// [`BerTester`](struct.BerTester.html)
+#[derive(Debug)]
+pub enum LinkTestError<TE, RE> {
+    /// Transmitter failure
+    Tx(TE),
+    /// Receiver failure
+    Rx(RE),
+}
+
+/// Drives a transmitter and a receiver through a matrix of settings,
+/// reporting pass/fail per combination
+///
+/// Works with two radios on one MCU or with any other pair of
+/// [`Device`](trait.Device.html) implementations. Both are reconfigured
+/// with [`configure()`](trait.Configuration.html#method.configure) for
+/// every case, keeping the channel and power of the default
+/// `RadioConfig`.
+pub struct LinkTester<T: Device, R: Device> {
+    tx: TxMode<T>,
+    rx: RxMode<R>,
+    packets: u16,
+}
+
+impl<T: Device, R: Device> LinkTester<T, R> {
+    /// Constructor, sending `packets` packets per case
+    pub fn new(tx: TxMode<T>, rx: RxMode<R>, packets: u16) -> Self {
+        LinkTester { tx, rx, packets }
+    }
+
+    /// Return the radios
+    pub fn into_inner(self) -> (TxMode<T>, RxMode<R>) {
+        (self.tx, self.rx)
+    }
+
+    /// Run all cases of [`LinkCase::matrix()`](struct.LinkCase.html#method.matrix),
+    /// calling `report` with the outcome of each
+    pub async fn run_matrix<DL, F>(
+        &mut self,
+        delay: &mut DL,
+        mut report: F,
+    ) -> Result<(), LinkTestError<T::Error, R::Error>>
+    where
+        DL: DelayNs,
+        F: FnMut(&LinkCase, &LinkOutcome),
+    {
+        for case in LinkCase::matrix() {
+            let outcome = self.run_case(&case, delay).await?;
+            report(&case, &outcome);
+        }
+        Ok(())
+    }
+
+    /// Configure both radios for `case` and exchange the packets
+    pub async fn run_case<DL: DelayNs>(
+        &mut self,
+        case: &LinkCase,
+        delay: &mut DL,
+    ) -> Result<LinkOutcome, LinkTestError<T::Error, R::Error>> {
+        let config = case.config();
+        self.tx.configure(&config).await.map_err(LinkTestError::Tx)?;
+        self.tx.flush_tx().await.map_err(LinkTestError::Tx)?;
+        self.tx.clear_interrupts().await.map_err(LinkTestError::Tx)?;
+        // Settings must not change while listening
+        self.rx.device().ce_disable();
+        self.rx.configure(&config).await.map_err(LinkTestError::Rx)?;
+        self.rx.flush_rx().await.map_err(LinkTestError::Rx)?;
+        self.rx.clear_interrupts().await.map_err(LinkTestError::Rx)?;
+        self.rx.device().ce_enable();
+        // Rx settling
+        delay.delay_us(130).await;
+
+        let len = usize::from(case.payload_len).clamp(1, 32);
+        let mut outcome = LinkOutcome::default();
+        for seq in 0..self.packets {
+            let mut packet = [0; 32];
+            for (i, byte) in packet.iter_mut().enumerate() {
+                *byte = (seq as u8).wrapping_add(i as u8);
+            }
+            let packet = packet.get(..len).unwrap_or_default();
+
+            self.tx.send(packet).await.map_err(LinkTestError::Tx)?;
+            outcome.sent = outcome.sent.saturating_add(1);
+            let acked = self
+                .tx
+                .wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay)
+                .await
+                .map_err(LinkTestError::Tx)?;
+            if acked {
+                outcome.acked = outcome.acked.saturating_add(1);
+            }
+
+            let mut waited = 0_u32;
+            while waited < RX_TIMEOUT_US {
+                if self.rx.can_read().await.map_err(LinkTestError::Rx)?.is_some() {
+                    let payload = self.rx.read().await.map_err(LinkTestError::Rx)?;
+                    if &payload[..] == packet {
+                        outcome.received = outcome.received.saturating_add(1);
+                    } else {
+                        outcome.corrupted = outcome.corrupted.saturating_add(1);
+                    }
+                    break;
+                }
+                delay.delay_us(100).await;
+                waited = waited.saturating_add(100);
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{Air, SimRadio, SimTimer};
+
+    #[test]
+    fn simulated_pair_passes_matrix() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let tx = SimRadio::new(ce, spi).await.unwrap().tx().await.map_err(|(_, e)| e).unwrap();
+            let (ce, spi) = air.radio();
+            let rx = SimRadio::new(ce, spi).await.unwrap().rx().await.map_err(|(_, e)| e).unwrap();
+
+            let mut tester = LinkTester::new(tx, rx, 4);
+            let mut cases = 0;
+            tester
+                .run_matrix(&mut SimTimer::new(), |case, outcome| {
+                    assert!(outcome.passed(case), "{:?}: {:?}", case, outcome);
+                    cases += 1;
+                })
+                .await
+                .unwrap();
+            assert_eq!(cases, LinkCase::matrix().count());
+        });
+    }
+
+    #[test]
+    fn lost_packets_fail_without_auto_ack() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let tx = SimRadio::new(ce, spi).await.unwrap().tx().await.map_err(|(_, e)| e).unwrap();
+            let (ce, spi) = air.radio();
+            let rx = SimRadio::new(ce, spi).await.unwrap().rx().await.map_err(|(_, e)| e).unwrap();
+
+            let mut tester = LinkTester::new(tx, rx, 4);
+            let case = LinkCase {
+                data_rate: DataRate::R2Mbps,
+                payload_len: 8,
+                auto_ack: false,
+                address_width: 5,
+            };
+            air.lose_next(1);
+            let outcome = tester.run_case(&case, &mut SimTimer::new()).await.unwrap();
+            assert_eq!((outcome.sent, outcome.received), (4, 3));
+            assert!(!outcome.passed(&case));
+
+            // Retransmits make up for the loss with auto-ack
+            let case = LinkCase { auto_ack: true, ..case };
+            air.lose_next(2);
+            let outcome = tester.run_case(&case, &mut SimTimer::new()).await.unwrap();
+            assert!(outcome.passed(&case), "{:?}", outcome);
+        });
+    }
+}
@@ -0,0 +1,144 @@
+use crate::config::{apply_changes, Configuration, PowerLevel, RadioConfig, RegisterSnapshot};
+use crate::device::Device;
+use crate::rx::RxMode;
+use crate::standby::StandbyMode;
+use crate::strategy::Strategy;
+use crate::timer::DelayTimer;
+use crate::tx::TxMode;
+use crate::PIPES_COUNT;
+use embedded_hal_async::delay::DelayNs;
+
+/// Packet sent by the loopback test
+const LOOPBACK_PACKET: &[u8] = b"nRF24 loopback";
+/// Interval at which to poll while waiting
+const POLL_INTERVAL_US: u32 = 100;
+/// How long to wait for the transmission, and then for the echo
+const WINDOW_US: u32 = 5_000;
+
+/// Outcome of
+/// [`StandbyMode::loopback_test()`](struct.StandbyMode.html#method.loopback_test)
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct LoopbackReport {
+    /// The chip reported `TX_DS` for the packet, so SPI, `CE`, the
+    /// oscillator and the TX state machine work
+    pub sent: bool,
+    /// The packet came back on pipe 0
+    pub received: bool,
+    /// `RPD` saw a carrier at the end of the window
+    pub carrier: bool,
+}
+
+impl<D: Device> StandbyMode<D> {
+    /// Smoke test without a second radio: send a packet without ACK at
+    /// minimum power, and listen for it on pipe 0 set to the TX address
+    ///
+    /// The nRF24L01 is half duplex and takes 130µs to settle into RX
+    /// mode, so it normally cannot hear its own frame, and `received`
+    /// is only set where something repeats it, e.g. a relay node or a
+    /// test jig. `sent` is the part that every module should pass.
+    ///
+    /// Restores the registers afterwards, except on error.
+    pub async fn loopback_test<DL: DelayNs>(
+        mut self,
+        delay: &mut DL,
+    ) -> Result<(Self, LoopbackReport), (D, D::Error)> {
+        let snapshot = match RegisterSnapshot::read(self.device()).await {
+            Ok(snapshot) => snapshot,
+            Err(e) => return Err((self.into_device(), e)),
+        };
+        let saved = match self.get_radio_config().await {
+            Ok(saved) => saved,
+            Err(e) => return Err((self.into_device(), e)),
+        };
+        let mut test = saved.clone();
+        test.power = PowerLevel::Min;
+        test.auto_ack = [false; PIPES_COUNT];
+        test.pipes_rx_lengths[0] = None;
+        test.rx_addrs = [Some(test.tx_addr), None, None, None, None, None];
+
+        let (mut standby, report) = Self::loop_back(self, &saved, &test, delay).await?;
+        match snapshot.restore(standby.device()).await {
+            Ok(()) => Ok((standby, report)),
+            Err(e) => Err((standby.into_device(), e)),
+        }
+    }
+
+    async fn loop_back<DL: DelayNs>(
+        mut standby: Self,
+        saved: &RadioConfig,
+        test: &RadioConfig,
+        delay: &mut DL,
+    ) -> Result<(Self, LoopbackReport), (D, D::Error)> {
+        if let Err(e) = apply_changes(&mut standby, saved, test).await {
+            return Err((standby.into_device(), e));
+        }
+        let mut report = LoopbackReport::default();
+
+        let mut tx = standby.tx().await?;
+        report.sent = match Self::send_once(&mut tx, delay).await {
+            Ok(sent) => sent,
+            Err(e) => return Err((tx.into_standby().into_device(), e)),
+        };
+
+        let mut rx = tx.into_standby().rx().await?;
+        if let Err(e) = Self::listen(&mut rx, &mut report, delay).await {
+            return Err((rx.standby().into_device(), e));
+        }
+        Ok((rx.standby(), report))
+    }
+
+    /// Send the test packet and wait for `TX_DS`. Flushes it on timeout.
+    async fn send_once<DL: DelayNs>(tx: &mut TxMode<D>, delay: &mut DL) -> Result<bool, D::Error> {
+        tx.send_no_ack(LOOPBACK_PACKET).await?;
+        let mut timer = DelayTimer::new(delay);
+        tx.wait_send_for(&mut Strategy::polling(POLL_INTERVAL_US), &mut timer, WINDOW_US)
+            .await
+    }
+
+    /// Wait for the test packet to come back
+    async fn listen<DL: DelayNs>(
+        rx: &mut RxMode<D>,
+        report: &mut LoopbackReport,
+        delay: &mut DL,
+    ) -> Result<(), D::Error> {
+        let mut waited_us = 0;
+        while waited_us < WINDOW_US {
+            while let Some((pipe_no, payload)) = rx.read_with_pipe().await? {
+                if pipe_no == 0 && *payload == *LOOPBACK_PACKET {
+                    report.received = true;
+                }
+            }
+            if report.received {
+                break;
+            }
+            delay.delay_us(POLL_INTERVAL_US).await;
+            waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+        }
+        report.carrier = rx.has_carrier().await?;
+        rx.flush_rx().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{Air, SimRadio, SimTimer};
+
+    #[test]
+    fn loopback_on_fresh_driver() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let chip = spi.chip();
+            let mut standby = SimRadio::new(ce, spi).await.unwrap();
+            let before = standby.get_radio_config().await.unwrap();
+
+            let (mut standby, report) =
+                standby.loopback_test(&mut SimTimer::new()).await.map_err(|(_, e)| e).unwrap();
+            assert!(report.sent);
+            assert_eq!(standby.get_radio_config().await.ok(), Some(before));
+            assert_eq!(chip.register(0x11), 0);
+        });
+    }
+}
@@ -0,0 +1,211 @@
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::registers::ObserveTx;
+use crate::rx::RxMode;
+use crate::tx::TxMode;
+
+/// Number of samples the rolling percentages cover
+pub const MONITOR_WINDOW: usize = 32;
+
+/// Rolling link quality from `OBSERVE_TX` and `RPD` samples
+///
+/// Call [`sample_tx()`](#method.sample_tx) after sends complete and
+/// [`sample_rx()`](#method.sample_rx) while listening. Both only read a
+/// register, so they do not disturb transfers in progress. A TX sample
+/// is only taken once per packet written to the FIFO, based on
+/// [`Stats::packets_sent`](struct.Stats.html#structfield.packets_sent),
+/// so calling it more often than packets are sent is harmless.
+#[derive(Debug, Default, Clone)]
+pub struct LinkMonitor {
+    /// Retransmits per packet, most recent at `tx_next - 1`
+    retransmits: [u8; MONITOR_WINDOW],
+    /// Bit per packet that `PLOS_CNT` counted as lost
+    lost: u32,
+    tx_next: usize,
+    tx_count: usize,
+    last_packets_sent: Option<u32>,
+    /// `None` until the first sample sets the baseline
+    last_plos: Option<u8>,
+    /// Bit per RPD sample that saw a carrier
+    busy: u32,
+    rx_next: usize,
+    rx_count: usize,
+}
+
+impl LinkMonitor {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample `OBSERVE_TX` if another packet has been sent since the
+    /// last sample
+    pub async fn sample_tx<D: Device>(&mut self, tx: &mut TxMode<D>) -> Result<(), D::Error> {
+        let packets_sent = tx.stats().packets_sent;
+        if self.last_packets_sent == Some(packets_sent) {
+            return Ok(());
+        }
+        let observe = tx.observe().await?;
+        self.last_packets_sent = Some(packets_sent);
+        self.record_tx(&observe);
+        Ok(())
+    }
+
+    /// Sample `RPD` to track how busy the channel is
+    pub async fn sample_rx<D: Device>(&mut self, rx: &mut RxMode<D>) -> Result<(), D::Error> {
+        let busy = rx.has_carrier().await?;
+        self.record_rpd(busy);
+        Ok(())
+    }
+
+    /// Account for the `OBSERVE_TX` of a completed packet
+    pub fn record_tx(&mut self, observe: &ObserveTx) {
+        let plos = observe.plos_cnt();
+        // PLOS_CNT saturates at 15 and is reset by writing RF_CH
+        let lost = match self.last_plos {
+            Some(last) if plos >= last => plos.saturating_sub(last),
+            Some(_) => plos,
+            None => 0,
+        };
+        self.last_plos = Some(plos);
+
+        let bit = 1 << self.tx_next;
+        if lost > 0 {
+            self.lost |= bit;
+        } else {
+            self.lost &= !bit;
+        }
+        if let Some(retransmits) = self.retransmits.get_mut(self.tx_next) {
+            *retransmits = observe.arc_cnt();
+        }
+        self.tx_next = self.tx_next.saturating_add(1) % MONITOR_WINDOW;
+        self.tx_count = self.tx_count.saturating_add(1).min(MONITOR_WINDOW);
+    }
+
+    /// Account for an `RPD` sample
+    pub fn record_rpd(&mut self, busy: bool) {
+        let bit = 1 << self.rx_next;
+        if busy {
+            self.busy |= bit;
+        } else {
+            self.busy &= !bit;
+        }
+        self.rx_next = self.rx_next.saturating_add(1) % MONITOR_WINDOW;
+        self.rx_count = self.rx_count.saturating_add(1).min(MONITOR_WINDOW);
+    }
+
+    /// Percentage of recent packets that were lost after all
+    /// retransmits
+    pub fn packet_loss_percent(&self) -> u8 {
+        percent(window_mask(self.lost, self.tx_count).count_ones() as usize, self.tx_count)
+    }
+
+    /// Percentage of recent transmissions, including retransmits, that
+    /// were not acknowledged
+    pub fn retransmit_percent(&self) -> u8 {
+        let retransmits: usize = self
+            .retransmits
+            .get(..self.tx_count)
+            .unwrap_or(&self.retransmits)
+            .iter()
+            .map(|r| usize::from(*r))
+            .sum();
+        percent(retransmits, retransmits.saturating_add(self.tx_count))
+    }
+
+    /// Percentage of recent `RPD` samples that saw a carrier
+    pub fn carrier_busy_percent(&self) -> u8 {
+        percent(window_mask(self.busy, self.rx_count).count_ones() as usize, self.rx_count)
+    }
+
+    /// Forget all samples
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Bits of `bits` that hold samples
+///
+/// Until the window is full, samples occupy the lowest `count` bits.
+fn window_mask(bits: u32, count: usize) -> u32 {
+    if count >= MONITOR_WINDOW {
+        bits
+    } else {
+        bits & (1_u32 << count).wrapping_sub(1)
+    }
+}
+
+fn percent(part: usize, total: usize) -> u8 {
+    part.saturating_mul(100).checked_div(total).unwrap_or(0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air};
+
+    #[test]
+    fn tx_samples_follow_the_air() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut monitor = LinkMonitor::new();
+
+            tx.send(b"clean").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            monitor.sample_tx(&mut tx).await.unwrap();
+            // No new packet, no new sample
+            monitor.sample_tx(&mut tx).await.unwrap();
+            assert_eq!((monitor.packet_loss_percent(), monitor.retransmit_percent()), (0, 0));
+
+            // Through on the first retransmit
+            air.lose_next(1);
+            tx.send(b"retry").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            monitor.sample_tx(&mut tx).await.unwrap();
+            assert_eq!((monitor.packet_loss_percent(), monitor.retransmit_percent()), (0, 33));
+
+            // Lost after all 3 retransmits, and again in those that
+            // clearing MAX_RT resumes before the flush
+            air.lose_next(8);
+            tx.send(b"lost").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(false));
+            monitor.sample_tx(&mut tx).await.unwrap();
+            assert_eq!((monitor.packet_loss_percent(), monitor.retransmit_percent()), (33, 57));
+            assert_eq!(&rx.read().await.unwrap()[..], b"clean");
+            assert_eq!(&rx.read().await.unwrap()[..], b"retry");
+            assert!(rx.is_empty().await.unwrap());
+
+            monitor.reset();
+            assert_eq!((monitor.packet_loss_percent(), monitor.retransmit_percent()), (0, 0));
+        });
+    }
+
+    #[test]
+    fn rx_samples_track_the_carrier() {
+        block_on(async {
+            let air = Air::new();
+            let (_tx, mut rx) = sim::pair(&air).await;
+            let chip = rx.device().spi.chip();
+            let mut monitor = LinkMonitor::new();
+            assert_eq!(monitor.carrier_busy_percent(), 0);
+
+            monitor.sample_rx(&mut rx).await.unwrap();
+            chip.set_carrier(true);
+            monitor.sample_rx(&mut rx).await.unwrap();
+            assert_eq!(monitor.carrier_busy_percent(), 50);
+
+            // Older samples drop out of the window
+            for _ in 0..MONITOR_WINDOW {
+                monitor.sample_rx(&mut rx).await.unwrap();
+            }
+            assert_eq!(monitor.carrier_busy_percent(), 100);
+            chip.set_carrier(false);
+            for _ in 0..MONITOR_WINDOW / 4 {
+                monitor.sample_rx(&mut rx).await.unwrap();
+            }
+            assert_eq!(monitor.carrier_busy_percent(), 75);
+        });
+    }
+}
@@ -0,0 +1,29 @@
+use core::convert::Infallible;
+use embedded_hal::digital::{ErrorType, OutputPin};
+
+/// `CE` pin for boards that strap `CE` permanently high
+///
+/// Pass it to
+/// [`NRF24L01::new_ce_tied()`](struct.NRF24L01.html#method.new_ce_tied),
+/// which tells the driver that `CE` is high throughout. Mode changes
+/// then go through power down and `PRIM_RX` instead, costing the 1.5ms
+/// oscillator startup each time, and the chip listens in "standby"
+/// after RX mode until the next `tx()`. It sends as soon as a packet is
+/// loaded, so operations that time or withhold transmissions fail with
+/// `Error::CeTied`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoCe;
+
+impl ErrorType for NoCe {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoCe {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
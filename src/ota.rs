@@ -0,0 +1,323 @@
+use crate::address::Address;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::payload::{copy_bytes, Payload};
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Header of a MySensors message: last hop, sender, destination,
+/// version and length, command and payload type, message type, sensor
+const HEADER_LEN: usize = 7;
+/// Protocol version in the low bits of the header's fourth byte
+const PROTOCOL_VERSION: u8 = 2;
+const C_STREAM: u8 = 4;
+const P_CUSTOM: u8 = 6;
+/// Sensor id of messages about the node itself
+const NODE_SENSOR_ID: u8 = 255;
+
+const ST_FIRMWARE_CONFIG_REQUEST: u8 = 0;
+const ST_FIRMWARE_CONFIG_RESPONSE: u8 = 1;
+const ST_FIRMWARE_REQUEST: u8 = 2;
+const ST_FIRMWARE_RESPONSE: u8 = 3;
+
+/// Bytes of firmware per block
+pub const FIRMWARE_BLOCK_SIZE: usize = 16;
+
+/// Interval at which to check for the end of a transmission
+const SEND_INTERVAL_US: u32 = 100;
+
+/// Radio address of MySensors node `node_id`, with the default base
+/// address `A8:A8:E1:FC` of its nRF24 transport
+///
+/// The gateway is node 0.
+pub fn mysensors_address(node_id: u8) -> Address {
+    Address::from_array([node_id, 0xFC, 0xE1, 0xA8, 0xA8])
+}
+
+/// Why an OTA request could not be served
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum OtaError {
+    /// The image is empty, or has more than 65535 blocks
+    ImageSize,
+    /// The request is shorter than its header says, or than its type
+    Malformed,
+    /// The node asked for blocks of another firmware
+    WrongFirmware {
+        /// Firmware type it asked for
+        fw_type: u16,
+        /// Firmware version it asked for
+        version: u16,
+    },
+    /// The node asked for a block past the end of the image
+    NoSuchBlock(u16),
+}
+
+/// What a node asked for, see [`OtaAnswer`](struct.OtaAnswer.html)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum OtaEvent {
+    /// The node asked for the firmware configuration while running
+    /// another image, and starts the update once it gets it
+    Config,
+    /// The node asked for the firmware configuration while running the
+    /// served image: its bootloader checked the image against the CRC
+    /// before starting it
+    Verified,
+    /// The node asked for this block. Blocks go from the last one down
+    /// to 0.
+    Block(u16),
+}
+
+/// Answer to an OTA request, to be sent to the node `via`, see
+/// [`OtaServer::send()`](struct.OtaServer.html#method.send)
+pub struct OtaAnswer {
+    /// Node the request came from over the last hop, either the
+    /// requesting node or a repeater
+    pub via: u8,
+    /// What the node asked for
+    pub event: OtaEvent,
+    /// MySensors message answering the request
+    pub packet: Payload,
+}
+
+/// Controller side of MySensors over-the-air updates, to flash nodes
+/// running a MySensors OTA bootloader such as MYSBootloader
+///
+/// The node drives the update: it asks for the firmware configuration,
+/// that is the type, version, number of 16 byte blocks and CRC of the
+/// image. If another image than its own is offered, it requests the
+/// blocks one by one, from the last to the first, checks the CRC over
+/// the flashed image, and only then starts it. Pass each received
+/// packet to [`answer()`](#method.answer) and send the answers with
+/// [`send()`](#method.send).
+///
+/// The CRC is CRC-16/MODBUS, over the image padded with `0xFF` to whole
+/// blocks. Configure the radio as the nodes' transport, by default
+/// channel 76 and 250 Kbps, with dynamic payload lengths and auto-ack,
+/// and receive on [`mysensors_address()`](fn.mysensors_address.html)
+/// of the gateway's node id.
+#[derive(Debug, Clone)]
+pub struct OtaServer<'a> {
+    node_id: u8,
+    fw_type: u16,
+    version: u16,
+    image: &'a [u8],
+    blocks: u16,
+    crc: u16,
+}
+
+impl<'a> OtaServer<'a> {
+    /// Constructor, serving `image` as firmware `fw_type` in `version`
+    /// from the gateway, node 0
+    pub fn new(fw_type: u16, version: u16, image: &'a [u8]) -> Result<Self, OtaError> {
+        let blocks = image.len().div_ceil(FIRMWARE_BLOCK_SIZE);
+        let blocks = u16::try_from(blocks).map_err(|_| OtaError::ImageSize)?;
+        if blocks == 0 {
+            return Err(OtaError::ImageSize);
+        }
+        let padding =
+            usize::from(blocks).saturating_mul(FIRMWARE_BLOCK_SIZE).saturating_sub(image.len());
+        let padded = image.iter().copied().chain(core::iter::repeat_n(0xFF, padding));
+        Ok(OtaServer {
+            node_id: 0,
+            fw_type,
+            version,
+            image,
+            blocks,
+            crc: crc16_modbus(padded),
+        })
+    }
+
+    /// Answer as node `node_id` instead, e.g. for a repeater that serves
+    /// the image
+    pub fn set_node_id(&mut self, node_id: u8) {
+        self.node_id = node_id;
+    }
+
+    /// Number of blocks of the image
+    pub fn blocks(&self) -> u16 {
+        self.blocks
+    }
+
+    /// CRC of the image, as the nodes check it
+    pub fn crc(&self) -> u16 {
+        self.crc
+    }
+
+    /// Answer `packet` if it is a MySensors OTA request to this node
+    ///
+    /// Returns `None` for other packets, e.g. the other messages of the
+    /// nodes, and fails for OTA requests that cannot be served.
+    pub fn answer(&self, packet: &[u8]) -> Result<Option<OtaAnswer>, OtaError> {
+        let Some(&[last, sender, destination, version_length, command, msg_type, _]) =
+            packet.get(..HEADER_LEN)
+        else {
+            return Ok(None);
+        };
+        let is_request = version_length & 0b11 == PROTOCOL_VERSION
+            && command & 0b111 == C_STREAM
+            && destination == self.node_id;
+        if !is_request {
+            return Ok(None);
+        }
+        let len = usize::from(version_length >> 3);
+        let payload = packet.get(HEADER_LEN..len.saturating_add(HEADER_LEN));
+        let payload = payload.ok_or(OtaError::Malformed)?;
+        let field = |i: usize| match payload.chunks_exact(2).nth(i) {
+            Some(&[lo, hi]) => Ok(u16::from_le_bytes([lo, hi])),
+            _ => Err(OtaError::Malformed),
+        };
+
+        let mut body = [0; 6 + FIRMWARE_BLOCK_SIZE];
+        body[0..2].copy_from_slice(&self.fw_type.to_le_bytes());
+        body[2..4].copy_from_slice(&self.version.to_le_bytes());
+        let (event, msg_type, body) = match msg_type {
+            ST_FIRMWARE_CONFIG_REQUEST => {
+                let running = [field(0)?, field(1)?, field(2)?, field(3)?];
+                let event = if running == [self.fw_type, self.version, self.blocks, self.crc] {
+                    OtaEvent::Verified
+                } else {
+                    OtaEvent::Config
+                };
+                body[4..6].copy_from_slice(&self.blocks.to_le_bytes());
+                body[6..8].copy_from_slice(&self.crc.to_le_bytes());
+                (event, ST_FIRMWARE_CONFIG_RESPONSE, &body[..8])
+            }
+            ST_FIRMWARE_REQUEST => {
+                let (fw_type, version, block) = (field(0)?, field(1)?, field(2)?);
+                if (fw_type, version) != (self.fw_type, self.version) {
+                    return Err(OtaError::WrongFirmware { fw_type, version });
+                }
+                if block >= self.blocks {
+                    return Err(OtaError::NoSuchBlock(block));
+                }
+                body[4..6].copy_from_slice(&block.to_le_bytes());
+                let start = usize::from(block).saturating_mul(FIRMWARE_BLOCK_SIZE);
+                let block_data = &mut body[6..];
+                let copied = copy_bytes(block_data, self.image.get(start..).unwrap_or_default());
+                block_data.get_mut(copied..).unwrap_or_default().fill(0xFF);
+                (OtaEvent::Block(block), ST_FIRMWARE_RESPONSE, &body[..])
+            }
+            _ => return Ok(None),
+        };
+
+        let mut reply = Payload::zeroed(body.len().saturating_add(HEADER_LEN));
+        let data = reply.data_mut();
+        copy_bytes(data, &[
+            self.node_id,
+            self.node_id,
+            sender,
+            PROTOCOL_VERSION | (body.len() as u8) << 3,
+            C_STREAM | P_CUSTOM << 5,
+            msg_type,
+            NODE_SENSOR_ID,
+        ]);
+        copy_bytes(data.get_mut(HEADER_LEN..).unwrap_or_default(), body);
+        Ok(Some(OtaAnswer {
+            via: last,
+            event,
+            packet: reply,
+        }))
+    }
+
+    /// Send `answer` to its node and return whether it was acknowledged,
+    /// polling with `delay`
+    ///
+    /// Waits for the TX FIFO to drain first, and leaves the TX and pipe
+    /// 0 addresses at the node. The node asks again if an answer is
+    /// lost.
+    pub async fn send<D: Device, DL: DelayNs>(
+        tx: &mut TxMode<D>,
+        answer: &OtaAnswer,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let address = mysensors_address(answer.via);
+        // Settings must not change while sending
+        tx.wait_empty().await?;
+        tx.set_tx_addr(&address).await?;
+        tx.set_rx_addr(0, &address).await?;
+        tx.send(&answer.packet).await?;
+        tx.wait_send(&mut Strategy::polling(SEND_INTERVAL_US), delay).await
+    }
+}
+
+/// CRC-16/MODBUS, as `_crc16_update()` of avr-libc from `0xFFFF`
+fn crc16_modbus<I: IntoIterator<Item = u8>>(data: I) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Request of `msg_type` from node 5 to the gateway
+    fn request(msg_type: u8, fields: &[u16]) -> Vec<u8> {
+        let len = 2 * fields.len() as u8;
+        let mut packet = vec![5, 5, 0, PROTOCOL_VERSION | len << 3, C_STREAM | P_CUSTOM << 5];
+        packet.extend_from_slice(&[msg_type, NODE_SENSOR_ID]);
+        for field in fields {
+            packet.extend_from_slice(&field.to_le_bytes());
+        }
+        packet
+    }
+
+    #[test]
+    fn crc_is_modbus() {
+        assert_eq!(crc16_modbus(b"123456789".iter().copied()), 0x4B37);
+    }
+
+    #[test]
+    fn config_is_answered() {
+        let image = [0x11; 40];
+        let server = OtaServer::new(7, 2, &image).unwrap();
+        assert_eq!(server.blocks(), 3);
+        let answer = server.answer(&request(0, &[0, 0, 0, 0, 1])).unwrap().unwrap();
+        assert_eq!(answer.via, 5);
+        assert_eq!(answer.event, OtaEvent::Config);
+        let [crc_lo, crc_hi] = server.crc().to_le_bytes();
+        assert_eq!(
+            *answer.packet,
+            [0, 0, 5, 2 | 8 << 3, 0xC4, 1, 255, 7, 0, 2, 0, 3, 0, crc_lo, crc_hi]
+        );
+
+        // After the update, the node runs the new image
+        let running = request(0, &[7, 2, 3, server.crc(), 1]);
+        let answer = server.answer(&running).unwrap().unwrap();
+        assert_eq!(answer.event, OtaEvent::Verified);
+    }
+
+    #[test]
+    fn blocks_are_padded() {
+        let image: Vec<u8> = (0..40).collect();
+        let server = OtaServer::new(7, 2, &image).unwrap();
+        let answer = server.answer(&request(2, &[7, 2, 2])).unwrap().unwrap();
+        assert_eq!(answer.event, OtaEvent::Block(2));
+        assert_eq!(answer.packet[3], 2 | 22 << 3);
+        assert_eq!(answer.packet[7..13], [7, 0, 2, 0, 2, 0]);
+        assert_eq!(answer.packet[13..21], [32, 33, 34, 35, 36, 37, 38, 39]);
+        assert_eq!(answer.packet[21..], [0xFF; 8]);
+    }
+
+    #[test]
+    fn bad_requests_fail() {
+        let image = [0; 16];
+        let server = OtaServer::new(7, 2, &image).unwrap();
+        assert_eq!(server.answer(&request(2, &[7, 2, 1])).err(), Some(OtaError::NoSuchBlock(1)));
+        assert_eq!(
+            server.answer(&request(2, &[7, 3, 0])).err(),
+            Some(OtaError::WrongFirmware { fw_type: 7, version: 3 })
+        );
+        assert_eq!(server.answer(&request(2, &[7, 2])).err(), Some(OtaError::Malformed));
+        // Other messages are not for the server
+        assert!(server.answer(&[0x55; 3]).unwrap().is_none());
+        assert!(server.answer(&request(4, &[])).unwrap().is_none());
+        assert!(OtaServer::new(7, 2, &[]).is_err());
+    }
+}
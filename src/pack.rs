@@ -0,0 +1,350 @@
+use crate::bits::{BitReader, BitWriter};
+
+/// Most fields a [`BatchPacker`](struct.BatchPacker.html) schema can
+/// have
+pub const MAX_FIELDS: usize = 8;
+/// Payload bytes, after the sample count
+const BODY_LEN: usize = 31;
+
+/// How a field of a sample is packed, see
+/// [`BatchPacker`](struct.BatchPacker.html)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SampleField {
+    /// Unsigned value of 1 to 32 bits
+    Unsigned(u8),
+    /// Signed value of 1 to 32 bits, in two's complement
+    Signed(u8),
+    /// Slowly changing signed value, packed in `bits` for the first
+    /// sample of a batch and as the difference to the previous sample in
+    /// `delta_bits` after that
+    Delta {
+        /// Width of the first value
+        bits: u8,
+        /// Width of the differences
+        delta_bits: u8,
+    },
+}
+
+/// Why a sample could not be packed
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PackError {
+    /// The schema has more than `MAX_FIELDS` fields, a width outside 1
+    /// to 32 bits, or the sample does not have one value per field
+    Schema,
+    /// A value, or its difference for a `SampleField::Delta`, does not fit
+    /// the width of its field. Send the batch and start a new one.
+    OutOfRange,
+    /// The payload is full. Send it and start a new batch.
+    Full,
+}
+
+impl SampleField {
+    /// Width of the value in the first sample, and in later ones
+    fn widths(&self) -> (u8, u8) {
+        match *self {
+            SampleField::Unsigned(bits) | SampleField::Signed(bits) => (bits, bits),
+            SampleField::Delta { bits, delta_bits } => (bits, delta_bits),
+        }
+    }
+
+    fn signed(&self) -> bool {
+        !matches!(self, SampleField::Unsigned(_))
+    }
+}
+
+/// Whether `value` fits `bits` bits, as two's complement if `signed`
+fn fits(value: i64, bits: u8, signed: bool) -> bool {
+    let bits = u32::from(bits);
+    if signed {
+        let half = 1_i64.checked_shl(bits.saturating_sub(1)).unwrap_or(0);
+        (half.saturating_neg()..half).contains(&value)
+    } else {
+        (0..1_i64.checked_shl(bits).unwrap_or(0)).contains(&value)
+    }
+}
+
+/// Validate `schema`
+fn check_schema(schema: &[SampleField]) -> Result<(), PackError> {
+    let valid = |bits: u8| (1..=32).contains(&bits);
+    if schema.len() > MAX_FIELDS
+        || !schema.iter().all(|field| {
+            let (first, rest) = field.widths();
+            valid(first) && valid(rest)
+        })
+    {
+        return Err(PackError::Schema);
+    }
+    Ok(())
+}
+
+/// Packs several small sensor samples into one payload, by a schema of
+/// bit widths and delta encoding
+///
+/// Sending each sample in a packet of its own wastes most of the 32
+/// bytes, and the airtime and energy of each packet. A sample is a
+/// value per [`SampleField`](enum.SampleField.html) of the schema, which sender and
+/// receiver have to agree on. The payload is the number of samples,
+/// followed by the fields of all samples as a bit stream, most
+/// significant bit first, padded with zeros. Decode with a
+/// [`BatchUnpacker`](struct.BatchUnpacker.html).
+pub struct BatchPacker<'s> {
+    schema: &'s [SampleField],
+    buf: [u8; 1 + BODY_LEN],
+    bits: usize,
+    previous: [i32; MAX_FIELDS],
+}
+
+impl<'s> BatchPacker<'s> {
+    /// Constructor, for samples laid out as in `schema`
+    pub fn new(schema: &'s [SampleField]) -> Result<Self, PackError> {
+        check_schema(schema)?;
+        Ok(BatchPacker {
+            schema,
+            buf: [0; 1 + BODY_LEN],
+            bits: 0,
+            previous: [0; MAX_FIELDS],
+        })
+    }
+
+    /// Add a sample of one value per field. Leaves the batch as it was
+    /// on error.
+    pub fn push(&mut self, sample: &[i32]) -> Result<(), PackError> {
+        if sample.len() != self.schema.len() {
+            return Err(PackError::Schema);
+        }
+        let first = self.is_empty();
+        if self.buf[0] == u8::MAX {
+            return Err(PackError::Full);
+        }
+
+        let mut needed = 0_usize;
+        for ((field, &value), &previous) in self.schema.iter().zip(sample).zip(&self.previous) {
+            let (bits, delta_bits) = field.widths();
+            let fit = match field {
+                SampleField::Delta { .. } if !first => {
+                    let delta = i64::from(value).saturating_sub(i64::from(previous));
+                    needed = needed.saturating_add(usize::from(delta_bits));
+                    fits(delta, delta_bits, true)
+                }
+                _ => {
+                    needed = needed.saturating_add(usize::from(bits));
+                    fits(i64::from(value), bits, field.signed())
+                }
+            };
+            if !fit {
+                return Err(PackError::OutOfRange);
+            }
+        }
+        if self.bits.saturating_add(needed) > BODY_LEN * 8 {
+            return Err(PackError::Full);
+        }
+
+        let mut writer = BitWriter {
+            buf: &mut self.buf[1..],
+            bits: self.bits,
+        };
+        let fields = self.schema.iter().zip(sample).zip(&mut self.previous);
+        for ((field, &value), previous) in fields {
+            let (bits, delta_bits) = field.widths();
+            let (encoded, width) = match field {
+                SampleField::Delta { .. } if !first => (value.wrapping_sub(*previous), delta_bits),
+                _ => (value, bits),
+            };
+            writer.push(encoded as u32 & mask(width), width).ok_or(PackError::Full)?;
+            *previous = value;
+        }
+        self.bits = writer.bits;
+        self.buf[0] = self.buf[0].saturating_add(1);
+        Ok(())
+    }
+
+    /// Samples in the batch
+    pub fn len(&self) -> usize {
+        usize::from(self.buf[0])
+    }
+
+    /// Whether no sample has been pushed since the last
+    /// [`clear()`](#method.clear)
+    pub fn is_empty(&self) -> bool {
+        self.buf[0] == 0
+    }
+
+    /// The batch, e.g. for
+    /// [`TxMode::send()`](struct.TxMode.html#method.send)
+    pub fn payload(&self) -> &[u8] {
+        self.buf.get(..self.bits.div_ceil(8).saturating_add(1)).unwrap_or(&self.buf)
+    }
+
+    /// Start a new batch
+    pub fn clear(&mut self) {
+        self.buf = [0; 1 + BODY_LEN];
+        self.bits = 0;
+    }
+}
+
+/// Lowest `bits` bits set
+fn mask(bits: u8) -> u32 {
+    u32::MAX.checked_shr(32_u32.saturating_sub(u32::from(bits))).unwrap_or(0)
+}
+
+/// Iterates over the samples of a payload packed by a
+/// [`BatchPacker`](struct.BatchPacker.html) with the same schema
+///
+/// Each sample has the values of the schema's fields first, and zeros
+/// after them. Ends early if the payload is truncated.
+pub struct BatchUnpacker<'s, 'p> {
+    schema: &'s [SampleField],
+    reader: BitReader<'p>,
+    remaining: u8,
+    first: bool,
+    previous: [i32; MAX_FIELDS],
+}
+
+impl<'s, 'p> BatchUnpacker<'s, 'p> {
+    /// Constructor, failing if `schema` is invalid or `payload` is empty
+    pub fn new(schema: &'s [SampleField], payload: &'p [u8]) -> Result<Self, PackError> {
+        check_schema(schema)?;
+        let (&count, body) = payload.split_first().ok_or(PackError::Schema)?;
+        Ok(BatchUnpacker {
+            schema,
+            reader: BitReader { buf: body, bits: 0 },
+            remaining: count,
+            first: true,
+            previous: [0; MAX_FIELDS],
+        })
+    }
+
+    /// Samples still to come, according to the payload
+    pub fn remaining(&self) -> usize {
+        usize::from(self.remaining)
+    }
+}
+
+impl Iterator for BatchUnpacker<'_, '_> {
+    type Item = [i32; MAX_FIELDS];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut sample = [0; MAX_FIELDS];
+        let fields = self.schema.iter().zip(&mut sample).zip(&mut self.previous);
+        for ((field, value_out), previous) in fields {
+            let (bits, delta_bits) = field.widths();
+            let delta = matches!(field, SampleField::Delta { .. }) && !self.first;
+            let width = if delta { delta_bits } else { bits };
+            let Some(raw) = self.reader.pull(width) else {
+                self.remaining = 0;
+                return None;
+            };
+            let value = if field.signed() && width < 32 {
+                // Sign-extend
+                let shift = 32_u32.saturating_sub(u32::from(width));
+                (raw.checked_shl(shift).unwrap_or(0) as i32).checked_shr(shift).unwrap_or(0)
+            } else {
+                raw as i32
+            };
+            *value_out = if delta { previous.wrapping_add(value) } else { value };
+            *previous = *value_out;
+        }
+        self.first = false;
+        self.remaining = self.remaining.saturating_sub(1);
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpack(schema: &[SampleField], payload: &[u8]) -> Vec<[i32; MAX_FIELDS]> {
+        BatchUnpacker::new(schema, payload).unwrap().collect()
+    }
+
+    fn sample(values: &[i32]) -> [i32; MAX_FIELDS] {
+        let mut sample = [0; MAX_FIELDS];
+        sample[..values.len()].copy_from_slice(values);
+        sample
+    }
+
+    #[test]
+    fn round_trip() {
+        let schema = [SampleField::Unsigned(4), SampleField::Signed(4)];
+        let mut packer = BatchPacker::new(&schema).unwrap();
+        packer.push(&[3, -2]).unwrap();
+        packer.push(&[15, 7]).unwrap();
+        assert_eq!(packer.len(), 2);
+        assert_eq!(packer.payload(), [2, 0x3E, 0xF7]);
+        assert_eq!(unpack(&schema, packer.payload()), [sample(&[3, -2]), sample(&[15, 7])]);
+    }
+
+    #[test]
+    fn delta_encoding() {
+        let schema = [SampleField::Delta {
+            bits: 8,
+            delta_bits: 3,
+        }];
+        let mut packer = BatchPacker::new(&schema).unwrap();
+        for value in [100, 102, 99] {
+            packer.push(&[value]).unwrap();
+        }
+        assert_eq!(packer.payload(), [3, 0x64, 0x54]);
+        // A difference of 4 does not fit 3 bits
+        assert_eq!(packer.push(&[103]), Err(PackError::OutOfRange));
+        assert_eq!(packer.payload(), [3, 0x64, 0x54]);
+        let values: Vec<i32> = unpack(&schema, packer.payload()).iter().map(|s| s[0]).collect();
+        assert_eq!(values, [100, 102, 99]);
+
+        // A new batch starts with a full value again
+        packer.clear();
+        packer.push(&[103]).unwrap();
+        assert_eq!(packer.payload(), [1, 103]);
+    }
+
+    #[test]
+    fn sign_extension() {
+        let schema = [SampleField::Signed(4), SampleField::Signed(32), SampleField::Unsigned(32)];
+        let mut packer = BatchPacker::new(&schema).unwrap();
+        packer.push(&[-8, i32::MIN, i32::MAX]).unwrap();
+        packer.push(&[7, i32::MAX, 0]).unwrap();
+        assert_eq!(packer.push(&[8, 0, 0]), Err(PackError::OutOfRange));
+        assert_eq!(packer.push(&[-9, 0, 0]), Err(PackError::OutOfRange));
+        assert_eq!(packer.push(&[0, 0, -1]), Err(PackError::OutOfRange));
+        assert_eq!(
+            unpack(&schema, packer.payload()),
+            [sample(&[-8, i32::MIN, i32::MAX]), sample(&[7, i32::MAX, 0])]
+        );
+    }
+
+    #[test]
+    fn batch_full() {
+        let schema = [SampleField::Unsigned(8)];
+        let mut packer = BatchPacker::new(&schema).unwrap();
+        for value in 0..31 {
+            packer.push(&[value]).unwrap();
+        }
+        assert_eq!(packer.push(&[31]), Err(PackError::Full));
+        assert_eq!(packer.len(), 31);
+        assert_eq!(packer.payload().len(), 32);
+        let values: Vec<i32> = unpack(&schema, packer.payload()).iter().map(|s| s[0]).collect();
+        assert_eq!(values, (0..31).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn truncated_payload() {
+        let schema = [SampleField::Unsigned(8)];
+        assert_eq!(unpack(&schema, &[3, 1, 2]), [sample(&[1]), sample(&[2])]);
+        assert!(BatchUnpacker::new(&schema, &[]).is_err());
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        assert!(BatchPacker::new(&[SampleField::Unsigned(0)]).is_err());
+        assert!(BatchPacker::new(&[SampleField::Signed(33)]).is_err());
+        assert!(BatchPacker::new(&[SampleField::Unsigned(1); MAX_FIELDS + 1]).is_err());
+        let schema = [SampleField::Unsigned(8)];
+        let mut packer = BatchPacker::new(&schema).unwrap();
+        assert_eq!(packer.push(&[1, 2]), Err(PackError::Schema));
+        assert!(packer.is_empty());
+    }
+}
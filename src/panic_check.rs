@@ -0,0 +1,162 @@
+//! Run-time checks for the `panic-free` feature
+//!
+//! The lints keep panicking constructs out of the driver at compile
+//! time. These tests feed it what a broken board or a hostile peer
+//! would, and only check that every call returns.
+
+use crate::blocking::block_on;
+use crate::registers::{Dynpd, EnAa, EnRxaddr};
+use crate::sim::{Air, SimRadio};
+use crate::{Address, Configuration, RawConfig, SuspendState, PIPES_COUNT};
+use crate::{Channel, Payload, RadioConfig};
+
+/// Inputs of every length up to 40 bytes, from a fixed pseudo-random
+/// sequence, plus all zeros and all ones
+fn garbage() -> Vec<Vec<u8>> {
+    let mut state = 0x2545_F491_u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    };
+    let mut inputs = Vec::new();
+    for len in 0..=40 {
+        inputs.push(vec![0; len]);
+        inputs.push(vec![0xFF; len]);
+        for _ in 0..8 {
+            inputs.push((0..len).map(|_| next()).collect());
+        }
+    }
+    inputs
+}
+
+#[test]
+fn garbage_miso_does_not_panic() {
+    block_on(async {
+        for level in [0x00, 0xFF, 0xA5] {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let chip = spi.chip();
+            let mut standby = SimRadio::new(ce, spi).await.unwrap();
+            standby.configure(&RadioConfig::default()).await.unwrap();
+
+            chip.set_stuck_miso(Some(level));
+            let _ = standby.get_radio_config().await;
+            let _ = standby.get_frequency().await;
+            let _ = standby.get_address_width().await;
+            let _ = standby.configure(&RadioConfig::default()).await;
+            chip.set_stuck_miso(None);
+
+            let mut rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+            chip.set_stuck_miso(Some(level));
+            let _ = rx.can_read().await;
+            let _ = rx.read_with_pipe().await;
+            let _ = rx.queue_ack_payload(1, b"ack").await;
+            let _ = rx.flush_ack_payloads(Some(1)).await;
+            chip.set_stuck_miso(None);
+
+            let mut standby = rx.standby();
+            standby.configure(&RadioConfig::default()).await.unwrap();
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            chip.set_stuck_miso(Some(level));
+            let _ = tx.send(b"x").await;
+            let _ = tx.poll_send().await;
+            let _ = tx.read_ack_payload().await;
+            let _ = tx.observe().await;
+        }
+    });
+}
+
+#[test]
+fn out_of_range_arguments_are_errors() {
+    block_on(async {
+        let air = Air::new();
+        let (ce, spi) = air.radio();
+        let mut standby = SimRadio::new(ce, spi).await.unwrap();
+        assert!(standby.set_rx_addr(PIPES_COUNT, b"abc").await.is_err());
+        assert!(standby.set_rx_addr(0, &[0; 9]).await.is_err());
+        assert!(standby.set_address_width(9).await.is_err());
+        assert!(standby.set_address_width(0).await.is_err());
+        let lengths = [Some(33); PIPES_COUNT];
+        assert!(standby.set_pipes_rx_lengths(&lengths).await.is_err());
+        assert!(RawConfig::new(Channel::default(), &[0; 9], 32).is_err());
+        assert!(Address::new(&[0; 9]).is_err());
+        assert_eq!(Payload::new(&[0; 40]).len(), 32);
+
+        for pipe_no in [PIPES_COUNT, 8, usize::MAX] {
+            let mut en_aa = EnAa(0xFF);
+            assert!(!en_aa.enaa_p(pipe_no));
+            en_aa.set_enaa_p(pipe_no, false);
+            assert_eq!(en_aa.0, 0xFF);
+            let mut en_rxaddr = EnRxaddr(0);
+            en_rxaddr.set_erx_p(pipe_no, true);
+            assert!(!en_rxaddr.erx_p(pipe_no));
+            assert_eq!(en_rxaddr.0, 0);
+            let mut dynpd = Dynpd(0);
+            dynpd.set_dpl_p(pipe_no, true);
+            assert!(!dynpd.dpl_p(pipe_no));
+            assert_eq!(dynpd.0, 0);
+        }
+
+        let mut rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+        assert!(rx.queue_ack_payload(6, b"ack").await.is_err());
+        assert!(rx.queue_ack_payload(255, b"ack").await.is_err());
+        assert!(rx.queue_ack_payload(1, &[0; 40]).await.is_err());
+        let mut tx = rx.standby().tx().await.map_err(|(_, e)| e).unwrap();
+        assert!(tx.send(&[0; 40]).await.is_err());
+    });
+}
+
+#[test]
+fn decoders_accept_any_bytes() {
+    for input in garbage() {
+        let _ = SuspendState::decode(&input);
+        #[cfg(feature = "config-bytes")]
+        let _ = RadioConfig::from_bytes(&input);
+        #[cfg(all(feature = "peers", feature = "config-bytes"))]
+        let _ = crate::Peer::from_bytes(&input);
+        #[cfg(feature = "diagnostics")]
+        let _ = crate::HealthFrame::decode(&input);
+        #[cfg(feature = "bridge")]
+        {
+            let mut bridge = crate::SerialBridge::new();
+            for &byte in &input {
+                let _ = bridge.push(byte);
+            }
+            let _ = bridge.push(0);
+        }
+        #[cfg(feature = "compress")]
+        {
+            let mut out = [0; 64];
+            let _ = crate::Compressor::decode(&input, &mut out);
+            let _ = crate::Compressor::decode(&input, &mut out[..3]);
+        }
+        #[cfg(feature = "pack")]
+        {
+            use crate::SampleField;
+            let schema = [SampleField::Unsigned(3), SampleField::Signed(7)];
+            if let Ok(unpacker) = crate::BatchUnpacker::new(&schema, &input) {
+                unpacker.for_each(drop);
+            }
+        }
+        #[cfg(feature = "unifying")]
+        {
+            let _ = crate::unifying::Frame::decode(&input);
+            let _ = crate::unifying::decode_promiscuous(&input);
+        }
+        #[cfg(feature = "stream")]
+        {
+            let mut reorder = crate::Reorder::<4>::new(0);
+            let seq = input.first().copied().unwrap_or(0);
+            let _ = reorder.push(seq, &input);
+            while reorder.pop().is_some() {}
+        }
+        #[cfg(feature = "ota")]
+        {
+            let image = [0xAB; 40];
+            let server = crate::OtaServer::new(1, 2, &image).unwrap();
+            let _ = server.answer(&input);
+        }
+    }
+}
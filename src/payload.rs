@@ -1,5 +1,50 @@
+use crate::command::{MAX_COMMAND_LEN, W_TX_PAYLOAD};
+use crate::error::InvalidArgument;
 use core::ops::Deref;
 
+/// Byte order of multi-byte fields, see
+/// [`Payload::read_u16()`](struct.Payload.html#method.read_u16)
+///
+/// C structs are laid out in the byte order of the MCU that packs them,
+/// which is little endian on AVR, ARM Cortex-M and ESP32.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ByteOrder {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first, network byte order
+    Big,
+}
+
+impl ByteOrder {
+    fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u16_from(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32_from(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
 /// Represents a received packet. Stores 32 bytes and the actual length.
 ///
 /// Use [`as_ref()`](#method.as_ref) or [`Deref`](#impl-Deref) to
@@ -13,26 +58,271 @@ impl Payload {
     /// Copy a slice
     pub fn new(source: &[u8]) -> Self {
         let mut data = [0; 32];
-        let len = source.len().min(data.len());
-        data[0..len].copy_from_slice(&source[0..len]);
+        let len = copy_bytes(&mut data, source);
         Payload { data, len }
     }
 
+    /// Zeroed payload of `len` bytes, to be filled with `data_mut()`
+    pub(crate) fn zeroed(len: usize) -> Self {
+        Payload {
+            data: [0; 32],
+            len: len.min(32),
+        }
+    }
+
+    /// Mutable slice of the content
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        self.data.get_mut(..self.len).unwrap_or_default()
+    }
+
     /// Read length
     pub fn len(&self) -> usize {
         self.len
     }
+
+    /// Is the payload empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read a `u16` at byte `offset`, or `None` if the payload is too
+    /// short, e.g. a `uint16_t` field of a C struct
+    pub fn read_u16(&self, offset: usize, order: ByteOrder) -> Option<u16> {
+        self.field(offset).map(|bytes| order.u16_from(bytes))
+    }
+
+    /// Read a `u32` at byte `offset`, or `None` if the payload is too
+    /// short
+    pub fn read_u32(&self, offset: usize, order: ByteOrder) -> Option<u32> {
+        self.field(offset).map(|bytes| order.u32_from(bytes))
+    }
+
+    /// Read an IEEE 754 `f32` at byte `offset`, or `None` if the payload
+    /// is too short, e.g. a `float` field of a C struct
+    pub fn read_f32(&self, offset: usize, order: ByteOrder) -> Option<f32> {
+        self.read_u32(offset, order).map(f32::from_bits)
+    }
+
+    /// Overwrite the `u16` at byte `offset`, within the length
+    pub fn write_u16(
+        &mut self,
+        offset: usize,
+        value: u16,
+        order: ByteOrder,
+    ) -> Result<(), InvalidArgument> {
+        self.set_field(offset, &order.u16_bytes(value))
+    }
+
+    /// Overwrite the `u32` at byte `offset`, within the length
+    pub fn write_u32(
+        &mut self,
+        offset: usize,
+        value: u32,
+        order: ByteOrder,
+    ) -> Result<(), InvalidArgument> {
+        self.set_field(offset, &order.u32_bytes(value))
+    }
+
+    /// Overwrite the `f32` at byte `offset`, within the length
+    pub fn write_f32(
+        &mut self,
+        offset: usize,
+        value: f32,
+        order: ByteOrder,
+    ) -> Result<(), InvalidArgument> {
+        self.write_u32(offset, value.to_bits(), order)
+    }
+
+    /// `N` bytes at `offset`
+    fn field<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        let end = offset.checked_add(N)?;
+        self.as_ref().get(offset..end)?.try_into().ok()
+    }
+
+    /// Copy `bytes` to `offset`
+    fn set_field(&mut self, offset: usize, bytes: &[u8]) -> Result<(), InvalidArgument> {
+        offset
+            .checked_add(bytes.len())
+            .and_then(|end| self.data_mut().get_mut(offset..end))
+            .ok_or(InvalidArgument::PayloadLength)?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Compose a payload field by field, in a fixed byte order
+///
+/// Mirrors a packed C struct on the peer, e.g. with
+/// `__attribute__((packed))`. Add [`pad()`](#method.pad) where the
+/// struct is not packed and the compiler aligns fields. Fails with
+/// `InvalidArgument::PayloadLength` once a field does not fit in 32
+/// bytes.
+#[derive(Debug)]
+pub struct PayloadBuilder {
+    data: [u8; 32],
+    len: usize,
+    order: ByteOrder,
+}
+
+impl PayloadBuilder {
+    /// Empty payload, writing fields in `order`
+    pub fn new(order: ByteOrder) -> Self {
+        PayloadBuilder {
+            data: [0; 32],
+            len: 0,
+            order,
+        }
+    }
+
+    /// Append raw bytes
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<&mut Self, InvalidArgument> {
+        let end = self.grow(bytes.len())?;
+        if let Some(dst) = self.data.get_mut(self.len..end) {
+            copy_bytes(dst, bytes);
+        }
+        self.len = end;
+        Ok(self)
+    }
+
+    /// Append `len` zero bytes, e.g. for alignment padding
+    pub fn pad(&mut self, len: usize) -> Result<&mut Self, InvalidArgument> {
+        self.len = self.grow(len)?;
+        Ok(self)
+    }
+
+    /// Length after appending `len` bytes, if they fit
+    fn grow(&self, len: usize) -> Result<usize, InvalidArgument> {
+        self.len
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(InvalidArgument::PayloadLength)
+    }
+
+    /// Append a `u8`
+    pub fn u8(&mut self, value: u8) -> Result<&mut Self, InvalidArgument> {
+        self.bytes(&[value])
+    }
+
+    /// Append a `u16`
+    pub fn u16(&mut self, value: u16) -> Result<&mut Self, InvalidArgument> {
+        let bytes = self.order.u16_bytes(value);
+        self.bytes(&bytes)
+    }
+
+    /// Append a `u32`
+    pub fn u32(&mut self, value: u32) -> Result<&mut Self, InvalidArgument> {
+        let bytes = self.order.u32_bytes(value);
+        self.bytes(&bytes)
+    }
+
+    /// Append an IEEE 754 `f32`
+    pub fn f32(&mut self, value: f32) -> Result<&mut Self, InvalidArgument> {
+        self.u32(value.to_bits())
+    }
+
+    /// Bytes so far, e.g. for [`TxMode::send()`](struct.TxMode.html#method.send)
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.get(..self.len).unwrap_or_default()
+    }
+
+    /// Copy into a `Payload`
+    pub fn build(&self) -> Payload {
+        Payload::new(self.as_bytes())
+    }
 }
 
 impl AsRef<[u8]> for Payload {
     fn as_ref(&self) -> &[u8] {
-        &self.data[0..self.len]
+        self.data.get(..self.len).unwrap_or_default()
     }
 }
 
 impl Deref for Payload {
     type Target = [u8];
     fn deref(&self) -> &[u8] {
-        &self.as_ref()
+        self.as_ref()
+    }
+}
+
+/// Caller-provided buffer for [`TxMode::send_buffer()`](struct.TxMode.html#method.send_buffer)
+///
+/// Holds the command byte followed by up to 32 bytes of payload, so
+/// the whole command is written to the SPI bus straight from this
+/// memory. Word aligned so that it can live in a `static` that a
+/// DMA-driven `SpiDevice` reads from without copying. The payload is
+/// left intact after sending and can be sent again.
+#[repr(C, align(4))]
+pub struct TxBuffer {
+    buf: [u8; MAX_COMMAND_LEN],
+    len: usize,
+}
+
+impl TxBuffer {
+    /// Empty buffer, usable in a `static`
+    pub const fn new() -> Self {
+        let mut buf = [0; MAX_COMMAND_LEN];
+        buf[0] = W_TX_PAYLOAD;
+        TxBuffer { buf, len: 0 }
+    }
+
+    /// Copy `data` into the buffer
+    ///
+    /// `data` can be at most 32 bytes long.
+    pub fn fill(&mut self, data: &[u8]) -> Result<(), InvalidArgument> {
+        self.set_len(data.len())?;
+        copy_bytes(self.payload_mut(), data);
+        Ok(())
+    }
+
+    /// Set the payload length, at most 32 bytes
+    ///
+    /// Use with [`payload_mut()`](#method.payload_mut) to assemble a
+    /// packet in place.
+    pub fn set_len(&mut self, len: usize) -> Result<(), InvalidArgument> {
+        if len >= MAX_COMMAND_LEN {
+            return Err(InvalidArgument::PayloadLength);
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    /// Read length
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Is the payload empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// All 32 payload bytes, regardless of the length
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        self.buf.get_mut(1..).unwrap_or_default()
+    }
+
+    /// The complete command
+    pub(crate) fn command(&self) -> &[u8] {
+        self.buf.get(..=self.len).unwrap_or_default()
+    }
+}
+
+impl Default for TxBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsRef<[u8]> for TxBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.buf.get(1..=self.len).unwrap_or_default()
+    }
+}
+
+/// Copy as much of `src` as fits into `dst`, and return how many bytes
+pub(crate) fn copy_bytes(dst: &mut [u8], src: &[u8]) -> usize {
+    for (dst, src) in dst.iter_mut().zip(src) {
+        *dst = *src;
     }
+    dst.len().min(src.len())
 }
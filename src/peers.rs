@@ -0,0 +1,356 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::config::{Configuration, DataRate};
+#[cfg(feature = "config-bytes")]
+use crate::config::{crc16, DecodeError};
+use crate::device::Device;
+#[cfg(feature = "config-bytes")]
+use crate::payload::copy_bytes;
+use crate::registers::RfSetup;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+
+/// A peer known to a [`PeerRegistry`](struct.PeerRegistry.html)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Peer {
+    /// Logical ID chosen by the application
+    pub id: u16,
+    /// Address the peer receives on
+    pub address: Address,
+    /// Channel the peer listens on
+    pub channel: Channel,
+    /// Air data rate of the peer
+    pub data_rate: DataRate,
+    /// Key for application-level encryption or authentication, which
+    /// the driver does not use itself
+    pub key: Option<[u8; 16]>,
+}
+
+#[cfg(feature = "config-bytes")]
+impl Peer {
+    /// Size of the encoding
+    pub const ENCODED_LEN: usize = 29;
+
+    /// Encode into a fixed-size layout for EEPROM storage, like
+    /// [`RadioConfig::to_bytes()`](struct.RadioConfig.html#method.to_bytes)
+    ///
+    /// | Offset | Content                                           |
+    /// |--------|---------------------------------------------------|
+    /// | 0..2   | ID, little endian                                 |
+    /// | 2      | Address width                                     |
+    /// | 3..8   | Address                                           |
+    /// | 8      | Channel                                           |
+    /// | 9      | Data rate: `0`: 250 Kbps, `1`: 1 Mbps, `2`: 2 Mbps |
+    /// | 10     | `1` if there is a key                             |
+    /// | 11..27 | Key                                               |
+    /// | 27..29 | CRC-16/CCITT-FALSE over bytes 0..27, big endian   |
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut buf = [0; Self::ENCODED_LEN];
+        buf[0..2].copy_from_slice(&self.id.to_le_bytes());
+        buf[2] = self.address.len() as u8;
+        copy_bytes(&mut buf[3..8], &self.address);
+        buf[8] = self.channel.into();
+        buf[9] = match self.data_rate {
+            DataRate::R250Kbps => 0,
+            DataRate::R1Mbps => 1,
+            DataRate::R2Mbps => 2,
+        };
+        if let Some(key) = &self.key {
+            buf[10] = 1;
+            buf[11..27].copy_from_slice(key);
+        }
+        let crc = crc16(&buf[0..27]);
+        buf[27..29].copy_from_slice(&crc.to_be_bytes());
+        buf
+    }
+
+    /// Decode from the layout written by [`to_bytes()`](#method.to_bytes)
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, DecodeError> {
+        let Ok(buf) = <&[u8; Self::ENCODED_LEN]>::try_from(buf) else {
+            return Err(DecodeError::Length);
+        };
+        if buf[27..29] != crc16(&buf[0..27]).to_be_bytes() {
+            return Err(DecodeError::Checksum);
+        }
+        let width = usize::from(buf[2]);
+        let address = buf
+            .get(3..width.saturating_add(3))
+            .and_then(|bytes| Address::new(bytes).ok())
+            .ok_or(DecodeError::InvalidField)?;
+        let channel = Channel::new(buf[8]).map_err(|_| DecodeError::InvalidField)?;
+        let data_rate = match buf[9] {
+            0 => DataRate::R250Kbps,
+            1 => DataRate::R1Mbps,
+            2 => DataRate::R2Mbps,
+            _ => return Err(DecodeError::InvalidField),
+        };
+        let key = match buf[10] {
+            0 => None,
+            1 => {
+                let mut key = [0; 16];
+                key.copy_from_slice(&buf[11..27]);
+                Some(key)
+            }
+            _ => return Err(DecodeError::InvalidField),
+        };
+        Ok(Peer {
+            id: u16::from_le_bytes([buf[0], buf[1]]),
+            address,
+            channel,
+            data_rate,
+            key,
+        })
+    }
+}
+
+/// Table of up to `N` peers by logical ID, for multi-peer applications
+///
+/// [`select()`](#method.select) points the radio at a peer, and
+/// [`send_to()`](#method.send_to) sends to it. On the receiving side,
+/// [`find_by_address()`](#method.find_by_address) tells which peer a
+/// pipe address belongs to. Store the peers one by one with
+/// [`Peer::to_bytes()`](struct.Peer.html#method.to_bytes) to keep them
+/// across resets.
+#[derive(Debug, Clone)]
+pub struct PeerRegistry<const N: usize> {
+    peers: [Option<Peer>; N],
+}
+
+impl<const N: usize> Default for PeerRegistry<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PeerRegistry<N> {
+    /// Empty registry
+    pub fn new() -> Self {
+        PeerRegistry {
+            peers: [const { None }; N],
+        }
+    }
+
+    /// Add `peer`, returning the one it replaces with the same ID
+    ///
+    /// Returns `peer` as the error if the registry is full.
+    pub fn insert(&mut self, peer: Peer) -> Result<Option<Peer>, Peer> {
+        if let Some(slot) = self.peers.iter_mut().flatten().find(|known| known.id == peer.id) {
+            return Ok(Some(core::mem::replace(slot, peer)));
+        }
+        match self.peers.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(peer);
+                Ok(None)
+            }
+            None => Err(peer),
+        }
+    }
+
+    /// Remove and return the peer with `id`
+    pub fn remove(&mut self, id: u16) -> Option<Peer> {
+        self.peers
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(|peer| peer.id == id))
+            .and_then(Option::take)
+    }
+
+    /// Peer with `id`
+    pub fn get(&self, id: u16) -> Option<&Peer> {
+        self.iter().find(|peer| peer.id == id)
+    }
+
+    /// Peer with `address`, e.g. the address of the pipe a packet was
+    /// received on
+    pub fn find_by_address(&self, address: &Address) -> Option<&Peer> {
+        self.iter().find(|peer| peer.address == *address)
+    }
+
+    /// All peers, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.iter().flatten()
+    }
+
+    /// Number of peers
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Is the registry empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Set channel, data rate, TX and pipe 0 address for the peer with
+    /// `id`, keeping the output power. Returns `false` if it is unknown.
+    ///
+    /// Waits for the TX FIFO to drain first, as the settings must not
+    /// change while sending.
+    ///
+    /// The address width is the peer's too, and `SETUP_AW` applies to
+    /// all pipes: after selecting a peer of another width, pipes 1 to 5
+    /// only match addresses of that width. Keep all peers at the width
+    /// of the addresses the radio receives on.
+    pub async fn select<D: Device>(&self, tx: &mut TxMode<D>, id: u16) -> Result<bool, D::Error> {
+        let Some(peer) = self.get(id) else {
+            return Ok(false);
+        };
+        tx.wait_empty().await?;
+        let (_, rf_setup) = tx.device().read_register::<RfSetup>().await?;
+        tx.set_rf(&peer.data_rate, rf_setup.rf_pwr()).await?;
+        tx.set_frequency(peer.channel).await?;
+        tx.set_address_width(peer.address.len() as u8).await?;
+        tx.set_tx_addr(&peer.address).await?;
+        tx.set_rx_addr(0, &peer.address).await?;
+        Ok(true)
+    }
+
+    /// [`select()`](#method.select) the peer with `id` and send `packet`
+    /// to it, returning whether it was acknowledged, and `false` for
+    /// unknown peers
+    ///
+    /// Polls the transmission with `delay`.
+    pub async fn send_to<D: Device, DL: DelayNs>(
+        &self,
+        tx: &mut TxMode<D>,
+        id: u16,
+        packet: &[u8],
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        if !self.select(tx, id).await? {
+            return Ok(false);
+        }
+        tx.send(packet).await?;
+        tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::rx::RxMode;
+    use crate::sim::{self, Air, SimRadio, SimTimer};
+    use crate::RadioConfig;
+
+    const SETUP_AW: u8 = 0x03;
+    const RX_ADDR_P0: u8 = 0x0A;
+    const TX_ADDR: u8 = 0x10;
+
+    fn peer(id: u16, address: &[u8], channel: u8, key: Option<[u8; 16]>) -> Peer {
+        Peer {
+            id,
+            address: Address::new(address).unwrap(),
+            channel: Channel::new(channel).unwrap(),
+            data_rate: DataRate::R2Mbps,
+            key,
+        }
+    }
+
+    #[cfg(feature = "config-bytes")]
+    #[test]
+    fn bytes_round_trip() {
+        for peer in [
+            peer(0x1234, &[1, 2, 3, 4, 5], 40, Some([0xA5; 16])),
+            peer(7, &[9, 8, 7], 125, None),
+        ] {
+            assert_eq!(Peer::from_bytes(&peer.to_bytes()), Ok(peer));
+        }
+    }
+
+    #[cfg(feature = "config-bytes")]
+    #[test]
+    fn corrupt_bytes_are_rejected() {
+        let bytes = peer(1, &[1, 2, 3, 4, 5], 40, None).to_bytes();
+        for i in 0..Peer::ENCODED_LEN {
+            let mut corrupt = bytes;
+            corrupt[i] ^= 0x10;
+            assert_eq!(Peer::from_bytes(&corrupt), Err(DecodeError::Checksum), "byte {}", i);
+        }
+        assert_eq!(Peer::from_bytes(&bytes[1..]), Err(DecodeError::Length));
+
+        // A valid checksum over invalid fields
+        for (offset, value) in [(2, 6), (2, 1), (8, 126), (9, 3), (10, 2)] {
+            let mut invalid = bytes;
+            invalid[offset] = value;
+            let crc = crc16(&invalid[0..27]);
+            invalid[27..29].copy_from_slice(&crc.to_be_bytes());
+            assert_eq!(Peer::from_bytes(&invalid), Err(DecodeError::InvalidField));
+        }
+    }
+
+    #[test]
+    fn insert_replace_remove() {
+        let mut peers = PeerRegistry::<2>::new();
+        assert!(peers.is_empty());
+        assert_eq!(peers.insert(peer(1, &[1; 5], 40, None)), Ok(None));
+        assert_eq!(peers.insert(peer(2, &[2; 5], 40, None)), Ok(None));
+        let old = peers.insert(peer(1, &[3; 5], 50, None)).unwrap().unwrap();
+        assert_eq!(old.address, Address::from_array([1; 5]));
+        assert_eq!(peers.insert(peer(4, &[4; 5], 40, None)).unwrap_err().id, 4);
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers.find_by_address(&Address::from_array([3; 5])).map(|p| p.id), Some(1));
+
+        assert_eq!(peers.remove(1).map(|p| p.id), Some(1));
+        assert!(peers.remove(1).is_none());
+        assert!(peers.get(1).is_none());
+        assert_eq!(peers.len(), 1);
+    }
+
+    /// A chip listening as `peer` on pipe 0
+    async fn receiver(air: &Air, peer: &Peer) -> RxMode<SimRadio> {
+        let config = RadioConfig {
+            channel: peer.channel,
+            data_rate: peer.data_rate,
+            tx_addr: peer.address,
+            rx_addrs: [Some(peer.address), None, None, None, None, None],
+            ..RadioConfig::default()
+        };
+        sim::standby(air, &config).await.rx().await.map_err(|(_, e)| e).unwrap()
+    }
+
+    #[test]
+    fn send_to_reaches_each_peer() {
+        block_on(async {
+            let air = Air::new();
+            let mut peers = PeerRegistry::<3>::new();
+            peers.insert(peer(1, &[0xA1; 5], 40, None)).unwrap();
+            peers.insert(peer(2, &[0xA2; 5], 70, None)).unwrap();
+            let mut rx1 = receiver(&air, peers.get(1).unwrap()).await;
+            let mut rx2 = receiver(&air, peers.get(2).unwrap()).await;
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut delay = SimTimer::new();
+
+            assert!(peers.send_to(&mut tx, 1, b"one", &mut delay).await.unwrap());
+            assert!(peers.send_to(&mut tx, 2, b"two", &mut delay).await.unwrap());
+            assert!(!peers.send_to(&mut tx, 3, b"three", &mut delay).await.unwrap());
+            assert_eq!(air.frames().len(), 2);
+            assert_eq!(&rx1.read().await.unwrap()[..], b"one");
+            assert_eq!(&rx2.read().await.unwrap()[..], b"two");
+            assert!(rx1.is_empty().await.unwrap() && rx2.is_empty().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn select_sets_the_width_for_all_pipes() {
+        block_on(async {
+            let air = Air::new();
+            let mut peers = PeerRegistry::<1>::new();
+            peers.insert(peer(1, &[0xA1; 3], 40, None)).unwrap();
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let chip = tx.device().spi.chip();
+            assert_eq!(chip.register(SETUP_AW), 0b11);
+
+            assert!(peers.select(&mut tx, 1).await.unwrap());
+            assert_eq!(chip.register(SETUP_AW), 0b01);
+            assert_eq!(&chip.address(TX_ADDR)[..3], &[0xA1; 3]);
+            assert_eq!(&chip.address(RX_ADDR_P0)[..3], &[0xA1; 3]);
+            assert_eq!(tx.get_frequency().await.unwrap(), Channel::new(40).unwrap());
+        });
+    }
+}
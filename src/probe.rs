@@ -0,0 +1,130 @@
+use crate::device::Device;
+use crate::error::Error;
+use crate::events::NoEvents;
+use crate::registers::SetupAw;
+use crate::{NRF24L01, PROBE_BACKOFF_MS};
+use core::fmt::Debug;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+
+/// Longest wait between two probes
+const MAX_BACKOFF_MS: u32 = 1_000;
+
+/// What a single probe found, see
+/// [`NRF24L01::probe()`](struct.NRF24L01.html#method.probe)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Diagnosis {
+    /// The chip answers with valid registers
+    Ok,
+    /// The SPI transaction failed, e.g. a Linux spidev timeout
+    NoResponse,
+    /// `STATUS` and `SETUP_AW` read as `0x00`: MISO is stuck low,
+    /// typically because the module has no power
+    AllZero,
+    /// `STATUS` and `SETUP_AW` read as `0xFF`: MISO is floating or
+    /// pulled up, typically a missing chip, a loose MISO wire or `CSN`
+    /// not reaching the module
+    AllOnes,
+    /// `SETUP_AW` holds this value, which the chip never does: SCK or
+    /// MOSI are miswired, or the SPI mode is not 0
+    InvalidSetupAw(u8),
+}
+
+/// Outcome of [`NRF24L01::probe()`](struct.NRF24L01.html#method.probe)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ProbeReport {
+    /// The first `Ok`, or the last diagnosis if none was
+    pub diagnosis: Diagnosis,
+    /// Probes made. More than one until `Ok` points to an unstable
+    /// supply or long wires rather than wrong wiring.
+    pub attempts: u8,
+}
+
+impl Diagnosis {
+    /// Classify `STATUS` and `SETUP_AW` as read in one transaction
+    fn of(status: u8, setup_aw: u8) -> Self {
+        match (status, setup_aw) {
+            (0x00, 0x00) => Diagnosis::AllZero,
+            (0xFF, 0xFF) => Diagnosis::AllOnes,
+            // AW is 1 to 3, the other bits are reserved as 0
+            (_, 1..=3) => Diagnosis::Ok,
+            (_, value) => Diagnosis::InvalidSetupAw(value),
+        }
+    }
+}
+
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug>
+    NRF24L01<E, CE, SPI>
+{
+    /// Probe for the chip up to `attempts` times, for bring-up
+    ///
+    /// Tells apart wiring faults, power issues and absent chips by what
+    /// reads back. Pass `&mut ce` and `&mut spi` to keep them for
+    /// [`new()`](#method.new) afterwards. Writes nothing to the chip.
+    ///
+    /// Waits on `delay` between attempts, 5ms at first and doubling up
+    /// to 1s, so that a supply still ramping up gets the time to settle.
+    pub async fn probe<DL: DelayNs>(
+        ce: CE,
+        spi: SPI,
+        attempts: u8,
+        delay: &mut DL,
+    ) -> Result<ProbeReport, Error<SPIE>> {
+        let mut device = Self::assemble(ce, spi, NoEvents)?;
+        let mut report = ProbeReport {
+            diagnosis: Diagnosis::NoResponse,
+            attempts: 0,
+        };
+        let mut backoff_ms = PROBE_BACKOFF_MS;
+        while report.attempts < attempts.max(1) {
+            if report.attempts > 0 {
+                delay.delay_ms(backoff_ms).await;
+                backoff_ms = backoff_ms.saturating_mul(2).min(MAX_BACKOFF_MS);
+            }
+            report.attempts = report.attempts.saturating_add(1);
+            report.diagnosis = match device.read_register::<SetupAw>().await {
+                Ok((status, setup_aw)) => Diagnosis::of(status.0, setup_aw.0),
+                Err(Error::SpiError(..)) => Diagnosis::NoResponse,
+                Err(e) => return Err(e),
+            };
+            if report.diagnosis == Diagnosis::Ok {
+                break;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{Air, SimRadio, SimTimer};
+    use crate::time::Clock;
+
+    #[test]
+    fn probe_backs_off_between_attempts() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            spi.chip().set_stuck_miso(Some(0));
+            let mut timer = SimTimer::new();
+            let report = SimRadio::probe(ce, spi, 4, &mut timer).await.unwrap();
+            assert_eq!(report, ProbeReport { diagnosis: Diagnosis::AllZero, attempts: 4 });
+            assert_eq!(timer.now_micros(), (5 + 10 + 20) * 1_000);
+        });
+    }
+
+    #[test]
+    fn probe_stops_at_first_answer() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let mut timer = SimTimer::new();
+            let report = SimRadio::probe(ce, spi, 4, &mut timer).await.unwrap();
+            assert_eq!(report, ProbeReport { diagnosis: Diagnosis::Ok, attempts: 1 });
+            assert_eq!(timer.now_micros(), 0);
+        });
+    }
+}
@@ -0,0 +1,85 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::command::R_RX_PAYLOAD;
+use crate::config::{Configuration, RadioConfig};
+use crate::device::Device;
+use crate::events::RadioEvents;
+use crate::registers::Status;
+use crate::rx::{RxMode, RX_P_NO_EMPTY};
+use crate::tx::TxMode;
+use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES, PIPES_COUNT};
+
+/// Link settings fixed at compile time, for fixed-function devices like
+/// simple remotes
+///
+/// Every packet is `PAYLOAD` bytes on a static payload length, addresses
+/// are `ADDR_WIDTH` bytes, and `ACK` enables auto-ack with retransmits.
+/// Packets are `[u8; PAYLOAD]` arrays, so the send and receive paths
+/// need neither length checks nor padding, and reading costs a single
+/// SPI transaction. Out of range parameters fail to compile.
+///
+/// Both ends apply [`config()`](#method.config) and use the same
+/// profile, e.g. `type Remote = LinkProfile<4, 3, true>;`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LinkProfile<const PAYLOAD: usize, const ADDR_WIDTH: usize, const ACK: bool>;
+
+impl<const PAYLOAD: usize, const ADDR_WIDTH: usize, const ACK: bool>
+    LinkProfile<PAYLOAD, ADDR_WIDTH, ACK>
+{
+    /// Evaluated by every method, so that invalid profiles fail to build
+    const VALID: () = {
+        assert!(PAYLOAD >= 1 && PAYLOAD <= 32, "PAYLOAD must be 1 to 32 bytes");
+        assert!(
+            ADDR_WIDTH >= MIN_ADDR_BYTES && ADDR_WIDTH <= MAX_ADDR_BYTES,
+            "ADDR_WIDTH must be 3 to 5 bytes"
+        );
+    };
+
+    /// Configuration for the profile on `channel`, sending to and
+    /// receiving on `address` with pipe 0
+    pub fn config(channel: Channel, address: [u8; ADDR_WIDTH]) -> RadioConfig {
+        let () = Self::VALID;
+        // VALID has checked the width
+        let address = Address::new(&address).unwrap_or(Address::from_array([0; MAX_ADDR_BYTES]));
+        let mut rx_addrs = [None; PIPES_COUNT];
+        rx_addrs[0] = Some(address);
+        RadioConfig {
+            channel,
+            auto_retransmit_count: if ACK { 3 } else { 0 },
+            auto_ack: [ACK; PIPES_COUNT],
+            pipes_rx_lengths: [Some(PAYLOAD as u8); PIPES_COUNT],
+            tx_addr: address,
+            rx_addrs,
+            ..RadioConfig::default()
+        }
+    }
+
+    /// Send `packet` asynchronously, like
+    /// [`TxMode::send()`](struct.TxMode.html#method.send)
+    pub async fn send<D: Device>(
+        tx: &mut TxMode<D>,
+        packet: &[u8; PAYLOAD],
+    ) -> Result<Status, D::Error> {
+        let () = Self::VALID;
+        tx.send_exact(packet).await
+    }
+
+    /// Read the next packet and its pipe number, or `None` if the RX
+    /// FIFO is empty
+    ///
+    /// The `STATUS` clocked out with `R_RX_PAYLOAD` tells whether there
+    /// was a packet, so this does not ask for its width first.
+    pub async fn read<D: Device>(
+        rx: &mut RxMode<D>,
+    ) -> Result<Option<(u8, [u8; PAYLOAD])>, D::Error> {
+        let () = Self::VALID;
+        let mut packet = [0; PAYLOAD];
+        let status = rx.device().read_payload(R_RX_PAYLOAD, &mut packet).await?;
+        let pipe_no = status.rx_p_no();
+        if pipe_no == RX_P_NO_EMPTY {
+            return Ok(None);
+        }
+        rx.device().events().on_packet_received(pipe_no, &packet);
+        Ok(Some((pipe_no, packet)))
+    }
+}
@@ -0,0 +1,205 @@
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::rx::RxMode;
+use crate::strategy::Strategy;
+use crate::tx::TxMode;
+use embedded_hal_async::delay::DelayNs;
+
+/// First byte of a ping: tag, then the little-endian sequence number
+const PING_TAG: u8 = b'P';
+
+/// First byte of a reply: tag, the sequence number of the ping it
+/// answers, `RPD`, and the little-endian count of pings received
+const REPLY_TAG: u8 = b'R';
+
+/// Interval at which to check for the end of a transmission
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Results of a [`RangeInitiator`](struct.RangeInitiator.html) run, e.g.
+/// at one distance
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct RangeStats {
+    /// Pings sent
+    pub sent: u16,
+    /// Pings acknowledged
+    pub acked: u16,
+    /// Retransmits of all pings, from `OBSERVE_TX`
+    pub retransmits: u32,
+    /// Replies received from the responder
+    pub replies: u16,
+    /// Replies for which the responder's `RPD` saw more than -64 dBm
+    pub strong_signal: u16,
+    /// Pings the responder has received in total, as of the last reply
+    pub responder_received: u16,
+}
+
+impl RangeStats {
+    /// Percentage of pings that were acknowledged
+    pub fn success_percent(&self) -> u8 {
+        percent(u32::from(self.acked), u32::from(self.sent))
+    }
+
+    /// Percentage of replies with a strong signal at the responder
+    pub fn strong_signal_percent(&self) -> u8 {
+        percent(u32::from(self.strong_signal), u32::from(self.replies))
+    }
+
+    /// Average retransmits per ping, in hundredths
+    pub fn retransmits_per_ping_x100(&self) -> u32 {
+        self.retransmits
+            .saturating_mul(100)
+            .checked_div(u32::from(self.sent))
+            .unwrap_or(0)
+    }
+}
+
+fn percent(part: u32, total: u32) -> u8 {
+    part.saturating_mul(100).checked_div(total).unwrap_or(0) as u8
+}
+
+/// Sending end of a range test
+///
+/// Sends pings to a [`RangeResponder`](struct.RangeResponder.html) and
+/// records how many got through, how many retransmits they took, and the
+/// signal strength the responder reports back in ACK payloads. Move the
+/// modules apart, or swap antennas, and [`run()`](#method.run) again to
+/// compare.
+///
+/// Requires auto-ack, auto-retransmit and dynamic payload lengths on
+/// both ends. The reply to a ping arrives with the ACK of the next one,
+/// so a run yields one reply less than pings acknowledged.
+#[derive(Debug, Default, Clone)]
+pub struct RangeInitiator {
+    seq: u16,
+}
+
+impl RangeInitiator {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `pings` pings, `interval_us` apart
+    pub async fn run<D: Device, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        pings: u16,
+        interval_us: u32,
+        delay: &mut DL,
+    ) -> Result<RangeStats, D::Error> {
+        tx.set_ack_payloads(true).await?;
+        // Replies left over from a previous run
+        tx.flush_rx().await?;
+
+        let mut stats = RangeStats::default();
+        for _ in 0..pings {
+            let [lo, hi] = self.seq.to_le_bytes();
+            tx.send(&[PING_TAG, lo, hi]).await?;
+            stats.sent = stats.sent.saturating_add(1);
+            let acked = tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay).await?;
+            let retransmits = u32::from(tx.observe().await?.arc_cnt());
+            stats.retransmits = stats.retransmits.saturating_add(retransmits);
+
+            if acked {
+                stats.acked = stats.acked.saturating_add(1);
+                if let Some(reply) = tx.read_ack_payload().await? {
+                    let answers = self.seq.wrapping_sub(1).to_le_bytes();
+                    if let [REPLY_TAG, s0, s1, rpd, r0, r1] = *reply.as_ref() {
+                        if [s0, s1] == answers {
+                            stats.replies = stats.replies.saturating_add(1);
+                            if rpd != 0 {
+                                stats.strong_signal = stats.strong_signal.saturating_add(1);
+                            }
+                            stats.responder_received = u16::from_le_bytes([r0, r1]);
+                        }
+                    }
+                }
+            }
+            self.seq = self.seq.wrapping_add(1);
+            delay.delay_us(interval_us).await;
+        }
+        Ok(stats)
+    }
+}
+
+/// Receiving end of a range test
+///
+/// Answers every ping of a [`RangeInitiator`](struct.RangeInitiator.html)
+/// with its `RPD` and the number of pings received, sent back as an ACK
+/// payload.
+#[derive(Debug, Default, Clone)]
+pub struct RangeResponder {
+    received: u16,
+    ack_payloads: bool,
+}
+
+impl RangeResponder {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pings received so far
+    pub fn received(&self) -> u16 {
+        self.received
+    }
+
+    /// Read the next packet and answer it if it is a ping. Returns the
+    /// sequence number of the ping.
+    ///
+    /// Packets that are not pings are dropped.
+    pub async fn poll<D: Device>(&mut self, rx: &mut RxMode<D>) -> Result<Option<u16>, D::Error> {
+        if !self.ack_payloads {
+            rx.set_ack_payloads(true).await?;
+            self.ack_payloads = true;
+        }
+
+        let Some((pipe_no, ping)) = rx.read_with_pipe().await? else {
+            return Ok(None);
+        };
+        let [PING_TAG, s0, s1] = *ping.as_ref() else {
+            return Ok(None);
+        };
+        // RPD latches when a packet is received
+        let rpd = rx.has_carrier().await?;
+        self.received = self.received.wrapping_add(1);
+
+        let [lo, hi] = self.received.to_le_bytes();
+        let reply = [REPLY_TAG, s0, s1, u8::from(rpd), lo, hi];
+        // Drop a reply that was never picked up, and only that
+        rx.flush_ack_payloads(Some(pipe_no)).await?;
+        rx.queue_ack_payload(pipe_no, &reply).await?;
+        Ok(Some(u16::from_le_bytes([s0, s1])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+
+    #[test]
+    fn responder_keeps_ack_payloads_of_other_pipes() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let chip = rx.device().spi.chip();
+            let mut responder = RangeResponder::new();
+            assert_eq!(responder.poll(&mut rx).await.ok(), Some(None));
+            rx.queue_ack_payload(3, b"other").await.unwrap();
+
+            let mut timer = SimTimer::new();
+            for seq in 0..2u8 {
+                tx.send(&[PING_TAG, seq, 0]).await.unwrap();
+                let acked = tx.wait_send(&mut Strategy::polling(100), &mut timer).await;
+                assert_eq!(acked.ok(), Some(true));
+                assert_eq!(responder.poll(&mut rx).await.ok(), Some(Some(u16::from(seq))));
+            }
+            // The reply to the first ping went out, the one to the second
+            // waits, and so does the other pipe's
+            assert_eq!(chip.ack_fifo_len(), 2);
+            assert_eq!(responder.received(), 2);
+        });
+    }
+}
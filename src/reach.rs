@@ -0,0 +1,235 @@
+use crate::address::Address;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::events::RadioEvents;
+use crate::strategy::Strategy;
+use crate::timer::RadioTimer;
+use crate::tx::TxMode;
+
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Outcome of [`ReachTracker::send()`](struct.ReachTracker.html#method.send)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ReachOutcome {
+    /// The peer acknowledged the packet
+    Acked,
+    /// The packet reached the maximum number of retransmits
+    MaxRt,
+    /// The peer is unreachable, so the packet was not sent
+    Suppressed,
+}
+
+/// Peer with `MAX_RT` events in a row
+#[derive(Debug, Clone)]
+struct Failing {
+    address: Address,
+    max_rts: u8,
+    /// Sends are suppressed until then, once unreachable
+    until: Option<u64>,
+}
+
+/// Marks peers unreachable after repeated `MAX_RT`, and holds back
+/// sends to them for a while
+///
+/// A receiver that is gone costs the full auto-retransmit sequence on
+/// every packet, which drains the battery of a duty-cycled sender.
+/// After `max_rts` packets in a row to the same TX address reached
+/// `MAX_RT`, [`send()`](#method.send) suppresses packets to it for the
+/// backoff period and reports
+/// [`RadioEvents::on_peer_unreachable()`](trait.RadioEvents.html#method.on_peer_unreachable).
+/// The first packet after the backoff goes out as a probe: an ACK
+/// reports
+/// [`RadioEvents::on_peer_reachable()`](trait.RadioEvents.html#method.on_peer_reachable),
+/// another `MAX_RT` starts the next backoff.
+///
+/// Keeps track of up to `N` failing peers at a time. Peers that answer
+/// free their slot. Requires auto-ack and auto-retransmit.
+#[derive(Debug, Clone)]
+pub struct ReachTracker<const N: usize> {
+    failing: [Option<Failing>; N],
+    max_rts: u8,
+    backoff_us: u64,
+}
+
+impl<const N: usize> ReachTracker<N> {
+    /// Constructor, marking peers unreachable for `backoff_us` on the
+    /// timer after `max_rts` packets in a row reached `MAX_RT`
+    pub fn new(max_rts: u8, backoff_us: u64) -> Self {
+        ReachTracker {
+            failing: [const { None }; N],
+            max_rts: max_rts.max(1),
+            backoff_us,
+        }
+    }
+
+    /// Whether sends to `peer` are suppressed at `now_us`
+    pub fn is_unreachable(&self, peer: &Address, now_us: u64) -> bool {
+        self.find(peer)
+            .and_then(|failing| failing.until)
+            .is_some_and(|until| now_us < until)
+    }
+
+    /// Forget the failures of `peer`, e.g. when it announced itself
+    pub fn reset(&mut self, peer: &Address) {
+        for slot in self.failing.iter_mut() {
+            if slot.as_ref().is_some_and(|failing| failing.address == *peer) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Send `packet` to `peer` unless it is unreachable, and wait for
+    /// the transmission to complete, polling on `timer`
+    ///
+    /// Waits for the TX FIFO to drain first, and leaves the TX and pipe
+    /// 0 addresses at `peer`.
+    pub async fn send<D: Device, T: RadioTimer>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        peer: &Address,
+        packet: &[u8],
+        timer: &mut T,
+    ) -> Result<ReachOutcome, D::Error> {
+        if self.is_unreachable(peer, timer.now_micros()) {
+            return Ok(ReachOutcome::Suppressed);
+        }
+
+        // Settings must not change while sending
+        tx.wait_empty().await?;
+        tx.set_tx_addr(peer).await?;
+        tx.set_rx_addr(0, peer).await?;
+        tx.send(packet).await?;
+        let acked = tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), timer).await?;
+
+        if acked {
+            if self.record_ack(peer) {
+                tx.device().events().on_peer_reachable(peer);
+            }
+            return Ok(ReachOutcome::Acked);
+        }
+        if self.record_max_rt(peer, timer.now_micros()) {
+            tx.device().events().on_peer_unreachable(peer);
+        }
+        Ok(ReachOutcome::MaxRt)
+    }
+
+    /// Note an ACK from `peer`, and return whether it was unreachable
+    fn record_ack(&mut self, peer: &Address) -> bool {
+        let was_unreachable = self.find(peer).is_some_and(|failing| failing.until.is_some());
+        self.reset(peer);
+        was_unreachable
+    }
+
+    /// Note a `MAX_RT` of a packet to `peer` at `now_us`, and return
+    /// whether this made it unreachable
+    fn record_max_rt(&mut self, peer: &Address, now_us: u64) -> bool {
+        let max_rts = self.max_rts;
+        let backoff_us = self.backoff_us;
+        let Some(slot) = self.slot(peer) else {
+            return false;
+        };
+        let failing = slot.get_or_insert_with(|| Failing {
+            address: *peer,
+            max_rts: 0,
+            until: None,
+        });
+        failing.max_rts = failing.max_rts.saturating_add(1);
+        if failing.max_rts < max_rts {
+            return false;
+        }
+        let first = failing.until.is_none();
+        failing.until = Some(now_us.saturating_add(backoff_us));
+        first
+    }
+
+    fn find(&self, peer: &Address) -> Option<&Failing> {
+        self.failing.iter().flatten().find(|failing| failing.address == *peer)
+    }
+
+    /// Slot of `peer`, or else a free one, or else the one with the
+    /// fewest failures, which is cleared. `None` if `N` is 0.
+    fn slot(&mut self, peer: &Address) -> Option<&mut Option<Failing>> {
+        let index = self
+            .failing
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|failing| failing.address == *peer))
+            .or_else(|| self.failing.iter().position(Option::is_none))
+            .or_else(|| {
+                let max_rts = |i: &usize| self.failing.get(*i).and_then(Option::as_ref);
+                (0..N).min_by_key(|i| max_rts(i).map_or(0, |f| f.max_rts))
+            })?;
+        let slot = self.failing.get_mut(index)?;
+        if slot.as_ref().is_some_and(|failing| failing.address != *peer) {
+            *slot = None;
+        }
+        Some(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Address = Address::from_array([1; 5]);
+    const B: Address = Address::from_array([2; 5]);
+    const C: Address = Address::from_array([3; 5]);
+
+    #[test]
+    fn unreachable_after_max_rts_in_a_row() {
+        let mut reach = ReachTracker::<2>::new(3, 1_000);
+        assert!(!reach.record_max_rt(&A, 0));
+        assert!(!reach.record_max_rt(&A, 10));
+        assert!(!reach.is_unreachable(&A, 10));
+        assert!(reach.record_max_rt(&A, 20));
+        assert!(reach.is_unreachable(&A, 20));
+        assert!(reach.is_unreachable(&A, 1_019));
+        assert!(!reach.is_unreachable(&B, 20));
+    }
+
+    #[test]
+    fn an_ack_resets_the_count() {
+        let mut reach = ReachTracker::<2>::new(2, 1_000);
+        assert!(!reach.record_max_rt(&A, 0));
+        assert!(!reach.record_ack(&A));
+        assert!(!reach.record_max_rt(&A, 10));
+        assert!(!reach.is_unreachable(&A, 10));
+    }
+
+    #[test]
+    fn backoff_expires_into_a_probe() {
+        let mut reach = ReachTracker::<2>::new(1, 1_000);
+        assert!(reach.record_max_rt(&A, 0));
+        assert!(!reach.is_unreachable(&A, 1_000));
+        // The probe fails: a new backoff, but no new event
+        assert!(!reach.record_max_rt(&A, 1_000));
+        assert!(reach.is_unreachable(&A, 1_999));
+        assert!(!reach.is_unreachable(&A, 2_000));
+        // The next probe is answered
+        assert!(reach.record_ack(&A));
+        assert!(!reach.record_ack(&A));
+    }
+
+    #[test]
+    fn slots_go_to_the_worst_peers() {
+        let mut reach = ReachTracker::<2>::new(2, 1_000);
+        assert!(!reach.record_max_rt(&A, 0));
+        assert!(reach.record_max_rt(&A, 0));
+        assert!(!reach.record_max_rt(&B, 0));
+        // C takes the slot of B, which has fewer failures
+        assert!(!reach.record_max_rt(&C, 0));
+        assert!(reach.is_unreachable(&A, 0));
+        assert!(reach.record_max_rt(&C, 0));
+        assert!(!reach.record_max_rt(&B, 0));
+
+        reach.reset(&A);
+        assert!(!reach.is_unreachable(&A, 0));
+    }
+
+    #[test]
+    fn no_slots() {
+        let mut reach = ReachTracker::<0>::new(1, 1_000);
+        assert!(!reach.record_max_rt(&A, 0));
+        assert!(!reach.is_unreachable(&A, 0));
+    }
+}
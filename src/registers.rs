@@ -1,29 +1,40 @@
-#![allow(unused)]
+//! Register definitions
+//!
+//! The bitfield layouts follow the nRF24L01+ datasheet, section 9. Use
+//! these to construct or inspect register values, e.g. with
+//! [`Device::read_register()`](../trait.Device.html#method.read_register).
 
-use crate::{MAX_ADDR_BYTES, MIN_ADDR_BYTES, PIPES_COUNT};
+use crate::{MAX_ADDR_BYTES, PIPES_COUNT};
+use core::fmt;
 
+/// A register that can be read and written with `R_REGISTER` and
+/// `W_REGISTER`
 pub trait Register {
     /// Address in the register map
     fn addr() -> u8;
 
+    /// Number of data bytes to read
     fn read_len() -> usize;
+    /// Number of data bytes to write
     fn write_len(&self) -> usize {
         Self::read_len()
     }
 
+    /// Serialize into `data`, which is `write_len()` bytes long
     fn encode(&self, data: &mut [u8]);
+    /// Deserialize from `data`, which is `read_len()` bytes long
     fn decode(data: &[u8]) -> Self;
 }
 
 macro_rules! def_simple {
-    ($name: ident) => {
+    ($(#[$meta: meta])* $name: ident) => {
+        $(#[$meta])*
         pub struct $name(pub u8);
 
         impl $name {
+            /// Take the first byte of `data`
             pub fn new(data: &[u8]) -> Self {
-                assert_eq!(data.len(), 1);
-
-                $name(data[0])
+                $name(data.first().copied().unwrap_or(0))
             }
         }
     };
@@ -42,11 +53,13 @@ macro_rules! impl_register {
             }
 
             fn encode(&self, buf: &mut [u8]) {
-                buf[0] = self.0;
+                if let Some(byte) = buf.first_mut() {
+                    *byte = self.0;
+                }
             }
 
             fn decode(buf: &[u8]) -> Self {
-                $name(buf[0])
+                $name(buf.first().copied().unwrap_or(0))
             }
         }
 
@@ -65,16 +78,23 @@ macro_rules! impl_register {
 }
 
 macro_rules! def_address_register {
-    ($name: ident, $addr: expr) => {
+    ($(#[$meta: meta])* $name: ident, $addr: expr) => {
+        $(#[$meta])*
         pub struct $name {
             addr: [u8; MAX_ADDR_BYTES],
             len: u8,
         }
 
         impl $name {
+            /// Copy up to `MAX_ADDR_BYTES` address bytes
             pub fn new(buf: &[u8]) -> Self {
                 Self::decode(buf)
             }
+
+            /// Address bytes
+            pub fn as_bytes(&self) -> &[u8] {
+                self.addr.get(..usize::from(self.len)).unwrap_or_default()
+            }
         }
 
         impl Register for $name {
@@ -91,17 +111,19 @@ macro_rules! def_address_register {
             }
 
             fn encode(&self, buf: &mut [u8]) {
-                let len = self.len.into();
-                buf.copy_from_slice(&self.addr[0..len]);
+                for (byte, addr) in buf.iter_mut().zip(self.as_bytes()) {
+                    *byte = *addr;
+                }
             }
 
             fn decode(buf: &[u8]) -> Self {
-                let len = buf.len();
-                assert!(len >= MIN_ADDR_BYTES);
-                assert!(len <= MAX_ADDR_BYTES);
+                // Callers validate against `MIN_ADDR_BYTES`
+                let len = buf.len().min(MAX_ADDR_BYTES);
 
                 let mut addr = [0; MAX_ADDR_BYTES];
-                addr[0..len].copy_from_slice(buf);
+                for (addr, byte) in addr.iter_mut().zip(buf) {
+                    *addr = *byte;
+                }
                 $name {
                     addr,
                     len: len as u8,
@@ -114,14 +136,28 @@ macro_rules! def_address_register {
 macro_rules! def_pipes_accessors {
     ($name: ident, $default: expr, $getter: ident, $setter: ident) => {
         impl $name {
+            /// Get the flag of pipe number `pipe_no`
+            ///
+            /// Pipes run from `0` to `PIPES_COUNT - 1`, others read as
+            /// `false`.
             #[inline]
             pub fn $getter(&self, pipe_no: usize) -> bool {
+                if pipe_no >= PIPES_COUNT {
+                    return false;
+                }
                 let mask = 1 << pipe_no;
                 self.0 & mask == mask
             }
 
+            /// Set the flag of pipe number `pipe_no`
+            ///
+            /// Pipes run from `0` to `PIPES_COUNT - 1`, others are
+            /// ignored.
             #[inline]
             pub fn $setter(&mut self, pipe_no: usize, enable: bool) {
+                if pipe_no >= PIPES_COUNT {
+                    return;
+                }
                 let mask = 1 << pipe_no;
                 if enable {
                     self.0 |= mask;
@@ -130,6 +166,7 @@ macro_rules! def_pipes_accessors {
                 }
             }
 
+            /// Construct from one flag per pipe
             pub fn from_bools(bools: &[bool; PIPES_COUNT]) -> Self {
                 let mut register = $name($default);
                 for (i, b) in bools.iter().enumerate() {
@@ -138,6 +175,7 @@ macro_rules! def_pipes_accessors {
                 register
             }
 
+            /// Obtain one flag per pipe
             pub fn to_bools(&self) -> [bool; PIPES_COUNT] {
                 let mut bools = [true; PIPES_COUNT];
                 for (i, b) in bools.iter_mut().enumerate() {
@@ -150,6 +188,7 @@ macro_rules! def_pipes_accessors {
 }
 
 bitfield! {
+    /// Configuration register
     pub struct Config(u8);
     impl Debug;
 
@@ -182,11 +221,12 @@ def_pipes_accessors!(EnAa, 0b0011_1111, enaa_p, set_enaa_p);
 
 /// Enabled RX Addresses
 #[derive(Debug)]
-pub struct EnRxaddr(u8);
+pub struct EnRxaddr(pub u8);
 impl_register!(EnRxaddr, 0x02);
 def_pipes_accessors!(EnRxaddr, 0, erx_p, set_erx_p);
 
 bitfield! {
+    /// Setup of Address Widths
     pub struct SetupAw(u8);
     impl Debug;
 
@@ -235,6 +275,8 @@ bitfield! {
     /// * `10`: -6 dBm
     /// * `11`: 0 dBm
     pub u8, rf_pwr, set_rf_pwr: 2, 1;
+    /// Setup LNA gain, nRF24L01 (non-plus) only
+    pub lna_hcurr, set_lna_hcurr: 0;
 }
 impl_register!(RfSetup, 0x06);
 
@@ -257,30 +299,107 @@ bitfield! {
 }
 impl_register!(Status, 0x07);
 
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RX_DR={} TX_DS={} MAX_RT={} RX_P_NO={} TX_FULL={}",
+            u8::from(self.rx_dr()),
+            u8::from(self.tx_ds()),
+            u8::from(self.max_rt()),
+            self.rx_p_no(),
+            u8::from(self.tx_full())
+        )
+    }
+}
+
+impl defmt::Format for Status {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "RX_DR={=u8} TX_DS={=u8} MAX_RT={=u8} RX_P_NO={=u8} TX_FULL={=u8}",
+            u8::from(self.rx_dr()),
+            u8::from(self.tx_ds()),
+            u8::from(self.max_rt()),
+            self.rx_p_no(),
+            u8::from(self.tx_full())
+        )
+    }
+}
+
 bitfield! {
+    /// Transmit observe register
     pub struct ObserveTx(u8);
     impl Debug;
 
+    /// Count lost packets, reset by writing `RF_CH`
     pub u8, plos_cnt, _: 7, 4;
+    /// Count retransmitted packets, reset when a new packet is sent
     pub u8, arc_cnt, _: 3, 0;
 }
 impl_register!(ObserveTx, 0x08);
 
-def_simple!(CD);
+impl fmt::Display for ObserveTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PLOS_CNT={} ARC_CNT={}", self.plos_cnt(), self.arc_cnt())
+    }
+}
+
+impl defmt::Format for ObserveTx {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "PLOS_CNT={=u8} ARC_CNT={=u8}",
+            self.plos_cnt(),
+            self.arc_cnt()
+        )
+    }
+}
+
+def_simple!(
+    /// Carrier detect (nRF24L01) or Received Power Detector (nRF24L01+),
+    /// in bit 0
+    CD
+);
 impl_register!(CD, 0x09);
 
-def_address_register!(RxAddrP0, 0x0A);
-def_address_register!(RxAddrP1, 0x0B);
-def_simple!(RxAddrP2);
+def_address_register!(
+    /// Receive address of pipe 0
+    RxAddrP0, 0x0A
+);
+def_address_register!(
+    /// Receive address of pipe 1
+    RxAddrP1, 0x0B
+);
+def_simple!(
+    /// Least significant receive address byte of pipe 2, the other bytes
+    /// are shared with pipe 1
+    RxAddrP2
+);
 impl_register!(RxAddrP2, 0x0C);
-def_simple!(RxAddrP3);
+def_simple!(
+    /// Least significant receive address byte of pipe 3, the other bytes
+    /// are shared with pipe 1
+    RxAddrP3
+);
 impl_register!(RxAddrP3, 0x0D);
-def_simple!(RxAddrP4);
+def_simple!(
+    /// Least significant receive address byte of pipe 4, the other bytes
+    /// are shared with pipe 1
+    RxAddrP4
+);
 impl_register!(RxAddrP4, 0x0E);
-def_simple!(RxAddrP5);
+def_simple!(
+    /// Least significant receive address byte of pipe 5, the other bytes
+    /// are shared with pipe 1
+    RxAddrP5
+);
 impl_register!(RxAddrP5, 0x0F);
 
-def_address_register!(TxAddr, 0x10);
+def_address_register!(
+    /// Transmit address
+    TxAddr, 0x10
+);
 
 macro_rules! def_rx_pw {
     ($name: ident, $addr: expr) => {
@@ -304,11 +423,11 @@ def_rx_pw!(RxPwP4, 0x15);
 def_rx_pw!(RxPwP5, 0x16);
 
 bitfield! {
-    /// Status register, always received on MISO while command is sent
-    /// on MOSI.
+    /// FIFO status register
     pub struct FifoStatus(u8);
     impl Debug;
 
+    /// A TX payload is reused by `REUSE_TX_PL`
     pub tx_reuse, _: 6;
     /// TX FIFO full flag
     pub tx_full, _: 5;
@@ -321,6 +440,34 @@ bitfield! {
 }
 impl_register!(FifoStatus, 0x17);
 
+impl fmt::Display for FifoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TX_REUSE={} TX_FULL={} TX_EMPTY={} RX_FULL={} RX_EMPTY={}",
+            u8::from(self.tx_reuse()),
+            u8::from(self.tx_full()),
+            u8::from(self.tx_empty()),
+            u8::from(self.rx_full()),
+            u8::from(self.rx_empty())
+        )
+    }
+}
+
+impl defmt::Format for FifoStatus {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "TX_REUSE={=u8} TX_FULL={=u8} TX_EMPTY={=u8} RX_FULL={=u8} RX_EMPTY={=u8}",
+            u8::from(self.tx_reuse()),
+            u8::from(self.tx_full()),
+            u8::from(self.tx_empty()),
+            u8::from(self.rx_full()),
+            u8::from(self.rx_empty())
+        )
+    }
+}
+
 /// Enable Dynamic Payload length
 pub struct Dynpd(pub u8);
 impl_register!(Dynpd, 0x1C);
@@ -0,0 +1,172 @@
+use crate::payload::Payload;
+
+/// What [`Reorder::push()`](struct.Reorder.html#method.push) did with
+/// a packet
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Arrival {
+    /// Held until it is next in sequence
+    Accepted,
+    /// Already delivered or held, e.g. a retransmission whose ACK got
+    /// lost
+    Duplicate,
+    /// Too far ahead of the next expected packet to be held
+    OutOfWindow,
+}
+
+/// Delivers packets strictly in sequence, for receivers that parse a
+/// stream incrementally
+///
+/// Each packet carries a sequence number that the sender increments,
+/// wrapping from 255 to 0. Packets arriving early are held in a window
+/// of `W` packets until the ones before them have arrived, so that
+/// reordering caused by retransmits does not reach the application. A
+/// packet that never arrives stalls delivery until it is
+/// [`skip()`](#method.skip)ped.
+///
+/// Standalone, for protocols of the application that number their
+/// packets. [`StreamReceiver`](struct.StreamReceiver.html) does not
+/// need it: its sender goes back to the first lost fragment, so the
+/// receiver only ever takes the next one in sequence.
+pub struct Reorder<const W: usize> {
+    /// Sequence number of the next packet to deliver
+    next: u8,
+    /// Slot of `next` in `held`
+    head: usize,
+    held: [Option<Payload>; W],
+}
+
+impl<const W: usize> Reorder<W> {
+    /// Constructor, expecting sequence number `first` next
+    ///
+    /// `W` must be at most 128 to tell old packets from early ones.
+    pub fn new(first: u8) -> Self {
+        Reorder {
+            next: first,
+            head: 0,
+            held: core::array::from_fn(|_| None),
+        }
+    }
+
+    /// Sequence number of the next packet to deliver
+    pub fn next_seq(&self) -> u8 {
+        self.next
+    }
+
+    /// Number of packets held back
+    pub fn held(&self) -> usize {
+        self.held.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Accept the packet with sequence number `seq`
+    pub fn push(&mut self, seq: u8, payload: &[u8]) -> Arrival {
+        let distance = usize::from(seq.wrapping_sub(self.next));
+        if distance >= W.min(128) {
+            // Half of the sequence space behind `next` has been delivered
+            return if distance >= 128 {
+                Arrival::Duplicate
+            } else {
+                Arrival::OutOfWindow
+            };
+        }
+        let index = self.head.wrapping_add(distance).checked_rem(W).unwrap_or(0);
+        let Some(slot) = self.held.get_mut(index) else {
+            return Arrival::OutOfWindow;
+        };
+        if slot.is_some() {
+            return Arrival::Duplicate;
+        }
+        *slot = Some(Payload::new(payload));
+        Arrival::Accepted
+    }
+
+    /// Take the next packet in sequence, if it has arrived
+    pub fn pop(&mut self) -> Option<Payload> {
+        let payload = self.held.get_mut(self.head)?.take()?;
+        self.advance();
+        Some(payload)
+    }
+
+    /// Give up waiting for the next packet, e.g. after a timeout, and
+    /// continue with the one after it
+    pub fn skip(&mut self) {
+        if let Some(slot) = self.held.get_mut(self.head) {
+            *slot = None;
+            self.advance();
+        }
+    }
+
+    /// Drop everything held and expect sequence number `first` next
+    pub fn reset(&mut self, first: u8) {
+        *self = Self::new(first);
+    }
+
+    fn advance(&mut self) {
+        self.next = self.next.wrapping_add(1);
+        self.head = self.head.wrapping_add(1).checked_rem(W).unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop_all<const W: usize>(reorder: &mut Reorder<W>) -> Vec<u8> {
+        core::iter::from_fn(|| reorder.pop()).map(|payload| payload[0]).collect()
+    }
+
+    #[test]
+    fn in_order() {
+        let mut reorder = Reorder::<4>::new(0);
+        for seq in 0..10 {
+            assert_eq!(reorder.push(seq, &[seq]), Arrival::Accepted);
+            assert_eq!(pop_all(&mut reorder), [seq]);
+        }
+        assert_eq!(reorder.next_seq(), 10);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut reorder = Reorder::<4>::new(0);
+        assert_eq!(reorder.push(2, &[2]), Arrival::Accepted);
+        assert_eq!(reorder.push(1, &[1]), Arrival::Accepted);
+        assert_eq!(reorder.held(), 2);
+        assert!(reorder.pop().is_none());
+        assert_eq!(reorder.push(0, &[0]), Arrival::Accepted);
+        assert_eq!(pop_all(&mut reorder), [0, 1, 2]);
+        assert_eq!(reorder.held(), 0);
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut reorder = Reorder::<4>::new(0);
+        assert_eq!(reorder.push(1, &[1]), Arrival::Accepted);
+        assert_eq!(reorder.push(1, &[1]), Arrival::Duplicate);
+        assert_eq!(reorder.push(0, &[0]), Arrival::Accepted);
+        assert_eq!(pop_all(&mut reorder), [0, 1]);
+        assert_eq!(reorder.push(0, &[0]), Arrival::Duplicate);
+        assert_eq!(reorder.push(1, &[1]), Arrival::Duplicate);
+    }
+
+    #[test]
+    fn window_overflow() {
+        let mut reorder = Reorder::<4>::new(0);
+        assert_eq!(reorder.push(4, &[4]), Arrival::OutOfWindow);
+        assert_eq!(reorder.push(3, &[3]), Arrival::Accepted);
+        // Gives up on 0 to 2
+        for _ in 0..3 {
+            reorder.skip();
+        }
+        assert_eq!(reorder.push(4, &[4]), Arrival::Accepted);
+        assert_eq!(pop_all(&mut reorder), [3, 4]);
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let mut reorder = Reorder::<4>::new(254);
+        assert_eq!(reorder.push(0, &[0]), Arrival::Accepted);
+        assert_eq!(reorder.push(255, &[255]), Arrival::Accepted);
+        assert_eq!(reorder.push(254, &[254]), Arrival::Accepted);
+        assert_eq!(pop_all(&mut reorder), [254, 255, 0]);
+        assert_eq!(reorder.push(200, &[200]), Arrival::Duplicate);
+    }
+}
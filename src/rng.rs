@@ -0,0 +1,46 @@
+/// Source of random numbers for the randomized features, such as
+/// [`Jitter`](struct.Jitter.html) backoff and
+/// [`Address::random()`](struct.Address.html#method.random)
+///
+/// Implement it once on the hardware RNG of your MCU, or pass a closure
+/// returning `u32`. With the `rand_core` feature,
+/// [`RandCore`](struct.RandCore.html) adapts any `rand_core::RngCore`.
+/// Use a cryptographically secure source where the numbers must not be
+/// guessed, e.g. for nonces.
+pub trait RadioRng {
+    /// Next random `u32`
+    fn next_u32(&mut self) -> u32;
+
+    /// Fill `buf` with random bytes, e.g. a nonce
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            for (byte, random) in chunk.iter_mut().zip(bytes) {
+                *byte = random;
+            }
+        }
+    }
+}
+
+impl<F: FnMut() -> u32> RadioRng for F {
+    fn next_u32(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// [`RadioRng`](trait.RadioRng.html) from a `rand_core::RngCore`, e.g.
+/// the RNG peripheral of a HAL
+#[cfg(feature = "rand_core")]
+#[derive(Debug)]
+pub struct RandCore<R>(pub R);
+
+#[cfg(feature = "rand_core")]
+impl<R: rand_core::RngCore> RadioRng for RandCore<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        self.0.fill_bytes(buf)
+    }
+}
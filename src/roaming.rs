@@ -0,0 +1,240 @@
+use crate::address::Address;
+use crate::channel::Channel;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::payload::Payload;
+use crate::strategy::Strategy;
+use crate::timer::RadioTimer;
+use crate::tx::TxMode;
+use core::cmp::Reverse;
+use embedded_hal_async::delay::DelayNs;
+
+/// Interval between polls of a transmission in progress
+const POLL_INTERVAL_US: u32 = 100;
+
+/// Quality assumed for gateways that have not been tried yet
+const INITIAL_QUALITY: u8 = 50;
+
+/// Default quality below which to look for a better gateway
+const ROAM_THRESHOLD: u8 = 30;
+
+/// Default time between handovers that `send()` starts, in µs
+const ROAM_INTERVAL_US: u64 = 1_000_000;
+
+/// A receiver that a mobile node can attach to
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Gateway {
+    /// Address the gateway listens on
+    pub address: Address,
+    /// RF channel the gateway listens on
+    pub channel: Channel,
+}
+
+/// Keeps a mobile PTX node connected across several gateways
+///
+/// Tracks the acknowledgment rate of each gateway as its quality, 0 to
+/// 100, in a moving average over roughly the last 8 packets. When the
+/// quality of the current gateway drops below the threshold,
+/// [`send()`](#method.send) hands over to the best other gateway: it
+/// switches the TX address, the pipe 0 address for ACKs and the channel,
+/// and sends the handover message. The first gateway to acknowledge it
+/// becomes the current one. `send()` starts a handover at most once a
+/// second, see [`set_roam_interval()`](#method.set_roam_interval), so
+/// that a node out of range of all gateways does not try them all on
+/// every packet.
+///
+/// Requires auto-ack and auto-retransmit, and all gateways to use the
+/// address width of the current configuration.
+pub struct Roaming<const N: usize> {
+    gateways: [Gateway; N],
+    quality: [u8; N],
+    current: usize,
+    threshold: u8,
+    hello: Payload,
+    interval_us: u64,
+    /// When `send()` last started a handover
+    last_roam: Option<u64>,
+}
+
+impl<const N: usize> Roaming<N> {
+    /// Constructor, starting with the first gateway
+    ///
+    /// `hello` is sent to a gateway when handing over to it, e.g. a node
+    /// id so that the gateway can route replies. It must fit in one
+    /// packet, 1 to 32 bytes.
+    pub fn new(gateways: [Gateway; N], hello: &[u8]) -> Result<Self, InvalidArgument> {
+        if !(1..=32).contains(&hello.len()) {
+            return Err(InvalidArgument::PayloadLength);
+        }
+        Ok(Roaming {
+            gateways,
+            quality: [INITIAL_QUALITY; N],
+            current: 0,
+            threshold: ROAM_THRESHOLD,
+            hello: Payload::new(hello),
+            interval_us: ROAM_INTERVAL_US,
+            last_roam: None,
+        })
+    }
+
+    /// Set the quality below which to hand over, 30 by default
+    pub fn set_threshold(&mut self, threshold: u8) {
+        self.threshold = threshold.min(100);
+    }
+
+    /// Set the least time between handovers that
+    /// [`send()`](#method.send) starts, in µs of its timer, 1 s by
+    /// default
+    pub fn set_roam_interval(&mut self, interval_us: u64) {
+        self.interval_us = interval_us;
+    }
+
+    /// Index of the current gateway
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// The current gateway
+    pub fn gateway(&self) -> Option<&Gateway> {
+        self.gateways.get(self.current)
+    }
+
+    /// Acknowledgment rate of gateway `index` in percent
+    pub fn quality(&self, index: usize) -> Option<u8> {
+        self.quality.get(index).copied()
+    }
+
+    /// Account for a packet sent to the current gateway
+    pub fn record(&mut self, acked: bool) {
+        if let Some(quality) = self.quality.get_mut(self.current) {
+            let sample = if acked { 100 } else { 0 };
+            *quality = (u16::from(*quality).saturating_mul(7).saturating_add(sample) / 8) as u8;
+        }
+    }
+
+    /// Switch to gateway `index` without a handover message, e.g. on
+    /// startup
+    pub async fn attach<D: Device>(&mut self, tx: &mut TxMode<D>, index: usize) -> Result<(), D::Error> {
+        let Some(gateway) = self.gateways.get(index) else {
+            return Ok(());
+        };
+        // Settings must not change while sending
+        tx.wait_empty().await?;
+        tx.set_frequency(gateway.channel).await?;
+        tx.set_tx_addr(&gateway.address).await?;
+        tx.set_rx_addr(0, &gateway.address).await?;
+        self.current = index;
+        Ok(())
+    }
+
+    /// Send `packet` to the current gateway and wait for the outcome,
+    /// handing over to another gateway if the quality has become too low
+    ///
+    /// Returns whether `packet` was acknowledged. It is not sent again
+    /// after a handover. Transmissions are polled on `timer`, which also
+    /// spaces the handovers.
+    pub async fn send<D: Device, T: RadioTimer>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        packet: &[u8],
+        timer: &mut T,
+    ) -> Result<bool, D::Error> {
+        let acked = send_and_wait(tx, packet, timer).await?;
+        self.record(acked);
+        let now = timer.now_micros();
+        let due = self
+            .last_roam
+            .is_none_or(|last| now.saturating_sub(last) >= self.interval_us);
+        if due && self.quality(self.current).is_some_and(|q| q < self.threshold) {
+            self.last_roam = Some(now);
+            self.roam(tx, timer).await?;
+        }
+        Ok(acked)
+    }
+
+    /// Try the other gateways, best first, until one acknowledges the
+    /// handover message. Returns whether the gateway has changed.
+    ///
+    /// Stays with the current gateway if none answers. Not limited by the
+    /// roam interval.
+    pub async fn roam<D: Device, DL: DelayNs>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let previous = self.current;
+        let mut order: [usize; N] = core::array::from_fn(|i| i);
+        order.sort_unstable_by_key(|&i| Reverse(self.quality.get(i).copied()));
+
+        for index in order {
+            if index == previous {
+                continue;
+            }
+            self.attach(tx, index).await?;
+            let acked = send_and_wait(tx, &self.hello, delay).await?;
+            self.record(acked);
+            if acked {
+                return Ok(true);
+            }
+        }
+        self.attach(tx, previous).await?;
+        Ok(false)
+    }
+}
+
+/// Send and wait until the transmission has completed
+async fn send_and_wait<D: Device, DL: DelayNs>(
+    tx: &mut TxMode<D>,
+    packet: &[u8],
+    delay: &mut DL,
+) -> Result<bool, D::Error> {
+    tx.send(packet).await?;
+    tx.wait_send(&mut Strategy::polling(POLL_INTERVAL_US), delay).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+    use crate::RadioConfig;
+
+    fn gateway(byte: u8) -> Gateway {
+        Gateway {
+            address: Address::from_array([byte; 5]),
+            channel: Channel::new(byte).unwrap(),
+        }
+    }
+
+    #[test]
+    fn hello_must_fit_a_packet() {
+        assert!(Roaming::new([gateway(10)], &[0; 33]).is_err());
+        assert!(Roaming::new([gateway(10)], &[]).is_err());
+        assert!(Roaming::new([gateway(10)], &[0; 32]).is_ok());
+    }
+
+    #[test]
+    fn handovers_are_spaced() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let mut tx = standby.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut roaming = Roaming::new([gateway(10), gateway(20)], b"hi").unwrap();
+            roaming.attach(&mut tx, 0).await.unwrap();
+            let mut timer = SimTimer::new();
+            let hellos = |air: &Air| air.frames().iter().filter(|f| f.payload == b"hi").count();
+
+            // No gateway answers, so the quality drops below 30 at once
+            for _ in 0..10 {
+                assert_eq!(roaming.send(&mut tx, b"x", &mut timer).await.ok(), Some(false));
+            }
+            assert_eq!(hellos(&air), 1);
+            assert_eq!(roaming.current(), 0);
+
+            timer.advance(ROAM_INTERVAL_US);
+            roaming.send(&mut tx, b"x", &mut timer).await.unwrap();
+            assert_eq!(hellos(&air), 2);
+        });
+    }
+}
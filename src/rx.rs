@@ -1,14 +1,103 @@
-use crate::command::{ReadRxPayload, ReadRxPayloadWidth};
+use crate::channel::Channel;
+use crate::command::{
+    FlushRx, FlushTx, Nop, ReadRxPayloadWidth, WriteAckPayload, R_RX_PAYLOAD, W_ACK_PAYLOAD,
+};
 use crate::config::Configuration;
+#[cfg(feature = "dedup")]
+use crate::dedup::DuplicateFilter;
 use crate::device::Device;
+use crate::error::InvalidArgument;
+use crate::events::RadioEvents;
 use crate::payload::Payload;
 use crate::registers::{FifoStatus, Status, CD};
 use crate::standby::StandbyMode;
+use crate::strategy::Strategy;
+use crate::time::Clock;
+use crate::PIPES_COUNT;
 use core::fmt;
+use core::ops::{ControlFlow, RangeInclusive};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+
+/// `RX_P_NO` when the RX FIFO is empty
+pub(crate) const RX_P_NO_EMPTY: u8 = 0b111;
+/// Packets the RX FIFO holds, so that a read skips no more than these
+/// before the chip has to be empty
+const RX_FIFO_DEPTH: usize = 3;
+
+/// What [`RxMode`](struct.RxMode.html) does with a packet on a static
+/// pipe for which `R_RX_PL_WID` reports another width than `RX_PW_Px`,
+/// with dynamic payload lengths on other pipes
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum LengthPolicy {
+    /// Read the configured length, which is what the chip stores for
+    /// the pipe
+    #[default]
+    Fit,
+    /// Drop the packet, returning an empty payload like for a corrupt
+    /// one
+    Reject,
+}
+
+/// What [`RxMode`](struct.RxMode.html) does with a packet whose
+/// dynamic payload width is reported as 0, as some clones do
+///
+/// Each one is counted in
+/// [`Stats::zero_width_payloads`](struct.Stats.html#structfield.zero_width_payloads).
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum ZeroWidthPolicy {
+    /// Drop the packet and read the next one
+    #[default]
+    Drop,
+    /// Return an empty payload, like for a corrupt packet
+    Deliver,
+    /// Flush the RX FIFO and return an empty payload, in case the clone
+    /// has got out of step with its FIFO
+    Flush,
+}
+
+/// Software filter on the first payload byte, see
+/// [`RxMode::set_prefix_filter()`](struct.RxMode.html#method.set_prefix_filter)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct PrefixFilter {
+    /// Expected value of the bits in `mask`
+    pub value: u8,
+    /// Bits of the first byte to compare
+    pub mask: u8,
+}
+
+impl PrefixFilter {
+    /// Accept packets whose first byte has `value` in the bits of `mask`
+    pub const fn new(value: u8, mask: u8) -> Self {
+        PrefixFilter { value, mask }
+    }
+
+    /// Does `payload` pass? Empty payloads never do.
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        payload
+            .first()
+            .is_some_and(|first| first & self.mask == self.value & self.mask)
+    }
+}
 
 /// Represents **RX Mode**
 pub struct RxMode<D: Device> {
     device: D,
+    /// The RX FIFO has been found full and not been drained since
+    full: bool,
+    policy: LengthPolicy,
+    filter: Option<PrefixFilter>,
+    zero_width: ZeroWidthPolicy,
+    /// Timestamp source, as set by `set_timestamp_source()`
+    clock: Option<&'static (dyn Clock + Sync)>,
+    /// When `can_read()` first saw the packet at the head of the FIFO
+    seen_at: Option<u64>,
+    /// Arrival time of the packet last read
+    last_arrival: Option<u64>,
+    /// Copies of the ACK payloads queued with `write_ack_payload()` and
+    /// not sent yet, in FIFO order, with their pipe numbers
+    acks: [(u8, Payload); 3],
+    acks_len: usize,
 }
 
 impl<D: Device> fmt::Debug for RxMode<D> {
@@ -21,12 +110,196 @@ impl<D: Device> RxMode<D> {
     /// Relies on everything being set up by `StandbyMode::rx()`, from
     /// which it is called
     pub(crate) fn new(device: D) -> Self {
-        RxMode { device }
+        RxMode {
+            device,
+            full: false,
+            policy: LengthPolicy::Fit,
+            filter: None,
+            zero_width: ZeroWidthPolicy::Drop,
+            clock: None,
+            seen_at: None,
+            last_arrival: None,
+            acks: [
+                (0, Payload::new(&[])),
+                (0, Payload::new(&[])),
+                (0, Payload::new(&[])),
+            ],
+            acks_len: 0,
+        }
+    }
+
+    /// Handle packets on static pipes whose reported width disagrees
+    /// with `RX_PW_Px` according to `policy`,
+    /// [`LengthPolicy::Fit`](enum.LengthPolicy.html#variant.Fit) by
+    /// default
+    ///
+    /// Packets on static pipes are read with the length of
+    /// [`set_pipes_rx_lengths()`](trait.Configuration.html#method.set_pipes_rx_lengths),
+    /// as cached by the device or else read back from the chip, and
+    /// without `R_RX_PL_WID` if no pipe is dynamic. Has to be set again
+    /// after leaving RX mode.
+    pub fn set_length_policy(&mut self, policy: LengthPolicy) {
+        self.policy = policy;
+    }
+
+    /// Drop packets that do not pass `filter`, e.g. of another protocol
+    /// on the same address, before they are returned
+    ///
+    /// Dropped packets are counted in
+    /// [`Stats::packets_filtered`](struct.Stats.html#structfield.packets_filtered).
+    /// Corrupt packets are still returned as empty payloads. `None`
+    /// disables filtering. Has to be set again after leaving RX mode.
+    pub fn set_prefix_filter(&mut self, filter: Option<PrefixFilter>) {
+        self.filter = filter;
+    }
+
+    /// Handle packets with a payload width of 0 according to `policy`,
+    /// [`ZeroWidthPolicy::Drop`](enum.ZeroWidthPolicy.html#variant.Drop)
+    /// by default
+    ///
+    /// Has to be set again after leaving RX mode.
+    pub fn set_zero_width_policy(&mut self, policy: ZeroWidthPolicy) {
+        self.zero_width = policy;
     }
 
     /// Disable `CE` so that you can switch into TX mode.
+    ///
+    /// ACK payloads still queued with
+    /// [`queue_ack_payload()`](#method.queue_ack_payload) are flushed
+    /// before entering the next mode, so that they are neither sent as
+    /// packets in TX mode nor answer another request later.
     pub fn standby(self) -> StandbyMode<D> {
-        StandbyMode::from_rx_tx(self.device)
+        StandbyMode::from_rx(self.device, self.acks_len > 0)
+    }
+
+    /// Give up the device with `CE` still high, for
+    /// `RadioMode::suspend()`
+    pub(crate) fn into_device(self) -> D {
+        self.device
+    }
+
+    /// Write `payload` with `W_ACK_PAYLOAD` to be sent with the ACK of
+    /// the next packet received on `pipe_no`, and return whether it fit
+    /// in the TX FIFO
+    ///
+    /// Requires dynamic payload lengths and `EN_ACK_PAY`, see
+    /// [`set_ack_payloads()`](trait.Configuration.html#method.set_ack_payloads).
+    /// The TX FIFO holds up to 3 ACK payloads for all pipes. The driver
+    /// keeps copies so that
+    /// [`flush_ack_payloads()`](#method.flush_ack_payloads) can flush a
+    /// single pipe, and counts one as sent when it reads a packet
+    /// received on its pipe.
+    pub async fn write_ack_payload(
+        &mut self,
+        pipe_no: u8,
+        payload: &[u8],
+    ) -> Result<bool, D::Error> {
+        if usize::from(pipe_no) >= PIPES_COUNT {
+            return Err(InvalidArgument::Pipe.into());
+        }
+        if payload.len() > 32 {
+            return Err(InvalidArgument::PayloadLength.into());
+        }
+        let command = WriteAckPayload {
+            pipe: pipe_no,
+            payload,
+        };
+        let (status, ()) = self.device.send_command(&command).await?;
+        // The chip ignores the payload if the FIFO was full
+        if status.tx_full() {
+            return Ok(false);
+        }
+        if self.acks_len >= self.acks.len() {
+            // Some were sent unnoticed, e.g. with packets still unread
+            self.acks.rotate_left(1);
+            self.acks_len = self.acks.len().saturating_sub(1);
+        }
+        if let Some(slot) = self.acks.get_mut(self.acks_len) {
+            *slot = (pipe_no, Payload::new(payload));
+            self.acks_len = self.acks_len.saturating_add(1);
+        }
+        Ok(true)
+    }
+
+    /// Same as [`write_ack_payload()`](#method.write_ack_payload)
+    pub async fn queue_ack_payload(
+        &mut self,
+        pipe_no: u8,
+        payload: &[u8],
+    ) -> Result<bool, D::Error> {
+        self.write_ack_payload(pipe_no, payload).await
+    }
+
+    /// Number of ACK payloads waiting in the TX FIFO, 0 to 3
+    ///
+    /// `FIFO_STATUS` only tells empty and full apart. In between, this
+    /// relies on the driver's count of the payloads queued and sent.
+    pub async fn ack_queue_depth(&mut self) -> Result<u8, D::Error> {
+        let (_, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        if fifo_status.tx_empty() {
+            self.acks_len = 0;
+            Ok(0)
+        } else if fifo_status.tx_full() {
+            Ok(3)
+        } else {
+            Ok(self.acks_len.clamp(1, 2) as u8)
+        }
+    }
+
+    /// Flush the ACK payloads pending for `pipe_no`, or for all pipes
+    /// with `None`, e.g. after a peer has left
+    ///
+    /// The chip can only flush the whole TX FIFO, so for a single pipe
+    /// the ACK payloads of the other pipes are queued again, in order.
+    pub async fn flush_ack_payloads(&mut self, pipe_no: Option<u8>) -> Result<(), D::Error> {
+        self.device.send_command(&FlushTx).await?;
+        let queued = self.acks_len;
+        self.acks_len = 0;
+        let Some(pipe_no) = pipe_no else {
+            return Ok(());
+        };
+        for i in 0..queued {
+            let Some((other, payload)) = self.acks.get(i) else {
+                break;
+            };
+            if *other != pipe_no {
+                self.device.write_payload(W_ACK_PAYLOAD | other, payload).await?;
+                self.acks.swap(self.acks_len, i);
+                self.acks_len = self.acks_len.saturating_add(1);
+            }
+        }
+        Ok(())
+    }
+
+    /// A packet on `pipe_no` has been read, so its ACK carried the first
+    /// payload queued for that pipe
+    fn ack_sent(&mut self, pipe_no: u8) {
+        let queued = self.acks.get_mut(..self.acks_len).unwrap_or_default();
+        if let Some(i) = queued.iter().position(|(pipe, _)| *pipe == pipe_no) {
+            queued.get_mut(i..).unwrap_or_default().rotate_left(1);
+            self.acks_len = self.acks_len.saturating_sub(1);
+        }
+    }
+
+    /// Record arrival times from `clock`, e.g. `&EmbassyTimer` with the
+    /// `embassy-time` feature
+    ///
+    /// A packet's arrival time is when [`can_read()`](#method.can_read)
+    /// first saw it, or else when it was read. So it is only as exact as
+    /// the polling or the IRQ handling. It is reported to
+    /// [`RadioEvents::on_packet_arrival()`](trait.RadioEvents.html#method.on_packet_arrival)
+    /// and by [`last_arrival()`](#method.last_arrival). `None` stops
+    /// recording. Has to be set again after leaving RX mode.
+    pub fn set_timestamp_source(&mut self, clock: Option<&'static (dyn Clock + Sync)>) {
+        self.clock = clock;
+        self.seen_at = None;
+        self.last_arrival = None;
+    }
+
+    /// Arrival time of the packet last returned by a read, if a
+    /// [timestamp source](#method.set_timestamp_source) is set
+    pub fn last_arrival(&self) -> Option<u64> {
+        self.last_arrival
     }
 
     /// Is there any incoming data to read? Return the pipe number.
@@ -44,15 +317,53 @@ impl<D: Device> RxMode<D> {
         clear.set_max_rt(true);
         self.device.write_register(clear).await?;
 
-        self.device
-            .read_register::<FifoStatus>().await
-            .map(|(status, fifo_status)| {
-                if !fifo_status.rx_empty() {
-                    Some(status.rx_p_no())
-                } else {
-                    None
-                }
-            })
+        let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        self.check_overflow(&fifo_status);
+        if fifo_status.rx_empty() {
+            return Ok(None);
+        }
+        let mut pipe_no = status.rx_p_no();
+        if pipe_no == RX_P_NO_EMPTY {
+            // A packet arrived after STATUS was latched, ask again
+            let (status, ()) = self.device.send_command(&Nop).await?;
+            pipe_no = status.rx_p_no();
+        }
+        if pipe_no == RX_P_NO_EMPTY {
+            Ok(None)
+        } else {
+            if self.seen_at.is_none() {
+                self.seen_at = self.clock.map(Clock::now_micros);
+            }
+            Ok(Some(pipe_no))
+        }
+    }
+
+    /// Report when the RX FIFO fills up, as the chip drops any packets
+    /// arriving while it is full
+    fn check_overflow(&mut self, fifo_status: &FifoStatus) {
+        if fifo_status.rx_full() && !self.full {
+            self.device.stats_mut().count_rx_overflow();
+            self.device.events().on_rx_overflow();
+        }
+        self.full = fifo_status.rx_full();
+    }
+
+    /// Wait according to `strategy` until there is incoming data to
+    /// read, and return the pipe number like
+    /// [`can_read()`](#method.can_read)
+    ///
+    /// With the `IRQ` pin, the `RX_DR` interrupt must not be masked.
+    pub async fn wait_read<IRQ: Wait, DL: DelayNs>(
+        &mut self,
+        strategy: &mut Strategy<IRQ>,
+        delay: &mut DL,
+    ) -> Result<u8, D::Error> {
+        loop {
+            if let Some(pipe_no) = self.can_read().await? {
+                return Ok(pipe_no);
+            }
+            strategy.wait(delay).await;
+        }
     }
 
     /// Is an in-band RF signal detected?
@@ -67,6 +378,81 @@ impl<D: Device> RxMode<D> {
             .map(|(_, cd)| cd.0 & 1 == 1)
     }
 
+    /// Switch to `channel` without leaving RX mode
+    ///
+    /// Drops `CE` just for the `RF_CH` write, so the chip is blind for
+    /// that one SPI transaction plus the 130μs RX settling, instead of a
+    /// round trip through [`StandbyMode`](struct.StandbyMode.html). A
+    /// packet on air meanwhile is lost, packets in the RX FIFO are kept.
+    /// `RPD` resets with `CE` and reads low until a carrier has been
+    /// seen for 40μs on the new channel. `CE` goes high again even if
+    /// the write fails.
+    pub async fn retune(&mut self, channel: Channel) -> Result<(), D::Error> {
+        self.device.ce_disable();
+        let result = self.set_frequency(channel).await;
+        self.device.ce_enable();
+        result
+    }
+
+    /// Percentage of time that `channel` is occupied, sampling `RPD`
+    /// `samples` times every `interval_us` after tuning to it
+    ///
+    /// For listen-before-talk and duty cycle checks. The previous channel
+    /// is restored afterwards. Packets arriving meanwhile stay in the RX
+    /// FIFO.
+    pub async fn channel_occupancy<DL: DelayNs>(
+        &mut self,
+        channel: Channel,
+        samples: u16,
+        interval_us: u32,
+        delay: &mut DL,
+    ) -> Result<u8, D::Error> {
+        let previous = self.get_frequency().await?;
+        self.retune(channel).await?;
+        // RX settling plus carrier detect
+        delay.delay_us(130 + 40).await;
+        let mut busy = 0_u32;
+        for _ in 0..samples {
+            if self.has_carrier().await? {
+                busy = busy.saturating_add(1);
+            }
+            delay.delay_us(interval_us).await;
+        }
+        self.retune(previous).await?;
+        Ok(busy.saturating_mul(100).checked_div(u32::from(samples)).unwrap_or(0) as u8)
+    }
+
+    /// Sweep `channels` over and over, passing each `(channel, rpd)`
+    /// sample to `sample` until it returns `ControlFlow::Break`
+    ///
+    /// Intended for live spectrum displays. Retunes with `CE` low and
+    /// waits 130μs for RX settling plus 40μs for carrier detect on each
+    /// channel. Packets arriving meanwhile stay in the RX FIFO. The
+    /// previous channel is restored before returning.
+    pub async fn stream_spectrum<DL, F>(
+        &mut self,
+        channels: RangeInclusive<Channel>,
+        delay: &mut DL,
+        mut sample: F,
+    ) -> Result<(), D::Error>
+    where
+        DL: DelayNs,
+        F: FnMut(Channel, bool) -> ControlFlow<()>,
+    {
+        let previous = self.get_frequency().await?;
+        'sweep: while !channels.is_empty() {
+            for channel in Channel::range(channels.clone()) {
+                self.retune(channel).await?;
+                delay.delay_us(130 + 40).await;
+                let rpd = self.has_carrier().await?;
+                if sample(channel, rpd).is_break() {
+                    break 'sweep;
+                }
+            }
+        }
+        self.retune(previous).await
+    }
+
     /// Is the RX queue empty?
     pub async fn is_empty(&mut self) -> Result<bool, D::Error> {
         self.device
@@ -76,19 +462,163 @@ impl<D: Device> RxMode<D> {
 
     /// Is the RX queue full?
     pub async fn is_full(&mut self) -> Result<bool, D::Error> {
-        self.device
-            .read_register::<FifoStatus>().await
-            .map(|(_, fifo_status)| fifo_status.rx_full())
+        let (_, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        self.check_overflow(&fifo_status);
+        Ok(fifo_status.rx_full())
     }
 
     /// Read the next received packet
+    ///
+    /// If the chip reports a payload width above 32 bytes, the packet is
+    /// corrupt. The RX FIFO is then flushed and an empty payload returned.
+    ///
+    /// Returns an empty payload as well if the RX FIFO is empty. Use
+    /// [`read_with_pipe()`](#method.read_with_pipe) to tell apart.
     pub async fn read(&mut self) -> Result<Payload, D::Error> {
-        let (_, payload_width) = self.device.send_command(&ReadRxPayloadWidth).await?;
-        let (_, payload) = self
-            .device
-            .send_command(&ReadRxPayload::new(payload_width as usize)).await?;
+        let payload = self
+            .read_with_pipe()
+            .await?
+            .map(|(_, payload)| payload)
+            .unwrap_or_else(|| Payload::new(&[]));
         Ok(payload)
     }
+
+    /// Read the next received packet and the number of the pipe it was
+    /// received on, or `None` if the RX FIFO is empty
+    ///
+    /// The pipe is taken from `RX_P_NO` in the `STATUS` returned by
+    /// `R_RX_PL_WID`, where `0b111` means that the FIFO is empty. Like
+    /// [`read()`](#method.read), a corrupt packet is flushed along with
+    /// the FIFO, returning an empty payload. Packets on static pipes are
+    /// read with their configured length, see
+    /// [`set_length_policy()`](#method.set_length_policy), and skipped
+    /// if they do not pass the [prefix filter](#method.set_prefix_filter).
+    /// Returns `None` once it has skipped as many packets as the FIFO
+    /// holds, so that a chip with a stuck `MISO` can not keep it busy.
+    pub async fn read_with_pipe(&mut self) -> Result<Option<(u8, Payload)>, D::Error> {
+        for _ in 0..RX_FIFO_DEPTH {
+            let (pipe_no, payload) = match self.read_fifo().await? {
+                Some(packet) => packet,
+                None => return Ok(None),
+            };
+            let passes = match &self.filter {
+                Some(filter) => payload.is_empty() || filter.matches(&payload),
+                None => true,
+            };
+            // Later packets in the FIFO were seen no earlier than now
+            let arrival = self.seen_at.take().or_else(|| self.clock.map(Clock::now_micros));
+            if passes {
+                self.last_arrival = arrival;
+                if !payload.is_empty() {
+                    if let Some(at) = arrival {
+                        self.device.events().on_packet_arrival(pipe_no, at);
+                    }
+                    self.device.events().on_packet_received(pipe_no, &payload);
+                }
+                return Ok(Some((pipe_no, payload)));
+            }
+            self.device.stats_mut().count_filtered();
+        }
+        Ok(None)
+    }
+
+    /// Payload lengths of the pipes, from the device or else the chip
+    async fn rx_lengths(&mut self) -> Result<[Option<u8>; PIPES_COUNT], D::Error> {
+        match self.device.rx_lengths() {
+            Some(lengths) => Ok(lengths),
+            None => self.get_pipes_rx_lengths().await,
+        }
+    }
+
+    /// Read the next packet from the RX FIFO, with the static lengths
+    /// applied
+    async fn read_fifo(&mut self) -> Result<Option<(u8, Payload)>, D::Error> {
+        let lengths = self.rx_lengths().await?;
+        // R_RX_PL_WID is undefined with EN_DPL cleared
+        let dynamic = lengths.iter().any(Option::is_none);
+        for _ in 0..RX_FIFO_DEPTH {
+            let (status, reported) = if dynamic {
+                self.device.send_command(&ReadRxPayloadWidth).await?
+            } else {
+                let (status, ()) = self.device.send_command(&Nop).await?;
+                (status, 0)
+            };
+            let pipe_no = status.rx_p_no();
+            if pipe_no == RX_P_NO_EMPTY {
+                return Ok(None);
+            }
+            self.ack_sent(pipe_no);
+            let static_len = lengths.get(usize::from(pipe_no)).copied().flatten();
+            let payload_width = static_len.unwrap_or(reported);
+            let mismatch = dynamic && static_len.is_some_and(|len| len != reported);
+            if mismatch && self.policy == LengthPolicy::Reject {
+                let mut dropped = Payload::zeroed(usize::from(payload_width.min(32)));
+                self.device.read_payload(R_RX_PAYLOAD, dropped.data_mut()).await?;
+                return Ok(Some((pipe_no, Payload::new(&[]))));
+            }
+            if payload_width == 0 {
+                self.device.stats_mut().count_zero_width();
+                if self.zero_width == ZeroWidthPolicy::Flush {
+                    self.device.send_command(&FlushRx).await?;
+                    return Ok(Some((pipe_no, Payload::new(&[]))));
+                }
+                // Clock out a byte to pop the packet from the FIFO
+                self.device.read_payload(R_RX_PAYLOAD, &mut [0]).await?;
+                if self.zero_width == ZeroWidthPolicy::Deliver {
+                    return Ok(Some((pipe_no, Payload::new(&[]))));
+                }
+                continue;
+            }
+            if payload_width > 32 {
+                self.device.send_command(&FlushRx).await?;
+                return Ok(Some((pipe_no, Payload::new(&[]))));
+            }
+            let mut payload = Payload::zeroed(usize::from(payload_width));
+            self.device
+                .read_payload(R_RX_PAYLOAD, payload.data_mut())
+                .await?;
+            return Ok(Some((pipe_no, payload)));
+        }
+        // More empty packets than the FIFO holds: the chip does not
+        // answer sensibly, so start over with an empty FIFO
+        self.device.send_command(&FlushRx).await?;
+        Ok(None)
+    }
+
+    /// Read all packets from the RX FIFO, passing each one with its
+    /// pipe number to `f`. Returns the number of packets read.
+    ///
+    /// `STATUS` only updates `RX_P_NO` after a payload has been clocked
+    /// out, so this loops until it reads `0b111` rather than counting on
+    /// a previous `FIFO_STATUS`.
+    pub async fn drain<F>(&mut self, mut f: F) -> Result<usize, D::Error>
+    where
+        F: FnMut(u8, &Payload),
+    {
+        let mut count = 0_usize;
+        while let Some((pipe_no, payload)) = self.read_with_pipe().await? {
+            f(pipe_no, &payload);
+            count = count.saturating_add(1);
+        }
+        Ok(count)
+    }
+
+    /// Read the next received packet from pipe `pipe_no` (as returned
+    /// by [`can_read()`](#method.can_read)), dropping it if `filter`
+    /// considers it a duplicate.
+    #[cfg(feature = "dedup")]
+    pub async fn read_unique(
+        &mut self,
+        pipe_no: u8,
+        filter: &mut DuplicateFilter,
+    ) -> Result<Option<Payload>, D::Error> {
+        let payload = self.read().await?;
+        if filter.is_duplicate(pipe_no, &payload) {
+            Ok(None)
+        } else {
+            Ok(Some(payload))
+        }
+    }
 }
 
 impl<D: Device> Configuration for RxMode<D> {
@@ -97,3 +627,114 @@ impl<D: Device> Configuration for RxMode<D> {
         &mut self.device
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{standby, Air};
+    use crate::RadioConfig;
+
+    #[test]
+    fn static_pipes_skip_payload_width() {
+        block_on(async {
+            let air = Air::new();
+            let config = RadioConfig::static_payloads(8);
+            let mut tx = standby(&air, &config).await.tx().await.map_err(|(_, e)| e).unwrap();
+            let (ce, spi) = air.radio();
+            let chip = spi.chip();
+            let mut rx = crate::sim::SimRadio::new(ce, spi).await.unwrap();
+            rx.configure(&config).await.unwrap();
+            let mut rx = rx.rx().await.map_err(|(_, e)| e).unwrap();
+
+            tx.send(b"12345678").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            chip.clear_opcodes();
+            assert_eq!(&rx.read().await.unwrap()[..], b"12345678");
+            assert!(!chip.opcodes().contains(&0x60));
+        });
+    }
+
+    #[test]
+    fn ack_payload_survives_reinit() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = crate::sim::pair(&air).await;
+            tx.set_ack_payloads(true).await.unwrap();
+            rx.set_ack_payloads(true).await.unwrap();
+            rx.device().spi.chip().power_cycle();
+            rx.device().ce_disable();
+            let mut timer = crate::sim::SimTimer::new();
+            rx.reinit(&RadioConfig::default(), &mut timer).await.unwrap();
+            rx.device().update_config(|config| config.set_prim_rx(true)).await.unwrap();
+            rx.device().ce_enable();
+
+            assert_eq!(rx.write_ack_payload(0, b"pong").await.ok(), Some(true));
+            tx.send(b"ping").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert_eq!(&tx.read_ack_payload().await.unwrap().unwrap()[..], b"pong");
+        });
+    }
+
+    #[test]
+    fn stream_spectrum_sweeps_channel_range() {
+        block_on(async {
+            let air = Air::new();
+            let (_, mut rx) = crate::sim::pair(&air).await;
+            let first = Channel::new(10).unwrap();
+            let last = Channel::new(13).unwrap();
+            let mut seen = [None; 8];
+            let mut samples = 0;
+            rx.stream_spectrum(first..=last, &mut crate::sim::SimTimer::new(), |channel, _| {
+                seen[samples] = Some(channel.value());
+                samples += 1;
+                if samples == seen.len() {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .await
+            .unwrap();
+            let sweep = [Some(10), Some(11), Some(12), Some(13)];
+            assert_eq!(seen[..4], sweep);
+            assert_eq!(seen[4..], sweep);
+            assert_eq!(rx.get_frequency().await.ok(), Some(Channel::default()));
+        });
+    }
+
+    #[test]
+    fn mixed_pipes_read_static_length() {
+        block_on(async {
+            let air = Air::new();
+            let mut config = RadioConfig::default();
+            config.pipes_rx_lengths[0] = Some(4);
+            let mut tx = standby(&air, &config).await.tx().await.map_err(|(_, e)| e).unwrap();
+            let mut rx = standby(&air, &config).await.rx().await.map_err(|(_, e)| e).unwrap();
+
+            tx.send(b"abcd").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            let read = rx.read_with_pipe().await.unwrap();
+            assert_eq!(read.map(|(pipe, payload)| (pipe, payload.len())), Some((0, 4)));
+        });
+    }
+
+    #[test]
+    fn arrival_is_stamped_when_first_seen() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = crate::sim::pair(&air).await;
+            let timer = crate::sim::SimTimer::new();
+            let clock: &'static crate::sim::SimTimer = Box::leak(Box::new(timer.clone()));
+            rx.set_timestamp_source(Some(clock));
+
+            tx.send(b"x").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            timer.advance(100);
+            assert_eq!(rx.can_read().await.ok(), Some(Some(0)));
+            timer.advance(50);
+            assert_eq!(&*rx.read().await.unwrap(), b"x");
+            assert_eq!(rx.last_arrival(), Some(100));
+        });
+    }
+}
@@ -0,0 +1,267 @@
+//! Byte layouts of the frames the crate defines, for host tools
+//!
+//! A Python or C# gateway that decodes [`HealthFrame`](../struct.HealthFrame.html)
+//! reports or talks to a [`SerialBridge`](../struct.SerialBridge.html)
+//! dongle can generate its parsers from [`SCHEMAS`](constant.SCHEMAS.html)
+//! in a build script, instead of copying the tables of the docs. The
+//! schemas are built from the same constants as the encoders, so they
+//! change along with them. [`Schema::write_json()`](struct.Schema.html#method.write_json)
+//! writes one as JSON.
+
+#[cfg(feature = "bridge")]
+use crate::bridge::{
+    CAPTURE_MAGIC, CAPTURE_VERSION, DOWN_CHANNEL, DOWN_SEND, UP_CAPTURE, UP_PACKET, UP_SENT,
+};
+#[cfg(feature = "diagnostics")]
+use crate::health::{self, HealthFrame};
+#[cfg(feature = "bridge")]
+use crate::MAX_ADDR_BYTES;
+use core::fmt;
+
+/// Encoding of a [`Field`](struct.Field.html). Integers are little
+/// endian.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FieldKind {
+    /// Fixed bytes that identify the frame, such as a tag, a frame type
+    /// or a layout version
+    Const(&'static [u8]),
+    /// Unsigned byte
+    U8,
+    /// Unsigned 16-bit integer
+    U16,
+    /// Unsigned 32-bit integer
+    U32,
+    /// Length byte, followed by up to `max` bytes
+    Prefixed {
+        /// Longest content
+        max: u8,
+    },
+    /// Up to `max` bytes, up to the end of the frame
+    Rest {
+        /// Longest content
+        max: u8,
+    },
+    /// Another frame, up to the end of this one
+    Nested(&'static Schema),
+}
+
+/// Named field of a [`Schema`](struct.Schema.html)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Field {
+    /// Name, in `snake_case`
+    pub name: &'static str,
+    /// Encoding
+    pub kind: FieldKind,
+}
+
+/// How frames are delimited on their transport
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Framing {
+    /// As is, e.g. one frame per radio packet
+    Raw,
+    /// COBS encoded and terminated with `0x00`, on a serial link
+    Cobs,
+}
+
+/// Byte layout of a frame
+///
+/// Fields follow each other without padding, in order.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Schema {
+    /// Name, in `snake_case`
+    pub name: &'static str,
+    /// Delimiting on the transport
+    pub framing: Framing,
+    /// Fields, in order
+    pub fields: &'static [Field],
+}
+
+impl Schema {
+    /// Length of the frame, or `None` if it varies
+    pub const fn fixed_len(&self) -> Option<usize> {
+        let mut len = 0_usize;
+        let mut fields = self.fields;
+        while let [field, rest @ ..] = fields {
+            len = len.saturating_add(match field.kind {
+                FieldKind::Const(bytes) => bytes.len(),
+                FieldKind::U8 => 1,
+                FieldKind::U16 => 2,
+                FieldKind::U32 => 4,
+                FieldKind::Prefixed { .. } | FieldKind::Rest { .. } | FieldKind::Nested(_) => {
+                    return None
+                }
+            });
+            fields = rest;
+        }
+        Some(len)
+    }
+
+    /// Write as a JSON object, e.g. from a build script
+    ///
+    /// Nested frames are referenced by name:
+    ///
+    /// ```json
+    /// {"name":"health_frame","framing":"raw","fields":[
+    ///   {"name":"tag","type":"const","value":[72]},
+    ///   {"name":"uptime_s","type":"u32"}, ...]}
+    /// ```
+    pub fn write_json<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        let framing = match self.framing {
+            Framing::Raw => "raw",
+            Framing::Cobs => "cobs",
+        };
+        write!(out, r#"{{"name":"{}","framing":"{}","fields":["#, self.name, framing)?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.write_char(',')?;
+            }
+            write!(out, r#"{{"name":"{}","type":"#, field.name)?;
+            match field.kind {
+                FieldKind::Const(bytes) => {
+                    out.write_str(r#""const","value":["#)?;
+                    for (i, byte) in bytes.iter().enumerate() {
+                        if i > 0 {
+                            out.write_char(',')?;
+                        }
+                        write!(out, "{}", byte)?;
+                    }
+                    out.write_char(']')?;
+                }
+                FieldKind::U8 => out.write_str(r#""u8""#)?,
+                FieldKind::U16 => out.write_str(r#""u16""#)?,
+                FieldKind::U32 => out.write_str(r#""u32""#)?,
+                FieldKind::Prefixed { max } => write!(out, r#""prefixed","max":{}"#, max)?,
+                FieldKind::Rest { max } => write!(out, r#""rest","max":{}"#, max)?,
+                FieldKind::Nested(schema) => write!(out, r#""nested","schema":"{}""#, schema.name)?,
+            }
+            out.write_char('}')?;
+        }
+        out.write_str("]}")
+    }
+}
+
+const fn field(name: &'static str, kind: FieldKind) -> Field {
+    Field { name, kind }
+}
+
+/// [`HealthFrame`](../struct.HealthFrame.html), sent by nodes
+#[cfg(feature = "diagnostics")]
+pub const HEALTH_FRAME: Schema = Schema {
+    name: "health_frame",
+    framing: Framing::Raw,
+    fields: &[
+        field("tag", FieldKind::Const(&[health::TAG])),
+        field("version", FieldKind::Const(&[health::VERSION])),
+        field("uptime_s", FieldKind::U32),
+        field("sent", FieldKind::U32),
+        field("acked", FieldKind::U32),
+        field("lost", FieldKind::U32),
+        field("busy_percent", FieldKind::U8),
+        field("config_hash", FieldKind::U16),
+    ],
+};
+
+// The encoder and the schema must agree on the length
+#[cfg(feature = "diagnostics")]
+const _: () = assert!(matches!(HEALTH_FRAME.fixed_len(), Some(HealthFrame::LEN)));
+
+/// [`CaptureRecord`](../struct.CaptureRecord.html). `pipe` has `0x80`
+/// set if `RPD` was high.
+#[cfg(feature = "bridge")]
+pub const CAPTURE_RECORD: Schema = Schema {
+    name: "capture_record",
+    framing: Framing::Raw,
+    fields: &[
+        field("magic", FieldKind::Const(&CAPTURE_MAGIC)),
+        field("version", FieldKind::Const(&[CAPTURE_VERSION])),
+        field("channel", FieldKind::U8),
+        field("status", FieldKind::U8),
+        field("pipe", FieldKind::U8),
+        field("address", FieldKind::Prefixed { max: MAX_ADDR_BYTES as u8 }),
+        field("payload", FieldKind::Prefixed { max: 32 }),
+    ],
+};
+
+/// Received packet, from
+/// [`SerialBridge::encode_packet()`](../struct.SerialBridge.html#method.encode_packet)
+#[cfg(feature = "bridge")]
+pub const BRIDGE_PACKET: Schema = Schema {
+    name: "bridge_packet",
+    framing: Framing::Cobs,
+    fields: &[
+        field("type", FieldKind::Const(&[UP_PACKET])),
+        field("pipe", FieldKind::U8),
+        field("rpd", FieldKind::U8),
+        field("payload", FieldKind::Rest { max: 32 }),
+    ],
+};
+
+/// Send result, from
+/// [`SerialBridge::encode_sent()`](../struct.SerialBridge.html#method.encode_sent)
+#[cfg(feature = "bridge")]
+pub const BRIDGE_SENT: Schema = Schema {
+    name: "bridge_sent",
+    framing: Framing::Cobs,
+    fields: &[
+        field("type", FieldKind::Const(&[UP_SENT])),
+        field("acked", FieldKind::U8),
+        field("retransmits", FieldKind::U8),
+    ],
+};
+
+/// Capture record, from
+/// [`SerialBridge::encode_capture()`](../struct.SerialBridge.html#method.encode_capture)
+#[cfg(feature = "bridge")]
+pub const BRIDGE_CAPTURE: Schema = Schema {
+    name: "bridge_capture",
+    framing: Framing::Cobs,
+    fields: &[
+        field("type", FieldKind::Const(&[UP_CAPTURE])),
+        field("record", FieldKind::Nested(&CAPTURE_RECORD)),
+    ],
+};
+
+/// [`BridgeCommand::Send`](../enum.BridgeCommand.html#variant.Send)
+/// from the host, with the address least significant byte first
+#[cfg(feature = "bridge")]
+pub const BRIDGE_SEND: Schema = Schema {
+    name: "bridge_send",
+    framing: Framing::Cobs,
+    fields: &[
+        field("type", FieldKind::Const(&[DOWN_SEND])),
+        field("address", FieldKind::Prefixed { max: MAX_ADDR_BYTES as u8 }),
+        field("payload", FieldKind::Rest { max: 32 }),
+    ],
+};
+
+/// [`BridgeCommand::SetChannel`](../enum.BridgeCommand.html#variant.SetChannel)
+/// from the host
+#[cfg(feature = "bridge")]
+pub const BRIDGE_CHANNEL: Schema = Schema {
+    name: "bridge_channel",
+    framing: Framing::Cobs,
+    fields: &[
+        field("type", FieldKind::Const(&[DOWN_CHANNEL])),
+        field("channel", FieldKind::U8),
+    ],
+};
+
+/// All schemas, nested ones before those that contain them
+///
+/// Frames of disabled features are left out.
+pub const SCHEMAS: &[&Schema] = &[
+    #[cfg(feature = "diagnostics")]
+    &HEALTH_FRAME,
+    #[cfg(feature = "bridge")]
+    &CAPTURE_RECORD,
+    #[cfg(feature = "bridge")]
+    &BRIDGE_PACKET,
+    #[cfg(feature = "bridge")]
+    &BRIDGE_SENT,
+    #[cfg(feature = "bridge")]
+    &BRIDGE_CAPTURE,
+    #[cfg(feature = "bridge")]
+    &BRIDGE_SEND,
+    #[cfg(feature = "bridge")]
+    &BRIDGE_CHANNEL,
+];
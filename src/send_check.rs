@@ -0,0 +1,239 @@
+//! Compile-time check that the futures returned by the public async
+//! API are `Send` whenever the SPI bus and `CE` pin are, so that the
+//! driver works on multi-threaded executors.
+//!
+//! Nothing outside of the tests is ever called; it only has to
+//! type-check. The tests move futures to other threads and run them
+//! there.
+#![allow(dead_code)]
+
+use crate::{
+    Address, Channel, ChipConfig, Clock, Configuration, CrcMode, DataRate, Failsafe, LinkProfile,
+    NRF24L01, RadioConfig, RadioTimer, RawConfig, RxMode, StandbyMode, TxBuffer, TxMode, Watchdog,
+};
+#[cfg(feature = "peers")]
+use crate::PeerRegistry;
+#[cfg(feature = "roaming")]
+use crate::Roaming;
+#[cfg(feature = "socket")]
+use crate::{Datagram, DatagramSocket, RadioSocket};
+use core::convert::Infallible;
+use embedded_hal::digital::{ErrorType as PinErrorType, OutputPin};
+use embedded_hal::spi::{ErrorType as SpiErrorType, Operation};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+
+struct Pin;
+
+impl PinErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+struct Spi;
+
+impl SpiErrorType for Spi {
+    type Error = Infallible;
+}
+
+impl SpiDevice for Spi {
+    async fn transaction(&mut self, _: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+struct Time;
+
+impl Clock for Time {
+    fn now_micros(&self) -> u64 {
+        0
+    }
+}
+
+impl DelayNs for Time {
+    async fn delay_ns(&mut self, _: u32) {}
+}
+
+impl RadioTimer for Time {}
+
+type Nrf = NRF24L01<Infallible, Pin, Spi>;
+
+fn assert_send<T: Send>(_: &T) {}
+
+fn standby_futures(mut standby: StandbyMode<Nrf>, raw: RawConfig, chip: ChipConfig) {
+    assert_send(&Nrf::new(Pin, Spi));
+    assert_send(&Nrf::probe(Pin, Spi, 3, &mut Time));
+    assert_send(&standby.flush_rx());
+    assert_send(&standby.flush_tx());
+    assert_send(&standby.get_frequency());
+    assert_send(&standby.detect_variant());
+    assert_send(&standby.set_frequency(Channel::MAX));
+    assert_send(&standby.set_rf(&DataRate::R1Mbps, 0));
+    assert_send(&standby.set_crc(CrcMode::TwoBytes));
+    assert_send(&standby.set_interrupt_mask(false, false, false));
+    assert_send(&standby.get_chip_config());
+    assert_send(&standby.set_chip_config(&chip));
+    assert_send(&standby.set_pipes_rx_enable(&[true; 6]));
+    assert_send(&standby.set_rx_addr(0, &[0; 5]));
+    assert_send(&standby.set_tx_addr(&[0; 5]));
+    assert_send(&standby.set_addresses(Address::from_array([0; 5]), [None; 6]));
+    assert_send(&standby.set_auto_retransmit(0, 0));
+    assert_send(&standby.get_auto_ack());
+    assert_send(&standby.set_auto_ack(&[true; 6]));
+    assert_send(&standby.get_address_width());
+    assert_send(&standby.set_address_width(5));
+    assert_send(&standby.get_interrupts());
+    assert_send(&standby.clear_interrupts());
+    assert_send(&standby.set_pipes_rx_lengths(&[None; 6]));
+    assert_send(&standby.get_pipes_rx_lengths());
+    assert_send(&standby.reinit(&RadioConfig::default(), &mut Time));
+    assert_send(&standby.configure_raw(&raw));
+    assert_send(&standby.get_radio_config());
+    assert_send(&standby.transaction(|config| config.channel = Channel::MAX));
+    assert_send(&standby.rx());
+}
+
+fn standby_power_futures(standby: StandbyMode<Nrf>, device: Nrf) {
+    assert_send(&StandbyMode::power_up(device));
+    assert_send(&standby.power_down());
+}
+
+fn standby_power_down_verified_future(standby: StandbyMode<Nrf>) {
+    assert_send(&standby.power_down_verified());
+}
+
+fn standby_tx_future(standby: StandbyMode<Nrf>) {
+    assert_send(&standby.tx());
+}
+
+fn rx_futures(mut rx: RxMode<Nrf>) {
+    assert_send(&rx.can_read());
+    assert_send(&rx.has_carrier());
+    assert_send(&rx.is_empty());
+    assert_send(&rx.is_full());
+    assert_send(&rx.read());
+    assert_send(&rx.read_with_pipe());
+    assert_send(&rx.drain(|_, _| {}));
+    assert_send(&rx.retune(Channel::MAX));
+    assert_send(&rx.queue_ack_payload(0, &[]));
+    assert_send(&rx.ack_queue_depth());
+    assert_send(&rx.flush_ack_payloads(None));
+}
+
+fn tx_futures(mut tx: TxMode<Nrf>) {
+    assert_send(&tx.is_empty());
+    assert_send(&tx.is_full());
+    assert_send(&tx.can_send());
+    assert_send(&tx.send(&[]));
+    assert_send(&tx.try_send(&[]));
+    assert_send(&tx.send_buffer(&TxBuffer::new()));
+    assert_send(&tx.poll_send());
+    assert_send(&tx.send_tracked(&[]));
+    assert_send(&tx.poll_tracked());
+    assert_send(&tx.wait_empty());
+    assert_send(&tx.flush_stale(&Time, 0));
+    assert_send(&tx.send_fast(&[], &Time));
+    assert_send(&tx.read_ack_payload());
+    assert_send(&tx.observe());
+    assert_send(&tx.fifo_level());
+    assert_send(&tx.telemetry());
+    assert_send(&tx.standby());
+}
+
+fn tx_standby_preserving_future(tx: TxMode<Nrf>) {
+    assert_send(&tx.standby_preserving());
+}
+
+fn watchdog_futures(mut watchdog: Watchdog, mut rx: RxMode<Nrf>, mut tx: TxMode<Nrf>) {
+    assert_send(&watchdog.check_rx(&mut rx, &mut Time, true));
+    assert_send(&watchdog.check_tx(&mut tx, &mut Time));
+}
+
+#[cfg(feature = "roaming")]
+fn roaming_futures(mut roaming: Roaming<2>, mut tx: TxMode<Nrf>) {
+    assert_send(&roaming.attach(&mut tx, 0));
+    assert_send(&roaming.send(&mut tx, &[], &mut Time));
+    assert_send(&roaming.roam(&mut tx, &mut Time));
+}
+
+#[cfg(feature = "socket")]
+fn socket_futures(mut socket: RadioSocket<Nrf>) {
+    let peer = Address::from_array([0; 5]);
+    assert_send(&socket.bind(&peer));
+    assert_send(&socket.send(&[], &mut Time));
+    assert_send(&socket.send_to(&peer, &[], &mut Time));
+    assert_send(&socket.recv());
+}
+
+#[cfg(feature = "socket")]
+fn datagram_futures(mut socket: DatagramSocket<Nrf, Time>) {
+    let peer = Address::from_array([0; 5]);
+    assert_send(&socket.send_to(&peer, &[]));
+    assert_send(&socket.receive_into(&mut []));
+}
+
+fn profile_futures(mut rx: RxMode<Nrf>, mut tx: TxMode<Nrf>) {
+    type Remote = LinkProfile<4, 3, true>;
+    assert_send(&Remote::send(&mut tx, &[0; 4]));
+    assert_send(&Remote::read(&mut rx));
+}
+
+#[cfg(feature = "peers")]
+fn peer_futures(peers: PeerRegistry<2>, mut tx: TxMode<Nrf>) {
+    assert_send(&peers.select(&mut tx, 0));
+    assert_send(&peers.send_to(&mut tx, 0, &[], &mut Time));
+}
+
+fn failsafe_futures(mut failsafe: Failsafe, mut rx: RxMode<Nrf>) {
+    assert_send(&failsafe.read(&mut rx, &Time));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+    use crate::{Configuration, Strategy};
+    use std::thread;
+
+    #[test]
+    fn futures_complete_on_another_thread() {
+        let air = Air::new();
+        let (mut tx, mut rx) = block_on(sim::pair(&air));
+        // Created here, polled there
+        let send = async move {
+            tx.send(b"moved").await.unwrap();
+            let acked = tx.wait_send(&mut Strategy::polling(100), &mut SimTimer::new()).await;
+            (tx, acked.unwrap())
+        };
+        let (mut tx, acked) = thread::spawn(move || block_on(send)).join().unwrap();
+        assert!(acked);
+        let read = async move { rx.read().await.unwrap() };
+        assert_eq!(&thread::spawn(move || block_on(read)).join().unwrap()[..], b"moved");
+        assert!(block_on(tx.is_empty()).unwrap());
+    }
+
+    #[test]
+    fn modes_move_between_threads() {
+        let air = Air::new();
+        let config = crate::RadioConfig::default();
+        let standby = block_on(sim::standby(&air, &config));
+        let rx = thread::spawn(move || {
+            block_on(async move {
+                let mut standby = standby;
+                standby.set_frequency(crate::Channel::MAX).await.unwrap();
+                standby.rx().await.map_err(|(_, e)| e).unwrap()
+            })
+        });
+        let mut rx = rx.join().unwrap();
+        assert_eq!(block_on(rx.get_frequency()).ok(), Some(crate::Channel::MAX));
+    }
+}
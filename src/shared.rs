@@ -0,0 +1,113 @@
+use crate::device::Device;
+use crate::tx::{TxMode, TxTelemetry};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+
+/// Async mutex for sharing the driver, or one of its modes, between
+/// tasks
+///
+/// `SharedRadio<M, T>` is `Sync` whenever `T` is `Send`, so it can be
+/// put into a `static` and used from multi-threaded executors. Use
+/// `CriticalSectionRawMutex` on multicore MCUs and for gateways, or
+/// `NoopRawMutex` when all tasks run on the same executor.
+///
+/// As mode transitions consume the mode, wrap an `Option` or an enum
+/// of the modes you want to switch between.
+pub struct SharedRadio<M: RawMutex, T> {
+    inner: Mutex<M, T>,
+}
+
+impl<M: RawMutex, T> SharedRadio<M, T> {
+    /// Constructor
+    pub const fn new(radio: T) -> Self {
+        SharedRadio {
+            inner: Mutex::new(radio),
+        }
+    }
+
+    /// Wait for exclusive access
+    pub async fn lock(&self) -> MutexGuard<'_, M, T> {
+        self.inner.lock().await
+    }
+
+    /// Get exclusive access if nobody else holds it
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, M, T>> {
+        self.inner.try_lock().ok()
+    }
+
+    /// Get a mutable reference without locking
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    /// Return the wrapped value
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<M: RawMutex, D: Device> SharedRadio<M, TxMode<D>> {
+    /// Read [`TxMode::telemetry()`](struct.TxMode.html#method.telemetry)
+    /// from another task, e.g. for live retry counts
+    ///
+    /// Waits for the lock, so the sending task should take it for each
+    /// call rather than for a whole transfer.
+    pub async fn telemetry(&self) -> Result<TxTelemetry, D::Error> {
+        self.lock().await.telemetry().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air, SimTimer};
+    use crate::Strategy;
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+    use std::thread;
+
+    #[test]
+    fn threads_take_turns() {
+        let air = Air::new();
+        let (tx, mut rx) = block_on(sim::pair(&air));
+        let shared = SharedRadio::<CriticalSectionRawMutex, _>::new(tx);
+        thread::scope(|scope| {
+            // Three, the depth of the RX FIFO
+            let senders: Vec<_> = (0..3u8)
+                .map(|i| {
+                    let shared = &shared;
+                    scope.spawn(move || {
+                        block_on(async move {
+                            let mut tx = shared.lock().await;
+                            tx.send(&[i]).await.unwrap();
+                            let mut strategy = Strategy::polling(100);
+                            tx.wait_send(&mut strategy, &mut SimTimer::new()).await.unwrap()
+                        })
+                    })
+                })
+                .collect();
+            for sender in senders {
+                assert!(sender.join().unwrap());
+            }
+        });
+
+        let mut received: Vec<u8> = (0..3).map(|_| block_on(rx.read()).unwrap()[0]).collect();
+        received.sort();
+        assert_eq!(received, [0, 1, 2]);
+    }
+
+    #[test]
+    fn telemetry_waits_for_the_lock() {
+        let air = Air::new();
+        let (tx, _rx) = block_on(sim::pair(&air));
+        let shared = SharedRadio::<CriticalSectionRawMutex, _>::new(tx);
+        let guard = shared.try_lock().unwrap();
+        assert!(shared.try_lock().is_none());
+        thread::scope(|scope| {
+            let telemetry = scope.spawn(|| block_on(shared.telemetry()).unwrap());
+            drop(guard);
+            assert_eq!(telemetry.join().unwrap().fifo_level, 0);
+        });
+    }
+}
+
@@ -0,0 +1,1036 @@
+//! Simulated chips on a shared air, for tests without hardware
+//!
+//! [`Air::radio()`](struct.Air.html#method.radio) attaches a chip and
+//! returns the `CE` pin and the SPI bus to construct the driver with, so
+//! that tests run the driver's own [`Device`](../trait.Device.html)
+//! implementation against it:
+//!
+//! ```ignore
+//! let air = Air::new();
+//! let (ce, spi) = air.radio();
+//! let chip = spi.chip();
+//! let standby = block_on(NRF24L01::new(ce, spi))?;
+//! ```
+//!
+//! A chip has the register map, the three level FIFOs, `CE` and the
+//! interrupt flags of the datasheet. Packets go on air as soon as a chip
+//! is in TX mode with `CE` high, and reach every chip in RX mode on the
+//! same channel, data rate, CRC and address, with auto-ack,
+//! retransmits and ACK payloads. Everything happens instantly, without
+//! simulated time: the outcome of a transmission is in `STATUS` by the
+//! next SPI transaction. [`SimChip`](struct.SimChip.html) inspects a
+//! chip and injects faults.
+
+use crate::time::Clock;
+use crate::timer::RadioTimer;
+use crate::variant::ChipVariant;
+use crate::NRF24L01;
+use core::convert::Infallible;
+#[cfg(test)]
+use core::future::Future;
+use embedded_hal::digital::{ErrorType as PinErrorType, OutputPin};
+use embedded_hal::spi::{self, ErrorType as SpiErrorType, Operation};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::vec::Vec;
+
+/// Driver on a simulated chip
+pub type SimRadio = NRF24L01<Infallible, SimCe, SimSpi>;
+
+/// Depth of each FIFO
+const FIFO_DEPTH: usize = 3;
+
+const CONFIG: u8 = 0x00;
+const EN_AA: u8 = 0x01;
+const EN_RXADDR: u8 = 0x02;
+const SETUP_AW: u8 = 0x03;
+const SETUP_RETR: u8 = 0x04;
+const RF_CH: u8 = 0x05;
+const RF_SETUP: u8 = 0x06;
+const STATUS: u8 = 0x07;
+const OBSERVE_TX: u8 = 0x08;
+const RPD: u8 = 0x09;
+const RX_ADDR_P0: u8 = 0x0A;
+const RX_ADDR_P1: u8 = 0x0B;
+const RX_ADDR_P2: u8 = 0x0C;
+const TX_ADDR: u8 = 0x10;
+const RX_PW_P0: u8 = 0x11;
+const FIFO_STATUS: u8 = 0x17;
+const DYNPD: u8 = 0x1C;
+const FEATURE: u8 = 0x1D;
+
+const RX_DR: u8 = 1 << 6;
+const TX_DS: u8 = 1 << 5;
+const MAX_RT: u8 = 1 << 4;
+/// Register bank indicator of the Beken chips in `STATUS`
+const RBANK: u8 = 1 << 7;
+
+/// Reset values of the single byte registers
+const RESET: [u8; 0x1E] = [
+    0x08, 0x3F, 0x03, 0x03, 0x03, 0x02, 0x0E, 0x0E, 0x00, 0x00, 0x00, 0x00, 0xC3, 0xC4, 0xC5,
+    0xC6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Packet as it went on air, see [`Air::frames()`](struct.Air.html#method.frames)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AirFrame {
+    /// Index of the sending chip, in the order of attaching
+    pub from: usize,
+    /// `RF_CH` of the sender
+    pub channel: u8,
+    /// Destination address, least significant byte first
+    pub address: Vec<u8>,
+    /// Payload
+    pub payload: Vec<u8>,
+    /// Sent with `W_TX_PAYLOAD_NOACK`
+    pub no_ack: bool,
+}
+
+#[derive(Debug, Clone)]
+struct TxPacket {
+    data: Vec<u8>,
+    no_ack: bool,
+    /// Unique on the air, to tell retransmits from new packets
+    serial: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Chip {
+    regs: [u8; 0x1E],
+    rx_addr_p0: [u8; 5],
+    rx_addr_p1: [u8; 5],
+    tx_addr: [u8; 5],
+    tx_fifo: VecDeque<TxPacket>,
+    rx_fifo: VecDeque<(u8, Vec<u8>)>,
+    /// ACK payloads, which share the TX FIFO with `tx_fifo`
+    ack_fifo: VecDeque<(u8, Vec<u8>)>,
+    ce: bool,
+    ce_tied: bool,
+    variant: ChipVariant,
+    /// `FEATURE` and `DYNPD` are accessible, after `ACTIVATE 0x73` on
+    /// the original nRF24L01
+    activated: bool,
+    /// Register bank 1 of the Beken chips is selected
+    bank1: bool,
+    /// Serial of the last packet received, to drop retransmits
+    last_rx: Option<u64>,
+    ack_all: bool,
+    carrier: bool,
+    stuck_miso: Option<u8>,
+    /// Transactions to pass before `spi_failures` apply
+    spi_passes: u32,
+    spi_failures: u32,
+    opcodes: Vec<u8>,
+}
+
+impl Chip {
+    fn new() -> Self {
+        Chip {
+            regs: RESET,
+            rx_addr_p0: [0xE7; 5],
+            rx_addr_p1: [0xC2; 5],
+            tx_addr: [0xE7; 5],
+            tx_fifo: VecDeque::new(),
+            rx_fifo: VecDeque::new(),
+            ack_fifo: VecDeque::new(),
+            ce: false,
+            ce_tied: false,
+            variant: ChipVariant::Plus,
+            activated: false,
+            bank1: false,
+            last_rx: None,
+            ack_all: false,
+            carrier: false,
+            stuck_miso: None,
+            spi_passes: 0,
+            spi_failures: 0,
+            opcodes: Vec::new(),
+        }
+    }
+
+    /// Lose all state, as in a brown-out
+    fn power_cycle(&mut self) {
+        let kept = Chip {
+            variant: self.variant,
+            ce: self.ce,
+            ce_tied: self.ce_tied,
+            ack_all: self.ack_all,
+            opcodes: core::mem::take(&mut self.opcodes),
+            ..Chip::new()
+        };
+        *self = kept;
+    }
+
+    fn reg(&self, addr: u8) -> u8 {
+        self.regs.get(usize::from(addr)).copied().unwrap_or(0)
+    }
+
+    fn features_locked(&self) -> bool {
+        matches!(self.variant, ChipVariant::NonPlus | ChipVariant::Beken) && !self.activated
+    }
+
+    fn feature(&self) -> u8 {
+        if self.features_locked() {
+            0
+        } else {
+            self.reg(FEATURE)
+        }
+    }
+
+    fn dynpd(&self) -> u8 {
+        if self.features_locked() {
+            0
+        } else {
+            self.reg(DYNPD)
+        }
+    }
+
+    /// Occupancy of the TX FIFO, with the ACK payloads
+    fn tx_len(&self) -> usize {
+        self.tx_fifo.len() + self.ack_fifo.len()
+    }
+
+    fn status(&self) -> u8 {
+        let rx_p_no = self.rx_fifo.front().map_or(0b111, |(pipe, _)| *pipe);
+        let mut status = (self.reg(STATUS) & (RX_DR | TX_DS | MAX_RT)) | (rx_p_no << 1);
+        if self.tx_len() >= FIFO_DEPTH {
+            status |= 1;
+        }
+        if self.bank1 {
+            status |= RBANK;
+        }
+        status
+    }
+
+    fn fifo_status(&self) -> u8 {
+        let mut fifo_status = 0;
+        if self.tx_len() >= FIFO_DEPTH {
+            fifo_status |= 1 << 5;
+        }
+        if self.tx_len() == 0 {
+            fifo_status |= 1 << 4;
+        }
+        if self.rx_fifo.len() >= FIFO_DEPTH {
+            fifo_status |= 1 << 1;
+        }
+        if self.rx_fifo.is_empty() {
+            fifo_status |= 1;
+        }
+        fifo_status
+    }
+
+    /// Content of register `addr` as read over SPI
+    fn read(&self, addr: u8) -> Vec<u8> {
+        if self.bank1 {
+            return Vec::new();
+        }
+        match addr {
+            RX_ADDR_P0 => self.rx_addr_p0.to_vec(),
+            RX_ADDR_P1 => self.rx_addr_p1.to_vec(),
+            TX_ADDR => self.tx_addr.to_vec(),
+            STATUS => [self.status()].to_vec(),
+            FIFO_STATUS => [self.fifo_status()].to_vec(),
+            RPD => [u8::from(self.carrier)].to_vec(),
+            FEATURE => [self.feature()].to_vec(),
+            DYNPD => [self.dynpd()].to_vec(),
+            _ => [self.reg(addr)].to_vec(),
+        }
+    }
+
+    fn write(&mut self, addr: u8, data: &[u8]) {
+        if self.bank1 {
+            return;
+        }
+        let Some(&value) = data.first() else {
+            return;
+        };
+        let address = match addr {
+            RX_ADDR_P0 => Some(&mut self.rx_addr_p0),
+            RX_ADDR_P1 => Some(&mut self.rx_addr_p1),
+            TX_ADDR => Some(&mut self.tx_addr),
+            _ => None,
+        };
+        if let Some(address) = address {
+            let len = data.len().min(address.len());
+            address[..len].copy_from_slice(&data[..len]);
+            return;
+        }
+        let value = match addr {
+            STATUS => {
+                self.regs[usize::from(STATUS)] &= !(value & (RX_DR | TX_DS | MAX_RT));
+                return;
+            }
+            OBSERVE_TX | RPD | FIFO_STATUS => return,
+            FEATURE | DYNPD if self.features_locked() => return,
+            RF_CH => {
+                // Writing RF_CH resets PLOS_CNT
+                self.regs[usize::from(OBSERVE_TX)] &= 0x0F;
+                value & 0x7F
+            }
+            // Only the nRF24L01+ has 250 Kbps
+            RF_SETUP if self.variant == ChipVariant::NonPlus => value & !(1 << 5),
+            _ => value,
+        };
+        if let Some(reg) = self.regs.get_mut(usize::from(addr)) {
+            *reg = value;
+        }
+    }
+
+    /// Run one SPI transaction of `input`, returning what the chip
+    /// shifts out
+    fn transfer(&mut self, input: &[u8], serials: &mut u64) -> Vec<u8> {
+        let Some(&opcode) = input.first() else {
+            return Vec::new();
+        };
+        self.opcodes.push(opcode);
+        let data = &input[1..];
+        let mut output = Vec::with_capacity(input.len());
+        output.push(self.status());
+        let mut response: Vec<u8> = Vec::new();
+        match opcode {
+            0x00..=0x1F => response = self.read(opcode),
+            0x20..=0x3F => self.write(opcode & 0x1F, data),
+            // R_RX_PL_WID
+            0x60 => response = [self.rx_fifo.front().map_or(0, |(_, p)| p.len() as u8)].to_vec(),
+            // R_RX_PAYLOAD
+            0x61 => {
+                if let Some((_, payload)) = self.rx_fifo.pop_front() {
+                    response = payload;
+                }
+            }
+            // W_TX_PAYLOAD, W_TX_PAYLOAD_NOACK
+            0xA0 | 0xB0 => {
+                let no_ack = opcode == 0xB0;
+                let dyn_ack = self.feature() & 1 != 0;
+                let valid = (1..=32).contains(&data.len()) && (!no_ack || dyn_ack);
+                if valid && self.tx_len() < FIFO_DEPTH {
+                    *serials += 1;
+                    self.tx_fifo.push_back(TxPacket {
+                        data: data.to_vec(),
+                        no_ack,
+                        serial: *serials,
+                    });
+                }
+            }
+            // W_ACK_PAYLOAD
+            0xA8..=0xAD if (1..=32).contains(&data.len()) && self.tx_len() < FIFO_DEPTH => {
+                self.ack_fifo.push_back((opcode & 0b111, data.to_vec()));
+            }
+            // ACTIVATE
+            0x50 => match (data.first(), self.variant) {
+                (Some(0x53), ChipVariant::Beken) => self.bank1 = !self.bank1,
+                (Some(0x73), ChipVariant::NonPlus | ChipVariant::Beken) => {
+                    self.activated = !self.activated
+                }
+                _ => {}
+            },
+            // FLUSH_TX
+            0xE1 => {
+                self.tx_fifo.clear();
+                self.ack_fifo.clear();
+            }
+            // FLUSH_RX
+            0xE2 => self.rx_fifo.clear(),
+            _ => {}
+        }
+        response.resize(data.len(), 0);
+        output.extend_from_slice(&response);
+        if let Some(level) = self.stuck_miso {
+            output.iter_mut().for_each(|byte| *byte = level);
+        }
+        output
+    }
+
+    fn powered(&self) -> bool {
+        self.reg(CONFIG) & 0b10 != 0
+    }
+
+    fn prim_rx(&self) -> bool {
+        self.reg(CONFIG) & 1 != 0
+    }
+
+    fn ce_high(&self) -> bool {
+        self.ce || self.ce_tied
+    }
+
+    /// In TX mode with a packet to send, held off by `MAX_RT`
+    fn can_send(&self) -> bool {
+        self.powered()
+            && !self.prim_rx()
+            && self.ce_high()
+            && !self.tx_fifo.is_empty()
+            && self.reg(STATUS) & MAX_RT == 0
+    }
+
+    fn listening(&self) -> bool {
+        self.powered() && self.prim_rx() && self.ce_high()
+    }
+
+    fn width(&self) -> usize {
+        usize::from(self.reg(SETUP_AW).clamp(1, 3)) + 2
+    }
+
+    /// Channel, data rate, CRC and address width, which sender and
+    /// receiver must agree on
+    fn link(&self) -> (u8, u8, u8, usize) {
+        let rate = self.reg(RF_SETUP) & 0b0010_1000;
+        let mut crc = self.reg(CONFIG) & 0b1100;
+        // EN_CRC is forced on with auto-ack
+        if self.reg(EN_AA) & 0x3F != 0 {
+            crc |= 0b1000;
+        }
+        (self.reg(RF_CH), rate, crc, self.width())
+    }
+
+    fn pipe_address(&self, pipe: u8) -> Vec<u8> {
+        let width = self.width();
+        match pipe {
+            0 => self.rx_addr_p0[..width].to_vec(),
+            1 => self.rx_addr_p1[..width].to_vec(),
+            _ => {
+                let mut address = self.rx_addr_p1[..width].to_vec();
+                address[0] = self.reg(RX_ADDR_P2 + pipe - 2);
+                address
+            }
+        }
+    }
+
+    fn pipe_dynamic(&self, pipe: u8) -> bool {
+        self.feature() & 0b100 != 0 && self.dynpd() & (1 << pipe) != 0
+    }
+
+    /// Pipe that receives a packet of `len` bytes for `address`
+    fn matching_pipe(&self, address: &[u8], len: usize, dynamic: bool) -> Option<u8> {
+        (0..6).find(|&pipe| {
+            let static_len = usize::from(self.reg(RX_PW_P0 + pipe));
+            let length_ok = match self.pipe_dynamic(pipe) {
+                true => dynamic,
+                false => !dynamic && static_len == len,
+            };
+            self.reg(EN_RXADDR) & (1 << pipe) != 0
+                && length_ok
+                && self.pipe_address(pipe) == address
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Medium {
+    chips: Vec<Chip>,
+    /// Fate of the next frames, packets and ACKs alike: `true` to lose
+    losses: VecDeque<bool>,
+    frames: Vec<AirFrame>,
+    serials: u64,
+}
+
+impl Medium {
+    fn lost(&mut self) -> bool {
+        self.losses.pop_front().unwrap_or(false)
+    }
+
+    /// Send whatever can be sent
+    fn step(&mut self) {
+        while let Some(from) = (0..self.chips.len()).find(|&i| self.chips[i].can_send()) {
+            self.transmit(from);
+        }
+    }
+
+    fn transmit(&mut self, from: usize) {
+        let sender = &self.chips[from];
+        let Some(packet) = sender.tx_fifo.front().cloned() else {
+            return;
+        };
+        let link = sender.link();
+        let address = sender.tx_addr[..sender.width()].to_vec();
+        let dynamic = sender.pipe_dynamic(0);
+        let expects_ack = sender.reg(EN_AA) & 1 != 0 && !packet.no_ack;
+        // ACKs come back to the TX address on pipe 0
+        let hears_acks = sender.pipe_address(0) == address && sender.reg(EN_RXADDR) & 1 != 0;
+        let retransmits = if expects_ack { sender.reg(SETUP_RETR) & 0xF } else { 0 };
+        let ack_all = sender.ack_all;
+        self.frames.push(AirFrame {
+            from,
+            channel: link.0,
+            address: address.clone(),
+            payload: packet.data.clone(),
+            no_ack: packet.no_ack,
+        });
+
+        let mut acked = None;
+        let mut attempts = 0;
+        while attempts <= retransmits {
+            attempts += 1;
+            if self.lost() {
+                continue;
+            }
+            let receivers: Vec<_> = (0..self.chips.len())
+                .filter_map(|to| {
+                    let chip = &self.chips[to];
+                    let pipe = chip.matching_pipe(&address, packet.data.len(), dynamic)?;
+                    (to != from && chip.listening() && chip.link() == link).then_some((to, pipe))
+                })
+                .collect();
+            // Every receiver gets the packet, the first one's ACK counts
+            let mut ack = match receivers.is_empty() {
+                true => ack_all.then_some(None),
+                false => None,
+            };
+            for (to, pipe) in receivers {
+                let answer = self.receive(to, pipe, &packet);
+                ack = ack.or(answer);
+            }
+            let Some(ack) = ack else {
+                continue;
+            };
+            if !expects_ack {
+                break;
+            }
+            if hears_acks && !self.lost() {
+                acked = Some(ack);
+                break;
+            }
+        }
+
+        let sender = &mut self.chips[from];
+        let observe_tx = &mut sender.regs[usize::from(OBSERVE_TX)];
+        if expects_ack && acked.is_none() {
+            let plos = (*observe_tx >> 4).saturating_add(1).min(15);
+            *observe_tx = (plos << 4) | retransmits;
+            sender.regs[usize::from(STATUS)] |= MAX_RT;
+            return;
+        }
+        *observe_tx = (*observe_tx & 0xF0) | (attempts - 1).min(15);
+        sender.tx_fifo.pop_front();
+        sender.regs[usize::from(STATUS)] |= TX_DS;
+        if let Some(Some(payload)) = acked {
+            if sender.feature() & 0b10 != 0 && sender.rx_fifo.len() < FIFO_DEPTH {
+                sender.rx_fifo.push_back((0, payload));
+                sender.regs[usize::from(STATUS)] |= RX_DR;
+            }
+        }
+    }
+
+    /// Deliver `packet` to pipe `pipe` of chip `to`. Returns `None` if
+    /// the chip dropped it, or whether it acknowledges, and with what
+    /// ACK payload.
+    fn receive(&mut self, to: usize, pipe: u8, packet: &TxPacket) -> Option<Option<Vec<u8>>> {
+        let chip = &mut self.chips[to];
+        if chip.last_rx != Some(packet.serial) {
+            if chip.rx_fifo.len() >= FIFO_DEPTH {
+                return None;
+            }
+            chip.rx_fifo.push_back((pipe, packet.data.clone()));
+            chip.regs[usize::from(STATUS)] |= RX_DR;
+            chip.last_rx = Some(packet.serial);
+        }
+        if packet.no_ack || chip.reg(EN_AA) & (1 << pipe) == 0 {
+            return Some(None);
+        }
+        let mut payload = None;
+        if chip.feature() & 0b10 != 0 {
+            if let Some(i) = chip.ack_fifo.iter().position(|(p, _)| *p == pipe) {
+                payload = chip.ack_fifo.remove(i).map(|(_, data)| data);
+                chip.regs[usize::from(STATUS)] |= TX_DS;
+            }
+        }
+        Some(payload)
+    }
+}
+
+fn lock(medium: &Mutex<Medium>) -> MutexGuard<'_, Medium> {
+    medium.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Radio medium shared by simulated chips
+#[derive(Debug, Default, Clone)]
+pub struct Air {
+    medium: Arc<Mutex<Medium>>,
+}
+
+impl Air {
+    /// Constructor, without chips
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn attach(&self, ce_tied: bool) -> SimChip {
+        let mut medium = lock(&self.medium);
+        let mut chip = Chip::new();
+        chip.ce_tied = ce_tied;
+        medium.chips.push(chip);
+        SimChip {
+            medium: self.medium.clone(),
+            index: medium.chips.len() - 1,
+        }
+    }
+
+    /// Attach a new chip, just powered on, and return its `CE` pin and
+    /// SPI bus
+    pub fn radio(&self) -> (SimCe, SimSpi) {
+        let chip = self.attach(false);
+        (SimCe { chip: chip.clone() }, SimSpi { chip })
+    }
+
+    /// Attach a new chip with `CE` tied high, for
+    /// [`NRF24L01::new_ce_tied()`](../struct.NRF24L01.html#method.new_ce_tied)
+    pub fn radio_ce_tied(&self) -> SimSpi {
+        SimSpi {
+            chip: self.attach(true),
+        }
+    }
+
+    /// Lose the next `frames` frames on air, packets and ACKs alike
+    pub fn lose_next(&self, frames: usize) {
+        self.set_losses(&std::vec![true; frames]);
+    }
+
+    /// Lose the next frames on air for which `losses` is `true`, e.g.
+    /// `[false, true]` for the ACK of the next packet
+    pub fn set_losses(&self, losses: &[bool]) {
+        lock(&self.medium).losses = losses.iter().copied().collect();
+    }
+
+    /// Packets that went on air, once per packet however often it was
+    /// retransmitted
+    pub fn frames(&self) -> Vec<AirFrame> {
+        lock(&self.medium).frames.clone()
+    }
+}
+
+/// Handle to inspect a simulated chip and inject faults
+#[derive(Debug, Clone)]
+pub struct SimChip {
+    medium: Arc<Mutex<Medium>>,
+    index: usize,
+}
+
+impl SimChip {
+    fn with<R>(&self, f: impl FnOnce(&mut Chip) -> R) -> R {
+        let mut medium = lock(&self.medium);
+        // Chips are never detached
+        let r = f(&mut medium.chips[self.index]);
+        medium.step();
+        r
+    }
+
+    /// Register at `addr` as read over SPI, the first byte for address
+    /// registers
+    pub fn register(&self, addr: u8) -> u8 {
+        self.with(|chip| chip.read(addr).first().copied().unwrap_or(0))
+    }
+
+    /// All 5 bytes of the address register at `addr`
+    pub fn address(&self, addr: u8) -> [u8; 5] {
+        self.with(|chip| {
+            let mut address = [0; 5];
+            let read = chip.read(addr);
+            let len = read.len().min(5);
+            address[..len].copy_from_slice(&read[..len]);
+            address
+        })
+    }
+
+    /// Level of `CE`
+    pub fn ce(&self) -> bool {
+        self.with(|chip| chip.ce_high())
+    }
+
+    /// Packets in the TX FIFO, next to send first
+    pub fn tx_fifo(&self) -> Vec<Vec<u8>> {
+        self.with(|chip| chip.tx_fifo.iter().map(|packet| packet.data.clone()).collect())
+    }
+
+    /// Packets in the RX FIFO with their pipe, next to read first
+    pub fn rx_fifo(&self) -> Vec<(u8, Vec<u8>)> {
+        self.with(|chip| chip.rx_fifo.iter().cloned().collect())
+    }
+
+    /// Number of queued ACK payloads
+    pub fn ack_fifo_len(&self) -> usize {
+        self.with(|chip| chip.ack_fifo.len())
+    }
+
+    /// Put a packet into the RX FIFO as if received on `pipe`, unless
+    /// the FIFO is full. Returns whether it was.
+    pub fn receive(&self, pipe: u8, payload: &[u8]) -> bool {
+        self.with(|chip| {
+            if chip.rx_fifo.len() >= FIFO_DEPTH {
+                return false;
+            }
+            chip.rx_fifo.push_back((pipe, payload.to_vec()));
+            chip.regs[usize::from(STATUS)] |= RX_DR;
+            true
+        })
+    }
+
+    /// Behave like this chip family, e.g. to test
+    /// [`detect_variant()`](../trait.Configuration.html#method.detect_variant).
+    /// `Unknown` behaves like the nRF24L01+, which is the default.
+    pub fn set_variant(&self, variant: ChipVariant) {
+        self.with(|chip| chip.variant = variant)
+    }
+
+    /// Have every packet acknowledged that no simulated chip receives,
+    /// as by a peer outside of the simulation
+    pub fn set_ack_all(&self, ack_all: bool) {
+        self.with(|chip| chip.ack_all = ack_all)
+    }
+
+    /// Report a carrier in `RPD`
+    pub fn set_carrier(&self, carrier: bool) {
+        self.with(|chip| chip.carrier = carrier)
+    }
+
+    /// Shift out `level` on MISO for every byte, as a missing chip
+    /// (`0xFF`) or an unpowered one (`0x00`) does, or `None` to answer
+    /// normally
+    pub fn set_stuck_miso(&self, level: Option<u8>) {
+        self.with(|chip| chip.stuck_miso = level)
+    }
+
+    /// Fail the next `count` SPI transactions
+    pub fn fail_transfers(&self, count: u32) {
+        self.fail_transfers_after(0, count)
+    }
+
+    /// Pass `after` SPI transactions, then fail `count`, e.g. to fail
+    /// a sequence of writes midway
+    pub fn fail_transfers_after(&self, after: u32, count: u32) {
+        self.with(|chip| {
+            chip.spi_passes = after;
+            chip.spi_failures = count;
+        })
+    }
+
+    /// Reset all registers and FIFOs, as after a power loss
+    pub fn power_cycle(&self) {
+        self.with(Chip::power_cycle)
+    }
+
+    /// Opcodes of all SPI transactions so far, oldest first
+    pub fn opcodes(&self) -> Vec<u8> {
+        self.with(|chip| chip.opcodes.clone())
+    }
+
+    /// Forget the opcodes so far
+    pub fn clear_opcodes(&self) {
+        self.with(|chip| chip.opcodes.clear())
+    }
+}
+
+/// `CE` pin of a simulated chip
+#[derive(Debug)]
+pub struct SimCe {
+    chip: SimChip,
+}
+
+impl SimCe {
+    /// Handle to the chip
+    pub fn chip(&self) -> SimChip {
+        self.chip.clone()
+    }
+}
+
+impl PinErrorType for SimCe {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimCe {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.chip.with(|chip| chip.ce = false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.chip.with(|chip| chip.ce = true);
+        Ok(())
+    }
+}
+
+/// Failure injected with
+/// [`SimChip::fail_transfers()`](struct.SimChip.html#method.fail_transfers)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SimSpiError;
+
+impl spi::Error for SimSpiError {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+/// SPI bus of a simulated chip
+#[derive(Debug)]
+pub struct SimSpi {
+    chip: SimChip,
+}
+
+impl SimSpi {
+    /// Handle to the chip
+    pub fn chip(&self) -> SimChip {
+        self.chip.clone()
+    }
+}
+
+impl SpiErrorType for SimSpi {
+    type Error = SimSpiError;
+}
+
+impl SpiDevice for SimSpi {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), SimSpiError> {
+        let mut input = Vec::new();
+        for operation in operations.iter() {
+            match operation {
+                Operation::Read(buf) => input.resize(input.len() + buf.len(), 0xFF),
+                Operation::Write(buf) => input.extend_from_slice(buf),
+                Operation::Transfer(read, write) => {
+                    let mut bytes = write.to_vec();
+                    bytes.resize(read.len().max(write.len()), 0xFF);
+                    input.extend_from_slice(&bytes);
+                }
+                Operation::TransferInPlace(buf) => input.extend_from_slice(buf),
+                Operation::DelayNs(_) => {}
+            }
+        }
+
+        let mut medium = lock(&self.chip.medium);
+        let Medium { chips, serials, .. } = &mut *medium;
+        let Some(chip) = chips.get_mut(self.chip.index) else {
+            return Err(SimSpiError);
+        };
+        if chip.spi_passes > 0 {
+            chip.spi_passes -= 1;
+        } else if chip.spi_failures > 0 {
+            chip.spi_failures -= 1;
+            return Err(SimSpiError);
+        }
+        let output = chip.transfer(&input, serials);
+        medium.step();
+        drop(medium);
+
+        let mut output = output.into_iter();
+        for operation in operations.iter_mut() {
+            let buf: &mut [u8] = match operation {
+                Operation::Read(buf) | Operation::TransferInPlace(buf) => buf,
+                Operation::Transfer(read, write) => {
+                    let skip = write.len().saturating_sub(read.len());
+                    for (byte, out) in read.iter_mut().zip(output.by_ref()) {
+                        *byte = out;
+                    }
+                    output.by_ref().take(skip).for_each(drop);
+                    continue;
+                }
+                Operation::Write(buf) => {
+                    output.by_ref().take(buf.len()).for_each(drop);
+                    continue;
+                }
+                Operation::DelayNs(_) => continue,
+            };
+            for (byte, out) in buf.iter_mut().zip(output.by_ref()) {
+                *byte = out;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// [`RadioTimer`](../trait.RadioTimer.html) on simulated time, which
+/// only passes in its delays or with [`advance()`](#method.advance)
+///
+/// Clones share the time, so a test can hold one to move time along
+/// while the code under test waits on another.
+#[derive(Debug, Default, Clone)]
+pub struct SimTimer {
+    now_ns: Arc<AtomicU64>,
+}
+
+impl SimTimer {
+    /// Constructor, with the time at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let `us` pass
+    pub fn advance(&self, us: u64) {
+        self.now_ns.fetch_add(us * 1_000, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimTimer {
+    fn now_micros(&self) -> u64 {
+        self.now_ns.load(Ordering::Relaxed) / 1_000
+    }
+}
+
+impl DelayNs for SimTimer {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.now_ns.fetch_add(u64::from(ns), Ordering::Relaxed);
+    }
+}
+
+impl RadioTimer for SimTimer {}
+
+/// A chip in Standby-I with `config` applied, for tests of the modules
+#[cfg(test)]
+pub(crate) async fn standby(
+    air: &Air,
+    config: &crate::RadioConfig,
+) -> crate::StandbyMode<SimRadio> {
+    use crate::Configuration;
+    let (ce, spi) = air.radio();
+    let mut standby = SimRadio::new(ce, spi).await.unwrap();
+    standby.configure(config).await.unwrap();
+    standby
+}
+
+/// A transmitter and a receiver with the default `RadioConfig`
+#[cfg(test)]
+pub(crate) async fn pair(air: &Air) -> (crate::TxMode<SimRadio>, crate::RxMode<SimRadio>) {
+    let config = crate::RadioConfig::default();
+    let tx = standby(air, &config).await.tx().await.map_err(|(_, e)| e).unwrap();
+    let rx = standby(air, &config).await.rx().await.map_err(|(_, e)| e).unwrap();
+    (tx, rx)
+}
+
+/// Delay on the simulated time that lets the other side of a
+/// [`join()`] run
+#[cfg(test)]
+#[cfg_attr(not(any(feature = "socket", feature = "stream")), allow(dead_code))]
+pub(crate) struct Yielding(pub(crate) SimTimer);
+
+#[cfg(test)]
+impl DelayNs for Yielding {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_ns(ns).await;
+        let mut yielded = false;
+        core::future::poll_fn(|cx| {
+            if yielded {
+                return core::task::Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            core::task::Poll::Pending
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+impl Clock for Yielding {
+    fn now_micros(&self) -> u64 {
+        self.0.now_micros()
+    }
+}
+
+#[cfg(test)]
+impl RadioTimer for Yielding {}
+
+/// Run `a` and `b` interleaved, as two tasks would
+#[cfg(test)]
+#[cfg_attr(not(any(feature = "socket", feature = "stream")), allow(dead_code))]
+pub(crate) async fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    use core::task::Poll;
+    let (mut a, mut b) = (core::pin::pin!(a), core::pin::pin!(b));
+    let (mut a_out, mut b_out) = (None, None);
+    core::future::poll_fn(|cx| {
+        if a_out.is_none() {
+            a_out = match a.as_mut().poll(cx) {
+                Poll::Ready(out) => Some(out),
+                Poll::Pending => None,
+            };
+        }
+        if b_out.is_none() {
+            b_out = match b.as_mut().poll(cx) {
+                Poll::Ready(out) => Some(out),
+                Poll::Pending => None,
+            };
+        }
+        match (a_out.take(), b_out.take()) {
+            (Some(a), Some(b)) => Poll::Ready((a, b)),
+            (a, b) => {
+                (a_out, b_out) = (a, b);
+                Poll::Pending
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::{Channel, ChipVariant, Configuration};
+
+    #[test]
+    fn packet_reaches_receiver_with_ack() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = pair(&air).await;
+            tx.send(b"hello").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert_eq!(&rx.read().await.unwrap()[..], b"hello");
+            assert_eq!(air.frames().len(), 1);
+        });
+    }
+
+    #[test]
+    fn other_channel_reaches_max_rt() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = pair(&air).await;
+            tx.set_frequency(Channel::new(40).unwrap()).await.unwrap();
+            tx.send(b"hello").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(false));
+            assert_eq!(rx.can_read().await.unwrap(), None);
+            assert_eq!(tx.observe().await.unwrap().arc_cnt(), 3);
+        });
+    }
+
+    #[test]
+    fn ack_payload_returns_to_sender() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = pair(&air).await;
+            tx.set_ack_payloads(true).await.unwrap();
+            rx.set_ack_payloads(true).await.unwrap();
+            rx.queue_ack_payload(0, b"pong").await.unwrap();
+            tx.send(b"ping").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert_eq!(&tx.read_ack_payload().await.unwrap().unwrap()[..], b"pong");
+        });
+    }
+
+    #[test]
+    fn retransmit_is_received_once() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = pair(&air).await;
+            // The packet arrives, its ACK is lost
+            air.set_losses(&[false, true]);
+            let chip = rx.device().spi.chip();
+            tx.send(b"once").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            assert_eq!(chip.rx_fifo().len(), 1);
+            assert_eq!(tx.observe().await.unwrap().arc_cnt(), 1);
+        });
+    }
+
+    #[test]
+    fn variants_are_detected() {
+        block_on(async {
+            for variant in [ChipVariant::Plus, ChipVariant::NonPlus, ChipVariant::Beken] {
+                let air = Air::new();
+                let (ce, spi) = air.radio();
+                spi.chip().set_variant(variant);
+                let mut standby = SimRadio::new(ce, spi).await.unwrap();
+                assert_eq!(standby.detect_variant().await.unwrap(), variant);
+            }
+        });
+    }
+}
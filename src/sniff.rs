@@ -0,0 +1,208 @@
+#[cfg(feature = "codec")]
+use crate::bits::BitWriter;
+use crate::payload::Payload;
+use crate::MAX_ADDR_BYTES;
+
+/// 2 byte address that, after the preamble, matches often enough on
+/// noise to receive frames of unknown addresses
+#[cfg_attr(not(any(feature = "learn", feature = "unifying")), allow(dead_code))]
+pub(crate) const PROMISCUOUS_ADDRESS: [u8; 2] = [0xAA, 0x00];
+
+/// Enhanced ShockBurst frame found in a promiscuous payload
+pub(crate) struct RawEsb {
+    /// Address, least significant byte first, of which `width` bytes
+    /// are used
+    pub address: [u8; MAX_ADDR_BYTES],
+    #[cfg_attr(not(feature = "learn"), allow(dead_code))]
+    pub width: usize,
+    #[cfg_attr(not(feature = "unifying"), allow(dead_code))]
+    pub pid: u8,
+    pub no_ack: bool,
+    pub payload: Payload,
+}
+
+/// Recover an Enhanced ShockBurst frame with a `width` byte address and
+/// a 16 bit CRC from `raw`, received in promiscuous mode without CRC
+///
+/// Returns `None` unless the CRC matches, which rejects nearly all of
+/// the noise.
+pub(crate) fn decode_esb(raw: &[u8], width: usize) -> Option<RawEsb> {
+    let bit = |i: usize| raw.get(i / 8).is_some_and(|byte| byte & (0x80 >> (i % 8)) != 0);
+    let bits = |start: usize, count: usize| {
+        (start..start.saturating_add(count)).fold(0u16, |value, i| value << 1 | u16::from(bit(i)))
+    };
+
+    if width > MAX_ADDR_BYTES {
+        return None;
+    }
+    // Address bits, 9 bit packet control field, then the payload
+    let address_bits = width.saturating_mul(8);
+    let header_bits = address_bits.saturating_add(9);
+    let len = usize::from(bits(address_bits, 6));
+    let crc_start = header_bits.saturating_add(len.saturating_mul(8));
+    if len == 0 || len > 32 || crc_start.saturating_add(16) > raw.len().saturating_mul(8) {
+        return None;
+    }
+    if esb_crc(raw, crc_start, 2) != bits(crc_start, 16) {
+        return None;
+    }
+
+    // On air, the address goes most significant byte first
+    let mut address = [0; MAX_ADDR_BYTES];
+    for (start, byte) in (0..).step_by(8).zip(address.get_mut(..width)?.iter_mut().rev()) {
+        *byte = bits(start, 8) as u8;
+    }
+    let mut payload = [0; 32];
+    for (start, byte) in (header_bits..).step_by(8).zip(payload.get_mut(..len)?) {
+        *byte = bits(start, 8) as u8;
+    }
+    Some(RawEsb {
+        address,
+        width,
+        pid: bits(address_bits.saturating_add(6), 2) as u8,
+        no_ack: bit(address_bits.saturating_add(8)),
+        payload: Payload::new(payload.get(..len)?),
+    })
+}
+
+/// CRC of `crc_len` bytes over the first `bits` bits of `frame`, as the
+/// chip computes it: X^8+X^2+X+1 or X^16+X^12+X^5+1, starting from all
+/// ones
+pub(crate) fn esb_crc(frame: &[u8], bits: usize, crc_len: usize) -> u16 {
+    let (poly, top, mask) = match crc_len {
+        1 => (0x07, 0x80, 0xFF),
+        _ => (0x1021, 0x8000, 0xFFFF),
+    };
+    (0..bits).fold(mask, |crc, i| {
+        let bit = frame.get(i / 8).is_some_and(|byte| byte & (0x80 >> (i % 8)) != 0);
+        let feedback = (crc & top != 0) != bit;
+        let crc = (crc << 1) & mask;
+        if feedback {
+            crc ^ poly
+        } else {
+            crc
+        }
+    })
+}
+
+/// Longest Enhanced ShockBurst frame in bytes: preamble, 5 address
+/// bytes, the 9 bit packet control field, 32 payload bytes and 2 CRC
+/// bytes
+#[cfg(feature = "codec")]
+pub(crate) const ESB_FRAME_MAX: usize = 42;
+
+/// Build the Enhanced ShockBurst frame the chip puts on air for
+/// `payload`, from the preamble to the CRC of `crc_len` bytes, 0 to 2
+///
+/// `address` is in register order, least significant byte first.
+/// Returns the length in bits, as the packet control field leaves the
+/// frame unaligned. The last byte is padded with zeros.
+#[cfg(feature = "codec")]
+pub(crate) fn encode_esb(
+    address: &[u8],
+    pid: u8,
+    no_ack: bool,
+    payload: &[u8],
+    crc_len: usize,
+    out: &mut [u8; ESB_FRAME_MAX],
+) -> Option<usize> {
+    if address.is_empty() || address.len() > MAX_ADDR_BYTES || payload.len() > 32 || crc_len > 2 {
+        return None;
+    }
+    *out = [0; ESB_FRAME_MAX];
+    let mut writer = BitWriter {
+        buf: &mut out[1..],
+        bits: 0,
+    };
+    // On air, the address goes most significant byte first
+    for &byte in address.iter().rev() {
+        writer.push(u32::from(byte), 8)?;
+    }
+    writer.push(payload.len() as u32, 6)?;
+    writer.push(u32::from(pid & 3), 2)?;
+    writer.push(u32::from(no_ack), 1)?;
+    for &byte in payload {
+        writer.push(u32::from(byte), 8)?;
+    }
+    if crc_len > 0 {
+        let crc = esb_crc(writer.buf, writer.bits, crc_len);
+        writer.push(u32::from(crc), (crc_len as u8).saturating_mul(8))?;
+    }
+    let bits = writer.bits;
+    // The preamble alternates starting with the first address bit
+    out[0] = if out[1] & 0x80 != 0 { 0xAA } else { 0x55 };
+    Some(bits.saturating_add(8))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn push(bits: &mut Vec<bool>, value: u32, count: u32) {
+        bits.extend((0..count).rev().map(|i| value >> i & 1 != 0));
+    }
+
+    /// `bits` followed by noise
+    fn pack(bits: &[bool]) -> [u8; 32] {
+        let mut raw = [0x55; 32];
+        for (i, &bit) in bits.iter().enumerate() {
+            let mask = 0x80 >> (i % 8);
+            raw[i / 8] = (raw[i / 8] & !mask) | if bit { mask } else { 0 };
+        }
+        raw
+    }
+
+    /// What a promiscuous receiver captures of the Enhanced ShockBurst
+    /// frame for `payload`, from the address on, with `address` in
+    /// register order. What follows the frame is noise.
+    pub(crate) fn raw_frame(address: &[u8], pid: u8, no_ack: bool, payload: &[u8]) -> [u8; 32] {
+        let mut bits = Vec::new();
+        for &byte in address.iter().rev() {
+            push(&mut bits, u32::from(byte), 8);
+        }
+        push(&mut bits, payload.len() as u32, 6);
+        push(&mut bits, u32::from(pid), 2);
+        push(&mut bits, u32::from(no_ack), 1);
+        for &byte in payload {
+            push(&mut bits, u32::from(byte), 8);
+        }
+        let crc = esb_crc(&pack(&bits), bits.len(), 2);
+        push(&mut bits, u32::from(crc), 16);
+        pack(&bits)
+    }
+
+    #[test]
+    fn crc_matches_the_chip() {
+        // CRC-16/CCITT-FALSE check value
+        assert_eq!(esb_crc(b"123456789", 72, 2), 0x29B1);
+        assert_eq!(esb_crc(&[], 0, 1), 0xFF);
+    }
+
+    #[test]
+    fn frames_decode_at_each_width() {
+        let address = [0xE7, 0xD3, 0xF0, 0x35, 0x77];
+        for width in crate::MIN_ADDR_BYTES..=MAX_ADDR_BYTES {
+            let raw = raw_frame(&address[..width], 2, true, b"hello");
+            let frame = decode_esb(&raw, width).unwrap();
+            assert_eq!(&frame.address[..width], &address[..width]);
+            assert_eq!((frame.pid, frame.no_ack), (2, true));
+            assert_eq!(&frame.payload[..], b"hello");
+        }
+    }
+
+    #[test]
+    fn corrupt_frames_are_rejected() {
+        let raw = raw_frame(&[0xE7; 5], 0, false, b"hello");
+        assert!(decode_esb(&raw, 5).is_some());
+        for bit in [0, 40, 60, 100] {
+            let mut corrupt = raw;
+            corrupt[bit / 8] ^= 0x80 >> (bit % 8);
+            assert!(decode_esb(&corrupt, 5).is_none(), "bit {}", bit);
+        }
+        // Too wide, a length of 0 and one past the end
+        assert!(decode_esb(&raw, MAX_ADDR_BYTES + 1).is_none());
+        assert!(decode_esb(&[0; 32], 5).is_none());
+        assert!(decode_esb(&raw[..12], 5).is_none());
+    }
+}
@@ -0,0 +1,882 @@
+use crate::address::Address;
+use crate::config::Configuration;
+use crate::device::Device;
+use crate::error::{InvalidArgument, StateError};
+use crate::payload::{copy_bytes, Payload};
+use crate::rx::RxMode;
+use crate::standby::StandbyMode;
+use crate::strategy::Strategy;
+use crate::timer::RadioTimer;
+use crate::tx::TxMode;
+use crate::PIPES_COUNT;
+use core::fmt;
+use core::mem;
+use embedded_hal_async::delay::DelayNs;
+
+/// Pipe that the bound address is received on
+const BOUND_PIPE: usize = 1;
+/// Largest packet
+const MAX_PACKET_LEN: usize = 32;
+/// Frame kinds, the first byte of every packet of a socket
+const DATA: u8 = 0x00;
+const HEARTBEAT: u8 = 0x01;
+/// Fetches a reply queued as ACK payload
+const POLL: u8 = 0x02;
+/// Request to reply to as ACK payload
+const REQUEST: u8 = 0x03;
+/// Request to reply to as a packet
+const REQUEST_PACKET: u8 = 0x04;
+/// Answer to a request, as either
+const REPLY: u8 = 0x05;
+/// Asks for the same frame back as ACK payload, to detect whether ACK
+/// payloads work
+const PROBE: u8 = 0x06;
+/// Interval between polls, and at which to check for replies
+const POLL_INTERVAL_US: u32 = 500;
+/// Interval between polls of a transmission in progress
+const SEND_INTERVAL_US: u32 = 100;
+
+/// Heartbeat settings of a [`RadioSocket`](struct.RadioSocket.html), see
+/// [`set_keepalive()`](struct.RadioSocket.html#method.set_keepalive)
+#[derive(Debug, Copy, Clone)]
+pub struct Keepalive {
+    /// Idle time after which a heartbeat is sent, in µs of the timer
+    pub interval_us: u64,
+    /// Unacknowledged packets in a row after which the peer is dead
+    pub max_missed: u8,
+    /// Called once the peer has been declared dead
+    pub on_dead: fn(&Address),
+    /// Called once a dead peer acknowledges again
+    pub on_alive: fn(&Address),
+}
+
+/// Liveness of the connected peer, as seen by
+/// [`RadioSocket::keepalive()`](struct.RadioSocket.html#method.keepalive)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PeerState {
+    /// The last packet to the peer was acknowledged, or it sent one
+    Alive,
+    /// This many packets in a row went unacknowledged
+    Missed(u8),
+    /// `max_missed` packets in a row went unacknowledged
+    Dead,
+}
+
+/// Keepalive state
+#[derive(Debug, Copy, Clone)]
+struct Liveness {
+    config: Keepalive,
+    /// When the last heartbeat or activity was seen by `keepalive()`
+    since: Option<u64>,
+    /// Something went over the link since
+    active: bool,
+    missed: u8,
+}
+
+/// Frame of `kind`, with `parts` after it
+fn frame(kind: u8, parts: &[&[u8]]) -> Result<Payload, InvalidArgument> {
+    let len = parts.iter().map(|part| part.len()).fold(1, usize::saturating_add);
+    if len > MAX_PACKET_LEN {
+        return Err(InvalidArgument::PayloadLength);
+    }
+    let mut frame = Payload::zeroed(len);
+    let data = frame.data_mut();
+    let mut at = copy_bytes(data, &[kind]);
+    for part in parts {
+        at = at.saturating_add(copy_bytes(data.get_mut(at..).unwrap_or_default(), part));
+    }
+    Ok(frame)
+}
+
+/// Request received by `recv()`, to be answered by `reply()`
+#[derive(Debug, Copy, Clone)]
+struct Pending {
+    seq: u8,
+    ack_payload: bool,
+}
+
+/// Data of `frame` if it is the reply to request `seq`
+fn reply_data(frame: &[u8], seq: u8) -> Option<Payload> {
+    match *frame {
+        [REPLY, reply_seq, ref data @ ..] if reply_seq == seq => Some(Payload::new(data)),
+        _ => None,
+    }
+}
+
+/// Mode the socket's radio is in
+enum Mode<D: Device> {
+    Standby(StandbyMode<D>),
+    Rx(RxMode<D>),
+    Tx(TxMode<D>),
+    /// A mode switch was cancelled, and the radio dropped with it
+    Lost,
+}
+
+/// Socket-style facade over addresses, pipes and modes
+///
+/// [`bind()`](#method.bind) receives on a local address and
+/// [`connect()`](#method.connect) sets the peer that
+/// [`send()`](#method.send) goes to. The socket switches between RX and
+/// TX mode as needed and listens again after sending once bound. For
+/// 1:N topologies, a hub binds its own address, which all nodes connect
+/// to, and answers with [`send_to()`](#method.send_to).
+///
+/// Every packet starts with a byte telling data from the socket's own
+/// frames, so data is up to [`MAX_LEN`](#associatedconstant.MAX_LEN)
+/// bytes and peers must be sockets too:
+///
+/// | First byte | Frame     | Rest                                      |
+/// |------------|-----------|-------------------------------------------|
+/// | `0x00`     | Data      | Data                                      |
+/// | `0x01`     | Heartbeat | Bound address of the sender, if any       |
+/// | `0x02`     | Poll      | Sequence number of the request            |
+/// | `0x03`     | Request   | Sequence number, data                     |
+/// | `0x04`     | Request   | Sequence number, data, to reply as packet |
+/// | `0x05`     | Reply     | Sequence number of the request, data      |
+/// | `0x06`     | Probe     | Nothing                                   |
+///
+/// Pipe 1 receives the bound address, and pipe 0 the ACKs from the
+/// peer. Both need auto-ack for `send()` to tell whether the peer got a
+/// packet. Pipe 0 is only enabled in TX mode: left on in RX with the
+/// last peer's address, it would acknowledge, and so steal, packets that
+/// other nodes send to that peer. Other settings are left as configured.
+///
+/// # Requests and replies
+///
+/// Between two sockets bound to their own addresses and connected to
+/// each other, [`request()`](#method.request) sends a request that the
+/// peer answers with [`reply()`](#method.reply) after receiving it.
+/// Replies are returned as ACK payloads to the polls that follow a
+/// request, which saves switching roles. Some clones do not implement
+/// ACK payloads correctly, so before the first request, the requester
+/// sends probes until one is answered as ACK payload, see
+/// [`probe_ack_payloads()`](#method.probe_ack_payloads). If none is,
+/// requests ask for the reply as a packet instead, and the requester
+/// listens for it. Each request tells the replier which way to answer,
+/// and polls and replies carry the sequence number of the request, so
+/// that late ones are told apart.
+///
+/// # Keepalive
+///
+/// With [`set_keepalive()`](#method.set_keepalive), a link that went
+/// silent is noticed before the next send. Call
+/// [`keepalive()`](#method.keepalive) from the main loop: when nothing
+/// went over the link for the interval, it sends a heartbeat. After
+/// several packets in a row without an ACK, the peer is declared dead.
+/// The sender of a packet is unknown, so of the packets received, only
+/// heartbeats naming the connected peer count as activity. Heartbeats go on meanwhile, each
+/// one after binding again, and the first ACK brings the session back.
+pub struct RadioSocket<D: Device> {
+    mode: Mode<D>,
+    local: Option<Address>,
+    peer: Option<Address>,
+    /// Whether replies as ACK payloads work, once detected
+    ack_payloads: Option<bool>,
+    /// Sequence number of the last request sent
+    seq: u8,
+    pending: Option<Pending>,
+    liveness: Option<Liveness>,
+}
+
+impl<D: Device> fmt::Debug for RadioSocket<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RadioSocket")
+    }
+}
+
+impl<D: Device> RadioSocket<D> {
+    /// Longest data that fits in a packet after the frame kind
+    pub const MAX_LEN: usize = MAX_PACKET_LEN - 1;
+
+    /// Constructor, neither bound nor connected
+    pub fn new(standby: StandbyMode<D>) -> Self {
+        RadioSocket {
+            mode: Mode::Standby(standby),
+            local: None,
+            peer: None,
+            ack_payloads: None,
+            seq: 0,
+            pending: None,
+            liveness: None,
+        }
+    }
+
+    /// Address passed to [`bind()`](#method.bind)
+    pub fn local_addr(&self) -> Option<&Address> {
+        self.local.as_ref()
+    }
+
+    /// Address passed to [`connect()`](#method.connect)
+    pub fn peer_addr(&self) -> Option<&Address> {
+        self.peer.as_ref()
+    }
+
+    /// Receive packets sent to `addr`, and start listening
+    ///
+    /// Sets the address width to that of `addr`, which peers must use
+    /// too, and `EN_ACK_PAY` for replies.
+    pub async fn bind(&mut self, addr: &Address) -> Result<(), D::Error> {
+        let standby = self.standby()?;
+        standby.set_ack_payloads(true).await?;
+        standby.set_address_width(addr.len() as u8).await?;
+        standby.set_rx_addr(BOUND_PIPE, addr).await?;
+        self.local = Some(*addr);
+        self.rx().await?;
+        Ok(())
+    }
+
+    /// Send to `peer` from now on
+    ///
+    /// Like a UDP socket, this checks nothing on air.
+    pub fn connect(&mut self, peer: &Address) -> Result<(), D::Error> {
+        self.check_width(peer)?;
+        self.peer = Some(*peer);
+        Ok(())
+    }
+
+    /// Send `packet` to the connected peer, and return whether it was
+    /// acknowledged
+    pub async fn send<DL: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let peer = self.peer.ok_or(StateError::NoPeer)?;
+        self.send_to(&peer, packet, delay).await
+    }
+
+    /// Send `packet` to `peer`, and return whether it was acknowledged
+    ///
+    /// Waits for the transmission to complete, polling with `delay`,
+    /// then returns to listening if bound. `packet` is up to
+    /// [`MAX_LEN`](#associatedconstant.MAX_LEN) bytes.
+    pub async fn send_to<DL: DelayNs>(
+        &mut self,
+        peer: &Address,
+        packet: &[u8],
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let frame = frame(DATA, &[packet])?;
+        self.send_frame(peer, &frame, delay).await
+    }
+
+    /// Send `frame` to `peer` like [`send_to()`](#method.send_to)
+    async fn send_frame<DL: DelayNs>(
+        &mut self,
+        peer: &Address,
+        frame: &[u8],
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let (acked, _) = self.exchange(peer, frame, delay).await?;
+        if self.local.is_some() {
+            self.rx().await?;
+        }
+        Ok(acked)
+    }
+
+    /// Send `frame` to `peer` and wait for the transmission to
+    /// complete, staying in TX mode. Returns whether it was acknowledged,
+    /// and the ACK payload if any.
+    async fn exchange<DL: DelayNs>(
+        &mut self,
+        peer: &Address,
+        frame: &[u8],
+        delay: &mut DL,
+    ) -> Result<(bool, Option<Payload>), D::Error> {
+        self.check_width(peer)?;
+        let unbound = self.local.is_none();
+        let tx = self.tx().await?;
+        if unbound {
+            tx.set_address_width(peer.len() as u8).await?;
+        }
+        tx.set_tx_addr(peer).await?;
+        tx.set_rx_addr(0, peer).await?;
+        tx.send(frame).await?;
+        let acked = tx.wait_send(&mut Strategy::polling(SEND_INTERVAL_US), delay).await?;
+        let ack_payload = if acked {
+            tx.read_ack_payload().await?
+        } else {
+            None
+        };
+        self.note_ack(peer, acked);
+        Ok((acked, ack_payload))
+    }
+
+    /// Send heartbeats to the connected peer when idle, or stop with
+    /// `None`
+    pub fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.liveness = keepalive.map(|config| Liveness {
+            config: Keepalive {
+                max_missed: config.max_missed.max(1),
+                ..config
+            },
+            since: None,
+            active: false,
+            missed: 0,
+        });
+    }
+
+    /// Liveness of the connected peer, `Alive` without keepalive
+    pub fn peer_state(&self) -> PeerState {
+        match &self.liveness {
+            Some(liveness) if liveness.missed >= liveness.config.max_missed => PeerState::Dead,
+            Some(liveness) if liveness.missed > 0 => PeerState::Missed(liveness.missed),
+            _ => PeerState::Alive,
+        }
+    }
+
+    /// Send a heartbeat to the connected peer if the link has been idle
+    /// for the interval, and return its liveness
+    ///
+    /// While the peer is dead, binds again before each heartbeat, to
+    /// restore the radio settings in case they were lost, and detects
+    /// anew whether replies arrive as ACK payloads once it is back, as
+    /// the peer may have restarted with other firmware. Does nothing
+    /// without keepalive or a connected peer.
+    pub async fn keepalive<T: RadioTimer>(&mut self, timer: &mut T) -> Result<PeerState, D::Error> {
+        let (Some(liveness), Some(peer)) = (&mut self.liveness, self.peer) else {
+            return Ok(self.peer_state());
+        };
+        let now = timer.now_micros();
+        let since = *liveness.since.get_or_insert(now);
+        if liveness.active {
+            liveness.active = false;
+            liveness.since = Some(now);
+            return Ok(self.peer_state());
+        }
+        if now.saturating_sub(since) < liveness.config.interval_us {
+            return Ok(self.peer_state());
+        }
+        liveness.since = Some(now);
+
+        if self.peer_state() == PeerState::Dead {
+            if let Some(local) = self.local {
+                self.bind(&local).await?;
+            }
+            self.ack_payloads = None;
+        }
+        let local = self.local.as_ref().map_or(&[][..], Address::as_bytes);
+        let heartbeat = frame(HEARTBEAT, &[local])?;
+        self.send_frame(&peer, &heartbeat, timer).await?;
+        if let Some(liveness) = &mut self.liveness {
+            liveness.active = false;
+        }
+        Ok(self.peer_state())
+    }
+
+    /// Record whether a packet to the peer got through, and report
+    /// changes of its liveness
+    fn note_ack(&mut self, peer: &Address, acked: bool) {
+        let connected = self.peer.as_ref() == Some(peer);
+        let Some(liveness) = &mut self.liveness else {
+            return;
+        };
+        if !connected {
+            return;
+        }
+        let max_missed = liveness.config.max_missed;
+        if acked {
+            liveness.active = true;
+            if liveness.missed >= max_missed {
+                (liveness.config.on_alive)(peer);
+            }
+            liveness.missed = 0;
+        } else if liveness.missed < max_missed {
+            liveness.missed = liveness.missed.saturating_add(1);
+            if liveness.missed == max_missed {
+                (liveness.config.on_dead)(peer);
+            }
+        }
+    }
+
+    /// Whether replies arrive as ACK payloads, `None` until
+    /// [`probe_ack_payloads()`](#method.probe_ack_payloads) has found out
+    pub fn ack_payloads(&self) -> Option<bool> {
+        self.ack_payloads
+    }
+
+    /// Detect whether the connected peer can answer with ACK payloads,
+    /// sending probes for up to `timeout_us`
+    ///
+    /// The peer answers the first probe that its
+    /// [`recv()`](#method.recv) sees with an ACK payload, which the next
+    /// probe fetches. ACK payloads are taken as not working if probes
+    /// were acknowledged all along without one, so the peer must be
+    /// calling `recv()` meanwhile. Returns `None` if no probe was
+    /// acknowledged at all. [`request()`](#method.request) calls it
+    /// first if need be.
+    pub async fn probe_ack_payloads<DL: DelayNs>(
+        &mut self,
+        timeout_us: u32,
+        delay: &mut DL,
+    ) -> Result<Option<bool>, D::Error> {
+        let peer = self.peer.ok_or(StateError::NoPeer)?;
+        let mut acked_any = false;
+        let mut waited_us = 0;
+        loop {
+            let (acked, ack_payload) = self.exchange(&peer, &[PROBE], delay).await?;
+            if ack_payload.is_some_and(|ack_payload| *ack_payload == [PROBE]) {
+                self.ack_payloads = Some(true);
+                break;
+            }
+            acked_any |= acked;
+            if waited_us >= timeout_us {
+                if acked_any {
+                    self.ack_payloads = Some(false);
+                }
+                break;
+            }
+            delay.delay_us(POLL_INTERVAL_US).await;
+            waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+        }
+        if self.local.is_some() {
+            self.rx().await?;
+        }
+        Ok(self.ack_payloads)
+    }
+
+    /// Send `packet` to the connected peer and wait up to `timeout_us`
+    /// for its [`reply()`](#method.reply)
+    ///
+    /// Returns `None` if the request was not acknowledged or no reply
+    /// came. `packet` is up to one byte shorter than
+    /// [`MAX_LEN`](#associatedconstant.MAX_LEN). Needs a bound address
+    /// for replies sent as packets, and drops other packets received
+    /// while waiting for one.
+    pub async fn request<DL: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        timeout_us: u32,
+        delay: &mut DL,
+    ) -> Result<Option<Payload>, D::Error> {
+        let peer = self.peer.ok_or(StateError::NoPeer)?;
+        if self.local.is_none() {
+            return Err(StateError::NotBound.into());
+        }
+        let ack_payloads = match self.ack_payloads {
+            Some(ack_payloads) => ack_payloads,
+            None => match self.probe_ack_payloads(timeout_us, delay).await? {
+                Some(ack_payloads) => ack_payloads,
+                None => return Ok(None),
+            },
+        };
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let kind = if ack_payloads { REQUEST } else { REQUEST_PACKET };
+        let request = frame(kind, &[&[seq], packet])?;
+        let (acked, _) = self.exchange(&peer, &request, delay).await?;
+
+        let mut reply = None;
+        let mut waited_us = 0;
+        while acked && reply.is_none() && waited_us < timeout_us {
+            delay.delay_us(POLL_INTERVAL_US).await;
+            waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+            if ack_payloads {
+                let (_, ack_payload) = self.exchange(&peer, &[POLL, seq], delay).await?;
+                reply = ack_payload.and_then(|ack_payload| reply_data(&ack_payload, seq));
+            } else {
+                let rx = self.rx().await?;
+                while let (None, Some((pipe_no, payload))) = (&reply, rx.read_with_pipe().await?) {
+                    if usize::from(pipe_no) == BOUND_PIPE {
+                        reply = reply_data(&payload, seq);
+                    }
+                }
+            }
+        }
+        self.rx().await?;
+        Ok(reply)
+    }
+
+    /// Answer the request last received by [`recv()`](#method.recv)
+    /// from the connected peer with `packet`, trying for up to
+    /// `timeout_us`, and return whether it was delivered
+    ///
+    /// Answers as the request asked, and returns `false` right away if
+    /// there is no request to answer. While the reply waits as ACK
+    /// payload, other packets on the bound address are dropped, and
+    /// they get the reply as ACK payload if they come before the poll.
+    pub async fn reply<DL: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        timeout_us: u32,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        let peer = self.peer.ok_or(StateError::NoPeer)?;
+        if self.local.is_none() {
+            return Err(StateError::NotBound.into());
+        }
+        let Some(pending) = self.pending.take() else {
+            return Ok(false);
+        };
+        let reply = frame(REPLY, &[&[pending.seq], packet])?;
+
+        let mut waited_us = 0;
+        if pending.ack_payload {
+            let rx = self.rx().await?;
+            rx.flush_ack_payloads(None).await?;
+            rx.queue_ack_payload(BOUND_PIPE as u8, &reply).await?;
+            let poll = [POLL, pending.seq];
+            while waited_us < timeout_us {
+                delay.delay_us(POLL_INTERVAL_US).await;
+                waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+                let mut polled = false;
+                while let Some((pipe_no, payload)) = rx.read_with_pipe().await? {
+                    polled |= usize::from(pipe_no) == BOUND_PIPE && *payload == poll;
+                }
+                // The chip only drops an ACK payload from the TX FIFO
+                // once it has been sent
+                if polled && rx.ack_queue_depth().await? == 0 {
+                    return Ok(true);
+                }
+            }
+            rx.flush_ack_payloads(None).await?;
+            return Ok(false);
+        }
+
+        while waited_us < timeout_us {
+            if self.send_frame(&peer, &reply, delay).await? {
+                return Ok(true);
+            }
+            delay.delay_us(POLL_INTERVAL_US).await;
+            waited_us = waited_us.saturating_add(POLL_INTERVAL_US);
+        }
+        Ok(false)
+    }
+
+    /// Next packet received on the bound address, or `None` if there is
+    /// none
+    ///
+    /// Requests are returned too, for [`reply()`](#method.reply) to
+    /// answer, and probes are answered. Packets on pipe 0 and the
+    /// socket's other frames are dropped, and so are packets that are
+    /// not frames of a socket.
+    pub async fn recv(&mut self) -> Result<Option<Payload>, D::Error> {
+        if self.local.is_none() {
+            return Err(StateError::NotBound.into());
+        }
+        let peer = self.peer;
+        let rx = self.rx().await?;
+        let mut heard = false;
+        let mut packet = None;
+        let mut pending = None;
+        while let Some((pipe_no, payload)) = rx.read_with_pipe().await? {
+            if usize::from(pipe_no) != BOUND_PIPE {
+                continue;
+            }
+            match *payload {
+                [DATA, ref data @ ..] => {
+                    packet = Some(Payload::new(data));
+                    break;
+                }
+                [kind @ (REQUEST | REQUEST_PACKET), seq, ref data @ ..] => {
+                    pending = Some(Pending {
+                        seq,
+                        ack_payload: kind == REQUEST,
+                    });
+                    packet = Some(Payload::new(data));
+                    break;
+                }
+                [HEARTBEAT, ref sender @ ..] => {
+                    heard |= peer.is_some_and(|peer| *peer == *sender);
+                }
+                // Fetched by the next probe
+                [PROBE] if rx.ack_queue_depth().await? == 0 => {
+                    rx.queue_ack_payload(BOUND_PIPE as u8, &[PROBE]).await?;
+                }
+                _ => {}
+            }
+        }
+        if pending.is_some() {
+            self.pending = pending;
+        }
+        // A heartbeat of the peer shows the link works
+        if let (true, Some(liveness)) = (heard, &mut self.liveness) {
+            liveness.active = true;
+        }
+        Ok(packet)
+    }
+
+    /// Return the radio in Standby-I mode, with packets still in the TX
+    /// FIFO if a send was cancelled
+    pub fn close(mut self) -> Result<StandbyMode<D>, D::Error> {
+        self.standby()?;
+        match self.mode {
+            Mode::Standby(standby) => Ok(standby),
+            _ => Err(StateError::RadioLost.into()),
+        }
+    }
+
+    /// All addresses must have the width of the bound one
+    fn check_width(&self, peer: &Address) -> Result<(), InvalidArgument> {
+        match &self.local {
+            Some(local) if local.len() != peer.len() => Err(InvalidArgument::AddressLength),
+            _ => Ok(()),
+        }
+    }
+
+    /// Switch to Standby-I
+    fn standby(&mut self) -> Result<&mut StandbyMode<D>, D::Error> {
+        self.mode = match mem::replace(&mut self.mode, Mode::Lost) {
+            Mode::Standby(standby) => Mode::Standby(standby),
+            Mode::Rx(rx) => Mode::Standby(rx.standby()),
+            Mode::Tx(tx) => Mode::Standby(tx.into_standby()),
+            Mode::Lost => Mode::Lost,
+        };
+        match &mut self.mode {
+            Mode::Standby(standby) => Ok(standby),
+            _ => Err(StateError::RadioLost.into()),
+        }
+    }
+
+    /// Pipes to enable: the bound one, and pipe 0 for ACKs when sending
+    fn pipes(&self, sending: bool) -> [bool; PIPES_COUNT] {
+        let mut enable = [false; PIPES_COUNT];
+        enable[0] = sending;
+        enable[BOUND_PIPE] = self.local.is_some();
+        enable
+    }
+
+    /// Switch to RX mode, with pipe 0 disabled
+    async fn rx(&mut self) -> Result<&mut RxMode<D>, D::Error> {
+        if !matches!(self.mode, Mode::Rx(_)) {
+            let enable = self.pipes(false);
+            self.standby()?.set_pipes_rx_enable(&enable).await?;
+            if let Mode::Standby(standby) = mem::replace(&mut self.mode, Mode::Lost) {
+                self.mode = match standby.rx().await {
+                    Ok(rx) => Mode::Rx(rx),
+                    Err((device, e)) => {
+                        self.mode = Mode::Standby(StandbyMode::from_rx_tx(device));
+                        return Err(e);
+                    }
+                };
+            }
+        }
+        match &mut self.mode {
+            Mode::Rx(rx) => Ok(rx),
+            _ => Err(StateError::RadioLost.into()),
+        }
+    }
+
+    /// Switch to TX mode, with pipe 0 enabled
+    ///
+    /// ACK payloads left over in RX mode are flushed, so that they are
+    /// not sent as packets.
+    async fn tx(&mut self) -> Result<&mut TxMode<D>, D::Error> {
+        if let Mode::Rx(rx) = &mut self.mode {
+            rx.flush_ack_payloads(None).await?;
+        }
+        if !matches!(self.mode, Mode::Tx(_)) {
+            let enable = self.pipes(true);
+            self.standby()?.set_pipes_rx_enable(&enable).await?;
+            if let Mode::Standby(standby) = mem::replace(&mut self.mode, Mode::Lost) {
+                self.mode = match standby.tx().await {
+                    Ok(tx) => Mode::Tx(tx),
+                    Err((device, e)) => {
+                        self.mode = Mode::Standby(StandbyMode::from_rx_tx(device));
+                        return Err(e);
+                    }
+                };
+            }
+        }
+        match &mut self.mode {
+            Mode::Tx(tx) => Ok(tx),
+            _ => Err(StateError::RadioLost.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, join, Air, SimRadio, SimTimer, Yielding};
+    use crate::RadioConfig;
+
+    /// Requester bound to `[0xA0; 5]` and replier bound to `[0xB0; 5]`,
+    /// connected to each other
+    async fn requester_replier(air: &Air) -> (RadioSocket<SimRadio>, RadioSocket<SimRadio>) {
+        let mut a = bound(air, 0xA0).await;
+        let mut b = bound(air, 0xB0).await;
+        a.connect(&Address::from_array([0xB0; 5])).unwrap();
+        b.connect(&Address::from_array([0xA0; 5])).unwrap();
+        (a, b)
+    }
+
+    /// Answer the first request with `reply` after `late_us`, or give up
+    /// after `timeout_us`
+    async fn serve(
+        b: &mut RadioSocket<SimRadio>,
+        reply: &[u8],
+        late_us: u32,
+        timeout_us: u32,
+        delay: &mut Yielding,
+    ) -> Option<bool> {
+        let mut waited_us = 0;
+        while waited_us < timeout_us {
+            if b.recv().await.unwrap().is_some() {
+                // In steps, for the requester to go on meanwhile
+                for _ in 0..late_us / 100 {
+                    delay.delay_us(100).await;
+                }
+                return Some(b.reply(reply, 20_000, delay).await.unwrap());
+            }
+            delay.delay_us(100).await;
+            waited_us += 100;
+        }
+        None
+    }
+
+    #[test]
+    fn hub_does_not_steal_packets_to_its_last_peer() {
+        block_on(async {
+            let air = Air::new();
+            let config = RadioConfig::default();
+            let peer = config.tx_addr;
+            let mut standby = sim::standby(&air, &config).await;
+            let chip = standby.device().spi.chip();
+            let mut hub = RadioSocket::new(standby);
+            hub.bind(&Address::from_array([0xB0; 5])).await.unwrap();
+            let _node = sim::standby(&air, &config).await.rx().await.map_err(|(_, e)| e);
+            let mut timer = SimTimer::new();
+            assert_eq!(hub.send_to(&peer, b"hi", &mut timer).await.ok(), Some(true));
+            assert_eq!(chip.register(0x02), 1 << BOUND_PIPE);
+
+            // Another node sends to the same peer
+            let other = sim::standby(&air, &config).await.tx().await;
+            let mut other = other.map_err(|(_, e)| e).unwrap();
+            other.send(b"to peer").await.unwrap();
+            assert_eq!(other.poll_send().await.ok(), Some(true));
+            assert!(chip.rx_fifo().is_empty());
+        });
+    }
+
+    /// A socket bound to `[byte; 5]`
+    async fn bound(air: &Air, byte: u8) -> RadioSocket<sim::SimRadio> {
+        let mut socket = RadioSocket::new(sim::standby(air, &RadioConfig::default()).await);
+        socket.bind(&Address::from_array([byte; 5])).await.unwrap();
+        socket
+    }
+
+    fn ignore(_: &Address) {}
+
+    #[test]
+    fn data_that_looks_like_frames_is_delivered() {
+        block_on(async {
+            let air = Air::new();
+            let mut a = bound(&air, 0xA0).await;
+            let mut b = bound(&air, 0xB0).await;
+            a.connect(&Address::from_array([0xB0; 5])).unwrap();
+            let mut timer = SimTimer::new();
+            for data in [&[DATA][..], &[HEARTBEAT], &[POLL], &[]] {
+                assert_eq!(a.send(data, &mut timer).await.ok(), Some(true));
+                assert_eq!(b.recv().await.ok().flatten().as_deref(), Some(data));
+            }
+            let long = [0; MAX_PACKET_LEN];
+            assert!(a.send(&long, &mut timer).await.is_err());
+        });
+    }
+
+    #[test]
+    fn only_heartbeats_of_the_peer_count() {
+        block_on(async {
+            let air = Air::new();
+            let mut a = bound(&air, 0xA0).await;
+            let mut b = bound(&air, 0xB0).await;
+            let mut c = bound(&air, 0xC0).await;
+            let b_addr = Address::from_array([0xB0; 5]);
+            b.connect(&Address::from_array([0xA0; 5])).unwrap();
+            b.set_keepalive(Some(Keepalive {
+                interval_us: 1_000,
+                max_missed: 3,
+                on_dead: ignore,
+                on_alive: ignore,
+            }));
+            let mut timer = SimTimer::new();
+            let active = |socket: &RadioSocket<_>| socket.liveness.is_some_and(|l| l.active);
+
+            let heartbeat = frame(HEARTBEAT, &[&[0xC0; 5]]).unwrap();
+            c.send_frame(&b_addr, &heartbeat, &mut timer).await.unwrap();
+            assert!(matches!(b.recv().await, Ok(None)));
+            assert!(!active(&b));
+
+            let heartbeat = frame(HEARTBEAT, &[&[0xA0; 5]]).unwrap();
+            a.send_frame(&b_addr, &heartbeat, &mut timer).await.unwrap();
+            assert!(matches!(b.recv().await, Ok(None)));
+            assert!(active(&b));
+        });
+    }
+
+    #[test]
+    fn reply_comes_as_ack_payload() {
+        block_on(async {
+            let air = Air::new();
+            let (mut a, mut b) = requester_replier(&air).await;
+            let timer = SimTimer::new();
+            let (mut da, mut db) = (Yielding(timer.clone()), Yielding(timer));
+            let (reply, served) = join(
+                a.request(b"temp?", 20_000, &mut da),
+                serve(&mut b, b"21", 0, 20_000, &mut db),
+            )
+            .await;
+            assert_eq!(reply.ok().flatten().as_deref(), Some(&b"21"[..]));
+            assert_eq!(served, Some(true));
+            assert_eq!(a.ack_payloads(), Some(true));
+        });
+    }
+
+    #[test]
+    fn reply_comes_as_packet_without_ack_payloads() {
+        block_on(async {
+            let air = Air::new();
+            let (mut a, mut b) = requester_replier(&air).await;
+            if let Mode::Rx(rx) = &mut b.mode {
+                rx.set_ack_payloads(false).await.unwrap();
+            }
+            let timer = SimTimer::new();
+            let (mut da, mut db) = (Yielding(timer.clone()), Yielding(timer));
+            let (probed, _) = join(
+                a.probe_ack_payloads(2_000, &mut da),
+                serve(&mut b, b"", 0, 2_000, &mut db),
+            )
+            .await;
+            assert_eq!(probed.ok(), Some(Some(false)));
+
+            let (reply, served) = join(
+                a.request(b"temp?", 20_000, &mut da),
+                serve(&mut b, b"21", 0, 20_000, &mut db),
+            )
+            .await;
+            assert_eq!(reply.ok().flatten().as_deref(), Some(&b"21"[..]));
+            assert_eq!(served, Some(true));
+        });
+    }
+
+    #[test]
+    fn late_reply_does_not_change_the_way() {
+        block_on(async {
+            let air = Air::new();
+            let (mut a, mut b) = requester_replier(&air).await;
+            let timer = SimTimer::new();
+            let (mut da, mut db) = (Yielding(timer.clone()), Yielding(timer));
+            // The reply comes after the requester gave up
+            let (reply, served) = join(
+                a.request(b"first", 5_000, &mut da),
+                serve(&mut b, b"late", 10_000, 20_000, &mut db),
+            )
+            .await;
+            assert!(matches!(reply, Ok(None)));
+            assert_eq!(served, Some(false));
+            assert_eq!(a.ack_payloads(), Some(true));
+            // Its polls are not taken as data
+            assert!(matches!(b.recv().await, Ok(None)));
+
+            let (reply, served) = join(
+                a.request(b"second", 20_000, &mut da),
+                serve(&mut b, b"21", 0, 20_000, &mut db),
+            )
+            .await;
+            assert_eq!(reply.ok().flatten().as_deref(), Some(&b"21"[..]));
+            assert_eq!(served, Some(true));
+        });
+    }
+}
@@ -1,8 +1,16 @@
+use crate::command::{FlushTx, Nop};
 use crate::config::Configuration;
 use crate::device::Device;
+use crate::error::StateError;
+use crate::events::{ModeKind, RadioEvents};
+use crate::registers::{Config, FifoStatus};
 use crate::rx::RxMode;
 use crate::tx::TxMode;
 use core::fmt;
+use embedded_hal_async::delay::DelayNs;
+
+/// Tpd2stby is 1.5ms for most crystals, give clones some slack
+pub(crate) const SETTLE_MS: u32 = 5;
 
 /// Represents **Standby-I** mode
 ///
@@ -10,9 +18,8 @@ use core::fmt;
 /// mode.
 pub struct StandbyMode<D: Device> {
     device: D,
-}
-
-unsafe impl<D: Device> Send for StandbyMode<D> {
+    /// ACK payloads queued in RX mode are left in the TX FIFO
+    stale_acks: bool,
 }
 
 impl<D: Device> fmt::Debug for StandbyMode<D> {
@@ -27,31 +34,145 @@ impl<D: Device> StandbyMode<D> {
     /// Puts the `device` into standy mode
     pub async fn power_up(mut device: D) -> Result<Self, (D, D::Error)> {
         match device.update_config(|config| config.set_pwr_up(true)).await {
-            Ok(()) => Ok(StandbyMode { device }),
+            Ok(()) => {
+                device.events().on_mode_change(ModeKind::Standby);
+                Ok(StandbyMode {
+                    device,
+                    stale_acks: false,
+                })
+            }
             Err(e) => Err((device, e)),
         }
     }
 
+    /// Power up so that the first packet goes out reliably, e.g. after
+    /// a cold boot or sleep
+    ///
+    /// Like [`power_up()`](#method.power_up), then waits for the
+    /// oscillator to settle and clears what the chip may have picked up
+    /// meanwhile, see [`settle()`](#method.settle). Without this, the
+    /// first packet is often lost, or sent twice from a stale FIFO.
+    pub async fn power_up_settled<DL: DelayNs>(
+        device: D,
+        delay: &mut DL,
+    ) -> Result<Self, (D, D::Error)> {
+        let mut standby = Self::power_up(device).await?;
+        match standby.settle(delay).await {
+            Ok(()) => Ok(standby),
+            Err(e) => Err((standby.device, e)),
+        }
+    }
+
+    /// Wait 5ms for the oscillator to settle after power up, send a
+    /// dummy `NOP`, flush both FIFOs and clear `STATUS`
+    ///
+    /// Flushing drops packets that were loaded or received before the
+    /// chip was last reset, and the `NOP` ends any SPI transaction that
+    /// a brown-out left half done.
+    pub async fn settle<DL: DelayNs>(&mut self, delay: &mut DL) -> Result<(), D::Error> {
+        delay.delay_ms(SETTLE_MS).await;
+        self.device.send_command(&Nop).await?;
+        self.flush_rx().await?;
+        self.flush_tx().await?;
+        self.stale_acks = false;
+        self.clear_interrupts().await
+    }
+
     /// Should be a no-op
     pub async fn power_down(mut self) -> Result<D, (Self, D::Error)> {
         match self.device.update_config(|config| config.set_pwr_up(false)).await {
-            Ok(()) => Ok(self.device),
+            Ok(()) => {
+                self.device.events().on_mode_change(ModeKind::PowerDown);
+                Ok(self.device)
+            }
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Power down and verify that the chip cannot radiate, e.g. for
+    /// regulatory duty cycling or test lab procedures
+    ///
+    /// Drops `CE`, flushes the TX FIFO, and writes `CONFIG` even if the
+    /// cached value already has `PWR_UP` cleared. Then reads `CONFIG`
+    /// and `FIFO_STATUS` back from the chip, and fails with
+    /// `Error::NotSilent` unless `PWR_UP` is clear and the TX
+    /// FIFO empty.
+    pub async fn power_down_verified(mut self) -> Result<D, (Self, D::Error)> {
+        match self.silence().await {
+            Ok(()) => {
+                self.device.events().on_mode_change(ModeKind::PowerDown);
+                Ok(self.device)
+            }
             Err(e) => Err((self, e)),
         }
     }
 
-    pub(crate) fn from_rx_tx(mut device: D) -> Self {
+    /// Steps and checks of `power_down_verified()`
+    async fn silence(&mut self) -> Result<(), D::Error> {
+        self.device.ce_disable();
+        self.device.send_command(&FlushTx).await?;
+        self.stale_acks = false;
+        self.device.update_config(|config| config.set_pwr_up(false)).await?;
+        self.device.restore_config().await?;
+        let (_, config) = self.device.read_register::<Config>().await?;
+        let (_, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        if config.pwr_up() || !fifo_status.tx_empty() {
+            return Err(StateError::NotSilent.into());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn from_rx_tx(device: D) -> Self {
+        Self::from_rx(device, false)
+    }
+
+    /// `from_rx_tx()`, flushing the TX FIFO before the next mode if
+    /// `stale_acks`
+    pub(crate) fn from_rx(mut device: D, stale_acks: bool) -> Self {
         device.ce_disable();
-        StandbyMode { device }
+        device.events().on_mode_change(ModeKind::Standby);
+        StandbyMode { device, stale_acks }
+    }
+
+    /// Give up the device without touching the chip, for error paths
+    pub(crate) fn into_device(self) -> D {
+        self.device
+    }
+
+    /// Flush ACK payloads left over from RX mode
+    async fn flush_stale_acks(&mut self) -> Result<(), D::Error> {
+        if self.stale_acks {
+            self.device.send_command(&FlushTx).await?;
+            self.stale_acks = false;
+        }
+        Ok(())
+    }
+
+    /// Set `PRIM_RX`, powering down meanwhile if `CE` is tied high, as
+    /// the chip only changes modes from Standby-I
+    async fn switch_prim_rx(device: &mut D, prim_rx: bool) -> Result<(), D::Error> {
+        if device.ce_tied() && device.update_config(|config| config.prim_rx()).await? != prim_rx {
+            device.update_config(|config| config.set_pwr_up(false)).await?;
+        }
+        device
+            .update_config(|config| {
+                config.set_prim_rx(prim_rx);
+                config.set_pwr_up(true);
+            })
+            .await
     }
 
     /// Go into RX mode
-    pub async fn rx(self) -> Result<RxMode<D>, (D, D::Error)> {
+    pub async fn rx(mut self) -> Result<RxMode<D>, (D, D::Error)> {
+        if let Err(e) = self.flush_stale_acks().await {
+            return Err((self.device, e));
+        }
         let mut device = self.device;
 
-        match device.update_config(|config| config.set_prim_rx(true)).await {
+        match Self::switch_prim_rx(&mut device, true).await {
             Ok(()) => {
                 device.ce_enable();
+                device.events().on_mode_change(ModeKind::Rx);
                 Ok(RxMode::new(device))
             }
             Err(e) => Err((device, e)),
@@ -59,12 +180,16 @@ impl<D: Device> StandbyMode<D> {
     }
 
     /// Go into TX mode
-    pub async fn tx(self) -> Result<TxMode<D>, (D, D::Error)> {
+    pub async fn tx(mut self) -> Result<TxMode<D>, (D, D::Error)> {
+        if let Err(e) = self.flush_stale_acks().await {
+            return Err((self.device, e));
+        }
         let mut device = self.device;
 
-        match device.update_config(|config| config.set_prim_rx(false)).await {
+        match Self::switch_prim_rx(&mut device, false).await {
             Ok(()) => {
                 // No need to device.ce_enable(); yet
+                device.events().on_mode_change(ModeKind::Tx);
                 Ok(TxMode::new(device))
             }
             Err(e) => Err((device, e)),
@@ -0,0 +1,90 @@
+/// Traffic counters maintained by the device
+///
+/// Obtain with [`stats()`](trait.Configuration.html#method.stats) in any
+/// mode. All counters wrap around on overflow.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct Stats {
+    /// SPI commands issued
+    pub commands: u32,
+    /// Bytes sent to the chip: command bytes and written data
+    pub spi_bytes_written: u32,
+    /// Bytes of data read back from the chip, excluding `STATUS`
+    pub spi_bytes_read: u32,
+    /// Payloads the TX FIFO accepted, not counting those written while
+    /// it was full
+    pub packets_sent: u32,
+    /// Of `packets_sent`, those sent without asking for an ACK
+    pub packets_sent_no_ack: u32,
+    /// Payloads read from the RX FIFO
+    pub packets_received: u32,
+    /// `TX_DS` interrupts cleared, each for at least one packet sent or
+    /// ACK payload delivered
+    pub tx_ds: u32,
+    /// Packets dropped after reaching the maximum number of retransmits
+    pub max_rt: u32,
+    /// `FLUSH_TX` and `FLUSH_RX` commands
+    pub fifo_flushes: u32,
+    /// Times the RX FIFO was found full, so that packets arriving
+    /// meanwhile were dropped by the chip
+    pub rx_overflows: u32,
+    /// Packets dropped by the RX prefix filter
+    pub packets_filtered: u32,
+    /// Packets with a dynamic payload width of 0
+    pub zero_width_payloads: u32,
+}
+
+impl Stats {
+    /// Account for a command, classified by its opcode
+    pub(crate) fn count_command(&mut self, opcode: u8, len: usize) {
+        let len = len as u32;
+        self.commands = self.commands.wrapping_add(1);
+        match opcode {
+            // R_REGISTER, R_RX_PL_WID, R_RX_PAYLOAD
+            0x00..=0x1F | 0x60 | 0x61 => {
+                self.spi_bytes_written = self.spi_bytes_written.wrapping_add(1);
+                self.spi_bytes_read = self.spi_bytes_read.wrapping_add(len.saturating_sub(1));
+            }
+            _ => self.spi_bytes_written = self.spi_bytes_written.wrapping_add(len),
+        }
+        match opcode {
+            // R_RX_PAYLOAD
+            0x61 => self.packets_received = self.packets_received.wrapping_add(1),
+            // FLUSH_TX, FLUSH_RX
+            0xE1 | 0xE2 => self.fifo_flushes = self.fifo_flushes.wrapping_add(1),
+            _ => {}
+        }
+    }
+
+    /// Account for a payload accepted by the TX FIFO
+    pub(crate) fn count_sent(&mut self, no_ack: bool) {
+        self.packets_sent = self.packets_sent.wrapping_add(1);
+        if no_ack {
+            self.packets_sent_no_ack = self.packets_sent_no_ack.wrapping_add(1);
+        }
+    }
+
+    /// Account for a cleared `TX_DS` interrupt
+    pub(crate) fn count_tx_ds(&mut self) {
+        self.tx_ds = self.tx_ds.wrapping_add(1);
+    }
+
+    /// Account for a `MAX_RT` interrupt
+    pub(crate) fn count_max_rt(&mut self) {
+        self.max_rt = self.max_rt.wrapping_add(1);
+    }
+
+    /// Account for a packet dropped by the prefix filter
+    pub(crate) fn count_filtered(&mut self) {
+        self.packets_filtered = self.packets_filtered.wrapping_add(1);
+    }
+
+    /// Account for a packet with a payload width of 0
+    pub(crate) fn count_zero_width(&mut self) {
+        self.zero_width_payloads = self.zero_width_payloads.wrapping_add(1);
+    }
+
+    /// Account for a full RX FIFO
+    pub(crate) fn count_rx_overflow(&mut self) {
+        self.rx_overflows = self.rx_overflows.wrapping_add(1);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::timer::RadioTimer;
+use core::convert::Infallible;
+use embedded_hal::digital::ErrorType;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+
+/// Polling interval used after the `IRQ` pin has failed
+const FALLBACK_INTERVAL_US: u32 = 100;
+
+/// How to wait for the chip, so that the same firmware runs on boards
+/// with and without the `IRQ` line wired
+///
+/// Pass to [`TxMode::wait_send()`](struct.TxMode.html#method.wait_send),
+/// [`TxMode::send_when_ready_with()`](struct.TxMode.html#method.send_when_ready_with)
+/// and [`RxMode::wait_read()`](struct.RxMode.html#method.wait_read).
+/// With `Irq`, the interrupts in use must not be masked.
+#[derive(Debug)]
+pub enum Strategy<IRQ> {
+    /// Wait for the `IRQ` pin to go low
+    Irq(IRQ),
+    /// Read `STATUS` every `interval_us`
+    Polling {
+        /// Time between two reads
+        interval_us: u32,
+    },
+}
+
+/// `IRQ` of a [`Strategy`](enum.Strategy.html) without the pin, see
+/// [`Strategy::polling()`](enum.Strategy.html#method.polling)
+#[derive(Debug)]
+pub enum NoIrq {}
+
+impl ErrorType for NoIrq {
+    type Error = Infallible;
+}
+
+impl Wait for NoIrq {
+    async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+        match *self {}
+    }
+}
+
+impl Strategy<NoIrq> {
+    /// Read `STATUS` every `interval_us`, on boards without the `IRQ`
+    /// line
+    pub const fn polling(interval_us: u32) -> Self {
+        Strategy::Polling { interval_us }
+    }
+}
+
+impl<IRQ: Wait> Strategy<IRQ> {
+    /// Use the `IRQ` pin if there is one, else poll every 100 µs
+    pub fn new(irq: Option<IRQ>) -> Self {
+        match irq {
+            Some(irq) => Strategy::Irq(irq),
+            None => Strategy::Polling {
+                interval_us: FALLBACK_INTERVAL_US,
+            },
+        }
+    }
+
+    /// Is the `IRQ` pin in use?
+    ///
+    /// Turns `false` when the pin reports an error, after which this
+    /// polls every 100 µs.
+    pub fn is_irq(&self) -> bool {
+        matches!(self, Strategy::Irq(_))
+    }
+
+    /// Wait until the chip may have something to report
+    pub async fn wait<DL: DelayNs>(&mut self, delay: &mut DL) {
+        match self {
+            Strategy::Irq(irq) => {
+                if irq.wait_for_low().await.is_err() {
+                    *self = Strategy::Polling {
+                        interval_us: FALLBACK_INTERVAL_US,
+                    };
+                }
+            }
+            Strategy::Polling { interval_us } => delay.delay_us(*interval_us).await,
+        }
+    }
+
+    /// Like [`wait()`](#method.wait), but return after `max_us` at the
+    /// latest
+    pub async fn wait_at_most<T: RadioTimer>(&mut self, timer: &mut T, max_us: u32) {
+        match self {
+            Strategy::Irq(irq) => {
+                if let Some(Err(_)) = timer.timeout(max_us, irq.wait_for_low()).await {
+                    *self = Strategy::Polling {
+                        interval_us: FALLBACK_INTERVAL_US,
+                    };
+                }
+            }
+            Strategy::Polling { interval_us } => timer.delay_us((*interval_us).min(max_us)).await,
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use crate::device::Device;
+use crate::payload::{copy_bytes, Payload};
+use crate::rx::RxMode;
+use crate::timer::RadioTimer;
+use crate::tracking::SendOutcome;
+use crate::tx::TxMode;
+
+/// First byte of a fragment: `[DATA, seq, data...]`
+const DATA: u8 = 0x00;
+/// First byte of a probe for credit: `[PROBE]`
+const PROBE: u8 = 0x01;
+/// First byte of the receiver's ACK payload: `[CREDIT, limit]`
+const CREDIT: u8 = 0x02;
+/// Bytes of the stream per fragment
+pub const FRAGMENT_LEN: usize = 30;
+/// Interval between polls of the fragments in flight
+const POLL_INTERVAL_US: u32 = 100;
+/// Interval between probes while the receiver has no room
+const PROBE_INTERVAL_US: u64 = 2_000;
+
+/// Whether `seq` comes before `limit`, within half of the sequence space
+fn before(seq: u8, limit: u8) -> bool {
+    (1..128).contains(&limit.wrapping_sub(seq))
+}
+
+/// Sends a byte stream in fragments, with up to 3 of them in flight
+///
+/// Instead of waiting for the ACK of each fragment before loading the
+/// next one, a window of fragments waits in the TX FIFO, so that the
+/// chip sends them back to back. When one reaches `MAX_RT`, the chip
+/// drops the ones behind it too, and the sender goes back to it.
+///
+/// The receiver drives the flow: a
+/// [`StreamReceiver`](struct.StreamReceiver.html) returns the sequence
+/// number up to which it has room in its ACK payloads, and the sender
+/// sends nothing past it. While the receiver has no room, the sender
+/// probes every 2 ms for new credit. Requires auto-ack, retransmits and
+/// ACK payloads on both ends. Both ends start at sequence number 0.
+pub struct StreamSender {
+    window: usize,
+    /// Sequence number of the first fragment not acknowledged
+    acked_seq: u8,
+    /// The receiver has room for fragments before this sequence number
+    limit: u8,
+}
+
+impl StreamSender {
+    /// Constructor, with up to `window` fragments in flight, 1 for
+    /// stop-and-wait and at most 3, the depth of the TX FIFO
+    pub fn new(window: u8) -> Self {
+        StreamSender {
+            window: usize::from(window.clamp(1, 3)),
+            acked_seq: 0,
+            limit: 0,
+        }
+    }
+
+    /// Send `data` and return how many of its bytes the receiver
+    /// acknowledged, polling on `timer`
+    ///
+    /// Gives up once no fragment was acknowledged for `timeout_us`, e.g.
+    /// because the receiver is gone or keeps having no room, and returns
+    /// the bytes sent until then. Send the rest with another call.
+    /// Leaves the TX FIFO empty. Do not mix with other sends meanwhile.
+    pub async fn send<D: Device, T: RadioTimer>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        data: &[u8],
+        timer: &mut T,
+        timeout_us: u32,
+    ) -> Result<usize, D::Error> {
+        let mut acked = 0;
+        let mut sent = 0;
+        let mut next_seq = self.acked_seq;
+        // Lengths of the packets in flight, oldest first, `None` for probes
+        let mut in_flight = [None; 3];
+        let mut count = 0;
+        let mut deadline = timer.now_micros().saturating_add(u64::from(timeout_us));
+        let mut last_probe: Option<u64> = None;
+
+        loop {
+            while let Some(ack) = tx.read_ack_payload().await? {
+                // Credit never goes back, so an old ACK payload is ignored
+                if let [CREDIT, limit] = ack[..] {
+                    if !before(limit, self.limit) {
+                        self.limit = limit;
+                    }
+                }
+            }
+
+            let now = timer.now_micros();
+            let expired = now >= deadline;
+            let packet = if expired || count == self.window || sent == data.len() {
+                None
+            } else if before(next_seq, self.limit) {
+                let mut packet = [0; 2 + FRAGMENT_LEN];
+                packet[0] = DATA;
+                packet[1] = next_seq;
+                let len = copy_bytes(&mut packet[2..], data.get(sent..).unwrap_or_default());
+                Some((packet, len.saturating_add(2)))
+            } else if count == 0
+                && last_probe.is_none_or(|last| now.saturating_sub(last) >= PROBE_INTERVAL_US)
+            {
+                last_probe = Some(now);
+                let mut packet = [0; 2 + FRAGMENT_LEN];
+                packet[0] = PROBE;
+                Some((packet, 1))
+            } else {
+                None
+            };
+            if let Some((packet, len)) = packet {
+                match tx.send_tracked(packet.get(..len).unwrap_or_default()).await {
+                    Ok(_) => {
+                        let data_len = (packet[0] == DATA).then_some(len.saturating_sub(2));
+                        if let Some(slot) = in_flight.get_mut(count) {
+                            *slot = data_len;
+                        }
+                        count = count.saturating_add(1);
+                        if let Some(data_len) = data_len {
+                            sent = sent.saturating_add(data_len);
+                            next_seq = next_seq.wrapping_add(1);
+                        }
+                        continue;
+                    }
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(e)) => return Err(e),
+                }
+            }
+
+            if count == 0 && (sent == data.len() || expired) {
+                return Ok(acked);
+            }
+            match tx.poll_tracked().await {
+                Ok((_, SendOutcome::Acked)) => {
+                    if let Some(len) = in_flight[0] {
+                        acked = acked.saturating_add(len);
+                        self.acked_seq = self.acked_seq.wrapping_add(1);
+                        deadline = now.saturating_add(u64::from(timeout_us));
+                    }
+                    in_flight.rotate_left(1);
+                    count = count.saturating_sub(1);
+                    continue;
+                }
+                Ok((_, _)) => {
+                    // The packets behind the failed one were flushed, and
+                    // their outcomes are already known
+                    while count > 1 {
+                        match tx.poll_tracked().await {
+                            Ok(_) | Err(nb::Error::WouldBlock) => count = count.saturating_sub(1),
+                            Err(nb::Error::Other(e)) => return Err(e),
+                        }
+                    }
+                    count = 0;
+                    sent = acked;
+                    next_seq = self.acked_seq;
+                    continue;
+                }
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+            timer.delay_us(POLL_INTERVAL_US).await;
+        }
+    }
+}
+
+/// Receives the byte stream of a [`StreamSender`](struct.StreamSender.html)
+/// into a buffer of `N` fragments
+///
+/// [`poll()`](#method.poll) takes the fragments out of the RX FIFO and
+/// queues the credit for the sender as an ACK payload, and
+/// [`read()`](#method.read) returns the stream in order. Fragments that
+/// the sender repeats because an ACK got lost are dropped. `N` must be
+/// between 1 and 127.
+pub struct StreamReceiver<const N: usize> {
+    /// Sequence number of the next fragment of the stream
+    expected: u8,
+    fragments: [Option<Payload>; N],
+    /// Slot of the oldest fragment in `fragments`
+    head: usize,
+    len: usize,
+    /// Bytes already read of the fragment at `head`
+    offset: usize,
+    /// Limit in the queued ACK payload
+    advertised: Option<u8>,
+}
+
+impl<const N: usize> Default for StreamReceiver<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> StreamReceiver<N> {
+    /// Constructor, with an empty buffer
+    pub fn new() -> Self {
+        StreamReceiver {
+            expected: 0,
+            fragments: core::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+            offset: 0,
+            advertised: None,
+        }
+    }
+
+    /// Number of bytes ready to read
+    pub fn available(&self) -> usize {
+        // Only the held fragments are in their slots
+        let held: usize = self.fragments.iter().flatten().map(|fragment| fragment.len()).sum();
+        held.saturating_sub(self.offset)
+    }
+
+    /// Take the fragments out of the RX FIFO, and return how many bytes
+    /// of the stream they added
+    ///
+    /// The stream comes in on `pipe_no`. Other packets are dropped, so
+    /// dedicate the radio to the stream while receiving it. Queues a new
+    /// ACK payload with the credit whenever the last one went out or the
+    /// credit changed, flushing the old one.
+    pub async fn poll<D: Device>(
+        &mut self,
+        rx: &mut RxMode<D>,
+        pipe_no: u8,
+    ) -> Result<usize, D::Error> {
+        let mut added = 0_usize;
+        let mut heard = false;
+        while let Some((pipe, packet)) = rx.read_with_pipe().await? {
+            if pipe != pipe_no {
+                continue;
+            }
+            // Its ACK carried the queued credit
+            heard = true;
+            if let [DATA, seq, ref data @ ..] = *packet.as_ref() {
+                let index = self.head.wrapping_add(self.len).checked_rem(N).unwrap_or(0);
+                match self.fragments.get_mut(index) {
+                    Some(slot) if seq == self.expected && self.len < N => {
+                        *slot = Some(Payload::new(data));
+                        self.len = self.len.saturating_add(1);
+                        self.expected = seq.wrapping_add(1);
+                        added = added.saturating_add(data.len());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let room = N.saturating_sub(self.len).min(127) as u8;
+        let limit = self.expected.wrapping_add(room);
+        if heard || self.advertised != Some(limit) {
+            rx.flush_ack_payloads(Some(pipe_no)).await?;
+            let queued = rx.queue_ack_payload(pipe_no, &[CREDIT, limit]).await?;
+            self.advertised = queued.then_some(limit);
+        }
+        Ok(added)
+    }
+
+    /// Copy the next bytes of the stream into `buf`, and return how many
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut copied = 0;
+        while copied < buf.len() && self.len > 0 {
+            let Some(slot) = self.fragments.get_mut(self.head) else {
+                break;
+            };
+            let Some(fragment) = slot else {
+                break;
+            };
+            let rest = fragment.get(self.offset..).unwrap_or_default();
+            let take = copy_bytes(buf.get_mut(copied..).unwrap_or_default(), rest);
+            copied = copied.saturating_add(take);
+            self.offset = self.offset.saturating_add(take);
+            if self.offset >= fragment.len() {
+                *slot = None;
+                self.head = self.head.wrapping_add(1).checked_rem(N).unwrap_or(0);
+                self.len = self.len.saturating_sub(1);
+                self.offset = 0;
+            }
+        }
+        copied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, join, Air, SimTimer, Yielding};
+    use crate::Configuration;
+    use embedded_hal_async::delay::DelayNs;
+
+    /// Stream `data` from a sender with `window` to a receiver that
+    /// reads `chunk` bytes every 500 µs, and return what it read
+    fn stream(data: &[u8], window: u8, chunk: usize) -> (usize, Vec<u8>) {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            tx.set_ack_payloads(true).await.unwrap();
+            rx.set_ack_payloads(true).await.unwrap();
+            let timer = SimTimer::new();
+            let (mut da, mut db) = (Yielding(timer.clone()), Yielding(timer));
+
+            let mut sender = StreamSender::new(window);
+            let mut receiver = StreamReceiver::<2>::new();
+            join(
+                async { sender.send(&mut tx, data, &mut da, 100_000).await.unwrap() },
+                async {
+                    let mut out = Vec::new();
+                    let mut buf = vec![0; chunk];
+                    while out.len() < data.len() {
+                        receiver.poll(&mut rx, 0).await.unwrap();
+                        let n = receiver.read(&mut buf);
+                        out.extend_from_slice(&buf[..n]);
+                        db.delay_us(500).await;
+                    }
+                    out
+                },
+            )
+            .await
+        })
+    }
+
+    #[test]
+    fn stream_arrives_in_order() {
+        let data: Vec<u8> = (0..=255).collect();
+        for window in 1..=3 {
+            let (sent, out) = stream(&data, window, 7);
+            assert_eq!(sent, data.len());
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn receiver_holds_the_sender_back() {
+        // The receiver reads slower than the radio, with room for two
+        // fragments only
+        let data = [0xA5; 200];
+        let (sent, out) = stream(&data, 3, 3);
+        assert_eq!(sent, data.len());
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn sender_gives_up_without_receiver() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _) = sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut sender = StreamSender::new(3);
+            let sent = sender.send(&mut tx, &[1; 40], &mut timer, 10_000).await;
+            assert_eq!(sent.ok(), Some(0));
+            assert!(tx.is_empty().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn repeated_fragments_are_dropped() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, mut rx) = sim::pair(&air).await;
+            let mut receiver = StreamReceiver::<4>::new();
+            for packet in [&[DATA, 0, 1, 2][..], &[DATA, 0, 1, 2], &[DATA, 1, 3]] {
+                tx.send(packet).await.unwrap();
+                assert_eq!(tx.poll_send().await.ok(), Some(true));
+            }
+            assert_eq!(receiver.poll(&mut rx, 0).await.ok(), Some(3));
+            let mut buf = [0; 8];
+            assert_eq!(receiver.read(&mut buf), 3);
+            assert_eq!(buf[..3], [1, 2, 3]);
+        });
+    }
+}
@@ -0,0 +1,225 @@
+use crate::device::Device;
+use crate::error::Error;
+use crate::events::{ModeKind, NoEvents};
+use crate::registers::{Config, Feature};
+use crate::rx::RxMode;
+use crate::standby::StandbyMode;
+use crate::variant::ChipVariant;
+use crate::{RadioMode, NRF24L01};
+use core::fmt::Debug;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiDevice;
+
+/// First byte of an encoded state
+const TAG: u8 = b'S';
+/// Second byte, the layout version
+const VERSION: u8 = 1;
+
+/// Driver state saved across a deep sleep that loses RAM, see
+/// [`RadioMode::suspend()`](enum.RadioMode.html#method.suspend)
+///
+/// Keep it in memory that survives the sleep, e.g. backup registers or
+/// flash, encoded in [`SuspendState::LEN`](#associatedconstant.LEN)
+/// bytes:
+///
+/// | Offset | Content                                   |
+/// |--------|-------------------------------------------|
+/// | 0      | `'S'`                                     |
+/// | 1      | Version, currently `1`                    |
+/// | 2      | Cached `CONFIG` register                  |
+/// | 3      | `ChipVariant`                             |
+/// | 4      | Mode                                      |
+/// | 5      | Bit 0: `CE` tied high                     |
+/// | 6..10  | Sequence counter, little endian           |
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SuspendState {
+    config: u8,
+    variant: ChipVariant,
+    mode: ModeKind,
+    ce_tied: bool,
+    /// Counter of the layers above the driver, e.g. the next sequence
+    /// number or packet id, stored along
+    pub sequence: u32,
+}
+
+impl SuspendState {
+    /// Size of the encoding
+    pub const LEN: usize = 10;
+
+    /// Mode the radio was suspended in, and is resumed to
+    pub fn mode(&self) -> ModeKind {
+        self.mode
+    }
+
+    /// Encode for storage
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        buf[0] = TAG;
+        buf[1] = VERSION;
+        buf[2] = self.config;
+        buf[3] = match self.variant {
+            ChipVariant::Unknown => 0,
+            ChipVariant::Plus => 1,
+            ChipVariant::NonPlus => 2,
+            ChipVariant::Beken => 3,
+        };
+        buf[4] = match self.mode {
+            ModeKind::PowerDown => 0,
+            ModeKind::Standby => 1,
+            // Not suspended in other modes
+            ModeKind::Rx | ModeKind::Tx => 2,
+        };
+        buf[5] = u8::from(self.ce_tied);
+        buf[6..10].copy_from_slice(&self.sequence.to_le_bytes());
+        buf
+    }
+
+    /// Decode a stored state, or `None` if it is not one, e.g. because
+    /// the storage was erased
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let &[TAG, VERSION, config, variant, mode, ce_tied, s0, s1, s2, s3, ..] = buf else {
+            return None;
+        };
+        let variant = match variant {
+            0 => ChipVariant::Unknown,
+            1 => ChipVariant::Plus,
+            2 => ChipVariant::NonPlus,
+            3 => ChipVariant::Beken,
+            _ => return None,
+        };
+        let mode = match mode {
+            0 => ModeKind::PowerDown,
+            1 => ModeKind::Standby,
+            2 => ModeKind::Rx,
+            _ => return None,
+        };
+        Some(SuspendState {
+            config,
+            variant,
+            mode,
+            ce_tied: ce_tied & 1 != 0,
+            sequence: u32::from_le_bytes([s0, s1, s2, s3]),
+        })
+    }
+}
+
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug>
+    RadioMode<NRF24L01<E, CE, SPI>>
+{
+    /// Give up the driver before a deep sleep that loses RAM, returning
+    /// its state along with `sequence`, and the pins
+    ///
+    /// Leaves the chip as it is, so that it keeps listening in RX mode
+    /// if `CE` stays high during the sleep. Settings of `RxMode` such as
+    /// static payload lengths are not kept, and are set again after
+    /// [`NRF24L01::resume()`](struct.NRF24L01.html#method.resume).
+    pub fn suspend(self, sequence: u32) -> (SuspendState, CE, SPI) {
+        let (device, mode) = match self {
+            RadioMode::PowerDown(device) => (device, ModeKind::PowerDown),
+            RadioMode::Standby(standby) => (standby.into_device(), ModeKind::Standby),
+            RadioMode::Rx(rx) => (rx.into_device(), ModeKind::Rx),
+        };
+        let state = SuspendState {
+            config: device.config.0,
+            variant: device.variant,
+            mode,
+            ce_tied: device.ce_tied,
+            sequence,
+        };
+        (state, device.ce, device.spi)
+    }
+}
+
+impl<E: Debug, CE: OutputPin<Error = E>, SPI: SpiDevice<u8, Error = SPIE>, SPIE: Debug>
+    NRF24L01<E, CE, SPI>
+{
+    /// Take up the driver again after a deep sleep, in the mode it was
+    /// suspended in, without configuring the chip again
+    ///
+    /// Reads `CONFIG` and `FEATURE` back to check that the chip kept its
+    /// registers, and fails with `Error::StateLost` if not, e.g. because
+    /// it lost power meanwhile. `CONFIG` alone may equal its reset value,
+    /// but the driver always sets `EN_DYN_ACK`, which a reset clears.
+    /// Fails with the pins then, to configure the chip from scratch with
+    /// [`NRF24L01::new()`](struct.NRF24L01.html#method.new).
+    pub async fn resume(
+        state: &SuspendState,
+        ce: CE,
+        spi: SPI,
+    ) -> Result<RadioMode<Self>, (CE, SPI, Error<SPIE>)> {
+        let mut device = Self::from_pins(ce, spi, NoEvents);
+        device.config = Config(state.config);
+        device.variant = state.variant;
+        device.ce_tied = state.ce_tied;
+
+        let checked = match device.ce.set_low() {
+            Ok(()) => device.check_kept(state).await,
+            Err(_) => Err(Error::PinError),
+        };
+        if let Err(e) = checked {
+            return Err((device.ce, device.spi, e));
+        }
+        Ok(match state.mode {
+            ModeKind::PowerDown => RadioMode::PowerDown(device),
+            ModeKind::Standby => RadioMode::Standby(StandbyMode::from_rx_tx(device)),
+            ModeKind::Rx | ModeKind::Tx => {
+                device.ce_enable();
+                RadioMode::Rx(RxMode::new(device))
+            }
+        })
+    }
+
+    /// Whether the chip still has the registers of `state`
+    async fn check_kept(&mut self, state: &SuspendState) -> Result<(), Error<SPIE>> {
+        let (_, config) = self.read_register::<Config>().await?;
+        let (_, feature) = self.read_register::<Feature>().await?;
+        if config.0 != state.config || !feature.en_dyn_ack() {
+            return Err(Error::StateLost);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{self, Air};
+    use crate::{Configuration, CrcMode, RadioConfig};
+
+    #[test]
+    fn resume_returns_to_rx() {
+        block_on(async {
+            let air = Air::new();
+            let standby = sim::standby(&air, &RadioConfig::default()).await;
+            let rx = standby.rx().await.map_err(|(_, e)| e).unwrap();
+            let (state, ce, spi) = RadioMode::Rx(rx).suspend(7);
+            let state = SuspendState::decode(&state.encode()).unwrap();
+            assert_eq!(state.sequence, 7);
+            let resumed = NRF24L01::resume(&state, ce, spi).await;
+            assert!(matches!(resumed, Ok(RadioMode::Rx(_))));
+        });
+    }
+
+    #[test]
+    fn power_cycle_is_noticed_at_reset_config() {
+        block_on(async {
+            let air = Air::new();
+            let mut standby = sim::standby(&air, &RadioConfig::default()).await;
+            let chip = standby.device().spi.chip();
+            standby.set_crc(CrcMode::OneByte).await.unwrap();
+            let device = standby.power_down().await.map_err(|(_, e)| e).unwrap();
+            let (state, ce, spi) = RadioMode::PowerDown(device).suspend(0);
+            // CONFIG is back at its reset value
+            chip.power_cycle();
+            assert_eq!(chip.register(0x00), state.config);
+
+            let Err((ce, spi, e)) = NRF24L01::resume(&state, ce, spi).await else {
+                panic!("resumed a power cycled chip");
+            };
+            assert!(matches!(e, Error::StateLost));
+            // The pins are back for a fresh start
+            assert!(NRF24L01::new(ce, spi).await.is_ok());
+        });
+    }
+}
@@ -0,0 +1,8 @@
+/// Monotonic time source for timeouts
+///
+/// Implement this on top of the system timer of your HAL or executor,
+/// e.g. `embassy_time::Instant::now().as_micros()`.
+pub trait Clock {
+    /// Microseconds since an arbitrary epoch, never going backwards
+    fn now_micros(&self) -> u64;
+}
@@ -0,0 +1,163 @@
+use crate::time::Clock;
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::Poll;
+use embedded_hal_async::delay::DelayNs;
+
+/// Delays, timeouts and the current time, for the time-dependent parts
+/// of the driver
+///
+/// A `RadioTimer` is both a `DelayNs` and a [`Clock`](trait.Clock.html),
+/// so it fits every method that takes either, and methods that need
+/// both take one. Nothing here allocates or assumes an executor: use
+/// [`EmbassyTimer`](struct.EmbassyTimer.html) with the `embassy-time`
+/// feature, [`ClockDelay`](struct.ClockDelay.html) to pair a clock with
+/// a delay of your HAL, or [`DelayTimer`](struct.DelayTimer.html) if
+/// there is only a delay.
+pub trait RadioTimer: DelayNs + Clock {
+    /// Run `future` for up to `timeout_us`, or return `None` if it did
+    /// not complete by then
+    ///
+    /// The default races `future` against a delay, polling both on
+    /// every wake-up.
+    async fn timeout<F: Future>(&mut self, timeout_us: u32, future: F) -> Option<F::Output> {
+        let mut future = pin!(future);
+        let mut delay = pin!(self.delay_us(timeout_us));
+        poll_fn(|cx| {
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(Some(output));
+            }
+            delay.as_mut().poll(cx).map(|()| None)
+        })
+        .await
+    }
+}
+
+/// [`RadioTimer`](trait.RadioTimer.html) from a clock and a delay that
+/// are separate in your HAL
+#[derive(Debug)]
+pub struct ClockDelay<C, DL> {
+    clock: C,
+    delay: DL,
+}
+
+impl<C: Clock, DL: DelayNs> ClockDelay<C, DL> {
+    /// Constructor
+    pub fn new(clock: C, delay: DL) -> Self {
+        ClockDelay { clock, delay }
+    }
+
+    /// Return the clock and the delay
+    pub fn into_inner(self) -> (C, DL) {
+        (self.clock, self.delay)
+    }
+}
+
+impl<C: Clock, DL: DelayNs> Clock for ClockDelay<C, DL> {
+    fn now_micros(&self) -> u64 {
+        self.clock.now_micros()
+    }
+}
+
+impl<C: Clock, DL: DelayNs> DelayNs for ClockDelay<C, DL> {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_ns(ns).await
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us).await
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms).await
+    }
+}
+
+impl<C: Clock, DL: DelayNs> RadioTimer for ClockDelay<C, DL> {}
+
+/// Fallback [`RadioTimer`](trait.RadioTimer.html) for a plain
+/// `DelayNs`, whose clock counts the time spent in its own delays
+///
+/// The clock stands still between delays, so it runs slow, and only
+/// suits features that mostly wait, like polling loops with timeouts.
+/// Delays cut short by a timeout are not counted.
+#[derive(Debug)]
+pub struct DelayTimer<DL> {
+    delay: DL,
+    elapsed_ns: u64,
+}
+
+impl<DL: DelayNs> DelayTimer<DL> {
+    /// Constructor, with the clock at 0
+    pub fn new(delay: DL) -> Self {
+        DelayTimer {
+            delay,
+            elapsed_ns: 0,
+        }
+    }
+
+    /// Return the delay
+    pub fn into_inner(self) -> DL {
+        self.delay
+    }
+}
+
+impl<DL: DelayNs> Clock for DelayTimer<DL> {
+    fn now_micros(&self) -> u64 {
+        self.elapsed_ns / 1_000
+    }
+}
+
+impl<DL: DelayNs> DelayNs for DelayTimer<DL> {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_ns(ns).await;
+        self.elapsed_ns = self.elapsed_ns.saturating_add(u64::from(ns));
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us).await;
+        self.elapsed_ns = self.elapsed_ns.saturating_add(u64::from(us) * 1_000);
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms).await;
+        self.elapsed_ns = self.elapsed_ns.saturating_add(u64::from(ms) * 1_000_000);
+    }
+}
+
+impl<DL: DelayNs> RadioTimer for DelayTimer<DL> {}
+
+/// [`RadioTimer`](trait.RadioTimer.html) on the `embassy-time` driver
+#[cfg(feature = "embassy-time")]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EmbassyTimer;
+
+#[cfg(feature = "embassy-time")]
+impl Clock for EmbassyTimer {
+    fn now_micros(&self) -> u64 {
+        embassy_time::Instant::now().as_micros()
+    }
+}
+
+#[cfg(feature = "embassy-time")]
+impl DelayNs for EmbassyTimer {
+    async fn delay_ns(&mut self, ns: u32) {
+        embassy_time::Timer::after_nanos(u64::from(ns)).await
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        embassy_time::Timer::after_micros(u64::from(us)).await
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        embassy_time::Timer::after_millis(u64::from(ms)).await
+    }
+}
+
+#[cfg(feature = "embassy-time")]
+impl RadioTimer for EmbassyTimer {
+    async fn timeout<F: Future>(&mut self, timeout_us: u32, future: F) -> Option<F::Output> {
+        let timeout = embassy_time::Duration::from_micros(u64::from(timeout_us));
+        embassy_time::with_timeout(timeout, future).await.ok()
+    }
+}
@@ -0,0 +1,457 @@
+use crate::command::{Command, MAX_COMMAND_LEN, W_TX_PAYLOAD, W_TX_PAYLOAD_NOACK};
+use crate::device::Device;
+use crate::registers::{Config, Feature, FifoStatus, Register, Status};
+use crate::stats::Stats;
+use crate::time::Clock;
+use crate::variant::ChipVariant;
+use crate::PIPES_COUNT;
+
+/// `Tpd2stby`: oscillator startup from power down, worst case
+const POWER_UP_US: u64 = 1_500;
+/// `Thce`: minimum `CE` high pulse to send a packet
+const CE_PULSE_US: u64 = 10;
+/// `Tpece2csn`: from the rising `CE` edge to `CSN` low
+const CE_TO_CSN_US: u64 = 4;
+/// Longest stretch in TX mode, for the nRF24L01
+const TX_LIMIT_US: u64 = 4_000;
+/// `FLUSH_TX` opcode
+const FLUSH_TX: u8 = 0b1110_0001;
+
+/// Datasheet timing that a [`TimingCheck`](struct.TimingCheck.html)
+/// found violated, with the time it measured
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TimingViolation {
+    /// `CE` went high less than the 1.5ms oscillator startup after
+    /// setting `PWR_UP`, so the first packet may be lost
+    PowerUp {
+        /// Since `PWR_UP` was set
+        elapsed_us: u64,
+    },
+    /// `CE` was high for less than 10µs in TX mode, which may not send
+    CePulse {
+        /// How long `CE` was high
+        held_us: u64,
+    },
+    /// An SPI command started less than 4µs after the rising `CE` edge
+    CeToCsn {
+        /// Since the edge
+        elapsed_us: u64,
+    },
+    /// The chip stayed in TX mode, with `CE` high and packets in the TX
+    /// FIFO, for more than 4ms, which the datasheet of the original
+    /// nRF24L01 forbids. Only checked on `ChipVariant::NonPlus`; waiting
+    /// in Standby-II with an empty FIFO does not count. Needs the variant
+    /// from `StandbyMode::detect_variant()`.
+    TxTooLong {
+        /// How long the chip was in TX mode
+        held_us: u64,
+    },
+    /// `PRIM_RX` changed while `CE` was high, skipping standby
+    ModeSwitchWithCeHigh,
+}
+
+/// [`Device`](trait.Device.html) wrapper that measures the timing of
+/// `CE` edges, `PWR_UP` changes and SPI commands against the datasheet
+/// with a caller [`Clock`](trait.Clock.html), for bring-up on new
+/// boards, e.g. with `CE` on a slow GPIO expander
+///
+/// Violations are counted, logged with `log::warn!()`, and the last one
+/// is kept for [`last_violation()`](#method.last_violation). Times are
+/// taken when the driver calls into the `CE` pin and the SPI bus, so
+/// they include how long the pin driver itself takes. Wrap a powered
+/// down device, and pass it to
+/// [`StandbyMode::power_up()`](struct.StandbyMode.html#method.power_up).
+pub struct TimingCheck<D, C> {
+    inner: D,
+    clock: C,
+    /// Since when `PWR_UP` has been set, if known
+    powered_since: Option<u64>,
+    prim_rx: bool,
+    ce_high_since: Option<u64>,
+    /// Rising `CE` edge not yet followed by an SPI command
+    ce_rose_at: Option<u64>,
+    /// Packets in the TX FIFO as far as the commands on the bus tell
+    tx_queued: u8,
+    /// Since when the chip has been in TX mode with packets to send
+    tx_since: Option<u64>,
+    violations: u32,
+    last: Option<TimingViolation>,
+}
+
+impl<D: Device, C: Clock> TimingCheck<D, C> {
+    /// Wrap `inner`, measuring with `clock`
+    pub fn new(inner: D, clock: C) -> Self {
+        TimingCheck {
+            inner,
+            clock,
+            powered_since: None,
+            prim_rx: false,
+            ce_high_since: None,
+            ce_rose_at: None,
+            tx_queued: 0,
+            tx_since: None,
+            violations: 0,
+            last: None,
+        }
+    }
+
+    /// Unwrap the device
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Violations seen so far
+    pub fn violations(&self) -> u32 {
+        self.violations
+    }
+
+    /// The most recent violation
+    pub fn last_violation(&self) -> Option<TimingViolation> {
+        self.last
+    }
+
+    fn report(&mut self, violation: TimingViolation) {
+        self.violations = self.violations.wrapping_add(1);
+        self.last = Some(violation);
+        log::warn!("nRF24L01 timing violation: {:?}", violation);
+    }
+
+    fn before_spi(&mut self) {
+        if let Some(rose_at) = self.ce_rose_at.take() {
+            let elapsed_us = self.clock.now_micros().saturating_sub(rose_at);
+            if elapsed_us < CE_TO_CSN_US {
+                self.report(TimingViolation::CeToCsn { elapsed_us });
+            }
+        }
+    }
+
+    /// The chip may have entered TX mode
+    fn tx_may_start(&mut self) {
+        if self.tx_since.is_none()
+            && self.tx_queued > 0
+            && self.ce_high_since.is_some()
+            && !self.prim_rx
+        {
+            self.tx_since = Some(self.clock.now_micros());
+        }
+    }
+
+    /// The chip has left TX mode, or may have
+    fn tx_ended(&mut self) {
+        if let Some(since) = self.tx_since.take() {
+            let held_us = self.clock.now_micros().saturating_sub(since);
+            if held_us > TX_LIMIT_US && self.inner.variant() == ChipVariant::NonPlus {
+                self.report(TimingViolation::TxTooLong { held_us });
+            }
+        }
+    }
+
+    /// A payload written with `opcode` while `status` was latched
+    fn on_payload(&mut self, opcode: u8, status: &Status) {
+        if matches!(opcode, W_TX_PAYLOAD | W_TX_PAYLOAD_NOACK) && !status.tx_full() {
+            self.tx_queued = self.tx_queued.saturating_add(1).min(3);
+            self.tx_may_start();
+        }
+    }
+
+    /// The TX FIFO has been found empty or flushed
+    fn on_tx_empty(&mut self) {
+        self.tx_queued = 0;
+        self.tx_ended();
+    }
+
+    /// `STATUS` was written with `clear`, and held `status` before
+    fn on_status_clear(&mut self, clear: &Status, status: &Status) {
+        if clear.tx_ds() && status.tx_ds() {
+            self.tx_queued = self.tx_queued.saturating_sub(1);
+            if self.tx_queued == 0 {
+                self.tx_ended();
+            }
+        }
+        if clear.max_rt() && status.max_rt() {
+            // Halted until then, and resumes now if packets are left
+            self.tx_ended();
+            self.tx_may_start();
+        }
+    }
+
+    /// `CONFIG` is about to hold `config`
+    fn on_config(&mut self, config: &Config) {
+        let now = self.clock.now_micros();
+        match (config.pwr_up(), self.powered_since) {
+            (true, None) => self.powered_since = Some(now),
+            (false, _) => self.powered_since = None,
+            (true, Some(_)) => {}
+        }
+        if config.prim_rx() != self.prim_rx && self.ce_high_since.is_some() {
+            self.report(TimingViolation::ModeSwitchWithCeHigh);
+        }
+        if config.prim_rx() || !config.pwr_up() {
+            self.tx_ended();
+        }
+        self.prim_rx = config.prim_rx();
+        self.tx_may_start();
+    }
+}
+
+impl<D: Device, C: Clock> Device for TimingCheck<D, C> {
+    type Error = D::Error;
+    type Events = D::Events;
+
+    fn ce_enable(&mut self) {
+        if self.ce_high_since.is_none() {
+            let now = self.clock.now_micros();
+            if let Some(since) = self.powered_since {
+                let elapsed_us = now.saturating_sub(since);
+                if elapsed_us < POWER_UP_US {
+                    self.report(TimingViolation::PowerUp { elapsed_us });
+                }
+            }
+            self.ce_high_since = Some(now);
+            self.ce_rose_at = Some(now);
+            self.tx_may_start();
+        }
+        self.inner.ce_enable();
+    }
+
+    fn ce_disable(&mut self) {
+        if let Some(since) = self.ce_high_since {
+            let held_us = self.clock.now_micros().saturating_sub(since);
+            if !self.prim_rx && held_us < CE_PULSE_US {
+                self.report(TimingViolation::CePulse { held_us });
+            }
+        }
+        self.tx_ended();
+        self.ce_high_since = None;
+        self.ce_rose_at = None;
+        self.inner.ce_disable();
+    }
+
+    fn ce_tied(&self) -> bool {
+        self.inner.ce_tied()
+    }
+
+    fn set_verify_writes(&mut self, enabled: bool) -> bool {
+        self.inner.set_verify_writes(enabled)
+    }
+
+    fn variant(&self) -> ChipVariant {
+        self.inner.variant()
+    }
+
+    fn set_variant(&mut self, variant: ChipVariant) {
+        self.inner.set_variant(variant);
+    }
+
+    fn rx_lengths(&self) -> Option<[Option<u8>; PIPES_COUNT]> {
+        self.inner.rx_lengths()
+    }
+
+    fn set_rx_lengths(&mut self, lengths: Option<[Option<u8>; PIPES_COUNT]>) {
+        self.inner.set_rx_lengths(lengths)
+    }
+
+    fn features(&self) -> Option<Feature> {
+        self.inner.features()
+    }
+
+    fn last_status(&self) -> Option<Status> {
+        self.inner.last_status()
+    }
+
+    fn stats_mut(&mut self) -> &mut Stats {
+        self.inner.stats_mut()
+    }
+
+    fn events(&mut self) -> &mut Self::Events {
+        self.inner.events()
+    }
+
+    async fn send_command<Cmd: Command>(
+        &mut self,
+        command: &Cmd,
+    ) -> Result<(Status, Cmd::Response), Self::Error> {
+        self.before_spi();
+        let mut data = [0; MAX_COMMAND_LEN];
+        let opcode = match data.get_mut(..command.len()) {
+            Some(data @ [_, ..]) => {
+                command.encode(data);
+                data.first().copied()
+            }
+            _ => None,
+        };
+        let result = self.inner.send_command(command).await;
+        if let (Some(FLUSH_TX), Ok(_)) = (opcode, &result) {
+            self.on_tx_empty();
+        }
+        result
+    }
+
+    async fn write_payload(&mut self, opcode: u8, data: &[u8]) -> Result<Status, Self::Error> {
+        self.before_spi();
+        let result = self.inner.write_payload(opcode, data).await;
+        if let Ok(status) = &result {
+            self.on_payload(opcode, status);
+        }
+        result
+    }
+
+    async fn read_payload(&mut self, opcode: u8, buf: &mut [u8]) -> Result<Status, Self::Error> {
+        self.before_spi();
+        self.inner.read_payload(opcode, buf).await
+    }
+
+    async fn write_raw(&mut self, buf: &[u8]) -> Result<Status, Self::Error> {
+        self.before_spi();
+        let result = self.inner.write_raw(buf).await;
+        if let (Ok(status), Some(&opcode)) = (&result, buf.first()) {
+            self.on_payload(opcode, status);
+        }
+        result
+    }
+
+    async fn write_register<R: Register>(&mut self, register: R) -> Result<Status, Self::Error> {
+        if R::addr() == Config::addr() {
+            let mut data = [0];
+            register.encode(&mut data);
+            self.on_config(&Config::decode(&data));
+        }
+        let clear = (R::addr() == Status::addr()).then(|| {
+            let mut data = [0];
+            register.encode(&mut data);
+            Status::decode(&data)
+        });
+        self.before_spi();
+        let result = self.inner.write_register(register).await;
+        if let (Some(clear), Ok(status)) = (clear, &result) {
+            self.on_status_clear(&clear, status);
+        }
+        result
+    }
+
+    async fn read_register<R: Register>(&mut self) -> Result<(Status, R), Self::Error> {
+        self.before_spi();
+        let result = self.inner.read_register::<R>().await;
+        if let (true, Ok((_, register))) = (R::addr() == FifoStatus::addr(), &result) {
+            let mut data = [0];
+            register.encode(&mut data);
+            if FifoStatus::decode(&data).tx_empty() {
+                self.on_tx_empty();
+            } else {
+                self.tx_queued = self.tx_queued.max(1);
+                self.tx_may_start();
+            }
+        }
+        result
+    }
+
+    async fn update_config<F, R>(&mut self, f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(&mut Config) -> R,
+    {
+        self.before_spi();
+        let mut updated = None;
+        let result = self
+            .inner
+            .update_config(|config| {
+                let result = f(config);
+                updated = Some(config.clone());
+                result
+            })
+            .await;
+        if let Some(config) = updated {
+            self.on_config(&config);
+        }
+        result
+    }
+
+    async fn restore_config(&mut self) -> Result<(), Self::Error> {
+        self.before_spi();
+        self.inner.restore_config().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::config::Configuration;
+    use crate::sim::{Air, SimChip, SimRadio, SimTimer};
+    use crate::{RadioConfig, StandbyMode, TxMode};
+
+    type Checked = TimingCheck<SimRadio, SimTimer>;
+
+    /// A transmitter of `variant` without a receiver, checked on `timer`
+    async fn checked_tx(
+        air: &Air,
+        variant: ChipVariant,
+        timer: &SimTimer,
+    ) -> (TxMode<Checked>, SimChip) {
+        let (ce, spi) = air.radio();
+        let chip = spi.chip();
+        chip.set_variant(variant);
+        let mut standby = SimRadio::new(ce, spi).await.unwrap();
+        standby.detect_variant().await.unwrap();
+        standby.configure(&RadioConfig::default()).await.unwrap();
+        let nrf = standby.power_down().await.map_err(|(_, e)| e).unwrap();
+        let standby = StandbyMode::power_up(TimingCheck::new(nrf, timer.clone()))
+            .await
+            .map_err(|(_, e)| e)
+            .unwrap();
+        timer.advance(POWER_UP_US);
+        (standby.tx().await.map_err(|(_, e)| e).unwrap(), chip)
+    }
+
+    /// Keep a packet pending for 5ms, then drop `CE`
+    async fn hold_packet(tx: &mut TxMode<Checked>, chip: &SimChip, timer: &SimTimer) {
+        tx.send(b"x").await.unwrap();
+        timer.advance(CE_PULSE_US);
+        // Neither TX_DS nor MAX_RT ever comes
+        chip.set_stuck_miso(Some(0));
+        assert!(tx.poll_send().await.is_err());
+        timer.advance(5_000);
+        tx.set_standby_ii(false);
+        chip.set_stuck_miso(None);
+    }
+
+    #[test]
+    fn long_tx_is_reported_on_non_plus() {
+        block_on(async {
+            let air = Air::new();
+            let timer = SimTimer::new();
+            let (mut tx, chip) = checked_tx(&air, ChipVariant::NonPlus, &timer).await;
+            hold_packet(&mut tx, &chip, &timer).await;
+            let last = tx.device().last_violation();
+            let held_us = CE_PULSE_US + 5_000;
+            assert_eq!(last, Some(TimingViolation::TxTooLong { held_us }));
+        });
+    }
+
+    #[test]
+    fn long_tx_is_fine_on_plus() {
+        block_on(async {
+            let air = Air::new();
+            let timer = SimTimer::new();
+            let (mut tx, chip) = checked_tx(&air, ChipVariant::Plus, &timer).await;
+            hold_packet(&mut tx, &chip, &timer).await;
+            assert_eq!(tx.device().violations(), 0);
+        });
+    }
+
+    #[test]
+    fn standby_ii_with_empty_fifo_is_fine() {
+        block_on(async {
+            let air = Air::new();
+            let timer = SimTimer::new();
+            let (mut tx, _chip) = checked_tx(&air, ChipVariant::NonPlus, &timer).await;
+            tx.set_standby_ii(true);
+            tx.send(b"x").await.unwrap();
+            timer.advance(CE_PULSE_US);
+            // MAX_RT right away, as nobody listens
+            assert_eq!(tx.poll_send().await.ok(), Some(false));
+            timer.advance(5_000);
+            tx.set_standby_ii(false);
+            assert_eq!(tx.device().violations(), 0);
+        });
+    }
+}
@@ -0,0 +1,81 @@
+/// Identifies a packet sent with
+/// [`TxMode::send_tracked()`](struct.TxMode.html#method.send_tracked)
+///
+/// Tokens are handed out in sending order and compare accordingly,
+/// wrapping around after `u32::MAX` packets.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct SendToken(u32);
+
+impl SendToken {
+    /// Sequence number of the packet
+    pub fn sequence(&self) -> u32 {
+        self.0
+    }
+}
+
+/// What happened to a packet, as reported by
+/// [`TxMode::poll_tracked()`](struct.TxMode.html#method.poll_tracked)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SendOutcome {
+    /// Sent, and acknowledged if auto-ack is enabled
+    Acked,
+    /// Maximum retransmissions reached without an ACK
+    Failed,
+    /// Discarded from the TX FIFO before being sent, because an earlier
+    /// packet failed or the FIFO was flushed
+    Flushed,
+}
+
+/// Tokens of the packets in the TX FIFO, in sending order
+///
+/// Sequence numbers in `head..resolved` have an outcome that has not
+/// been reported yet, `resolved..tail` are still in flight.
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    head: u32,
+    resolved: u32,
+    tail: u32,
+    /// Indexed by sequence number modulo 4, which stays consistent
+    /// across wrap-around
+    outcomes: [Option<SendOutcome>; 4],
+}
+
+impl Tracker {
+    /// Hand out the token for the next packet
+    pub(crate) fn push(&mut self) -> SendToken {
+        let token = SendToken(self.tail);
+        self.tail = self.tail.wrapping_add(1);
+        token
+    }
+
+    /// Tokens handed out and not yet returned by `pop()`
+    pub(crate) fn pending(&self) -> u32 {
+        self.tail.wrapping_sub(self.head)
+    }
+
+    /// Packets still in flight
+    pub(crate) fn unresolved(&self) -> u32 {
+        self.tail.wrapping_sub(self.resolved)
+    }
+
+    /// Record `outcome` for the next `count` packets in flight
+    pub(crate) fn resolve(&mut self, count: u32, outcome: SendOutcome) {
+        for _ in 0..count.min(self.unresolved()) {
+            if let Some(slot) = self.outcomes.get_mut((self.resolved % 4) as usize) {
+                *slot = Some(outcome);
+            }
+            self.resolved = self.resolved.wrapping_add(1);
+        }
+    }
+
+    /// Take the oldest outcome that has not been reported yet
+    pub(crate) fn pop(&mut self) -> Option<(SendToken, SendOutcome)> {
+        if self.head == self.resolved {
+            return None;
+        }
+        let token = SendToken(self.head);
+        let outcome = self.outcomes.get_mut((self.head % 4) as usize).and_then(Option::take);
+        self.head = self.head.wrapping_add(1);
+        outcome.map(|outcome| (token, outcome))
+    }
+}
@@ -1,9 +1,21 @@
-use crate::command::{FlushTx, WriteTxPayload};
+use crate::coex::Coexistence;
+use crate::command::{
+    FlushRx, FlushTx, Nop, ReadRxPayloadWidth, R_RX_PAYLOAD, W_TX_PAYLOAD, W_TX_PAYLOAD_NOACK,
+};
 use crate::config::Configuration;
 use crate::device::Device;
+use crate::error::{InvalidArgument, StateError};
+use crate::events::RadioEvents;
+use crate::payload::{copy_bytes, Payload, TxBuffer};
 use crate::registers::{FifoStatus, ObserveTx, Status};
+use crate::rx::RX_P_NO_EMPTY;
 use crate::standby::StandbyMode;
+use crate::strategy::Strategy;
+use crate::timer::RadioTimer;
+use crate::tracking::{SendOutcome, SendToken, Tracker};
 use core::fmt;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
 
 /// Represents **TX Mode** and the associated **TX Settling** and
 /// **Standby-II** states
@@ -18,8 +30,77 @@ use core::fmt;
 /// warranty could get void.
 pub struct TxMode<D: Device> {
     device: D,
+    /// Packets in the TX FIFO, counted up for each one loaded and down
+    /// for each `TX_DS` cleared since it was empty with the TX
+    /// interrupts cleared, or `UNKNOWN`. One `TX_DS` may stand for
+    /// several packets, so it can be too high but never too low.
+    loaded: u8,
+    /// When `flush_stale()` first found the FIFO not empty
+    stale_since: Option<u64>,
+    tracker: Tracker,
+    /// Length to pad or truncate packets to for static payload peers
+    static_len: Option<u8>,
+    /// Keep `CE` high between packets
+    standby_ii: bool,
+    /// Since when the chip has been in TX mode with `standby_ii`
+    tx_since: Option<u64>,
+    /// FIFO level to report draining below
+    watermark: Option<u8>,
+    /// A packet has been queued since the last watermark event
+    watermark_armed: bool,
+    /// Hook asked before raising `CE`
+    coex: Option<Coexistence>,
+    /// `coex` granted the medium and `CE` has not dropped since
+    granted: bool,
+    completion: Completion,
 }
 
+/// How [`TxMode::poll_send()`](struct.TxMode.html#method.poll_send)
+/// detects that sending is complete, see
+/// [`TxMode::set_completion()`](struct.TxMode.html#method.set_completion)
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum Completion {
+    /// The `TX_DS` and `MAX_RT` flags in `STATUS` when exactly one
+    /// packet is in flight, the emptiness of the TX FIFO in
+    /// `FIFO_STATUS` otherwise
+    #[default]
+    Auto,
+    /// Only the flags in `STATUS`, for clones with unreliable
+    /// `FIFO_STATUS` reporting
+    ///
+    /// Each flag covers at least one packet, as the chip does not count
+    /// them. With several packets in flight, the driver counts down the
+    /// packets it has loaded, so that `poll_send()` completes once per
+    /// packet, but if two complete between polls the count falls behind.
+    /// Send one packet at a time to be safe.
+    Status,
+    /// Only the emptiness of the TX FIFO in `FIFO_STATUS`
+    Fifo,
+}
+
+/// Snapshot of a transfer in progress, see
+/// [`TxMode::telemetry()`](struct.TxMode.html#method.telemetry)
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct TxTelemetry {
+    /// Retransmits of the current packet so far, `ARC_CNT`
+    pub retransmits: u8,
+    /// Packets lost since the channel was last set, `PLOS_CNT`
+    pub lost_packets: u8,
+    /// Packets in the TX FIFO, like
+    /// [`TxMode::fifo_level()`](struct.TxMode.html#method.fifo_level)
+    pub fifo_level: u8,
+    /// `TX_DS` is pending
+    pub sent: bool,
+    /// `MAX_RT` is pending, stalling the FIFO until the sending task
+    /// handles it
+    pub max_rt: bool,
+}
+
+/// `TxMode::loaded` can not be tracked
+const UNKNOWN: u8 = u8::MAX;
+/// Interval at which to ask for the medium again while it is denied
+const DENIED_POLL_US: u32 = 100;
+
 impl<D: Device> fmt::Debug for TxMode<D> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "TxMode")
@@ -30,14 +111,59 @@ impl<D: Device> TxMode<D> {
     /// Relies on everything being set up by `StandbyMode::tx()`, from
     /// which it is called
     pub(crate) fn new(device: D) -> Self {
-        TxMode { device }
+        TxMode {
+            device,
+            loaded: UNKNOWN,
+            stale_since: None,
+            tracker: Tracker::default(),
+            static_len: None,
+            standby_ii: false,
+            tx_since: None,
+            watermark: None,
+            watermark_armed: false,
+            coex: None,
+            granted: false,
+            completion: Completion::Auto,
+        }
     }
 
     /// Disable `CE` so that you can switch into RX mode.
+    ///
+    /// Waits until the TX FIFO is empty like
+    /// [`wait_empty()`](#method.wait_empty), which flushes it after
+    /// `MAX_RT`. See [`standby_preserving()`](#method.standby_preserving)
+    /// to keep the packets.
     pub async fn standby(mut self) -> Result<StandbyMode<D>, D::Error> {
         self.wait_empty().await?;
 
-        Ok(StandbyMode::from_rx_tx(self.device))
+        Ok(self.into_standby())
+    }
+
+    /// Disable `CE` without waiting for the TX FIFO, and return how many
+    /// packets remain in it like [`fifo_level()`](#method.fifo_level)
+    ///
+    /// Unlike [`standby()`](#method.standby), nothing is flushed after
+    /// `MAX_RT`. A packet on air finishes, and the rest are sent when
+    /// returning to TX mode. Clear `MAX_RT` first if it is set, or
+    /// flush with [`flush_tx()`](trait.Configuration.html#method.flush_tx)
+    /// to drop them after all. In RX mode with ACK payloads enabled, the
+    /// chip sends them as ACK payloads.
+    ///
+    /// Fails with `Error::CeTied` if `CE` is tied high, as the
+    /// chip keeps sending then.
+    pub async fn standby_preserving(mut self) -> Result<(StandbyMode<D>, u8), D::Error> {
+        if self.device.ce_tied() {
+            return Err(StateError::CeTied.into());
+        }
+        self.ce_disable();
+        let level = self.fifo_level().await?;
+        Ok((self.into_standby(), level))
+    }
+
+    /// Disable `CE` right away, leaving the TX FIFO as it is
+    pub(crate) fn into_standby(mut self) -> StandbyMode<D> {
+        self.ce_disable();
+        StandbyMode::from_rx_tx(self.device)
     }
 
     /// Is TX FIFO empty?
@@ -52,17 +178,351 @@ impl<D: Device> TxMode<D> {
         Ok(fifo_status.tx_full())
     }
 
+    /// Number of packets in the TX FIFO, 0 to 3, counting the one being
+    /// sent
+    ///
+    /// `FIFO_STATUS` only tells empty and full apart. In between, the
+    /// driver counts the packets it loaded down on each `TX_DS` it
+    /// clears, and returns 2 if it lost track. As one `TX_DS` can stand
+    /// for two packets sent between polls, that may be 2 for 1.
+    pub async fn fifo_level(&mut self) -> Result<u8, D::Error> {
+        let (_, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        let level = self.level_of(&fifo_status);
+        self.note_level(level);
+        Ok(level)
+    }
+
+    /// TX FIFO level from `FIFO_STATUS` and the packets loaded
+    fn level_of(&self, fifo_status: &FifoStatus) -> u8 {
+        if fifo_status.tx_empty() {
+            0
+        } else if fifo_status.tx_full() {
+            3
+        } else if self.loaded == 1 {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Call
+    /// [`RadioEvents::on_tx_watermark()`](trait.RadioEvents.html#method.on_tx_watermark)
+    /// once the TX FIFO drains below `watermark` packets, 1 to 3
+    ///
+    /// The driver notices when [`fifo_level()`](#method.fifo_level)
+    /// reads the level, or when a send completes with the FIFO empty.
+    /// The event fires once after each packet queued, so a producer can
+    /// refill from it. `None` disables the event.
+    pub fn set_watermark(&mut self, watermark: Option<u8>) -> Result<(), D::Error> {
+        if watermark.is_some_and(|watermark| !(1..=3).contains(&watermark)) {
+            return Err(InvalidArgument::Watermark.into());
+        }
+        self.watermark = watermark;
+        self.watermark_armed = false;
+        Ok(())
+    }
+
+    /// Fire the watermark event if the FIFO is at `level`
+    fn note_level(&mut self, level: u8) {
+        if self.watermark_armed && self.watermark.is_some_and(|watermark| level < watermark) {
+            self.watermark_armed = false;
+            self.device.events().on_tx_watermark(level);
+        }
+    }
+
     /// Does the TX FIFO have space?
     pub async fn can_send(&mut self) -> Result<bool, D::Error> {
         let full = self.is_full().await?;
         Ok(!full)
     }
 
+    /// Pad or truncate every packet passed to [`send()`](#method.send)
+    /// to `len` bytes, for peers with static payload lengths
+    ///
+    /// `None` sends packets as they are. See
+    /// [`RadioConfig::static_payloads()`](struct.RadioConfig.html#method.static_payloads).
+    pub fn set_static_payload_len(&mut self, len: Option<u8>) -> Result<(), D::Error> {
+        if len.is_some_and(|len| !(1..=32).contains(&len)) {
+            return Err(InvalidArgument::PayloadLength.into());
+        }
+        self.static_len = len;
+        Ok(())
+    }
+
     /// Send asynchronously
+    ///
+    /// `packet` can be at most 32 bytes long, unless a static payload
+    /// length is set which it is padded with zeros or truncated to.
     pub async fn send(&mut self, packet: &[u8]) -> Result<Status, D::Error> {
-        let state = self.device.send_command(&WriteTxPayload::new(packet)).await?;
-        self.device.ce_enable();
-        Ok(state.0)
+        self.send_with(W_TX_PAYLOAD, packet).await
+    }
+
+    /// Send asynchronously without asking for an ACK
+    ///
+    /// Uses `W_TX_PAYLOAD_NOACK`, so the receiver does not acknowledge
+    /// and the packet is not retransmitted, for broadcasts and other
+    /// traffic where a late packet is worthless. `poll_send()` then
+    /// reports success once the packet is on air, not that it arrived.
+    /// Requires `EN_DYN_ACK`, which
+    /// [`NRF24L01::new()`](struct.NRF24L01.html#method.new) sets.
+    pub async fn send_no_ack(&mut self, packet: &[u8]) -> Result<Status, D::Error> {
+        self.send_with(W_TX_PAYLOAD_NOACK, packet).await
+    }
+
+    /// `send()` with the TX payload command `opcode`
+    pub(crate) async fn send_with(&mut self, opcode: u8, packet: &[u8]) -> Result<Status, D::Error> {
+        let status = self.load(opcode, packet).await?;
+        self.ce_enable();
+        Ok(status)
+    }
+
+    /// `send()` of a packet that is known to fit, without padding, for
+    /// compile-time profiles
+    pub(crate) async fn send_exact(&mut self, packet: &[u8]) -> Result<Status, D::Error> {
+        let status = self.load_exact(W_TX_PAYLOAD, packet).await?;
+        self.ce_enable();
+        Ok(status)
+    }
+
+    /// Write `packet` to the TX FIFO without touching `CE`
+    async fn load(&mut self, opcode: u8, packet: &[u8]) -> Result<Status, D::Error> {
+        let mut padded = [0; 32];
+        let packet = match self.static_len {
+            Some(len) => {
+                let padded = padded.get_mut(..usize::from(len)).unwrap_or_default();
+                copy_bytes(padded, packet);
+                &*padded
+            }
+            None if packet.len() > 32 => return Err(InvalidArgument::PayloadLength.into()),
+            None => packet,
+        };
+        self.load_exact(opcode, packet).await
+    }
+
+    /// Write `packet` of at most 32 bytes to the TX FIFO as it is
+    async fn load_exact(&mut self, opcode: u8, packet: &[u8]) -> Result<Status, D::Error> {
+        let status = self.device.write_payload(opcode, packet).await?;
+        self.note_load(opcode, &status);
+        Ok(status)
+    }
+
+    /// Account for a payload written with `opcode`, given the `STATUS`
+    /// from before the write
+    fn note_load(&mut self, opcode: u8, status: &Status) {
+        // The chip ignores the payload if the FIFO was full
+        if !status.tx_full() {
+            self.device.stats_mut().count_sent(opcode == W_TX_PAYLOAD_NOACK);
+            if self.loaded != UNKNOWN {
+                self.loaded = self.loaded.saturating_add(1);
+            }
+            self.watermark_armed = self.watermark.is_some();
+        }
+    }
+
+    /// Send asynchronously if the TX FIFO has space, returning whether
+    /// the packet was loaded
+    ///
+    /// If the `STATUS` of the last command showed `TX_FULL`, a `NOP`
+    /// checks again before the payload is clocked in. Otherwise it costs
+    /// a single SPI transaction: `STATUS` is clocked out before the
+    /// payload is clocked in, and the chip ignores the payload if
+    /// `TX_FULL` was set. Never waits for the FIFO to drain, unlike
+    /// [`send_when_ready()`](#method.send_when_ready).
+    pub async fn try_send(&mut self, packet: &[u8]) -> Result<bool, D::Error> {
+        if self.device.last_status().is_some_and(|status| status.tx_full()) {
+            let (status, ()) = self.device.send_command(&Nop).await?;
+            if status.tx_full() {
+                return Ok(false);
+            }
+        }
+        let status = self.send(packet).await?;
+        Ok(!status.tx_full())
+    }
+
+    /// Send the packet assembled in `buffer` asynchronously
+    ///
+    /// Writes the command straight from `buffer`, without copying it to
+    /// the stack, so a DMA-driven `SpiDevice` can transfer it from a
+    /// `static`. Returns the `STATUS` from before the write like
+    /// [`send()`](#method.send): with `TX_FULL` set, the chip dropped
+    /// the packet.
+    pub async fn send_buffer(&mut self, buffer: &TxBuffer) -> Result<Status, D::Error> {
+        let status = self.device.write_raw(buffer.command()).await?;
+        self.note_load(W_TX_PAYLOAD, &status);
+        self.ce_enable();
+        Ok(status)
+    }
+
+    /// Send asynchronously, returning a token that
+    /// [`poll_tracked()`](#method.poll_tracked) reports the outcome for
+    ///
+    /// Returns `WouldBlock` if the TX FIFO is full, or if three tokens
+    /// are pending. Do not mix with the untracked `send*()` and
+    /// completion methods while tokens are pending.
+    pub async fn send_tracked(&mut self, packet: &[u8]) -> nb::Result<SendToken, D::Error> {
+        if self.tracker.pending() >= 3 {
+            return Err(nb::Error::WouldBlock);
+        }
+        let status = self.send(packet).await?;
+        if status.tx_full() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.tracker.push())
+    }
+
+    /// Poll completion of packets sent with
+    /// [`send_tracked()`](#method.send_tracked), returning one outcome
+    /// at a time in sending order
+    ///
+    /// Returns `WouldBlock` while the oldest pending packet is in flight,
+    /// and also when no token is pending. On `MAX_RT` the failed packet
+    /// and all packets behind it are reported, and the FIFO is flushed.
+    pub async fn poll_tracked(&mut self) -> nb::Result<(SendToken, SendOutcome), D::Error> {
+        if let Some(done) = self.tracker.pop() {
+            return Ok(done);
+        }
+        let unresolved = self.tracker.unresolved();
+        if unresolved == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        if status.max_rt() {
+            self.device.stats_mut().count_max_rt();
+            self.device.events().on_max_rt();
+            let remaining = if fifo_status.tx_full() {
+                3
+            } else if self.loaded == UNKNOWN {
+                unresolved
+            } else {
+                u32::from(self.level_of(&fifo_status))
+            };
+            self.tracker.resolve(unresolved.saturating_sub(remaining), SendOutcome::Acked);
+            self.tracker.resolve(1, SendOutcome::Failed);
+            self.tracker.resolve(unresolved, SendOutcome::Flushed);
+            self.device.send_command(&FlushTx).await?;
+            self.clear_interrupts_and_ce().await?;
+            self.set_empty();
+        } else if fifo_status.tx_empty() {
+            self.tracker.resolve(unresolved, SendOutcome::Acked);
+            self.clear_interrupts_and_ce().await?;
+            self.set_empty();
+            self.device.events().on_send_complete();
+        } else if status.tx_ds() {
+            let mut clear = Status(0);
+            clear.set_tx_ds(true);
+            self.device.write_register(clear).await?;
+            // With 3 in flight, 1 or 2 remain: report one now, and the
+            // other once the FIFO is empty or MAX_RT tells more
+            let remaining = if fifo_status.tx_full() {
+                3
+            } else {
+                unresolved.saturating_sub(1).clamp(1, 2)
+            };
+            self.tracker.resolve(unresolved.saturating_sub(remaining), SendOutcome::Acked);
+            self.note_sent();
+        }
+
+        self.tracker.pop().ok_or(nb::Error::WouldBlock)
+    }
+
+    /// Account for a `TX_DS` cleared with the FIFO not known to be
+    /// empty
+    fn note_sent(&mut self) {
+        if self.loaded != UNKNOWN {
+            self.loaded = self.loaded.saturating_sub(1);
+        }
+    }
+
+    /// Send asynchronously once the TX FIFO has space, polling it
+    /// every 100µs using `delay`.
+    ///
+    /// If a queued packet cannot be delivered and the maximum amount of
+    /// retries is reached, the TX FIFO is flushed like in
+    /// [`wait_empty()`](#method.wait_empty).
+    pub async fn send_when_ready<DL: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        delay: &mut DL,
+    ) -> Result<Status, D::Error> {
+        while !self.make_room().await? {
+            delay.delay_us(100).await;
+        }
+        self.send(packet).await
+    }
+
+    /// Send asynchronously once the TX FIFO has space, waiting for the
+    /// `IRQ` pin in between.
+    ///
+    /// The `TX_DS` and `MAX_RT` interrupts must not be masked. Clears
+    /// `TX_DS` when woken up. If a queued packet cannot be delivered and
+    /// the maximum amount of retries is reached, the TX FIFO is flushed
+    /// like in [`wait_empty()`](#method.wait_empty). Fails with
+    /// `Error::MediumDenied` if the full FIFO cannot be sent as the
+    /// [coexistence hook](#method.set_coexistence) denies the medium.
+    pub async fn send_when_ready_irq<IRQ: Wait>(
+        &mut self,
+        packet: &[u8],
+        irq: &mut IRQ,
+    ) -> Result<Status, D::Error> {
+        while !self.make_room().await? {
+            if self.denied() {
+                return Err(StateError::MediumDenied.into());
+            }
+            // The pin error is not of our concern, just poll again
+            let _ = irq.wait_for_low().await;
+            let mut clear = Status(0);
+            clear.set_tx_ds(true);
+            if self.device.write_register(clear).await?.tx_ds() {
+                self.note_sent();
+            }
+        }
+        self.send(packet).await
+    }
+
+    /// Send asynchronously once the TX FIFO has space, waiting in
+    /// between according to `strategy`
+    ///
+    /// Behaves like [`send_when_ready_irq()`](#method.send_when_ready_irq)
+    /// with the `IRQ` pin, and like
+    /// [`send_when_ready()`](#method.send_when_ready) without.
+    pub async fn send_when_ready_with<IRQ: Wait, DL: DelayNs>(
+        &mut self,
+        packet: &[u8],
+        strategy: &mut Strategy<IRQ>,
+        delay: &mut DL,
+    ) -> Result<Status, D::Error> {
+        while !self.make_room().await? {
+            self.wait_granted(strategy, delay).await;
+            let mut clear = Status(0);
+            clear.set_tx_ds(true);
+            if self.device.write_register(clear).await?.tx_ds() {
+                self.note_sent();
+            }
+        }
+        self.send(packet).await
+    }
+
+    /// Does the TX FIFO have space? Keeps transmission going if not.
+    async fn make_room(&mut self) -> Result<bool, D::Error> {
+        let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        if status.max_rt() {
+            // TX won't continue while MAX_RT is set
+            self.device.stats_mut().count_max_rt();
+            self.device.events().on_max_rt();
+            self.device.send_command(&FlushTx).await?;
+            let mut clear = Status(0);
+            clear.set_tx_ds(true);
+            clear.set_max_rt(true);
+            self.device.write_register(clear).await?;
+            self.set_empty();
+            Ok(true)
+        } else if fifo_status.tx_full() {
+            self.ce_enable();
+            Ok(false)
+        } else {
+            Ok(true)
+        }
     }
 
     /// Poll completion of one or multiple send operations and check whether transmission was
@@ -74,33 +534,445 @@ impl<D: Device> TxMode<D> {
     /// Automatic retransmission (set_auto_retransmit) and acks (set_auto_ack) have to be
     /// enabled if you actually want to know if transmission was successful. 
     /// Else the nrf24 just transmits the packet once and assumes it was received.
+    ///
+    /// Costs one SPI transaction per call. If more than one packet has
+    /// been sent since the last completion, or the driver cannot tell,
+    /// there is one more when the FIFO has been sent and the interrupt
+    /// flags need clearing.
     pub async fn poll_send(&mut self) -> nb::Result<bool, D::Error> {
+        match self.completion {
+            Completion::Auto if self.loaded == 1 => return self.poll_send_single().await,
+            Completion::Status => return self.poll_send_single().await,
+            _ => {}
+        }
+
         let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
         // We need to clear all the TX interrupts whenever we return Ok here so that the next call
         // to poll_send correctly recognizes max_rt and send completion.
         if status.max_rt() {
+            self.device.stats_mut().count_max_rt();
+            self.device.events().on_max_rt();
             // If MAX_RT is set, the packet is not removed from the FIFO, so if we do not flush
             // the FIFO, we end up in an infinite loop
             self.device.send_command(&FlushTx).await?;
             self.clear_interrupts_and_ce().await?;
+            self.set_empty();
             Ok(false)
         } else if fifo_status.tx_empty() {
+            if status.tx_ds() {
+                self.clear_interrupts_and_ce().await?;
+            } else {
+                // Nothing to clear, save the SPI transaction
+                self.idle();
+            }
+            self.set_empty();
+            self.device.events().on_send_complete();
+            Ok(true)
+        } else {
+            self.ce_enable();
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// `poll_send()` when exactly one packet is in flight, so that its
+    /// `TX_DS` means that the FIFO is empty, or with `Completion::Status`.
+    /// Writing `STATUS` returns its previous value, so this checks and
+    /// clears the interrupts with a single SPI transaction.
+    async fn poll_send_single(&mut self) -> nb::Result<bool, D::Error> {
+        let mut clear = Status(0);
+        clear.set_tx_ds(true);
+        clear.set_max_rt(true);
+        let status = self.device.write_register(clear).await?;
+
+        if status.max_rt() {
+            self.device.stats_mut().count_max_rt();
+            self.device.events().on_max_rt();
+            self.device.send_command(&FlushTx).await?;
+            // Clearing MAX_RT with CE high resumed the retransmits, which
+            // may have raised it again before the flush
             self.clear_interrupts_and_ce().await?;
+            self.set_empty();
+            Ok(false)
+        } else if status.tx_ds() && self.loaded > 1 && self.loaded != UNKNOWN {
+            // Only with Completion::Status: more packets follow
+            self.loaded = self.loaded.saturating_sub(1);
+            Ok(true)
+        } else if status.tx_ds() {
+            self.idle();
+            self.set_empty();
+            self.device.events().on_send_complete();
             Ok(true)
         } else {
-            self.device.ce_enable();
+            self.ce_enable();
             Err(nb::Error::WouldBlock)
         }
     }
 
-    async fn clear_interrupts_and_ce(&mut self) -> nb::Result<(), D::Error> {
+    /// Choose how [`poll_send()`](#method.poll_send) detects completion,
+    /// and so [`wait_send()`](#method.wait_send) too
+    ///
+    /// Has to be set again after leaving TX mode.
+    pub fn set_completion(&mut self, completion: Completion) {
+        self.completion = completion;
+    }
+
+    /// Wait for the `IRQ` pin and return whether the transmission was
+    /// successful.
+    ///
+    /// This is the cheapest way to complete a send: writing `STATUS`
+    /// returns its previous value, so the outcome is read and the
+    /// interrupt cleared with a single SPI transaction. After `MAX_RT`
+    /// the TX FIFO is flushed and the interrupt cleared again with two
+    /// more, as the chip resumes retransmitting meanwhile.
+    ///
+    /// The `TX_DS` and `MAX_RT` interrupts must not be masked. Returns
+    /// after the first of them, so with several packets in the FIFO,
+    /// call again until [`is_empty()`](#method.is_empty). `CE` drops
+    /// once the FIFO is known to be empty, unless in
+    /// [Standby-II](#method.set_standby_ii).
+    ///
+    /// Fails with `Error::MediumDenied` instead of waiting for a pin that
+    /// cannot go low if the [coexistence hook](#method.set_coexistence)
+    /// denies the medium. The packets stay in the FIFO; call again to
+    /// ask again.
+    pub async fn wait_send_irq<IRQ: Wait>(&mut self, irq: &mut IRQ) -> Result<bool, D::Error> {
+        self.ce_enable();
+        loop {
+            if self.denied() {
+                return Err(StateError::MediumDenied.into());
+            }
+            // The pin error is not of our concern, STATUS tells
+            let _ = irq.wait_for_low().await;
+            let mut clear = Status(0);
+            clear.set_tx_ds(true);
+            clear.set_max_rt(true);
+            let status = self.device.write_register(clear).await?;
+
+            if status.max_rt() {
+                self.device.stats_mut().count_max_rt();
+                self.device.events().on_max_rt();
+                self.device.send_command(&FlushTx).await?;
+                // As in poll_send_single()
+                self.clear_interrupts_and_ce().await?;
+                self.set_empty();
+                return Ok(false);
+            } else if status.tx_ds() {
+                if self.loaded == 1 {
+                    self.idle();
+                    self.set_empty();
+                } else {
+                    self.note_sent();
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Wait according to `strategy` until the FIFO has been sent, and
+    /// return whether sending was successful like
+    /// [`poll_send()`](#method.poll_send)
+    pub async fn wait_send<IRQ: Wait, DL: DelayNs>(
+        &mut self,
+        strategy: &mut Strategy<IRQ>,
+        delay: &mut DL,
+    ) -> Result<bool, D::Error> {
+        self.ce_enable();
+        loop {
+            self.wait_granted(strategy, delay).await;
+            match self.poll_send().await {
+                Ok(acked) => return Ok(acked),
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+    }
+
+    /// Like [`wait_send()`](#method.wait_send), but give up after
+    /// `timeout_us` on the clock of `timer`, flush the TX FIFO and return
+    /// `false`
+    ///
+    /// For peers that may be gone with auto-retransmit off, or a chip
+    /// that stopped raising `IRQ`.
+    pub async fn wait_send_for<IRQ: Wait, T: RadioTimer>(
+        &mut self,
+        strategy: &mut Strategy<IRQ>,
+        timer: &mut T,
+        timeout_us: u32,
+    ) -> Result<bool, D::Error> {
+        self.ce_enable();
+        let deadline = timer.now_micros().saturating_add(u64::from(timeout_us));
+        loop {
+            match self.poll_send().await {
+                Ok(acked) => return Ok(acked),
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+            let left = deadline.saturating_sub(timer.now_micros());
+            if left == 0 {
+                self.flush_tx().await?;
+                self.clear_interrupts().await?;
+                self.idle();
+                self.set_empty();
+                return Ok(false);
+            }
+            let left = left.min(u64::from(u32::MAX)) as u32;
+            if self.denied() {
+                timer.delay_us(left.min(DENIED_POLL_US)).await;
+            } else {
+                strategy.wait_at_most(timer, left).await;
+            }
+        }
+    }
+
+    /// Wait according to `strategy`, or shortly to ask for the medium
+    /// again while the coexistence hook denies it, as no interrupt can
+    /// come then
+    async fn wait_granted<IRQ: Wait, DL: DelayNs>(
+        &mut self,
+        strategy: &mut Strategy<IRQ>,
+        delay: &mut DL,
+    ) {
+        if self.denied() {
+            delay.delay_us(DENIED_POLL_US).await;
+        } else {
+            strategy.wait(delay).await;
+        }
+    }
+
+    /// The coexistence hook denied the medium when `CE` was to rise last
+    fn denied(&self) -> bool {
+        self.coex.is_some() && !self.granted
+    }
+
+    /// The FIFO is empty with the TX interrupts cleared
+    fn set_empty(&mut self) {
+        self.note_level(0);
+        self.loaded = 0;
+        self.stale_since = None;
+        // Back in Standby-II
+        self.tx_since = None;
+    }
+
+    /// Done sending: drop `CE` to save power, unless staying in
+    /// Standby-II without a coexistence hook, which would hold the grant
+    fn idle(&mut self) {
+        if !self.standby_ii || self.coex.is_some() {
+            self.ce_disable();
+        }
+    }
+
+    /// Ask `hook` before every transmission, so that a co-located radio
+    /// can arbitrate the medium, or `None` to transmit whenever asked
+    ///
+    /// While the medium is denied, packets wait in the TX FIFO with `CE`
+    /// low. Polling completions like [`poll_send()`](#method.poll_send)
+    /// and [`wait_empty()`](#method.wait_empty) ask again each time, and
+    /// so does [`wait_send()`](#method.wait_send), polling while denied
+    /// even with the `IRQ` pin. [`wait_send_irq()`](#method.wait_send_irq)
+    /// fails with `Error::MediumDenied` instead of waiting for a pin that
+    /// cannot go low, and [`send_at()`](#method.send_at) misses its slot. In
+    /// Standby-II, the grant is only held while packets follow each
+    /// other: `CE` drops and the grant is released once the FIFO has
+    /// been sent.
+    ///
+    /// Fails with `Error::CeTied` if `CE` is tied high, as
+    /// transmissions can not be withheld then.
+    pub fn set_coexistence(&mut self, hook: Option<Coexistence>) -> Result<(), D::Error> {
+        if hook.is_some() && self.device.ce_tied() {
+            return Err(StateError::CeTied.into());
+        }
+        self.release();
+        self.coex = hook;
+        Ok(())
+    }
+
+    /// Raise `CE` if the medium is granted
+    fn ce_enable(&mut self) {
+        if !self.granted {
+            match self.coex {
+                Some(coex) if !(coex.request)() => return,
+                Some(_) => self.granted = true,
+                None => {}
+            }
+        }
+        self.device.ce_enable();
+    }
+
+    /// Drop `CE` and give the medium back
+    fn ce_disable(&mut self) {
+        self.device.ce_disable();
+        self.release();
+    }
+
+    /// Give the medium back if it was granted
+    fn release(&mut self) {
+        if self.granted {
+            self.granted = false;
+            if let Some(coex) = self.coex {
+                (coex.release)();
+            }
+        }
+    }
+
+    /// Keep `CE` high after sends complete, so the chip waits in
+    /// Standby-II instead of Standby-I
+    ///
+    /// Packets written while earlier ones are still in the FIFO then
+    /// follow without returning to standby, so the 130µs TX settling is
+    /// paid once per burst for back-to-back packets. Use
+    /// [`send_fast()`](#method.send_fast) to keep bursts within the
+    /// 4ms TX mode limit. Standby-II draws more current than Standby-I.
+    pub fn set_standby_ii(&mut self, enable: bool) {
+        self.standby_ii = enable;
+        if !enable {
+            self.ce_disable();
+            self.tx_since = None;
+        }
+    }
+
+    /// Send like [`send()`](#method.send), for back-to-back packets in
+    /// [Standby-II](#method.set_standby_ii)
+    ///
+    /// If the chip has been in TX mode for 4ms, this first waits for the
+    /// FIFO to drain and returns to Standby-I, as the datasheet demands.
+    /// Like [`wait_empty()`](#method.wait_empty), that discards the FIFO
+    /// if a packet reaches the maximum retransmits.
+    pub async fn send_fast<T: RadioTimer>(
+        &mut self,
+        packet: &[u8],
+        timer: &T,
+    ) -> Result<Status, D::Error> {
+        const TX_LIMIT_US: u64 = 4_000;
+        if let Some(since) = self.tx_since {
+            if timer.now_micros().saturating_sub(since) >= TX_LIMIT_US {
+                self.wait_empty().await?;
+                self.tx_since = None;
+            }
+        }
+        let status = self.send(packet).await?;
+        if self.standby_ii && self.tx_since.is_none() {
+            self.tx_since = Some(timer.now_micros());
+        }
+        Ok(status)
+    }
+
+    /// Send `packet` so that it goes on air at `at_us` on `timer`, for
+    /// TDMA slots and time-stamped samples
+    ///
+    /// Waits for the TX FIFO to drain first, like
+    /// [`wait_empty()`](#method.wait_empty). If the slot is far enough
+    /// ahead, the chip powers down meanwhile and powers up again in time
+    /// for its 1.5ms oscillator startup. The packet is loaded ahead of
+    /// the slot, and a last short delay on `timer` raises `CE` the 130µs
+    /// TX settling before `at_us`, as precisely as that delay is.
+    ///
+    /// Returns how many µs late `CE` went high, 0 if on time, e.g. when
+    /// the call came too late or a busy executor delayed the task. Fails
+    /// with `Error::CeTied` if `CE` is tied high.
+    pub async fn send_at<T: RadioTimer>(
+        &mut self,
+        at_us: u64,
+        packet: &[u8],
+        timer: &mut T,
+    ) -> Result<u64, D::Error> {
+        const TX_SETTLING_US: u64 = 130;
+        const POWER_UP_US: u64 = 1_500;
+        /// Covers the SPI transactions, and a late wake-up from `delay`
+        const LOAD_LEAD_US: u64 = 500;
+
+        if self.device.ce_tied() {
+            return Err(StateError::CeTied.into());
+        }
+        self.wait_empty().await?;
+        self.ce_disable();
+        self.tx_since = None;
+        let ce_at = at_us.saturating_sub(TX_SETTLING_US);
+        let load_at = ce_at.saturating_sub(LOAD_LEAD_US);
+
+        let power_up_at = load_at.saturating_sub(POWER_UP_US);
+        // Not worth it for less than the power up itself
+        if timer.now_micros().saturating_add(POWER_UP_US) < power_up_at {
+            self.device.update_config(|config| config.set_pwr_up(false)).await?;
+            Self::delay_until(power_up_at, timer).await;
+            self.device.update_config(|config| config.set_pwr_up(true)).await?;
+        }
+
+        Self::delay_until(load_at, timer).await;
+        self.load(W_TX_PAYLOAD, packet).await?;
+        Self::delay_until(ce_at, timer).await;
+        self.ce_enable();
+        Ok(timer.now_micros().saturating_sub(ce_at))
+    }
+
+    /// Sleep on `timer` until `at_us`, in delays of up to `u32::MAX` µs
+    ///
+    /// Stops once the delays add up to the wait, even if the clock lags
+    /// behind them, so that a clock which only advances in the delays
+    /// of `timer` cannot make it spin.
+    async fn delay_until<T: RadioTimer>(at_us: u64, timer: &mut T) {
+        let mut remaining = at_us.saturating_sub(timer.now_micros());
+        while remaining > 0 {
+            let step = remaining.min(u64::from(u32::MAX));
+            timer.delay_us(step as u32).await;
+            let left = at_us.saturating_sub(timer.now_micros());
+            remaining = left.min(remaining.saturating_sub(step));
+        }
+    }
+
+    /// Flush the TX FIFO if it has not been empty for `max_age_us`
+    ///
+    /// Call this periodically. Otherwise a dead link leaves up to three
+    /// stale packets in the FIFO which are delivered when the peer
+    /// returns, possibly minutes later. The age is counted from the
+    /// first call that finds the FIFO holding packets, so choose
+    /// `max_age_us` longer than the FIFO takes to drain with all
+    /// retransmissions, and do not call this while keeping the FIFO
+    /// filled continuously.
+    ///
+    /// Returns whether packets were flushed, which is also reported to
+    /// [`RadioEvents::on_stale_flush()`](trait.RadioEvents.html#method.on_stale_flush).
+    pub async fn flush_stale<T: RadioTimer>(
+        &mut self,
+        timer: &T,
+        max_age_us: u64,
+    ) -> Result<bool, D::Error> {
+        if self.loaded == 0 {
+            return Ok(false);
+        }
+        let now = timer.now_micros();
+        let (_, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        if fifo_status.tx_empty() {
+            self.stale_since = None;
+            return Ok(false);
+        }
+
+        match self.stale_since {
+            Some(since) if now.saturating_sub(since) >= max_age_us => {
+                self.device.send_command(&FlushTx).await?;
+                let unresolved = self.tracker.unresolved();
+                self.tracker.resolve(unresolved, SendOutcome::Flushed);
+                // Resume after a MAX_RT
+                self.clear_interrupts_and_ce().await?;
+                self.set_empty();
+                self.device.events().on_stale_flush();
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => {
+                self.stale_since = Some(now);
+                Ok(false)
+            }
+        }
+    }
+
+    async fn clear_interrupts_and_ce(&mut self) -> Result<(), D::Error> {
         let mut clear = Status(0);
         clear.set_tx_ds(true);
         clear.set_max_rt(true);
         self.device.write_register(clear).await?;
 
         // Can save power now
-        self.device.ce_disable();
+        self.idle();
 
         Ok(())
     }
@@ -112,15 +984,19 @@ impl<D: Device> TxMode<D> {
     /// lost.
     pub async fn wait_empty(&mut self) -> Result<(), D::Error> {
         let mut empty = false;
+        let mut lost = false;
         while !empty {
             let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
             empty = fifo_status.tx_empty();
             if !empty {
-                self.device.ce_enable();
+                self.ce_enable();
             }
 
             // TX won't continue while MAX_RT is set
             if status.max_rt() {
+                self.device.stats_mut().count_max_rt();
+                self.device.events().on_max_rt();
+                lost = true;
                 let mut clear = Status(0);
                 // If MAX_RT is set, the packet is not removed from the FIFO, so if we do not flush
                 // the FIFO, we end up in an infinite loop
@@ -132,16 +1008,62 @@ impl<D: Device> TxMode<D> {
             }
         }
         // Can save power now
-        self.device.ce_disable();
+        self.ce_disable();
+        // TX_DS is left set
+        self.loaded = UNKNOWN;
+        self.stale_since = None;
+        if !lost {
+            self.device.events().on_send_complete();
+        }
 
         Ok(())
     }
 
+    /// Read a payload that the receiver attached to an ACK, or `None`
+    /// if there is none
+    ///
+    /// ACK payloads land in the RX FIFO. They require `EN_ACK_PAY` and
+    /// dynamic payload lengths on both ends. A corrupt one is flushed
+    /// along with the RX FIFO.
+    pub async fn read_ack_payload(&mut self) -> Result<Option<Payload>, D::Error> {
+        let (status, payload_width) = self.device.send_command(&ReadRxPayloadWidth).await?;
+        if status.rx_p_no() == RX_P_NO_EMPTY {
+            return Ok(None);
+        }
+        if payload_width > 32 {
+            self.device.send_command(&FlushRx).await?;
+            return Ok(None);
+        }
+        let mut payload = Payload::zeroed(usize::from(payload_width));
+        self.device
+            .read_payload(R_RX_PAYLOAD, payload.data_mut())
+            .await?;
+        Ok(Some(payload))
+    }
+
     /// Read the `OBSERVE_TX` register
     pub async fn observe(&mut self) -> Result<ObserveTx, D::Error> {
         let (_, observe_tx) = self.device.read_register().await?;
         Ok(observe_tx)
     }
+
+    /// Read the progress of the current transfer without affecting it
+    ///
+    /// Only reads registers: interrupts stay set and no events fire, so
+    /// a dashboard can call this from another task through
+    /// [`SharedRadio::telemetry()`](struct.SharedRadio.html#method.telemetry)
+    /// while the sending task polls.
+    pub async fn telemetry(&mut self) -> Result<TxTelemetry, D::Error> {
+        let (status, fifo_status) = self.device.read_register::<FifoStatus>().await?;
+        let (_, observe_tx) = self.device.read_register::<ObserveTx>().await?;
+        Ok(TxTelemetry {
+            retransmits: observe_tx.arc_cnt(),
+            lost_packets: observe_tx.plos_cnt(),
+            fifo_level: self.level_of(&fifo_status),
+            sent: status.tx_ds(),
+            max_rt: status.max_rt(),
+        })
+    }
 }
 
 impl<D: Device> Configuration for TxMode<D> {
@@ -149,4 +1071,303 @@ impl<D: Device> Configuration for TxMode<D> {
     fn device(&mut self) -> &mut Self::Inner {
         &mut self.device
     }
+
+    async fn flush_tx(&mut self) -> Result<(), D::Error> {
+        self.device.send_command(&FlushTx).await?;
+        let unresolved = self.tracker.unresolved();
+        self.tracker.resolve(unresolved, SendOutcome::Flushed);
+        // TX_DS may still be set
+        self.loaded = UNKNOWN;
+        self.stale_since = None;
+        Ok(())
+    }
+
+    async fn clear_interrupts(&mut self) -> Result<(), D::Error> {
+        let mut clear = Status(0);
+        clear.set_rx_dr(true);
+        clear.set_tx_ds(true);
+        clear.set_max_rt(true);
+        self.device.write_register(clear).await?;
+        // A TX_DS for a packet in flight may have been cleared
+        self.loaded = UNKNOWN;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{Air, SimRadio};
+    use crate::time::Clock;
+    use crate::RadioConfig;
+    use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    /// A transmitter without a receiver, and its chip
+    async fn lone_tx(air: &Air) -> (TxMode<SimRadio>, crate::sim::SimChip) {
+        let (ce, spi) = air.radio();
+        let chip = spi.chip();
+        let mut standby = SimRadio::new(ce, spi).await.unwrap();
+        standby.configure(&RadioConfig::default()).await.unwrap();
+        (standby.tx().await.map_err(|(_, e)| e).unwrap(), chip)
+    }
+
+    #[test]
+    fn try_send_stops_at_full_fifo() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            // MAX_RT halts the FIFO with the first packet in it
+            for _ in 0..3 {
+                assert_eq!(tx.try_send(b"x").await.ok(), Some(true));
+            }
+            assert_eq!(tx.try_send(b"x").await.ok(), Some(false));
+            chip.clear_opcodes();
+            assert_eq!(tx.try_send(b"x").await.ok(), Some(false));
+            assert_eq!(chip.opcodes(), [0xFF]);
+            assert_eq!(tx.stats().packets_sent, 3);
+        });
+    }
+
+    static GRANT: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn fifo_level_counts_down_on_tx_ds() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = crate::sim::pair(&air).await;
+            let coex = Coexistence {
+                request: || GRANT.load(Ordering::Relaxed),
+                release: || {},
+            };
+            tx.set_coexistence(Some(coex)).unwrap();
+            GRANT.store(true, Ordering::Relaxed);
+            tx.send(b"first").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            // Denied, so both wait in the FIFO
+            GRANT.store(false, Ordering::Relaxed);
+            tx.send(b"a").await.unwrap();
+            tx.send(b"b").await.unwrap();
+            assert_eq!(tx.fifo_level().await.ok(), Some(2));
+            // As for a TX_DS cleared while the FIFO is not known to be empty
+            tx.note_sent();
+            assert_eq!(tx.fifo_level().await.ok(), Some(1));
+        });
+    }
+
+    #[test]
+    fn max_rt_resolves_tracked_without_dummies() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            // Known to be empty from here on
+            tx.send(b"empty").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(false));
+            let first = tx.send_tracked(b"a").await.ok().unwrap();
+            let second = tx.send_tracked(b"b").await.ok().unwrap();
+            chip.clear_opcodes();
+            let outcomes = [tx.poll_tracked().await.ok(), tx.poll_tracked().await.ok()];
+            assert_eq!(
+                outcomes,
+                [Some((first, SendOutcome::Failed)), Some((second, SendOutcome::Flushed))]
+            );
+            assert!(!chip.opcodes().contains(&W_TX_PAYLOAD));
+        });
+    }
+
+    #[test]
+    fn send_buffer_reports_full_fifo() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            let mut buffer = TxBuffer::new();
+            buffer.fill(b"buffered").unwrap();
+            for _ in 0..3 {
+                assert!(!tx.send_buffer(&buffer).await.unwrap().tx_full());
+            }
+            assert!(tx.send_buffer(&buffer).await.unwrap().tx_full());
+            assert_eq!(chip.tx_fifo().len(), 3);
+            assert_eq!(tx.stats().packets_sent, 3);
+        });
+    }
+
+    #[test]
+    fn no_ack_sends_are_counted_apart() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _) = lone_tx(&air).await;
+            tx.send_no_ack(b"x").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(true));
+            let stats = tx.stats();
+            assert_eq!((stats.packets_sent, stats.packets_sent_no_ack), (1, 1));
+            #[cfg(feature = "diagnostics")]
+            {
+                let health = crate::HealthFrame::from_stats(0, &stats, 0, 0);
+                assert_eq!((health.sent, health.acked), (0, 0));
+            }
+        });
+    }
+
+    #[test]
+    fn wait_send_polls_until_acked() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = crate::sim::pair(&air).await;
+            let mut timer = crate::sim::SimTimer::new();
+            tx.send(b"x").await.unwrap();
+            let acked = tx.wait_send(&mut Strategy::polling(100), &mut timer).await;
+            assert_eq!(acked.ok(), Some(true));
+            assert!(tx.is_empty().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn wait_send_for_flushes_after_timeout() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            let mut timer = crate::sim::SimTimer::new();
+            tx.send(b"x").await.unwrap();
+            // Neither TX_DS nor MAX_RT ever comes
+            chip.set_stuck_miso(Some(0));
+            let acked = tx.wait_send_for(&mut Strategy::polling(100), &mut timer, 1_000).await;
+            assert_eq!(acked.ok(), Some(false));
+            assert_eq!(timer.now_micros(), 1_000);
+            chip.set_stuck_miso(None);
+            assert!(chip.tx_fifo().is_empty());
+            assert!(!chip.ce());
+        });
+    }
+
+    #[test]
+    fn send_at_on_a_delay_only_timer() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            // Its clock only moves in its own delays
+            let mut timer = crate::timer::DelayTimer::new(crate::sim::SimTimer::new());
+            let late_us = tx.send_at(10_000, b"x", &mut timer).await;
+            assert_eq!(late_us.ok(), Some(0));
+            assert_eq!(timer.now_micros(), 10_000 - 130);
+            assert!(chip.ce());
+        });
+    }
+
+    /// `IRQ` pin that is low whenever waited on, which must only happen
+    /// while the medium is granted, as `CE` is low otherwise
+    struct GrantedIrq(&'static AtomicBool);
+
+    impl embedded_hal::digital::ErrorType for GrantedIrq {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Wait for GrantedIrq {
+        async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+            assert!(self.0.load(Ordering::Relaxed), "waited for IRQ while denied");
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+    }
+
+    static DENIED_GRANT: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn wait_send_irq_fails_while_denied() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            let coex = Coexistence {
+                request: || DENIED_GRANT.load(Ordering::Relaxed),
+                release: || {},
+            };
+            tx.set_coexistence(Some(coex)).unwrap();
+            tx.send(b"x").await.unwrap();
+            let mut irq = GrantedIrq(&DENIED_GRANT);
+            assert!(matches!(tx.wait_send_irq(&mut irq).await, Err(crate::Error::MediumDenied)));
+            assert_eq!(chip.tx_fifo().len(), 1);
+            assert!(!chip.ce());
+        });
+    }
+
+    static LATE_GRANT: AtomicBool = AtomicBool::new(false);
+    static LATE_REQUESTS: AtomicU32 = AtomicU32::new(0);
+
+    #[test]
+    fn wait_send_asks_again_while_denied() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = crate::sim::pair(&air).await;
+            let coex = Coexistence {
+                request: || {
+                    // Granted from the third request on
+                    let granted = LATE_REQUESTS.fetch_add(1, Ordering::Relaxed) >= 2;
+                    LATE_GRANT.store(granted, Ordering::Relaxed);
+                    granted
+                },
+                release: || LATE_GRANT.store(false, Ordering::Relaxed),
+            };
+            tx.set_coexistence(Some(coex)).unwrap();
+            tx.send(b"x").await.unwrap();
+            let mut strategy = Strategy::new(Some(GrantedIrq(&LATE_GRANT)));
+            let mut timer = crate::sim::SimTimer::new();
+            assert_eq!(tx.wait_send(&mut strategy, &mut timer).await.ok(), Some(true));
+            assert!(LATE_REQUESTS.load(Ordering::Relaxed) >= 3);
+            assert!(timer.now_micros() > 0);
+        });
+    }
+
+    static BURST_RELEASES: AtomicU32 = AtomicU32::new(0);
+
+    #[test]
+    fn standby_ii_releases_the_grant_when_empty() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            let config = RadioConfig::default();
+            let _rx = crate::sim::standby(&air, &config).await.rx().await.map_err(|(_, e)| e);
+            let coex = Coexistence {
+                request: || true,
+                release: || {
+                    BURST_RELEASES.fetch_add(1, Ordering::Relaxed);
+                },
+            };
+            tx.set_coexistence(Some(coex)).unwrap();
+            tx.set_standby_ii(true);
+            tx.send(b"a").await.unwrap();
+            tx.send(b"b").await.unwrap();
+            assert_eq!(tx.wait_empty().await.ok(), Some(()));
+            assert!(!chip.ce());
+            assert_eq!(BURST_RELEASES.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn max_rt_stays_cleared_after_a_single_packet() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, chip) = lone_tx(&air).await;
+            tx.send(b"x").await.unwrap();
+            assert_eq!(tx.poll_send().await.ok(), Some(false));
+            assert_eq!(chip.register(0x07) & 0x10, 0);
+            // So the next packet goes out
+            tx.send(b"y").await.unwrap();
+            assert_eq!(chip.tx_fifo().len(), 1);
+            assert_eq!(air.frames().len(), 2);
+        });
+    }
 }
@@ -0,0 +1,189 @@
+//! Frame formats of nRF24-based Logitech Unifying keyboards and mice
+//!
+//! For security research on your own devices and for bridging them to
+//! other inputs. Unifying devices send Enhanced ShockBurst frames at
+//! 2 Mbps with 5 byte addresses, dynamic payloads and a 16 bit CRC.
+//!
+//! Finding a device works without knowing its address: in promiscuous
+//! mode, see [`promiscuous_config()`](fn.promiscuous_config.html), the
+//! chip matches on noise after a preamble and returns whatever follows.
+//! [`decode_promiscuous()`](fn.decode_promiscuous.html) picks out real
+//! frames by their CRC. Once the address is known, configure it on pipe
+//! 0 with dynamic payloads and read [`Frame`](struct.Frame.html)s.
+//!
+//! Keystrokes of current devices are encrypted and only passed through
+//! as raw bytes.
+
+use crate::channel::Channel;
+use crate::config::{DataRate, RawConfig};
+use crate::error::InvalidArgument;
+use crate::payload::Payload;
+use crate::sniff::{decode_esb, PROMISCUOUS_ADDRESS};
+
+/// Frame types, the second byte of a frame
+const KEYBOARD: u8 = 0xC1;
+const MOUSE: u8 = 0xC2;
+const MULTIMEDIA: u8 = 0xC3;
+const ENCRYPTED_KEYBOARD: u8 = 0xD3;
+const KEEPALIVE: u8 = 0x40;
+const SET_KEEPALIVE: u8 = 0x4F;
+
+/// Frame length of mouse reports and keepalive timeouts
+const LONG_LEN: usize = 10;
+/// Frame length of keepalives
+const KEEPALIVE_LEN: usize = 5;
+
+/// Address width of Unifying devices
+const ESB_ADDR_LEN: usize = 5;
+
+/// Checksum that ends every frame: the two's complement of the sum of
+/// all other bytes
+pub fn checksum(frame: &[u8]) -> u8 {
+    frame
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg()
+}
+
+/// Does the last byte of `frame` match the checksum of the others?
+pub fn checksum_ok(frame: &[u8]) -> bool {
+    match frame.split_last() {
+        Some((last, rest)) if !rest.is_empty() => checksum(rest) == *last,
+        _ => false,
+    }
+}
+
+/// Decoded mouse report
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct MouseReport {
+    /// Button bitmap, bit 0 for the left button
+    pub buttons: u8,
+    /// Movement to the right
+    pub x: i16,
+    /// Movement down
+    pub y: i16,
+    /// Scrolling up
+    pub wheel_v: i8,
+    /// Scrolling right
+    pub wheel_h: i8,
+}
+
+/// Contents of a Unifying frame
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FrameKind {
+    /// Mouse movement and buttons
+    Mouse(MouseReport),
+    /// Unencrypted HID keyboard report, as sent by old devices
+    Keyboard([u8; 7]),
+    /// Multimedia keys, HID consumer usages
+    Multimedia([u8; 4]),
+    /// Encrypted keyboard report
+    EncryptedKeyboard,
+    /// Keepalive, with the timeout in ms where present
+    Keepalive(Option<u16>),
+    /// Keepalive timeout set by the device, in ms
+    SetKeepalive(u16),
+    /// Frame type not known here, e.g. HID++ traffic
+    Other(u8),
+}
+
+/// A Unifying frame that passed its checksum
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Frame {
+    /// Device index on the receiver, usually 0
+    pub device_index: u8,
+    /// Decoded contents
+    pub kind: FrameKind,
+}
+
+impl Frame {
+    /// Decode `payload`, as received on the device address. Returns
+    /// `None` for a bad checksum or a frame that is too short.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        if !checksum_ok(payload) {
+            return None;
+        }
+        let (&device_index, rest) = payload.split_first()?;
+        let (&frame_type, body) = rest.split_first()?;
+        let kind = match frame_type {
+            MOUSE if payload.len() == LONG_LEN => FrameKind::Mouse(decode_mouse(body)?),
+            KEYBOARD if payload.len() == LONG_LEN => {
+                FrameKind::Keyboard(body.get(..7)?.try_into().ok()?)
+            }
+            MULTIMEDIA if payload.len() >= 7 => {
+                FrameKind::Multimedia(body.get(..4)?.try_into().ok()?)
+            }
+            ENCRYPTED_KEYBOARD => FrameKind::EncryptedKeyboard,
+            KEEPALIVE if payload.len() == KEEPALIVE_LEN => {
+                FrameKind::Keepalive(Some(u16::from_be_bytes(body.get(..2)?.try_into().ok()?)))
+            }
+            KEEPALIVE => FrameKind::Keepalive(None),
+            SET_KEEPALIVE if payload.len() == LONG_LEN => {
+                FrameKind::SetKeepalive(u16::from_be_bytes(body.get(1..3)?.try_into().ok()?))
+            }
+            other => FrameKind::Other(other),
+        };
+        Some(Frame { device_index, kind })
+    }
+}
+
+/// Buttons, a zero byte, 12 bit X and Y packed into 3 bytes, then the
+/// wheels
+fn decode_mouse(body: &[u8]) -> Option<MouseReport> {
+    let &[buttons, _, x_low, xy, y_high, wheel_v, wheel_h, ..] = body else {
+        return None;
+    };
+    let x = u16::from(x_low) | u16::from(xy & 0x0F) << 8;
+    let y = u16::from(xy >> 4) | u16::from(y_high) << 4;
+    Some(MouseReport {
+        buttons,
+        x: sign_extend_12(x),
+        y: sign_extend_12(y),
+        wheel_v: wheel_v as i8,
+        wheel_h: wheel_h as i8,
+    })
+}
+
+fn sign_extend_12(value: u16) -> i16 {
+    ((value << 4) as i16) >> 4
+}
+
+/// Settings to receive raw frames on `channel` in promiscuous mode
+///
+/// Uses a 2 byte address of `0x00AA`, which after the preamble matches
+/// often enough on noise, and disables CRC so that every match is
+/// returned. Feed the payloads to
+/// [`decode_promiscuous()`](fn.decode_promiscuous.html).
+pub fn promiscuous_config(channel: Channel) -> Result<RawConfig, InvalidArgument> {
+    let mut raw = RawConfig::new(channel, &PROMISCUOUS_ADDRESS, 32)?;
+    raw.data_rate = DataRate::R2Mbps;
+    Ok(raw)
+}
+
+/// An Enhanced ShockBurst frame recovered from promiscuous mode
+pub struct EsbFrame {
+    /// Address of the sender, least significant byte first like
+    /// [`Address`](../struct.Address.html)
+    pub address: [u8; ESB_ADDR_LEN],
+    /// Packet id, to tell retransmits apart
+    pub pid: u8,
+    /// Did the sender ask for no acknowledgment?
+    pub no_ack: bool,
+    /// Payload, for [`Frame::decode()`](struct.Frame.html#method.decode)
+    pub payload: Payload,
+}
+
+/// Recover an Enhanced ShockBurst frame from `raw`, a payload received
+/// with [`promiscuous_config()`](fn.promiscuous_config.html)
+///
+/// Returns `None` unless the CRC matches, which rejects nearly all of
+/// the noise.
+pub fn decode_promiscuous(raw: &[u8]) -> Option<EsbFrame> {
+    let frame = decode_esb(raw, ESB_ADDR_LEN)?;
+    Some(EsbFrame {
+        address: frame.address,
+        pid: frame.pid,
+        no_ack: frame.no_ack,
+        payload: frame.payload,
+    })
+}
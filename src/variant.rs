@@ -0,0 +1,19 @@
+/// Chip family, as found by
+/// [`detect_variant()`](trait.Configuration.html#method.detect_variant)
+///
+/// The configuration methods adapt to the recorded variant.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum ChipVariant {
+    /// Not detected yet, treated like `Plus`
+    #[default]
+    Unknown,
+    /// nRF24L01+, or a clone that behaves the same
+    Plus,
+    /// Original nRF24L01: no 250 kbps, and `FEATURE` is writable only
+    /// after an `ACTIVATE` command. `set_rf()` rejects 250 kbps and sets
+    /// the `LNA_HCURR` bit.
+    NonPlus,
+    /// Beken BK242x or a module based on it, like the RFM7x, which has
+    /// a second register bank switched by `ACTIVATE`
+    Beken,
+}
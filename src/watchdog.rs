@@ -0,0 +1,270 @@
+use crate::command::Nop;
+use crate::config::{Configuration, RadioConfig};
+use crate::device::Device;
+use crate::events::RadioEvents;
+use crate::registers::{FifoStatus, SetupAw, Status};
+use crate::rx::RxMode;
+use crate::stats::Stats;
+use crate::timer::RadioTimer;
+use crate::tx::TxMode;
+
+/// Consecutive implausible `STATUS` reads after which the chip is
+/// considered locked up, see
+/// [`Watchdog::set_bad_status_limit()`](struct.Watchdog.html#method.set_bad_status_limit)
+const BAD_STATUS_LIMIT: u8 = 3;
+
+/// Detects a locked up chip and reinitializes it from a cached
+/// [`RadioConfig`](struct.RadioConfig.html)
+///
+/// Call [`check_tx()`](#method.check_tx) or
+/// [`check_rx()`](#method.check_rx) periodically, e.g. from the main
+/// loop. The chip is considered locked up if
+///
+/// * no `TX_DS`, `RX_DR` or `MAX_RT` has been observed within the
+///   timeout while traffic is expected, or
+/// * `STATUS` reads back `0x00` or `0xFF` on several consecutive
+///   checks, with `SETUP_AW` confirming that the bus is dead.
+///
+/// Interrupts are observed both directly in `STATUS` and through the
+/// [`Stats`](struct.Stats.html) counters of the driver calls that
+/// cleared them, `tx_ds` rather than `packets_sent`, which also counts
+/// packets that pile up in the FIFO of a stuck chip. A recovery runs
+/// [`reinit()`](trait.Configuration.html#method.reinit), which flushes
+/// the FIFOs, and is reported to
+/// [`RadioEvents::on_recovery()`](trait.RadioEvents.html#method.on_recovery).
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    config: RadioConfig,
+    timeout_us: u64,
+    bad_status_limit: u8,
+    /// Since when traffic has been expected without progress
+    since: Option<u64>,
+    /// `tx_ds`, `packets_received` and `max_rt` at the last check
+    counters: Option<(u32, u32, u32)>,
+    bad_status: u8,
+    recoveries: u32,
+}
+
+impl Watchdog {
+    /// Constructor, recovering with `config` after `timeout_us` without
+    /// progress
+    ///
+    /// Choose `timeout_us` well above the longest retransmit cycle, that
+    /// is `(auto_retransmit_count + 1) * (auto_retransmit_delay + 1) *
+    /// 250 µS` plus air time. The checks take a
+    /// [`RadioTimer`](trait.RadioTimer.html) for the timeout and for
+    /// the wake-up time of a recovery.
+    pub fn new(config: RadioConfig, timeout_us: u64) -> Self {
+        Watchdog {
+            config,
+            timeout_us,
+            bad_status_limit: BAD_STATUS_LIMIT,
+            since: None,
+            counters: None,
+            bad_status: 0,
+            recoveries: 0,
+        }
+    }
+
+    /// The configuration restored on recovery
+    pub fn config(&self) -> &RadioConfig {
+        &self.config
+    }
+
+    /// Replace the configuration restored on recovery, after
+    /// reconfiguring the radio
+    pub fn set_config(&mut self, config: RadioConfig) {
+        self.config = config;
+    }
+
+    /// Consecutive implausible `STATUS` reads that trigger a recovery,
+    /// 3 by default
+    pub fn set_bad_status_limit(&mut self, limit: u8) {
+        self.bad_status_limit = limit.max(1);
+    }
+
+    /// Number of recoveries so far
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+
+    /// Check a transmitter, expecting traffic while the TX FIFO is not
+    /// empty. Returns whether the chip has been reinitialized.
+    ///
+    /// Packets in the TX FIFO are lost on recovery.
+    pub async fn check_tx<D: Device, T: RadioTimer>(
+        &mut self,
+        tx: &mut TxMode<D>,
+        timer: &mut T,
+    ) -> Result<bool, D::Error> {
+        let (status, fifo_status) = tx.device().read_register::<FifoStatus>().await?;
+        let bus_dead = Self::bus_dead(tx.device(), &status).await?;
+        let stats = tx.stats();
+        if !self.locked_up(&status, bus_dead, &stats, !fifo_status.tx_empty(), timer) {
+            return Ok(false);
+        }
+
+        tx.device().ce_disable();
+        tx.reinit(&self.config, timer).await?;
+        self.recovered(tx.device());
+        Ok(true)
+    }
+
+    /// Check a receiver. Set `traffic_expected` if packets are due, e.g.
+    /// from a peer that sends periodically. Returns whether the chip has
+    /// been reinitialized.
+    ///
+    /// Packets in the RX FIFO are lost on recovery.
+    pub async fn check_rx<D: Device, T: RadioTimer>(
+        &mut self,
+        rx: &mut RxMode<D>,
+        timer: &mut T,
+        traffic_expected: bool,
+    ) -> Result<bool, D::Error> {
+        let (status, ()) = rx.device().send_command(&Nop).await?;
+        let bus_dead = Self::bus_dead(rx.device(), &status).await?;
+        let stats = rx.stats();
+        if !self.locked_up(&status, bus_dead, &stats, traffic_expected, timer) {
+            return Ok(false);
+        }
+
+        // Settings must not change while listening
+        rx.device().ce_disable();
+        rx.reinit(&self.config, timer).await?;
+        rx.device().ce_enable();
+        self.recovered(rx.device());
+        Ok(true)
+    }
+
+    /// Is `status` implausible and the bus found dead?
+    ///
+    /// `0x00` is also a legal `STATUS` with a packet from pipe 0 pending,
+    /// so `SETUP_AW` is read to tell them apart.
+    async fn bus_dead<D: Device>(device: &mut D, status: &Status) -> Result<bool, D::Error> {
+        let dead = match status.0 {
+            // Bit 7 always reads 0
+            0xFF => true,
+            0x00 => {
+                let (_, setup_aw) = device.read_register::<SetupAw>().await?;
+                setup_aw.aw() == 0
+            }
+            _ => false,
+        };
+        Ok(dead)
+    }
+
+    /// Account for a check, returning whether to recover
+    fn locked_up<T: RadioTimer>(
+        &mut self,
+        status: &Status,
+        bus_dead: bool,
+        stats: &Stats,
+        traffic_expected: bool,
+        timer: &T,
+    ) -> bool {
+        self.bad_status = if bus_dead { self.bad_status.saturating_add(1) } else { 0 };
+        if self.bad_status >= self.bad_status_limit {
+            return true;
+        }
+
+        let counters = (stats.tx_ds, stats.packets_received, stats.max_rt);
+        let progress = status.tx_ds()
+            || status.rx_dr()
+            || status.max_rt()
+            || self.counters != Some(counters);
+        self.counters = Some(counters);
+
+        let now = timer.now_micros();
+        match self.since {
+            _ if !traffic_expected => {
+                self.since = None;
+                false
+            }
+            Some(since) if !progress => now.saturating_sub(since) >= self.timeout_us,
+            _ => {
+                self.since = Some(now);
+                false
+            }
+        }
+    }
+
+    fn recovered<D: Device>(&mut self, device: &mut D) {
+        self.since = None;
+        self.counters = None;
+        self.bad_status = 0;
+        self.recoveries = self.recoveries.wrapping_add(1);
+        device.events().on_recovery();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::block_on;
+    use crate::sim::{Air, SimTimer};
+    use crate::{ChipVariant, Coexistence};
+
+    #[test]
+    fn recovery_restores_features() {
+        block_on(async {
+            let air = Air::new();
+            let (ce, spi) = air.radio();
+            let chip = spi.chip();
+            chip.set_variant(ChipVariant::NonPlus);
+            let mut standby = crate::sim::SimRadio::new(ce, spi).await.unwrap();
+            standby.detect_variant().await.unwrap();
+            let config = RadioConfig::default();
+            standby.configure(&config).await.unwrap();
+            standby.set_ack_payloads(true).await.unwrap();
+            let features = chip.register(0x1D);
+            assert_eq!(features & 0b110, 0b110);
+
+            chip.power_cycle();
+            standby.reinit(&config, &mut SimTimer::new()).await.unwrap();
+            assert_eq!(chip.register(0x1D), features);
+            assert!(standby.get_ack_payloads().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn loaded_packets_are_no_progress() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = crate::sim::pair(&air).await;
+            // Packets pile up in the FIFO without being sent
+            let coex = Coexistence {
+                request: || false,
+                release: || {},
+            };
+            tx.set_coexistence(Some(coex)).unwrap();
+            let mut timer = SimTimer::new();
+            let mut watchdog = Watchdog::new(RadioConfig::default(), 1_000);
+            let mut recovered = false;
+            for _ in 0..3 {
+                tx.try_send(b"stuck").await.unwrap();
+                timer.advance(600);
+                recovered |= watchdog.check_tx(&mut tx, &mut timer).await.unwrap();
+            }
+            assert!(recovered);
+        });
+    }
+
+    #[test]
+    fn sent_packets_are_progress() {
+        block_on(async {
+            let air = Air::new();
+            let (mut tx, _rx) = crate::sim::pair(&air).await;
+            let mut timer = SimTimer::new();
+            let mut watchdog = Watchdog::new(RadioConfig::default(), 1_000);
+            for _ in 0..3 {
+                tx.send(b"fine").await.unwrap();
+                // Traffic is expected as long as TX_DS is pending
+                watchdog.check_tx(&mut tx, &mut timer).await.unwrap();
+                assert_eq!(tx.poll_send().await.ok(), Some(true));
+                timer.advance(600);
+            }
+            assert_eq!(tx.stats().tx_ds, 3);
+            assert_eq!(watchdog.recoveries(), 0);
+        });
+    }
+}
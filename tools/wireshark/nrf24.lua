@@ -0,0 +1,50 @@
+-- Wireshark dissector for nRF24 capture records, as produced by
+-- CaptureRecord::encode() in embedded-nrf24l01-async
+--
+-- Copy to the Wireshark plugins folder. An extcap strips the COBS
+-- framing and the 0x03 frame type of SerialBridge::encode_capture()
+-- frames and writes the records with link type USER0 (147).
+
+local nrf24 = Proto("nrf24", "nRF24 capture record")
+
+local f = nrf24.fields
+f.magic = ProtoField.string("nrf24.magic", "Magic")
+f.version = ProtoField.uint8("nrf24.version", "Version")
+f.channel = ProtoField.uint8("nrf24.channel", "Channel")
+f.status = ProtoField.uint8("nrf24.status", "STATUS", base.HEX)
+f.pipe = ProtoField.uint8("nrf24.pipe", "Pipe", base.DEC, nil, 0x07)
+f.rpd = ProtoField.bool("nrf24.rpd", "RPD", 8, nil, 0x80)
+f.address_width = ProtoField.uint8("nrf24.address_width", "Address width")
+f.address = ProtoField.bytes("nrf24.address", "Address (LSB first)")
+f.payload_len = ProtoField.uint8("nrf24.payload_len", "Payload length")
+f.payload = ProtoField.bytes("nrf24.payload", "Payload")
+
+function nrf24.dissector(buffer, pinfo, tree)
+    if buffer:len() < 8 or buffer(0, 2):string() ~= "N4" then
+        return 0
+    end
+    pinfo.cols.protocol = "nRF24"
+
+    local subtree = tree:add(nrf24, buffer(), "nRF24 capture record")
+    subtree:add(f.magic, buffer(0, 2))
+    subtree:add(f.version, buffer(2, 1))
+    subtree:add(f.channel, buffer(3, 1))
+    subtree:add(f.status, buffer(4, 1))
+    subtree:add(f.pipe, buffer(5, 1))
+    subtree:add(f.rpd, buffer(5, 1))
+
+    local width = buffer(6, 1):uint()
+    subtree:add(f.address_width, buffer(6, 1))
+    subtree:add(f.address, buffer(7, width))
+    local len = buffer(7 + width, 1):uint()
+    subtree:add(f.payload_len, buffer(7 + width, 1))
+    if len > 0 then
+        subtree:add(f.payload, buffer(8 + width, len))
+    end
+
+    pinfo.cols.info = string.format("ch %d pipe %d, %d bytes",
+        buffer(3, 1):uint(), bit.band(buffer(5, 1):uint(), 0x07), len)
+    return 8 + width + len
+end
+
+DissectorTable.get("wtap_encap"):add(wtap.USER0, nrf24)